@@ -9,15 +9,31 @@ use std::time::Duration;
 
 use io_error::*;
 
+#[derive(Clone)]
 pub struct Config {
+    pub linger: Duration,
     pub send_timeout: Option<Duration>,
     pub send_priority: u8,
     pub recv_timeout: Option<Duration>,
     pub recv_priority: u8,
     pub retry_ivl: Duration,
-    pub retry_ivl_max: Option<Duration>
+    pub retry_ivl_max: Option<Duration>,
+    pub recv_max_size: u64,
+    pub send_buffer_size: usize,
+    pub recv_buffer_size: usize,
+    pub tcp_nodelay: bool,
+    pub tcp_keepalive: Option<Duration>,
+    pub tcp_keepalive_ivl: Option<Duration>,
+    pub tcp_keepalive_cnt: Option<u32>,
+    pub tcp_send_buffer_size: Option<usize>,
+    pub tcp_recv_buffer_size: Option<usize>,
+    pub tls_cert: Option<Vec<u8>>,
+    pub tls_private_key: Option<Vec<u8>>,
+    pub tls_ca_certs: Option<Vec<u8>>,
+    pub tls_verify_client: bool
 }
 
+#[derive(Clone, Debug, PartialEq)]
 pub enum ConfigOption {
     /// Specifies how long the socket should try to send pending outbound messages 
     /// after `drop` have been called. Default value is 1 second.
@@ -40,6 +56,21 @@ pub enum ConfigOption {
     /// Default is 1024kB.
     RecvMaxSize(u64),
 
+    /// Size of the outbound queue of each pipe, in bytes.
+    /// When set, a send completes as soon as the message is queued,
+    /// and the queued messages are written in the background.
+    /// A pipe accepts messages while its queue holds less than this size.
+    /// Zero value means no queue: a send completes once the message is written.
+    /// Applies to endpoints subsequently added to the socket. Default value is 0.
+    SendBufferSize(usize),
+
+    /// Size of the inbound queue of each pipe, in bytes.
+    /// When set, messages are received in the background until the queue holds at least this size,
+    /// and a receive completes immediately when a message is queued.
+    /// Zero value means no queue: messages are read only when a receive is requested.
+    /// Applies to endpoints subsequently added to the socket. Default value is 0.
+    RecvBufferSize(usize),
+
     /// For connection-based transports such as TCP, this option specifies how long to wait, 
     /// when connection is broken before trying to re-establish it. 
     /// Note that actual reconnect interval may be randomised to some extent 
@@ -57,6 +88,45 @@ pub enum ConfigOption {
     /// See [Socket::set_tcp_nodelay](struct.Socket.html#method.set_tcp_nodelay).
     TcpNoDelay(bool),
 
+    /// Enables SO_KEEPALIVE on the `tcp` streams, with the specified idle time
+    /// before the first probe is sent, in whole seconds.
+    /// Value of `None` disables keepalive. Default value is `None`.
+    TcpKeepAlive(Option<Duration>),
+
+    /// Interval between two keepalive probes, in whole seconds.
+    /// Only used when `TcpKeepAlive` is enabled, and ignored on platforms that cannot configure it.
+    /// Value of `None` means the system default is used. Default value is `None`.
+    TcpKeepAliveInterval(Option<Duration>),
+
+    /// Number of unanswered keepalive probes before the connection is considered broken.
+    /// Only used when `TcpKeepAlive` is enabled, and ignored on platforms that cannot configure it.
+    /// Value of `None` means the system default is used. Default value is `None`.
+    TcpKeepAliveCount(Option<u32>),
+
+    /// Size of the SO_SNDBUF kernel buffer of the `tcp` streams, in bytes.
+    /// Value of `None` means the system default is used. Default value is `None`.
+    TcpSendBufferSize(Option<usize>),
+
+    /// Size of the SO_RCVBUF kernel buffer of the `tcp` streams, in bytes.
+    /// Value of `None` means the system default is used. Default value is `None`.
+    TcpRecvBufferSize(Option<usize>),
+
+    /// PEM encoded certificate chain that the `tls+tcp` transport presents to the peer.
+    /// Required to bind, and to connect when the server verifies client certificates.
+    TlsCertificate(Vec<u8>),
+
+    /// PEM encoded private key matching the first certificate of `TlsCertificate`.
+    TlsPrivateKey(Vec<u8>),
+
+    /// PEM encoded certificates of the authorities trusted to sign the peer certificate.
+    /// When not specified, the system default authorities are used.
+    TlsCaCertificates(Vec<u8>),
+
+    /// When `true`, the `tls+tcp` endpoints bound by the socket require
+    /// the clients to present a certificate signed by a trusted authority.
+    /// Default value is `false`.
+    TlsVerifyClient(bool),
+
     /// Defined on `Sub` socket. Subscribes for a particular topic.
     /// A single `Sub` socket can handle multiple subscriptions.
     Subscribe(String),
@@ -64,28 +134,108 @@ pub enum ConfigOption {
     /// Defined on Sub` socket. Unsubscribes from a particular topic.
     Unsubscribe(String),
 
+    /// Defined on `Sub` socket. The topics currently subscribed to, in alphabetical order.
+    /// Can only be read, with `get_option`.
+    Subscriptions(Vec<String>),
+
     /// This option is defined on the Req socket.
     /// If a reply is not received in the specified amount of time, 
     /// the request will be automatically resent. 
     /// Default value is 1 minute.
     ReqResendIvl(Duration),
 
+    /// This option is defined on the Req socket, in addition to `ReqResendIvl`.
+    /// On each resend, the previous interval is doubled until this maximum is reached.
+    /// Value of `None` means that the request is always resent after `ReqResendIvl`.
+    /// If ReqResendIvlMax is less than ReqResendIvl, it is ignored.
+    /// Default value is `None`.
+    ReqResendIvlMax(Option<Duration>),
+
+    /// This option is defined on the Req socket.
+    /// Maximum number of times a request is resent. Once reached without a reply,
+    /// receive function will return a ConnectionAborted error.
+    /// Value of `None` means that the request is resent until a reply is received.
+    /// Default value is `None`.
+    ReqResendMax(Option<u32>),
+
+    /// This option is defined on the Req socket.
+    /// When `true`, a resent request goes to another peer than the previous attempt,
+    /// as long as one is available. Default value is `false`.
+    ReqResendToOtherPeer(bool),
+
     /// Specifies how long to wait for responses to the survey.
     /// Once the deadline expires, receive function will return a TimedOut error 
     /// and all subsequent responses to the survey will be silently dropped.
+    /// Each survey, sent by the socket or by one of its contexts, has its own deadline.
     /// Default value is 1 second.
     SurveyDeadline(Duration)
 }
 
+/// Options applying to a single endpoint, overriding the socket ones for that endpoint only.
+/// They are kept when the endpoint is re-established after an error.
+/// See [Socket::connect_with](struct.Socket.html#method.connect_with)
+/// and [Socket::bind_with](struct.Socket.html#method.bind_with).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EndpointOptions {
+    options: Vec<ConfigOption>
+}
+
+/// Identifies the option to read with [Socket::get_option](struct.Socket.html#method.get_option).
+/// The `TlsCertificate`, `TlsPrivateKey` and `TlsCaCertificates` options can not be read back,
+/// getting them fails with an `InvalidInput` error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigOptionKind {
+    Linger,
+    SendTimeout,
+    SendPriority,
+    RecvTimeout,
+    RecvPriority,
+    RecvMaxSize,
+    SendBufferSize,
+    RecvBufferSize,
+    RetryIvl,
+    RetryIvlMax,
+    TcpNoDelay,
+    TcpKeepAlive,
+    TcpKeepAliveInterval,
+    TcpKeepAliveCount,
+    TcpSendBufferSize,
+    TcpRecvBufferSize,
+    TlsCertificate,
+    TlsPrivateKey,
+    TlsCaCertificates,
+    TlsVerifyClient,
+    Subscriptions,
+    ReqResendIvl,
+    ReqResendIvlMax,
+    ReqResendMax,
+    ReqResendToOtherPeer,
+    SurveyDeadline
+}
+
 impl Default for Config {
     fn default() -> Config {
         Config {
+            linger: Duration::from_secs(1),
             send_timeout: None,
             send_priority: 8,
             recv_timeout: None,
             recv_priority: 8,
             retry_ivl: Duration::from_millis(100),
-            retry_ivl_max: None
+            retry_ivl_max: None,
+            recv_max_size: 1024 * 1024,
+            send_buffer_size: 0,
+            recv_buffer_size: 0,
+            tcp_nodelay: false,
+            tcp_keepalive: None,
+            tcp_keepalive_ivl: None,
+            tcp_keepalive_cnt: None,
+            tcp_send_buffer_size: None,
+            tcp_recv_buffer_size: None,
+            tls_cert: None,
+            tls_private_key: None,
+            tls_ca_certs: None,
+            tls_verify_client: false
         }
     }
 }
@@ -93,31 +243,164 @@ impl Default for Config {
 impl Config {
     pub fn set(&mut self, cfg_opt: ConfigOption) -> Result<()> {
         match cfg_opt {
+            ConfigOption::Linger(linger) => self.linger = linger,
             ConfigOption::SendTimeout(timeout) => self.send_timeout = timeout,
             ConfigOption::SendPriority(priority) => self.send_priority = priority,
             ConfigOption::RecvTimeout(timeout) => self.recv_timeout = timeout,
             ConfigOption::RecvPriority(priority) => self.recv_priority = priority,
             ConfigOption::RetryIvl(ivl) => self.retry_ivl = ivl,
             ConfigOption::RetryIvlMax(ivl) => self.retry_ivl_max = ivl,
+            ConfigOption::RecvMaxSize(size) => self.recv_max_size = size,
+            ConfigOption::SendBufferSize(size) => self.send_buffer_size = size,
+            ConfigOption::RecvBufferSize(size) => self.recv_buffer_size = size,
+            ConfigOption::TcpNoDelay(nodelay) => self.tcp_nodelay = nodelay,
+            ConfigOption::TcpKeepAlive(idle) => self.tcp_keepalive = idle,
+            ConfigOption::TcpKeepAliveInterval(ivl) => self.tcp_keepalive_ivl = ivl,
+            ConfigOption::TcpKeepAliveCount(cnt) => self.tcp_keepalive_cnt = cnt,
+            ConfigOption::TcpSendBufferSize(size) => self.tcp_send_buffer_size = size,
+            ConfigOption::TcpRecvBufferSize(size) => self.tcp_recv_buffer_size = size,
+            ConfigOption::TlsCertificate(pem) => self.tls_cert = Some(pem),
+            ConfigOption::TlsPrivateKey(pem) => self.tls_private_key = Some(pem),
+            ConfigOption::TlsCaCertificates(pem) => self.tls_ca_certs = Some(pem),
+            ConfigOption::TlsVerifyClient(verify) => self.tls_verify_client = verify,
             _ => return Err(invalid_input_io_error("option not supported"))
         }
         Ok(())
     }
+
+    pub fn get(&self, kind: ConfigOptionKind) -> Result<ConfigOption> {
+        let opt = match kind {
+            ConfigOptionKind::Linger => ConfigOption::Linger(self.linger),
+            ConfigOptionKind::SendTimeout => ConfigOption::SendTimeout(self.send_timeout),
+            ConfigOptionKind::SendPriority => ConfigOption::SendPriority(self.send_priority),
+            ConfigOptionKind::RecvTimeout => ConfigOption::RecvTimeout(self.recv_timeout),
+            ConfigOptionKind::RecvPriority => ConfigOption::RecvPriority(self.recv_priority),
+            ConfigOptionKind::RetryIvl => ConfigOption::RetryIvl(self.retry_ivl),
+            ConfigOptionKind::RetryIvlMax => ConfigOption::RetryIvlMax(self.retry_ivl_max),
+            ConfigOptionKind::RecvMaxSize => ConfigOption::RecvMaxSize(self.recv_max_size),
+            ConfigOptionKind::SendBufferSize => ConfigOption::SendBufferSize(self.send_buffer_size),
+            ConfigOptionKind::RecvBufferSize => ConfigOption::RecvBufferSize(self.recv_buffer_size),
+            ConfigOptionKind::TcpNoDelay => ConfigOption::TcpNoDelay(self.tcp_nodelay),
+            ConfigOptionKind::TcpKeepAlive => ConfigOption::TcpKeepAlive(self.tcp_keepalive),
+            ConfigOptionKind::TcpKeepAliveInterval => ConfigOption::TcpKeepAliveInterval(self.tcp_keepalive_ivl),
+            ConfigOptionKind::TcpKeepAliveCount => ConfigOption::TcpKeepAliveCount(self.tcp_keepalive_cnt),
+            ConfigOptionKind::TcpSendBufferSize => ConfigOption::TcpSendBufferSize(self.tcp_send_buffer_size),
+            ConfigOptionKind::TcpRecvBufferSize => ConfigOption::TcpRecvBufferSize(self.tcp_recv_buffer_size),
+            ConfigOptionKind::TlsVerifyClient => ConfigOption::TlsVerifyClient(self.tls_verify_client),
+            _ => return Err(invalid_input_io_error("option not supported"))
+        };
+        Ok(opt)
+    }
+}
+
+impl EndpointOptions {
+    pub fn new() -> EndpointOptions {
+        EndpointOptions::default()
+    }
+
+    /// Adds an option to apply to the endpoint.
+    /// Only the priorities, the retry intervals, the receive max size,
+    /// the queue sizes and the tcp options can be set per endpoint.
+    pub fn with(mut self, opt: ConfigOption) -> EndpointOptions {
+        self.options.push(opt);
+        self
+    }
+
+    #[doc(hidden)]
+    pub fn validate(&self) -> Result<()> {
+        if self.options.iter().all(|opt| opt.is_endpoint_specific()) {
+            Ok(())
+        } else {
+            Err(invalid_input_io_error("option not supported by endpoints"))
+        }
+    }
+}
+
+impl Config {
+    /// Returns the config of an endpoint, that is the socket one overridden by the endpoint options.
+    pub fn endpoint_config(&self, options: &EndpointOptions) -> Config {
+        let mut config = self.clone();
+
+        for opt in options.options.iter().filter(|opt| opt.is_endpoint_specific()) {
+            let _ = config.set(opt.clone());
+        }
+
+        config
+    }
 }
 
 impl ConfigOption {
     #[doc(hidden)]
-    pub fn is_generic(&self) -> bool {
+    pub fn is_endpoint_specific(&self) -> bool {
         match *self {
-            ConfigOption::Linger(_)       |
-            ConfigOption::SendTimeout(_)  |
-            ConfigOption::SendPriority(_) |
-            ConfigOption::RecvTimeout(_)  |
-            ConfigOption::RecvPriority(_) |
-            ConfigOption::RetryIvl(_)     |
-            ConfigOption::RetryIvlMax(_)  |
-            ConfigOption::TcpNoDelay(_)   => true,
+            ConfigOption::SendPriority(_)         |
+            ConfigOption::RecvPriority(_)         |
+            ConfigOption::RecvMaxSize(_)          |
+            ConfigOption::SendBufferSize(_)       |
+            ConfigOption::RecvBufferSize(_)       |
+            ConfigOption::RetryIvl(_)             |
+            ConfigOption::RetryIvlMax(_)          |
+            ConfigOption::TcpNoDelay(_)           |
+            ConfigOption::TcpKeepAlive(_)         |
+            ConfigOption::TcpKeepAliveInterval(_) |
+            ConfigOption::TcpKeepAliveCount(_)    |
+            ConfigOption::TcpSendBufferSize(_)    |
+            ConfigOption::TcpRecvBufferSize(_)    => true,
             _ => false
         }
     }
-}
\ No newline at end of file
+
+    /// Identifies the option, the subscription changes being identified by `Subscriptions`.
+    pub fn kind(&self) -> ConfigOptionKind {
+        match *self {
+            ConfigOption::Linger(_)               => ConfigOptionKind::Linger,
+            ConfigOption::SendTimeout(_)          => ConfigOptionKind::SendTimeout,
+            ConfigOption::SendPriority(_)         => ConfigOptionKind::SendPriority,
+            ConfigOption::RecvTimeout(_)          => ConfigOptionKind::RecvTimeout,
+            ConfigOption::RecvPriority(_)         => ConfigOptionKind::RecvPriority,
+            ConfigOption::RecvMaxSize(_)          => ConfigOptionKind::RecvMaxSize,
+            ConfigOption::SendBufferSize(_)       => ConfigOptionKind::SendBufferSize,
+            ConfigOption::RecvBufferSize(_)       => ConfigOptionKind::RecvBufferSize,
+            ConfigOption::RetryIvl(_)             => ConfigOptionKind::RetryIvl,
+            ConfigOption::RetryIvlMax(_)          => ConfigOptionKind::RetryIvlMax,
+            ConfigOption::TcpNoDelay(_)           => ConfigOptionKind::TcpNoDelay,
+            ConfigOption::TcpKeepAlive(_)         => ConfigOptionKind::TcpKeepAlive,
+            ConfigOption::TcpKeepAliveInterval(_) => ConfigOptionKind::TcpKeepAliveInterval,
+            ConfigOption::TcpKeepAliveCount(_)    => ConfigOptionKind::TcpKeepAliveCount,
+            ConfigOption::TcpSendBufferSize(_)    => ConfigOptionKind::TcpSendBufferSize,
+            ConfigOption::TcpRecvBufferSize(_)    => ConfigOptionKind::TcpRecvBufferSize,
+            ConfigOption::TlsCertificate(_)       => ConfigOptionKind::TlsCertificate,
+            ConfigOption::TlsPrivateKey(_)        => ConfigOptionKind::TlsPrivateKey,
+            ConfigOption::TlsCaCertificates(_)    => ConfigOptionKind::TlsCaCertificates,
+            ConfigOption::TlsVerifyClient(_)      => ConfigOptionKind::TlsVerifyClient,
+            ConfigOption::Subscribe(_)            |
+            ConfigOption::Unsubscribe(_)          |
+            ConfigOption::Subscriptions(_)        => ConfigOptionKind::Subscriptions,
+            ConfigOption::ReqResendIvl(_)         => ConfigOptionKind::ReqResendIvl,
+            ConfigOption::ReqResendIvlMax(_)      => ConfigOptionKind::ReqResendIvlMax,
+            ConfigOption::ReqResendMax(_)         => ConfigOptionKind::ReqResendMax,
+            ConfigOption::ReqResendToOtherPeer(_) => ConfigOptionKind::ReqResendToOtherPeer,
+            ConfigOption::SurveyDeadline(_)       => ConfigOptionKind::SurveyDeadline
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn is_generic(&self) -> bool {
+        self.kind().is_generic()
+    }
+}
+
+impl ConfigOptionKind {
+    #[doc(hidden)]
+    pub fn is_generic(&self) -> bool {
+        match *self {
+            ConfigOptionKind::Subscriptions        |
+            ConfigOptionKind::ReqResendIvl         |
+            ConfigOptionKind::ReqResendIvlMax      |
+            ConfigOptionKind::ReqResendMax         |
+            ConfigOptionKind::ReqResendToOtherPeer |
+            ConfigOptionKind::SurveyDeadline       => false,
+            _ => true
+        }
+    }
+}
@@ -8,7 +8,7 @@ use std::fmt;
 use std::io::Result;
 use std::time::Duration;
 
-use core::EndpointSpec;
+use core::{EndpointSpec, ContextId};
 use core::network::Network;
 
 pub trait Context : Network + Scheduler + fmt::Debug {
@@ -31,8 +31,13 @@ pub enum Schedulable {
     Rebind(EndpointSpec),
     SendTimeout,
     RecvTimeout,
+    ContextSendTimeout(ContextId),
+    ContextRecvTimeout(ContextId),
     ReqResend,
-    SurveyCancel
+    ContextReqResend(ContextId),
+    SurveyCancel,
+    ContextSurveyCancel(ContextId),
+    Linger
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
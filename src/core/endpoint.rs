@@ -5,31 +5,82 @@
 // This file may not be copied, modified, or distributed except according to those terms.
 
 use std::rc::Rc;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+use std::io;
 
 use super::{EndpointId, Message, EndpointSpec};
 use super::context::Context;
+use super::stats::Statistics;
+use super::config::EndpointOptions;
 
 pub enum Request {
-    Close(bool)
+    Close(bool, Sender<Reply>),
+    Statistics(Sender<Reply>),
+    Info(Sender<Reply>)
+}
+
+pub enum Reply {
+    Close,
+    Statistics(Statistics),
+    Info(EndpointInfo),
+    Err(io::Error)
+}
+
+/// Progress of an endpoint, see [EndpointInfo](../../struct.EndpointInfo.html).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EndpointState {
+    /// The connection to the remote endpoint is being established.
+    Connecting,
+    /// The connection is established, the protocol handshake is in progress.
+    Handshaking,
+    /// The handshake succeeded, the pipe is used by the protocol.
+    Active,
+    /// The local endpoint accepts connections.
+    Listening,
+    /// The endpoint failed, the next attempt to re-establish it will be made at the specified time.
+    Retrying(Instant)
+}
+
+/// Description of an endpoint created by `connect` or `bind`, for diagnostics.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EndpointInfo {
+    pub id: EndpointId,
+    pub url: String,
+    pub state: EndpointState,
+    /// Number of connections currently accepted by a local endpoint.
+    pub accepted_pipes: usize,
+    /// Addresses of the peers connected to a local endpoint, when the transport provides them.
+    pub peer_addrs: Vec<String>
+}
+
+/// Status of a pipe maintained by the backend.
+pub struct PipeStatus {
+    pub state: EndpointState,
+    pub peer_addr: Option<String>
 }
 
 pub struct Endpoint {
     id: EndpointId,
     url: Option<String>,
     send_priority: u8,
-    recv_priority: u8
+    recv_priority: u8,
+    retry_ivl: Option<Duration>,
+    options: EndpointOptions
 }
 
 pub struct Pipe(Endpoint);
 pub struct Acceptor(Endpoint);
 
 impl Endpoint {
-    fn new_created(id: EndpointId, url: String, send_prio: u8, recv_prio: u8) -> Endpoint {
+    fn new_created(id: EndpointId, url: String, send_prio: u8, recv_prio: u8, options: EndpointOptions) -> Endpoint {
         Endpoint {
             id: id,
             url: Some(url),
             send_priority: send_prio,
             recv_priority: recv_prio,
+            retry_ivl: None,
+            options: options
         }
     }
 
@@ -39,6 +90,8 @@ impl Endpoint {
             url: None,
             send_priority: send_prio,
             recv_priority: recv_prio,
+            retry_ivl: None,
+            options: EndpointOptions::default()
         }
     }
 
@@ -58,20 +111,25 @@ impl Endpoint {
             id: self.id,
             url: url,
             send_priority: self.send_priority,
-            recv_priority: self.recv_priority
+            recv_priority: self.recv_priority,
+            retry_ivl: self.retry_ivl,
+            options: self.options
         })
     }
 }
 
 impl From<EndpointSpec> for Pipe {
     fn from(spec: EndpointSpec) -> Pipe {
-        Pipe::new_connected(spec.id, spec.url, spec.send_priority, spec.recv_priority)
+        let mut pipe = Pipe::new_connected(spec.id, spec.url, spec.send_priority, spec.recv_priority, spec.options);
+
+        pipe.0.retry_ivl = spec.retry_ivl;
+        pipe
     }
 }
 
 impl Pipe {
-    pub fn new_connected(id: EndpointId, url: String, send_prio: u8, recv_prio: u8) -> Pipe {
-        Pipe(Endpoint::new_created(id, url, send_prio, recv_prio))
+    pub fn new_connected(id: EndpointId, url: String, send_prio: u8, recv_prio: u8, options: EndpointOptions) -> Pipe {
+        Pipe(Endpoint::new_created(id, url, send_prio, recv_prio, options))
     }
 
     pub fn new_accepted(id: EndpointId, send_prio: u8, recv_prio: u8) -> Pipe {
@@ -96,11 +154,14 @@ impl Pipe {
     pub fn get_recv_priority(&self) -> u8 {
         self.0.recv_priority
     }
+    pub fn reset_retry_ivl(&mut self) {
+        self.0.retry_ivl = None;
+    }
 }
 
 impl Acceptor {
-    pub fn new(id: EndpointId, url: String, send_prio: u8, recv_prio: u8) -> Acceptor {
-        Acceptor(Endpoint::new_created(id, url, send_prio, recv_prio))
+    pub fn new(id: EndpointId, url: String, send_prio: u8, recv_prio: u8, options: EndpointOptions) -> Acceptor {
+        Acceptor(Endpoint::new_created(id, url, send_prio, recv_prio, options))
     }
     pub fn open(&self, network: &mut Context) {
         self.0.open(network, false)
@@ -118,6 +179,6 @@ impl Acceptor {
 
 impl From<EndpointSpec> for Acceptor {
     fn from(spec: EndpointSpec) -> Acceptor {
-        Acceptor::new(spec.id, spec.url, spec.send_priority, spec.recv_priority)
+        Acceptor::new(spec.id, spec.url, spec.send_priority, spec.recv_priority, spec.options)
     }
 }
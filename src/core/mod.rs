@@ -11,8 +11,12 @@ pub mod socket;
 pub mod session;
 pub mod endpoint;
 pub mod device;
+pub mod stats;
 
 use std::fmt;
+use std::time::Duration;
+
+use self::config::EndpointOptions;
 
 /*****************************************************************************/
 /*                                                                           */
@@ -20,7 +24,7 @@ use std::fmt;
 /*                                                                           */
 /*****************************************************************************/
 
-#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct EndpointId(usize);
 
 impl fmt::Debug for EndpointId {
@@ -57,7 +61,11 @@ pub struct EndpointSpec {
     pub id: EndpointId,
     pub url: String,
     pub send_priority: u8,
-    pub recv_priority: u8
+    pub recv_priority: u8,
+    /// Interval used by the previous attempt to re-establish the endpoint, if any.
+    pub retry_ivl: Option<Duration>,
+    /// Options given when the endpoint was created, used again to re-establish it.
+    pub options: EndpointOptions
 }
 
 /*****************************************************************************/
@@ -102,6 +110,27 @@ impl From<usize> for DeviceId {
     }
 }
 
+/*****************************************************************************/
+/*                                                                           */
+/* ContextId                                                                 */
+/*                                                                           */
+/*****************************************************************************/
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ContextId(usize);
+
+impl fmt::Debug for ContextId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<usize> for ContextId {
+    fn from(value: usize) -> ContextId {
+        ContextId(value)
+    }
+}
+
 /*****************************************************************************/
 /*                                                                           */
 /* Message                                                                   */
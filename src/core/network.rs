@@ -8,12 +8,13 @@ use std::rc::Rc;
 use std::io::Result;
 
 use core::{SocketId, EndpointId, Message};
+use core::config::Config;
 
 pub trait Network {
-    fn connect(&mut self, sid: SocketId, url: &str, pids: (u16, u16)) -> Result<EndpointId>;
-    fn reconnect(&mut self, sid: SocketId, eid: EndpointId, url: &str, pids: (u16, u16)) -> Result<()>;
-    fn bind(&mut self, sid: SocketId, url: &str, pids: (u16, u16)) -> Result<EndpointId>;
-    fn rebind(&mut self, sid: SocketId, eid: EndpointId, url: &str, pids: (u16, u16)) -> Result<()>;
+    fn connect(&mut self, sid: SocketId, url: &str, pids: (u16, u16), config: &Config) -> Result<EndpointId>;
+    fn reconnect(&mut self, sid: SocketId, eid: EndpointId, url: &str, pids: (u16, u16), config: &Config) -> Result<()>;
+    fn bind(&mut self, sid: SocketId, url: &str, pids: (u16, u16), config: &Config) -> Result<EndpointId>;
+    fn rebind(&mut self, sid: SocketId, eid: EndpointId, url: &str, pids: (u16, u16), config: &Config) -> Result<()>;
     fn open(&mut self, eid: EndpointId, remote: bool);
     fn close(&mut self, eid: EndpointId, remote: bool);
     fn send(&mut self, eid: EndpointId, msg: Rc<Message>);
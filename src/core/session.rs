@@ -7,6 +7,7 @@
 use std::collections::HashMap;
 use std::sync::mpsc;
 use std::io;
+use std::time::Duration;
 
 use core::{SocketId, DeviceId, socket, device};
 use sequence::Sequence;
@@ -14,6 +15,7 @@ use sequence::Sequence;
 pub enum Request {
     CreateSocket(socket::ProtocolCtor),
     CreateDevice(SocketId, SocketId),
+    Poll(Vec<(SocketId, bool, bool)>, Duration),
     Shutdown
 }
 
@@ -21,13 +23,15 @@ pub enum Reply {
     Err(io::Error),
     SocketCreated(SocketId, mpsc::Receiver<socket::Reply>),
     DeviceCreated(DeviceId, mpsc::Receiver<device::Reply>),
+    Poll(Vec<(bool, bool)>),
     Shutdown
 }
 
 pub struct Session {
     reply_sender: mpsc::Sender<Reply>,
     sockets: SocketCollection,
-    devices: DeviceCollection
+    devices: DeviceCollection,
+    poll: Option<Vec<(SocketId, bool, bool)>>
 }
 
 struct SocketCollection {
@@ -46,7 +50,8 @@ impl Session {
         Session {
             reply_sender: reply_tx,
             sockets: SocketCollection::new(seq.clone()),
-            devices: DeviceCollection::new(seq.clone())
+            devices: DeviceCollection::new(seq.clone()),
+            poll: None
         }
     }
 
@@ -77,6 +82,10 @@ impl Session {
         self.sockets.remove(sid);
     }
 
+    pub fn has_closing_sockets(&self) -> bool {
+        self.sockets.has_closing()
+    }
+
 /*****************************************************************************/
 /*                                                                           */
 /* Devices                                                                   */
@@ -101,6 +110,50 @@ impl Session {
     pub fn remove_device(&mut self, did: DeviceId) {
         self.devices.remove(did);
     }
+
+/*****************************************************************************/
+/*                                                                           */
+/* Poll                                                                      */
+/*                                                                           */
+/*****************************************************************************/
+
+    /// Replies right away if any of the polled sockets is ready, 
+    /// otherwise the poll is kept pending and `false` is returned.
+    pub fn poll(&mut self, requests: Vec<(SocketId, bool, bool)>) -> bool {
+        if self.sockets.is_any_ready(&requests) {
+            self.send_poll_reply(&requests);
+            true
+        } else {
+            self.poll = Some(requests);
+            false
+        }
+    }
+
+    /// Completes the pending poll if the socket that just raised a readiness event is part of it.
+    pub fn on_socket_ready(&mut self, sid: SocketId) -> bool {
+        let completed = match self.poll {
+            Some(ref requests) => requests.iter().any(|r| r.0 == sid) && self.sockets.is_any_ready(requests),
+            None => false
+        };
+
+        if completed {
+            self.on_poll_timeout();
+        }
+
+        completed
+    }
+
+    pub fn on_poll_timeout(&mut self) {
+        if let Some(requests) = self.poll.take() {
+            self.send_poll_reply(&requests);
+        }
+    }
+
+    fn send_poll_reply(&self, requests: &[(SocketId, bool, bool)]) {
+        let readiness = self.sockets.poll(requests);
+
+        self.send_reply(Reply::Poll(readiness));
+    }
 }
 
 impl SocketCollection {
@@ -127,6 +180,25 @@ impl SocketCollection {
     fn remove(&mut self, id: SocketId) {
         self.sockets.remove(&id);
     }
+
+    fn has_closing(&self) -> bool {
+        self.sockets.values().any(|socket| socket.is_closing())
+    }
+
+    fn poll(&self, requests: &[(SocketId, bool, bool)]) -> Vec<(bool, bool)> {
+        requests.iter().map(|&(sid, send, recv)| self.poll_socket(sid, send, recv)).collect()
+    }
+
+    fn poll_socket(&self, sid: SocketId, send: bool, recv: bool) -> (bool, bool) {
+        match self.sockets.get(&sid) {
+            Some(socket) => (send && socket.can_send(), recv && socket.can_recv()),
+            None => (false, false)
+        }
+    }
+
+    fn is_any_ready(&self, requests: &[(SocketId, bool, bool)]) -> bool {
+        self.poll(requests).iter().any(|&(send, recv)| send || recv)
+    }
 }
 
 impl DeviceCollection {
@@ -4,24 +4,50 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::collections::HashMap;
-use std::sync::mpsc::Sender;
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, Sender, Receiver};
 use std::io;
 use std::boxed::FnBox;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use std::cmp;
+#[cfg(not(windows))]
+use std::os::unix::io::RawFd;
 
-use super::{SocketId, EndpointId, Message, EndpointSpec};
-use super::endpoint::{Pipe, Acceptor};
-use super::config::{Config, ConfigOption};
+use rand::{self, Rng};
+
+use super::{SocketId, EndpointId, ContextId, Message, EndpointSpec};
+use super::endpoint::{self, Pipe, Acceptor, EndpointInfo, EndpointState, PipeStatus};
+use super::config::{Config, ConfigOption, ConfigOptionKind, EndpointOptions};
+use super::stats::{Statistics, Traffic};
 use super::context::{Context, Schedulable, Scheduled, Event};
+use sequence::Sequence;
 use io_error::*;
+#[cfg(not(windows))]
+use efd::Efd;
 
 pub enum Request {
-    Connect(String),
-    Bind(String),
+    Connect(String, EndpointOptions),
+    Bind(String, EndpointOptions),
     Send(Message),
+    TrySend(Message),
     Recv,
+    TryRecv,
+    Collect(Duration),
     SetOption(ConfigOption),
+    GetOption(ConfigOptionKind),
+    #[cfg(not(windows))]
+    SendFd,
+    #[cfg(not(windows))]
+    RecvFd,
+    SetNotifier(Notifier),
+    Monitor,
+    Statistics,
+    Endpoints,
+    CreateContext,
+    ContextSend(ContextId, Message),
+    ContextRecv(ContextId),
+    ContextCollect(ContextId, Duration),
+    CloseContext(ContextId),
     Close
 }
 
@@ -31,7 +57,40 @@ pub enum Reply {
     Bind(EndpointId),
     Send,
     Recv(Message),
-    SetOption
+    Collect(Vec<Message>),
+    SetOption,
+    GetOption(ConfigOption),
+    #[cfg(not(windows))]
+    Fd(RawFd),
+    SetNotifier,
+    Monitor(Receiver<MonitorEvent>),
+    Statistics(Statistics),
+    Endpoints(Vec<EndpointInfo>),
+    ContextCreated(ContextId, Receiver<Reply>),
+    Close
+}
+
+/// Called by the backend each time the socket has processed a request or an event,
+/// so that a facade waiting for a reply without blocking knows when to check again.
+pub type Notifier = Box<Fn() + Send>;
+
+/// Lifecycle events of the endpoints of a socket, see [Socket::monitor](../../struct.Socket.html#method.monitor).
+#[derive(Debug)]
+pub enum MonitorEvent {
+    /// A local endpoint is listening on the url, after a bind or a successful rebind.
+    Bound(EndpointId, String),
+    /// The local endpoint, identified by the first id, accepted a connection from a peer.
+    Accepted(EndpointId, EndpointId),
+    /// The handshake with the peer succeeded, the pipe is now used by the protocol.
+    Opened(EndpointId),
+    /// The pipe failed before being opened because the peer sent an invalid handshake.
+    HandshakeRejected(EndpointId, io::Error),
+    /// The pipe received a message larger than the `RecvMaxSize` option, it is closed right after.
+    MessageTooLong(EndpointId),
+    /// The pipe failed with the specified error.
+    Closed(EndpointId, io::Error),
+    /// A new attempt to establish the endpoint will be made after the specified delay.
+    ReconnectScheduled(EndpointId, Duration)
 }
 
 pub struct Socket {
@@ -39,8 +98,25 @@ pub struct Socket {
     reply_sender: Sender<Reply>,
     protocol: Box<Protocol>,
     pipes: HashMap<EndpointId, Pipe>,
+    pipe_ids: HashSet<EndpointId>,
     acceptors: HashMap<EndpointId, Acceptor>,
-    config: Config
+    config: Config,
+    retries: HashMap<EndpointId, (Scheduled, Instant)>,
+    closing_endpoints: HashMap<EndpointId, Sender<endpoint::Reply>>,
+    closing: bool,
+    linger: Option<Scheduled>,
+    #[cfg(not(windows))]
+    send_efd: Option<Efd>,
+    #[cfg(not(windows))]
+    recv_efd: Option<Efd>,
+    notifier: Option<Notifier>,
+    monitors: Vec<Sender<MonitorEvent>>,
+    stats: Statistics,
+    endpoint_stats: HashMap<EndpointId, Statistics>,
+    acceptor_ids: HashMap<EndpointId, EndpointId>,
+    urls: HashMap<EndpointId, String>,
+    context_ids: Sequence,
+    contexts: HashMap<ContextId, Sender<Reply>>
 }
 
 /*****************************************************************************/
@@ -57,21 +133,48 @@ pub trait Protocol {
     fn remove_pipe(&mut self, ctx: &mut Context, eid: EndpointId) -> Option<Pipe>;
 
     fn send(&mut self, ctx: &mut Context, msg: Message, timeout: Option<Scheduled>);
+    fn try_send(&mut self, ctx: &mut Context, msg: Message);
     fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId);
     fn on_send_timeout(&mut self, ctx: &mut Context);
     fn on_send_ready(&mut self, ctx: &mut Context, eid: EndpointId);
+    fn can_send(&self) -> bool;
     
     fn recv(&mut self, ctx: &mut Context, timeout: Option<Scheduled>);
+    fn try_recv(&mut self, ctx: &mut Context);
     fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, msg: Message);
     fn on_recv_timeout(&mut self, ctx: &mut Context);
     fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId);
+    fn can_recv(&self) -> bool;
+
+    /// Receives all the messages arriving until the deadline, and replies them at once.
+    fn collect(&mut self, _: &mut Context, _: Duration) -> io::Result<()> {
+        Err(other_io_error("collect not supported by the protocol"))
+    }
 
     fn set_option(&mut self, _: ConfigOption) -> io::Result<()> {
         Err(invalid_input_io_error("option not supported"))
     }
+    fn get_option(&self, _: ConfigOptionKind) -> io::Result<ConfigOption> {
+        Err(invalid_input_io_error("option not supported"))
+    }
     fn on_timer_tick(&mut self, _: &mut Context, _: Schedulable) {
     }
-    fn on_device_plugged(&mut self, _: &mut Context) {}
+    /// Switches the protocol to raw mode, where messages are exchanged with their protocol header.
+    fn set_raw(&mut self) {}
+
+    /// Registers a context, an independent sequence of operations sharing the pipes of the socket.
+    /// The replies of the context operations are sent through the specified channel.
+    fn create_context(&mut self, _: ContextId, _: Sender<Reply>) -> io::Result<()> {
+        Err(other_io_error("contexts not supported by the protocol"))
+    }
+    fn context_send(&mut self, _: &mut Context, _: ContextId, _: Message, _: Option<Scheduled>) {}
+    fn on_context_send_timeout(&mut self, _: &mut Context, _: ContextId) {}
+    fn context_recv(&mut self, _: &mut Context, _: ContextId, _: Option<Scheduled>) {}
+    fn on_context_recv_timeout(&mut self, _: &mut Context, _: ContextId) {}
+    fn context_collect(&mut self, _: &mut Context, _: ContextId, _: Duration) -> io::Result<()> {
+        Err(other_io_error("collect not supported by the protocol"))
+    }
+    fn close_context(&mut self, _: &mut Context, _: ContextId) {}
     fn close(&mut self, ctx: &mut Context);
 }
 
@@ -90,8 +193,25 @@ impl Socket {
             reply_sender: reply_tx,
             protocol: proto,
             pipes: HashMap::new(),
+            pipe_ids: HashSet::new(),
             acceptors: HashMap::new(),
-            config: Config::default()
+            config: Config::default(),
+            retries: HashMap::new(),
+            closing_endpoints: HashMap::new(),
+            closing: false,
+            linger: None,
+            #[cfg(not(windows))]
+            send_efd: None,
+            #[cfg(not(windows))]
+            recv_efd: None,
+            notifier: None,
+            monitors: Vec::new(),
+            stats: Statistics::default(),
+            endpoint_stats: HashMap::new(),
+            acceptor_ids: HashMap::new(),
+            urls: HashMap::new(),
+            context_ids: Sequence::new(),
+            contexts: HashMap::new()
         }
     }
 
@@ -112,38 +232,61 @@ impl Socket {
 /*                                                                           */
 /*****************************************************************************/
 
-    pub fn connect(&mut self, ctx: &mut Context, url: String) {
+    pub fn connect(&mut self, ctx: &mut Context, url: String, options: EndpointOptions) {
+        if let Err(e) = options.validate() {
+            return self.send_reply(Reply::Err(e));
+        }
+
         let pids = self.get_protocol_ids();
+        let config = self.config.endpoint_config(&options);
 
-        match ctx.connect(self.id, &url, pids) {
-            Ok(id) => self.on_connect_success(ctx, url, id),
+        match ctx.connect(self.id, &url, pids, &config) {
+            Ok(id) => self.on_connect_success(ctx, url, id, &config, options),
             Err(e) => self.on_connect_error(e)
         };
     }
 
-    fn on_connect_success(&mut self, ctx: &mut Context, url: String, eid: EndpointId) {
-        let pipe = self.connect_pipe(eid, url);
+    fn on_connect_success(&mut self, ctx: &mut Context, url: String, eid: EndpointId, config: &Config, options: EndpointOptions) {
+        self.urls.insert(eid, url.clone());
+
+        let pipe = Pipe::new_connected(eid, url, config.send_priority, config.recv_priority, options);
 
+        self.endpoint_stats.insert(eid, Statistics::default());
         self.insert_pipe(ctx, eid, pipe);
         self.send_reply(Reply::Connect(eid));
     }
 
     fn on_connect_error(&mut self, err: io::Error) {
+        self.stats.connect_errors += 1;
         self.send_reply(Reply::Err(err));
     }
 
-    fn schedule_reconnect(&mut self, ctx: &mut Context, spec: EndpointSpec) {
+    fn schedule_reconnect(&mut self, ctx: &mut Context, mut spec: EndpointSpec) {
+        let ivl = next_retry_ivl(&self.config.endpoint_config(&spec.options), spec.retry_ivl);
+        let delay = randomize_retry_ivl(ivl);
+
+        spec.retry_ivl = Some(ivl);
+
+        let eid = spec.id;
         let task = Schedulable::Reconnect(spec);
-        let delay = self.config.retry_ivl;
-        let _ = ctx.schedule(task, delay); 
-        // TODO maybe we should keep track of the scheduled reconnection
-        // In case the facade wants to close the ep somewhere between the error and the timeout
+
+        if let Ok(scheduled) = ctx.schedule(task, delay) {
+            self.retries.insert(eid, (scheduled, Instant::now() + delay));
+            self.emit(|| MonitorEvent::ReconnectScheduled(eid, delay));
+        }
     }
 
     pub fn reconnect(&mut self, ctx: &mut Context, spec: EndpointSpec) {
+        self.retries.remove(&spec.id);
+
+        if self.closing {
+            return;
+        }
+
         let pids = self.get_protocol_ids();
+        let config = self.config.endpoint_config(&spec.options);
 
-        match ctx.reconnect(self.id, spec.id, &spec.url, pids) {
+        match ctx.reconnect(self.id, spec.id, &spec.url, pids, &config) {
             Ok(_)  => self.on_reconnect_success(ctx, spec),
             Err(_) => self.on_reconnect_error(ctx, spec)
         }
@@ -154,6 +297,7 @@ impl Socket {
     }
 
     fn on_reconnect_error(&mut self, ctx: &mut Context, spec: EndpointSpec) {
+        self.count(spec.id, |stats| stats.connect_errors += 1);
         self.schedule_reconnect(ctx, spec);
     }
 
@@ -163,50 +307,76 @@ impl Socket {
 /*                                                                           */
 /*****************************************************************************/
 
-    pub fn bind(&mut self, ctx: &mut Context, url: String) {
+    pub fn bind(&mut self, ctx: &mut Context, url: String, options: EndpointOptions) {
+        if let Err(e) = options.validate() {
+            return self.send_reply(Reply::Err(e));
+        }
+
         let pids = self.get_protocol_ids();
+        let config = self.config.endpoint_config(&options);
 
-        match ctx.bind(self.id, &url, pids) {
-            Ok(id) => self.on_bind_success(ctx, url, id),
+        match ctx.bind(self.id, &url, pids, &config) {
+            Ok(id) => self.on_bind_success(ctx, url, id, &config, options),
             Err(e) => self.on_bind_error(e)
         };
     }
 
-    fn on_bind_success(&mut self, ctx: &mut Context, url: String, eid: EndpointId) {
-        let acceptor = self.connect_acceptor(eid, url);
+    fn on_bind_success(&mut self, ctx: &mut Context, url: String, eid: EndpointId, config: &Config, options: EndpointOptions) {
+        self.emit(|| MonitorEvent::Bound(eid, url.clone()));
+        self.urls.insert(eid, url.clone());
+
+        let acceptor = Acceptor::new(eid, url, config.send_priority, config.recv_priority, options);
 
         acceptor.open(ctx);
 
+        self.endpoint_stats.insert(eid, Statistics::default());
         self.acceptors.insert(eid, acceptor);
         self.send_reply(Reply::Bind(eid));
     }
 
     fn on_bind_error(&mut self, err: io::Error) {
+        self.stats.bind_errors += 1;
         self.send_reply(Reply::Err(err));
     }
 
-    fn schedule_rebind(&mut self, ctx: &mut Context, spec: EndpointSpec) {
+    fn schedule_rebind(&mut self, ctx: &mut Context, mut spec: EndpointSpec) {
+        let ivl = next_retry_ivl(&self.config.endpoint_config(&spec.options), spec.retry_ivl);
+        let delay = randomize_retry_ivl(ivl);
+
+        spec.retry_ivl = Some(ivl);
+
+        let eid = spec.id;
         let task = Schedulable::Rebind(spec);
-        let delay = self.config.retry_ivl;
-        let _ = ctx.schedule(task, delay); 
-        // TODO maybe we should keep track of the scheduled reconnection
-        // In case the facade wants to close the ep somewhere between the error and the timeout
+
+        if let Ok(scheduled) = ctx.schedule(task, delay) {
+            self.retries.insert(eid, (scheduled, Instant::now() + delay));
+            self.emit(|| MonitorEvent::ReconnectScheduled(eid, delay));
+        }
     }
 
     pub fn rebind(&mut self, ctx: &mut Context, spec: EndpointSpec) {
+        self.retries.remove(&spec.id);
+
+        if self.closing {
+            return;
+        }
+
         let pids = self.get_protocol_ids();
+        let config = self.config.endpoint_config(&spec.options);
 
-        match ctx.rebind(self.id, spec.id, &spec.url, pids) {
+        match ctx.rebind(self.id, spec.id, &spec.url, pids, &config) {
             Ok(_)  => self.on_rebind_success(ctx, spec),
             Err(_) => self.on_rebind_error(ctx, spec)
         };
     }
 
     fn on_rebind_success(&mut self, ctx: &mut Context, spec: EndpointSpec) {
+        self.emit(|| MonitorEvent::Bound(spec.id, spec.url.clone()));
         self.insert_acceptor(ctx, spec.id, Acceptor::from(spec))
     }
 
     fn on_rebind_error(&mut self, ctx: &mut Context, spec: EndpointSpec) {
+        self.count(spec.id, |stats| stats.bind_errors += 1);
         self.schedule_rebind(ctx, spec);
     }
 
@@ -217,8 +387,14 @@ impl Socket {
 /*****************************************************************************/
 
     pub fn on_pipe_opened(&mut self, ctx: &mut Context, eid: EndpointId) {
-        if let Some(pipe) = self.pipes.remove(&eid) {
+        if let Some(mut pipe) = self.pipes.remove(&eid) {
+            pipe.reset_retry_ivl();
             self.protocol.add_pipe(ctx, eid, pipe);
+            self.emit(|| MonitorEvent::Opened(eid));
+
+            if !self.acceptor_ids.contains_key(&eid) {
+                self.count(eid, |stats| stats.established_connections += 1);
+            }
         }
     }
 
@@ -226,18 +402,69 @@ impl Socket {
         let pipe = self.accept_pipe(aid, eid);
 
         self.insert_pipe(ctx, eid, pipe);
+        self.acceptor_ids.insert(eid, aid);
+        self.count(eid, |stats| stats.accepted_connections += 1);
+        self.emit(|| MonitorEvent::Accepted(aid, eid));
     }
 
-    pub fn on_pipe_error(&mut self, ctx: &mut Context, eid: EndpointId, _: io::Error) {
+    pub fn on_pipe_error(&mut self, ctx: &mut Context, eid: EndpointId, err: io::Error) {
+        if err.kind() == io::ErrorKind::InvalidData {
+            warn!("[{:?}] pipe {:?} closed: {}", ctx, eid, err);
+        }
+        if is_msg_too_long(&err) {
+            self.emit(|| MonitorEvent::MessageTooLong(eid));
+        }
+
+        if self.closing || self.closing_endpoints.contains_key(&eid) {
+            return self.on_pipe_closed(ctx, eid);
+        }
+
+        self.pipe_ids.remove(&eid);
+        self.report_pipe_error(eid, &err);
+        self.acceptor_ids.remove(&eid);
+
         if let Some(spec) = self.remove_pipe(ctx, eid) {
             self.schedule_reconnect(ctx, spec);
         }
     }
 
+    pub fn on_pipe_closed(&mut self, ctx: &mut Context, eid: EndpointId) {
+        self.on_endpoint_closed(eid);
+        self.acceptor_ids.remove(&eid);
+
+        if self.pipe_ids.remove(&eid) && self.closing && self.pipe_ids.is_empty() {
+            self.on_closed(ctx);
+        }
+    }
+
+    /// Pipes waiting for the handshake are not yet handed to the protocol.
+    /// When such a connected pipe fails for another reason than the handshake,
+    /// the peer could not be reached at all.
+    fn report_pipe_error(&mut self, eid: EndpointId, err: &io::Error) {
+        let handshaking = self.pipes.contains_key(&eid);
+        let rejected = err.kind() == io::ErrorKind::InvalidData;
+        let accepted = self.acceptor_ids.contains_key(&eid);
+
+        if !handshaking {
+            self.count(eid, |stats| stats.broken_connections += 1);
+        } else if rejected || accepted {
+            self.count(eid, |stats| stats.dropped_connections += 1);
+        } else {
+            self.count(eid, |stats| stats.connect_errors += 1);
+        }
+
+        if handshaking && rejected {
+            self.emit(|| MonitorEvent::HandshakeRejected(eid, clone_io_error(err)));
+        } else {
+            self.emit(|| MonitorEvent::Closed(eid, clone_io_error(err)));
+        }
+    }
+
     fn insert_pipe(&mut self, ctx: &mut Context, eid: EndpointId, pipe: Pipe) {
         pipe.open(ctx);
 
         self.pipes.insert(eid, pipe);
+        self.pipe_ids.insert(eid);
     }
 
     fn remove_pipe(&mut self, ctx: &mut Context, eid: EndpointId) -> Option<EndpointSpec> {
@@ -250,10 +477,6 @@ impl Socket {
         None
     }
 
-    fn connect_pipe(&self, eid: EndpointId, url: String) -> Pipe {
-        Pipe::new_connected(eid, url, self.config.send_priority, self.config.recv_priority)
-    }
-
     fn accept_pipe(&self, aid: EndpointId, eid: EndpointId) -> Pipe {
         let (send_prio, recv_prio) = if let Some(acceptor) = self.acceptors.get(&aid) {
             (acceptor.get_send_priority(), acceptor.get_recv_priority())
@@ -271,11 +494,17 @@ impl Socket {
 /*****************************************************************************/
 
     pub fn on_acceptor_error(&mut self, ctx: &mut Context, eid: EndpointId, _: io::Error) {
+        self.count(eid, |stats| stats.accept_errors += 1);
+
         if let Some(spec) = self.remove_acceptor(ctx, eid) {
             self.schedule_rebind(ctx, spec);
         }
     }
 
+    pub fn on_acceptor_closed(&mut self, _: &mut Context, eid: EndpointId) {
+        self.on_endpoint_closed(eid);
+    }
+
     fn insert_acceptor(&mut self, ctx: &mut Context, eid: EndpointId, acceptor: Acceptor) {
         acceptor.open(ctx);
 
@@ -286,12 +515,79 @@ impl Socket {
         self.acceptors.remove(&eid).map_or(None, |acceptor| acceptor.close(ctx))
     }
 
-    fn connect_acceptor(&self, eid: EndpointId, url: String) -> Acceptor {
-        Acceptor::new(
-            eid,
-            url,
-            self.config.send_priority,
-            self.config.recv_priority)
+/*****************************************************************************/
+/*                                                                           */
+/* endpoint                                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+    pub fn close_endpoint(&mut self, ctx: &mut Context, eid: EndpointId, remote: bool, reply_tx: Sender<endpoint::Reply>) {
+        if let Some((scheduled, _)) = self.retries.remove(&eid) {
+            ctx.cancel(scheduled);
+            self.endpoint_stats.remove(&eid);
+            self.urls.remove(&eid);
+            let _ = reply_tx.send(endpoint::Reply::Close);
+            return;
+        }
+
+        let found = if remote {
+            self.close_pipe(ctx, eid)
+        } else {
+            self.close_acceptor(ctx, eid)
+        };
+
+        if found {
+            self.closing_endpoints.insert(eid, reply_tx);
+        } else {
+            let _ = reply_tx.send(endpoint::Reply::Err(invalid_input_io_error("unknown endpoint")));
+        }
+    }
+
+    fn close_pipe(&mut self, ctx: &mut Context, eid: EndpointId) -> bool {
+        let pipe = match self.pipes.remove(&eid) {
+            Some(pipe) => Some(pipe),
+            None => self.protocol.remove_pipe(ctx, eid)
+        };
+
+        match pipe {
+            Some(pipe) => {
+                // Removing a single endpoint does not linger,
+                // so the pipe is closed again in case it has pending sends.
+                pipe.close(ctx);
+                ctx.close(eid, true);
+                true
+            },
+            None => false
+        }
+    }
+
+    fn close_acceptor(&mut self, ctx: &mut Context, eid: EndpointId) -> bool {
+        match self.acceptors.remove(&eid) {
+            Some(acceptor) => {
+                acceptor.close(ctx);
+
+                for pid in self.accepted_by(eid) {
+                    self.close_pipe(ctx, pid);
+                }
+                true
+            },
+            None => false
+        }
+    }
+
+    fn accepted_by(&self, aid: EndpointId) -> Vec<EndpointId> {
+        self.acceptor_ids.iter().
+            filter(|&(_, id)| *id == aid).
+            map(|(pid, _)| *pid).
+            collect()
+    }
+
+    fn on_endpoint_closed(&mut self, eid: EndpointId) {
+        if let Some(reply_tx) = self.closing_endpoints.remove(&eid) {
+            self.endpoint_stats.remove(&eid);
+            self.urls.remove(&eid);
+            let _ = reply_tx.send(endpoint::Reply::Close);
+        }
     }
 
 /*****************************************************************************/
@@ -313,6 +609,10 @@ impl Socket {
         }
     }
 
+    pub fn try_send(&mut self, ctx: &mut Context, msg: Message) {
+        self.protocol.try_send(ctx, msg);
+    }
+
     pub fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId) {
         self.protocol.on_send_ack(ctx, eid);
     }
@@ -329,6 +629,10 @@ impl Socket {
         self.protocol.on_send_ready(ctx, eid)
     }
 
+    pub fn can_send(&self) -> bool {
+        self.protocol.can_send()
+    }
+
 /*****************************************************************************/
 /*                                                                           */
 /* recv                                                                      */
@@ -348,6 +652,10 @@ impl Socket {
         }
     }
 
+    pub fn try_recv(&mut self, ctx: &mut Context) {
+        self.protocol.try_recv(ctx);
+    }
+
     pub fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, msg: Message) {
         self.protocol.on_recv_ack(ctx, eid, msg);
     }
@@ -364,211 +672,1395 @@ impl Socket {
         self.protocol.on_recv_ready(ctx, eid)
     }
 
+    pub fn can_recv(&self) -> bool {
+        self.protocol.can_recv()
+    }
+
+    /// The deadline bounds the operation, the recv timeout does not apply.
+    pub fn collect(&mut self, ctx: &mut Context, deadline: Duration) {
+        if let Err(e) = self.protocol.collect(ctx, deadline) {
+            self.send_reply(Reply::Err(e));
+        }
+    }
+
 /*****************************************************************************/
 /*                                                                           */
-/* options                                                                   */
+/* contexts                                                                  */
 /*                                                                           */
 /*****************************************************************************/
 
-    pub fn set_option(&mut self, _: &mut Context, opt: ConfigOption) {
-        let res = if opt.is_generic() {
-            self.config.set(opt)
-        } else {
-            self.protocol.set_option(opt)
-        };
-        let reply = match res {
-            Ok(()) => Reply::SetOption,
-            Err(e) => Reply::Err(e)
-        };
+    pub fn create_context(&mut self) {
+        let cid = ContextId::from(self.context_ids.next());
+        let (reply_tx, reply_rx) = mpsc::channel();
 
-        self.send_reply(reply);
+        match self.protocol.create_context(cid, reply_tx.clone()) {
+            Ok(()) => {
+                self.contexts.insert(cid, reply_tx);
+                self.send_reply(Reply::ContextCreated(cid, reply_rx));
+            },
+            Err(e) => self.send_reply(Reply::Err(e))
+        }
     }
 
-    pub fn on_timer_tick(&mut self, ctx: &mut Context, task: Schedulable) {
-        self.protocol.on_timer_tick(ctx, task)
+    pub fn context_send(&mut self, ctx: &mut Context, cid: ContextId, msg: Message) {
+        if let Some(delay) = self.get_send_timeout() {
+            let task = Schedulable::ContextSendTimeout(cid);
+
+            match ctx.schedule(task, delay) {
+                Ok(timeout) => self.protocol.context_send(ctx, cid, msg, Some(timeout)),
+                Err(e) => self.send_context_reply(cid, Reply::Err(e))
+            }
+        } else {
+            self.protocol.context_send(ctx, cid, msg, None);
+        }
     }
 
-    pub fn on_device_plugged(&mut self, ctx: &mut Context) {
-        self.protocol.on_device_plugged(ctx)
+    pub fn on_context_send_timeout(&mut self, ctx: &mut Context, cid: ContextId) {
+        self.protocol.on_context_send_timeout(ctx, cid);
     }
 
-    pub fn close(&mut self, ctx: &mut Context) {
-        for (_, pipe) in self.pipes.drain() {
-            pipe.close(ctx);
+    pub fn context_recv(&mut self, ctx: &mut Context, cid: ContextId) {
+        if let Some(delay) = self.get_recv_timeout() {
+            let task = Schedulable::ContextRecvTimeout(cid);
+
+            match ctx.schedule(task, delay) {
+                Ok(timeout) => self.protocol.context_recv(ctx, cid, Some(timeout)),
+                Err(e) => self.send_context_reply(cid, Reply::Err(e))
+            }
+        } else {
+            self.protocol.context_recv(ctx, cid, None);
         }
-        for (_, acceptor) in self.acceptors.drain() {
-            acceptor.close(ctx);
+    }
+
+    pub fn on_context_recv_timeout(&mut self, ctx: &mut Context, cid: ContextId) {
+        self.protocol.on_context_recv_timeout(ctx, cid);
+    }
+
+    /// Operations pending on the context are abandoned, nobody waits for their replies anymore.
+    pub fn context_collect(&mut self, ctx: &mut Context, cid: ContextId, deadline: Duration) {
+        if let Err(e) = self.protocol.context_collect(ctx, cid, deadline) {
+            self.send_context_reply(cid, Reply::Err(e));
         }
+    }
 
-        self.protocol.close(ctx);
+    pub fn close_context(&mut self, ctx: &mut Context, cid: ContextId) {
+        if self.contexts.remove(&cid).is_some() {
+            self.protocol.close_context(ctx, cid);
+        }
+    }
 
-        ctx.raise(Event::Closed);
+    fn send_context_reply(&self, cid: ContextId, reply: Reply) {
+        if let Some(reply_tx) = self.contexts.get(&cid) {
+            let _ = reply_tx.send(reply);
+        }
     }
-}
 
 /*****************************************************************************/
 /*                                                                           */
-/* tests                                                                     */
+/* readiness fds                                                             */
 /*                                                                           */
 /*****************************************************************************/
 
-#[cfg(test)]
-mod tests {
-    use std::fmt;
-    use std::rc::Rc;
-    use std::sync::mpsc;
-    use std::io;
-    use std::time::Duration;
-
-    use super::*;
-    use core::network::Network;
-    use core::context::*;
-    use core::{SocketId, EndpointId, Message};
-    use core::endpoint::Pipe;
-    use io_error::*;
-
-    struct TestProto;
+    #[cfg(not(windows))]
+    pub fn send_fd(&mut self) {
+        let ready = self.protocol.can_send();
+        let reply = open_efd(&mut self.send_efd, ready).map(Reply::Fd).unwrap_or_else(Reply::Err);
 
-    impl Protocol for TestProto {
-        fn id(&self) -> u16 {0}
-        fn peer_id(&self) -> u16 {0}
-        fn add_pipe(&mut self, _: &mut Context, _: EndpointId, _: Pipe) {}
-        fn remove_pipe(&mut self, _: &mut Context, _: EndpointId) -> Option<Pipe> {None}
-        fn send(&mut self, _: &mut Context, _: Message, _: Option<Scheduled>) {}
-        fn on_send_ack(&mut self, _: &mut Context, _: EndpointId) {}
-        fn on_send_timeout(&mut self, _: &mut Context) {}
-        fn on_send_ready(&mut self, _: &mut Context, _: EndpointId) {}
-        fn recv(&mut self, _: &mut Context, _: Option<Scheduled>) {}
-        fn on_recv_ack(&mut self, _: &mut Context, _: EndpointId, _: Message) {}
-        fn on_recv_timeout(&mut self, _: &mut Context) {}
-        fn on_recv_ready(&mut self, _: &mut Context, _: EndpointId) {}
-        fn close(&mut self, _: &mut Context) {}
+        self.send_reply(reply);
     }
 
-    struct FailingNetwork;
+    #[cfg(not(windows))]
+    pub fn recv_fd(&mut self) {
+        let ready = self.protocol.can_recv();
+        let reply = open_efd(&mut self.recv_efd, ready).map(Reply::Fd).unwrap_or_else(Reply::Err);
 
-    impl Network for FailingNetwork {
-        fn connect(&mut self, _: SocketId, _: &str, _: (u16, u16)) -> io::Result<EndpointId> {
-            Err(other_io_error("FailingNetwork can only fail"))
-        }
-        fn reconnect(&mut self, _: SocketId, _: EndpointId, _: &str, _: (u16, u16)) -> io::Result<()> {
-            Err(other_io_error("FailingNetwork can only fail"))
-        }
-        fn bind(&mut self, _: SocketId, _: &str, _: (u16, u16)) -> io::Result<EndpointId> {
-            Err(other_io_error("FailingNetwork can only fail"))
-        }
-        fn rebind(&mut self, _: SocketId, _: EndpointId, _: &str, _: (u16, u16)) -> io::Result<()> {
-            Err(other_io_error("FailingNetwork can only fail"))
-        }
-        fn open(&mut self, _: EndpointId, _: bool) {
-        }
-        fn close(&mut self, _: EndpointId, _: bool) {
-        }
-        fn send(&mut self, _: EndpointId, _: Rc<Message>) {
-        }
-        fn recv(&mut self, _: EndpointId) {
-        }
+        self.send_reply(reply);
     }
 
-    impl Scheduler for FailingNetwork {
-        fn schedule(&mut self, _: Schedulable, _: Duration) -> io::Result<Scheduled> {
-            Err(other_io_error("FailingNetwork can only fail"))
+    /// Signals or clears the readiness fds, if they were requested,
+    /// so that they reflect what the protocol can do right now.
+    #[cfg(not(windows))]
+    pub fn update_fds(&mut self) {
+        if let Some(ref mut efd) = self.send_efd {
+            efd.set(self.protocol.can_send());
         }
-        fn cancel(&mut self, _: Scheduled){
+        if let Some(ref mut efd) = self.recv_efd {
+            efd.set(self.protocol.can_recv());
         }
     }
 
-    impl fmt::Debug for FailingNetwork {
-        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-            write!(f, "FailingNetwork")
-        }
-    }
+/*****************************************************************************/
+/*                                                                           */
+/* monitor                                                                   */
+/*                                                                           */
+/*****************************************************************************/
 
-    impl Context for FailingNetwork {
-        fn raise(&mut self, _: Event) {
-        }
+    pub fn monitor(&mut self) {
+        let (tx, rx) = mpsc::channel();
+
+        self.monitors.push(tx);
+        self.send_reply(Reply::Monitor(rx));
     }
 
-    #[test]
-    fn when_connect_fails() {
-        let id = SocketId::from(1);
-        let (tx, rx) = mpsc::channel();
-        let proto = Box::new(TestProto) as Box<Protocol>;
-        let mut network = FailingNetwork;
-        let mut socket = Socket::new(id, tx, proto);
+    /// Builds the event for each monitor, dropping the ones whose receiver is gone.
+    fn emit<F : Fn() -> MonitorEvent>(&mut self, event: F) {
+        self.monitors.retain(|tx| tx.send(event()).is_ok());
+    }
 
-        socket.connect(&mut network, String::from("test://fake"));
+/*****************************************************************************/
+/*                                                                           */
+/* statistics                                                                */
+/*                                                                           */
+/*****************************************************************************/
 
-        let reply = rx.recv().expect("Socket should have sent a reply to the connect request");
+    /// The traffic of the live pipes is maintained by the backend and provided by the caller.
+    pub fn statistics(&mut self, traffic: Vec<(EndpointId, Traffic)>) {
+        let mut stats = self.stats;
 
-        match reply {
-            Reply::Err(_) => {},
-            _ => {
-                assert!(false, "Socket should have replied an error to the connect request");
-            },
+        for &(_, ref pipe_traffic) in &traffic {
+            stats.add_traffic(pipe_traffic);
         }
+
+        self.set_gauges(&mut stats, |_| true);
+        self.send_reply(Reply::Statistics(stats));
     }
 
-    struct WorkingNetwork(EndpointId);
+    pub fn endpoint_statistics(&mut self, eid: EndpointId, traffic: Vec<(EndpointId, Traffic)>, reply_tx: Sender<endpoint::Reply>) {
+        let mut stats = match self.endpoint_stats.get(&eid) {
+            Some(stats) => *stats,
+            None => {
+                let _ = reply_tx.send(endpoint::Reply::Err(invalid_input_io_error("unknown endpoint")));
+                return;
+            }
+        };
 
-    impl Network for WorkingNetwork {
-        fn connect(&mut self, _: SocketId, _: &str, _: (u16, u16)) -> io::Result<EndpointId> {
-            Ok(self.0)
-        }
-        fn reconnect(&mut self, _: SocketId, _: EndpointId, _: &str, _: (u16, u16)) -> io::Result<()> {
-            Ok(())
-        }
-        fn bind(&mut self, _: SocketId, _: &str, _: (u16, u16)) -> io::Result<EndpointId> {
-            Ok(self.0)
-        }
-        fn rebind(&mut self, _: SocketId, _: EndpointId, _: &str, _: (u16, u16)) -> io::Result<()> {
-            Ok(())
+        for &(pid, ref pipe_traffic) in &traffic {
+            if self.owner_of(pid) == eid {
+                stats.add_traffic(pipe_traffic);
+            }
         }
-        fn open(&mut self, _: EndpointId, _: bool) {}
-        fn close(&mut self, _: EndpointId, _: bool) {}
-        fn send(&mut self, _: EndpointId, _: Rc<Message>) {}
-        fn recv(&mut self, _: EndpointId) {}
-    }
 
-    impl Scheduler for WorkingNetwork {
-        fn schedule(&mut self, _: Schedulable, _: Duration) -> io::Result<Scheduled> {
-            Ok(Scheduled::from(0))
-        }
-        fn cancel(&mut self, _: Scheduled){
-        }
+        self.set_gauges(&mut stats, |id| self.owner_of(id) == eid);
+        let _ = reply_tx.send(endpoint::Reply::Statistics(stats));
     }
 
-    impl fmt::Debug for WorkingNetwork {
-        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-            write!(f, "WorkingNetwork")
-        }
+    pub fn on_pipe_traffic(&mut self, eid: EndpointId, traffic: Traffic) {
+        self.count(eid, |stats| stats.add_traffic(&traffic));
     }
 
-    impl Context for WorkingNetwork {
-        fn raise(&mut self, _: Event) {
+    /// Updates the counters of the socket and of the endpoint owning the pipe.
+    fn count<F : Fn(&mut Statistics)>(&mut self, eid: EndpointId, update: F) {
+        let owner = self.owner_of(eid);
+
+        update(&mut self.stats);
 
+        if let Some(stats) = self.endpoint_stats.get_mut(&owner) {
+            update(stats);
+        }
+    }
+
+    /// Accepted pipes are accounted to the endpoint that accepted them.
+    fn owner_of(&self, eid: EndpointId) -> EndpointId {
+        self.acceptor_ids.get(&eid).cloned().unwrap_or(eid)
+    }
+
+    /// Gauges are computed from the current state of the pipes matching the filter.
+    fn set_gauges<F : Fn(EndpointId) -> bool>(&self, stats: &mut Statistics, filter: F) {
+        let opened = |eid: &&EndpointId| {
+            !self.pipes.contains_key(eid) && !self.closing_endpoints.contains_key(eid)
+        };
+
+        stats.inprogress_connections = self.pipes.keys().filter(|eid| filter(**eid)).count() as u64;
+        stats.current_connections = self.pipe_ids.iter().filter(|eid| filter(**eid)).filter(opened).count() as u64;
+        stats.current_ep_errors = self.retries.keys().filter(|eid| filter(**eid)).count() as u64;
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* endpoints                                                                 */
+/*                                                                           */
+/*****************************************************************************/
+
+    /// The status of the live pipes is maintained by the backend and provided by the caller.
+    pub fn endpoints(&mut self, pipes: Vec<(EndpointId, PipeStatus)>) {
+        let pipes = pipes.into_iter().collect();
+        let mut infos: Vec<EndpointInfo> = self.urls.keys().map(|eid| self.describe(*eid, &pipes)).collect();
+
+        infos.sort_by_key(|info| info.id);
+        self.send_reply(Reply::Endpoints(infos));
+    }
+
+    pub fn endpoint_info(&mut self, eid: EndpointId, pipes: Vec<(EndpointId, PipeStatus)>, reply_tx: Sender<endpoint::Reply>) {
+        let reply = if self.urls.contains_key(&eid) {
+            endpoint::Reply::Info(self.describe(eid, &pipes.into_iter().collect()))
+        } else {
+            endpoint::Reply::Err(invalid_input_io_error("unknown endpoint"))
+        };
+
+        let _ = reply_tx.send(reply);
+    }
+
+    fn describe(&self, eid: EndpointId, pipes: &HashMap<EndpointId, PipeStatus>) -> EndpointInfo {
+        let accepted = self.accepted_by(eid);
+        let state = if let Some(&(_, due)) = self.retries.get(&eid) {
+            EndpointState::Retrying(due)
+        } else if self.acceptors.contains_key(&eid) {
+            EndpointState::Listening
+        } else if self.pipes.contains_key(&eid) {
+            pipes.get(&eid).map_or(EndpointState::Connecting, |status| status.state)
+        } else {
+            EndpointState::Active
+        };
+
+        EndpointInfo {
+            id: eid,
+            url: self.urls.get(&eid).cloned().unwrap_or_default(),
+            state: state,
+            accepted_pipes: accepted.len(),
+            peer_addrs: accepted.iter().filter_map(|pid| pipes.get(pid).and_then(|status| status.peer_addr.clone())).collect()
+        }
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* options                                                                   */
+/*                                                                           */
+/*****************************************************************************/
+
+    pub fn set_option(&mut self, _: &mut Context, opt: ConfigOption) {
+        let res = if opt.is_generic() {
+            self.config.set(opt)
+        } else {
+            self.protocol.set_option(opt)
+        };
+        let reply = match res {
+            Ok(()) => Reply::SetOption,
+            Err(e) => Reply::Err(e)
+        };
+
+        self.send_reply(reply);
+    }
+
+    pub fn get_option(&mut self, kind: ConfigOptionKind) {
+        let res = if kind.is_generic() {
+            self.config.get(kind)
+        } else {
+            self.protocol.get_option(kind)
+        };
+        let reply = match res {
+            Ok(opt) => Reply::GetOption(opt),
+            Err(e) => Reply::Err(e)
+        };
+
+        self.send_reply(reply);
+    }
+
+    pub fn set_notifier(&mut self, notifier: Notifier) {
+        self.notifier = Some(notifier);
+        self.send_reply(Reply::SetNotifier);
+    }
+
+    pub fn notify(&self) {
+        if let Some(ref notifier) = self.notifier {
+            notifier();
+        }
+    }
+
+    pub fn on_timer_tick(&mut self, ctx: &mut Context, task: Schedulable) {
+        self.protocol.on_timer_tick(ctx, task)
+    }
+
+    /// Devices forward the messages between sockets as they are, header included.
+    pub fn on_device_plugged(&mut self, _: &mut Context) {
+        self.protocol.set_raw()
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* close                                                                     */
+/*                                                                           */
+/*****************************************************************************/
+
+    /// Pipes that are still sending a message when closed keep on trying until the linger period is over,
+    /// the socket is disposed of when all of its pipes are closed.
+    pub fn close(&mut self, ctx: &mut Context) {
+        for (_, pipe) in self.pipes.drain() {
+            pipe.close(ctx);
+        }
+        for (_, acceptor) in self.acceptors.drain() {
+            acceptor.close(ctx);
+        }
+
+        self.protocol.close(ctx);
+        self.closing = true;
+        self.send_reply(Reply::Close);
+
+        if self.pipe_ids.is_empty() {
+            return self.on_closed(ctx);
+        }
+        if self.config.linger == Duration::from_millis(0) {
+            return self.on_linger_timeout(ctx);
+        }
+
+        match ctx.schedule(Schedulable::Linger, self.config.linger) {
+            Ok(timeout) => self.linger = Some(timeout),
+            Err(_) => self.on_linger_timeout(ctx)
+        }
+    }
+
+    pub fn is_closing(&self) -> bool {
+        self.closing
+    }
+
+    pub fn on_linger_timeout(&mut self, ctx: &mut Context) {
+        self.linger = None;
+
+        for eid in self.pipe_ids.drain() {
+            ctx.close(eid, true);
+        }
+
+        self.on_closed(ctx);
+    }
+
+    fn on_closed(&mut self, ctx: &mut Context) {
+        if let Some(timeout) = self.linger.take() {
+            ctx.cancel(timeout);
+        }
+
+        for (_, (scheduled, _)) in self.retries.drain() {
+            ctx.cancel(scheduled);
+        }
+        for (_, reply_tx) in self.closing_endpoints.drain() {
+            let _ = reply_tx.send(endpoint::Reply::Close);
+        }
+
+        self.pipe_ids.clear();
+
+        ctx.raise(Event::Closed);
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* retry interval                                                            */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Doubles the previous interval up to `retry_ivl_max`, starting from `retry_ivl`.
+fn next_retry_ivl(config: &Config, previous: Option<Duration>) -> Duration {
+    let ivl = config.retry_ivl;
+    let ivl_max = match config.retry_ivl_max {
+        Some(ivl_max) if ivl_max > ivl => ivl_max,
+        _ => return ivl
+    };
+
+    match previous {
+        Some(previous) => cmp::min(previous.checked_mul(2).unwrap_or(ivl_max), ivl_max),
+        None => ivl
+    }
+}
+
+/// Picks a delay between the half and the whole of the interval
+/// so that peers disconnected at the same time do not retry in lockstep.
+fn randomize_retry_ivl(ivl: Duration) -> Duration {
+    let millis = ivl.as_secs() * 1000 + (ivl.subsec_nanos() / 1_000_000) as u64;
+
+    if millis < 2 {
+        return ivl;
+    }
+
+    let jitter = rand::thread_rng().gen_range(0, millis / 2 + 1);
+
+    Duration::from_millis(millis - jitter)
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* readiness fds                                                             */
+/*                                                                           */
+/*****************************************************************************/
+
+/// The fd is created on first request and then kept until the socket is dropped.
+#[cfg(not(windows))]
+fn open_efd(slot: &mut Option<Efd>, ready: bool) -> io::Result<RawFd> {
+    if let Some(ref efd) = *slot {
+        return Ok(efd.get_fd());
+    }
+
+    let mut efd = try!(Efd::new());
+    let fd = efd.get_fd();
+
+    efd.set(ready);
+    *slot = Some(efd);
+
+    Ok(fd)
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* tests                                                                     */
+/*                                                                           */
+/*****************************************************************************/
+
+#[cfg(test)]
+mod tests {
+    use std::fmt;
+    use std::rc::Rc;
+    use std::sync::mpsc;
+    use std::io;
+    use std::time::Duration;
+
+    use super::*;
+    use core::network::Network;
+    use core::context::*;
+    use core::{SocketId, EndpointId, Message};
+    use core::endpoint::Pipe;
+    use io_error::*;
+
+    struct TestProto;
+
+    impl Protocol for TestProto {
+        fn id(&self) -> u16 {0}
+        fn peer_id(&self) -> u16 {0}
+        fn add_pipe(&mut self, _: &mut Context, _: EndpointId, _: Pipe) {}
+        fn remove_pipe(&mut self, _: &mut Context, _: EndpointId) -> Option<Pipe> {None}
+        fn send(&mut self, _: &mut Context, _: Message, _: Option<Scheduled>) {}
+        fn try_send(&mut self, _: &mut Context, _: Message) {}
+        fn on_send_ack(&mut self, _: &mut Context, _: EndpointId) {}
+        fn on_send_timeout(&mut self, _: &mut Context) {}
+        fn on_send_ready(&mut self, _: &mut Context, _: EndpointId) {}
+        fn can_send(&self) -> bool {false}
+        fn recv(&mut self, _: &mut Context, _: Option<Scheduled>) {}
+        fn try_recv(&mut self, _: &mut Context) {}
+        fn on_recv_ack(&mut self, _: &mut Context, _: EndpointId, _: Message) {}
+        fn on_recv_timeout(&mut self, _: &mut Context) {}
+        fn on_recv_ready(&mut self, _: &mut Context, _: EndpointId) {}
+        fn can_recv(&self) -> bool {false}
+        fn close(&mut self, _: &mut Context) {}
+    }
+
+    struct FailingNetwork;
+
+    impl Network for FailingNetwork {
+        fn connect(&mut self, _: SocketId, _: &str, _: (u16, u16), _: &Config) -> io::Result<EndpointId> {
+            Err(other_io_error("FailingNetwork can only fail"))
+        }
+        fn reconnect(&mut self, _: SocketId, _: EndpointId, _: &str, _: (u16, u16), _: &Config) -> io::Result<()> {
+            Err(other_io_error("FailingNetwork can only fail"))
+        }
+        fn bind(&mut self, _: SocketId, _: &str, _: (u16, u16), _: &Config) -> io::Result<EndpointId> {
+            Err(other_io_error("FailingNetwork can only fail"))
+        }
+        fn rebind(&mut self, _: SocketId, _: EndpointId, _: &str, _: (u16, u16), _: &Config) -> io::Result<()> {
+            Err(other_io_error("FailingNetwork can only fail"))
+        }
+        fn open(&mut self, _: EndpointId, _: bool) {
+        }
+        fn close(&mut self, _: EndpointId, _: bool) {
+        }
+        fn send(&mut self, _: EndpointId, _: Rc<Message>) {
+        }
+        fn recv(&mut self, _: EndpointId) {
+        }
+    }
+
+    impl Scheduler for FailingNetwork {
+        fn schedule(&mut self, _: Schedulable, _: Duration) -> io::Result<Scheduled> {
+            Err(other_io_error("FailingNetwork can only fail"))
+        }
+        fn cancel(&mut self, _: Scheduled){
+        }
+    }
+
+    impl fmt::Debug for FailingNetwork {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "FailingNetwork")
+        }
+    }
+
+    impl Context for FailingNetwork {
+        fn raise(&mut self, _: Event) {
         }
     }
 
     #[test]
-    fn when_connect_succeeds() {
+    fn when_connect_fails() {
         let id = SocketId::from(1);
         let (tx, rx) = mpsc::channel();
         let proto = Box::new(TestProto) as Box<Protocol>;
-        let mut network = WorkingNetwork(EndpointId::from(1));
+        let mut network = FailingNetwork;
         let mut socket = Socket::new(id, tx, proto);
 
-        socket.connect(&mut network, String::from("test://fake"));
+        socket.connect(&mut network, String::from("test://fake"), EndpointOptions::default());
 
         let reply = rx.recv().expect("Socket should have sent a reply to the connect request");
 
         match reply {
-            Reply::Connect(eid) => {
-                assert_eq!(EndpointId::from(1), eid);
-            },
+            Reply::Err(_) => {},
             _ => {
-                assert!(false, "Socket should have replied an ack to the connect request");
+                assert!(false, "Socket should have replied an error to the connect request");
             },
         }
     }
+
+    struct WorkingNetwork(EndpointId);
+
+    impl Network for WorkingNetwork {
+        fn connect(&mut self, _: SocketId, _: &str, _: (u16, u16), _: &Config) -> io::Result<EndpointId> {
+            Ok(self.0)
+        }
+        fn reconnect(&mut self, _: SocketId, _: EndpointId, _: &str, _: (u16, u16), _: &Config) -> io::Result<()> {
+            Ok(())
+        }
+        fn bind(&mut self, _: SocketId, _: &str, _: (u16, u16), _: &Config) -> io::Result<EndpointId> {
+            Ok(self.0)
+        }
+        fn rebind(&mut self, _: SocketId, _: EndpointId, _: &str, _: (u16, u16), _: &Config) -> io::Result<()> {
+            Ok(())
+        }
+        fn open(&mut self, _: EndpointId, _: bool) {}
+        fn close(&mut self, _: EndpointId, _: bool) {}
+        fn send(&mut self, _: EndpointId, _: Rc<Message>) {}
+        fn recv(&mut self, _: EndpointId) {}
+    }
+
+    impl Scheduler for WorkingNetwork {
+        fn schedule(&mut self, _: Schedulable, _: Duration) -> io::Result<Scheduled> {
+            Ok(Scheduled::from(0))
+        }
+        fn cancel(&mut self, _: Scheduled){
+        }
+    }
+
+    impl fmt::Debug for WorkingNetwork {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "WorkingNetwork")
+        }
+    }
+
+    impl Context for WorkingNetwork {
+        fn raise(&mut self, _: Event) {
+
+        }
+    }
+
+    #[test]
+    fn when_connect_succeeds() {
+        let id = SocketId::from(1);
+        let (tx, rx) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = WorkingNetwork(EndpointId::from(1));
+        let mut socket = Socket::new(id, tx, proto);
+
+        socket.connect(&mut network, String::from("test://fake"), EndpointOptions::default());
+
+        let reply = rx.recv().expect("Socket should have sent a reply to the connect request");
+
+        match reply {
+            Reply::Connect(eid) => {
+                assert_eq!(EndpointId::from(1), eid);
+            },
+            _ => {
+                assert!(false, "Socket should have replied an ack to the connect request");
+            },
+        }
+    }
+
+    #[derive(Default)]
+    struct ClosingNetwork {
+        closed_pipes: Vec<EndpointId>,
+        closed_events: usize
+    }
+
+    impl Network for ClosingNetwork {
+        fn connect(&mut self, _: SocketId, _: &str, _: (u16, u16), _: &Config) -> io::Result<EndpointId> {
+            Ok(EndpointId::from(1))
+        }
+        fn reconnect(&mut self, _: SocketId, _: EndpointId, _: &str, _: (u16, u16), _: &Config) -> io::Result<()> {
+            Ok(())
+        }
+        fn bind(&mut self, _: SocketId, _: &str, _: (u16, u16), _: &Config) -> io::Result<EndpointId> {
+            Ok(EndpointId::from(2))
+        }
+        fn rebind(&mut self, _: SocketId, _: EndpointId, _: &str, _: (u16, u16), _: &Config) -> io::Result<()> {
+            Ok(())
+        }
+        fn open(&mut self, _: EndpointId, _: bool) {}
+        fn close(&mut self, eid: EndpointId, _: bool) {
+            self.closed_pipes.push(eid);
+        }
+        fn send(&mut self, _: EndpointId, _: Rc<Message>) {}
+        fn recv(&mut self, _: EndpointId) {}
+    }
+
+    impl Scheduler for ClosingNetwork {
+        fn schedule(&mut self, _: Schedulable, _: Duration) -> io::Result<Scheduled> {
+            Ok(Scheduled::from(0))
+        }
+        fn cancel(&mut self, _: Scheduled){
+        }
+    }
+
+    impl fmt::Debug for ClosingNetwork {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "ClosingNetwork")
+        }
+    }
+
+    impl Context for ClosingNetwork {
+        fn raise(&mut self, evt: Event) {
+            if let Event::Closed = evt {
+                self.closed_events += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn when_closed_without_pipes() {
+        let (tx, _rx) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = ClosingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+
+        socket.close(&mut network);
+
+        assert_eq!(1, network.closed_events);
+    }
+
+    #[test]
+    fn when_closed_pipes_are_waited_for() {
+        let (tx, _rx) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = ClosingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+
+        socket.connect(&mut network, String::from("test://fake"), EndpointOptions::default());
+        socket.close(&mut network);
+
+        assert_eq!(vec![EndpointId::from(1)], network.closed_pipes);
+        assert_eq!(0, network.closed_events);
+
+        socket.on_pipe_closed(&mut network, EndpointId::from(1));
+
+        assert_eq!(1, network.closed_events);
+    }
+
+    #[test]
+    fn when_linger_expires_pipes_are_closed_again() {
+        let (tx, _rx) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = ClosingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+
+        socket.connect(&mut network, String::from("test://fake"), EndpointOptions::default());
+        socket.close(&mut network);
+        socket.on_linger_timeout(&mut network);
+
+        assert_eq!(vec![EndpointId::from(1), EndpointId::from(1)], network.closed_pipes);
+        assert_eq!(1, network.closed_events);
+
+        socket.on_pipe_closed(&mut network, EndpointId::from(1));
+
+        assert_eq!(1, network.closed_events);
+    }
+
+    #[test]
+    fn when_linger_is_zero_pipes_are_closed_again_immediately() {
+        let (tx, _rx) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = ClosingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+
+        socket.set_option(&mut network, ConfigOption::Linger(Duration::from_millis(0)));
+        socket.connect(&mut network, String::from("test://fake"), EndpointOptions::default());
+        socket.close(&mut network);
+
+        assert_eq!(vec![EndpointId::from(1), EndpointId::from(1)], network.closed_pipes);
+        assert_eq!(1, network.closed_events);
+    }
+
+    #[derive(Default)]
+    struct RetryingNetwork {
+        reconnect_fails: bool,
+        retries: Vec<EndpointSpec>,
+        delays: Vec<Duration>
+    }
+
+    impl Network for RetryingNetwork {
+        fn connect(&mut self, _: SocketId, _: &str, _: (u16, u16), _: &Config) -> io::Result<EndpointId> {
+            Ok(EndpointId::from(1))
+        }
+        fn reconnect(&mut self, _: SocketId, _: EndpointId, _: &str, _: (u16, u16), _: &Config) -> io::Result<()> {
+            if self.reconnect_fails {
+                Err(other_io_error("RetryingNetwork was told to fail"))
+            } else {
+                Ok(())
+            }
+        }
+        fn bind(&mut self, _: SocketId, _: &str, _: (u16, u16), _: &Config) -> io::Result<EndpointId> {
+            Ok(EndpointId::from(2))
+        }
+        fn rebind(&mut self, _: SocketId, _: EndpointId, _: &str, _: (u16, u16), _: &Config) -> io::Result<()> {
+            Ok(())
+        }
+        fn open(&mut self, _: EndpointId, _: bool) {}
+        fn close(&mut self, _: EndpointId, _: bool) {}
+        fn send(&mut self, _: EndpointId, _: Rc<Message>) {}
+        fn recv(&mut self, _: EndpointId) {}
+    }
+
+    impl Scheduler for RetryingNetwork {
+        fn schedule(&mut self, schedulable: Schedulable, delay: Duration) -> io::Result<Scheduled> {
+            if let Schedulable::Reconnect(spec) = schedulable {
+                self.retries.push(spec);
+                self.delays.push(delay);
+            }
+            Ok(Scheduled::from(0))
+        }
+        fn cancel(&mut self, _: Scheduled){
+        }
+    }
+
+    impl fmt::Debug for RetryingNetwork {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "RetryingNetwork")
+        }
+    }
+
+    impl Context for RetryingNetwork {
+        fn raise(&mut self, _: Event) {
+        }
+    }
+
+    #[derive(Default)]
+    struct HoldingProto(Option<Pipe>);
+
+    impl Protocol for HoldingProto {
+        fn id(&self) -> u16 {0}
+        fn peer_id(&self) -> u16 {0}
+        fn add_pipe(&mut self, _: &mut Context, _: EndpointId, pipe: Pipe) {self.0 = Some(pipe)}
+        fn remove_pipe(&mut self, _: &mut Context, _: EndpointId) -> Option<Pipe> {self.0.take()}
+        fn send(&mut self, _: &mut Context, _: Message, _: Option<Scheduled>) {}
+        fn try_send(&mut self, _: &mut Context, _: Message) {}
+        fn on_send_ack(&mut self, _: &mut Context, _: EndpointId) {}
+        fn on_send_timeout(&mut self, _: &mut Context) {}
+        fn on_send_ready(&mut self, _: &mut Context, _: EndpointId) {}
+        fn can_send(&self) -> bool {false}
+        fn recv(&mut self, _: &mut Context, _: Option<Scheduled>) {}
+        fn try_recv(&mut self, _: &mut Context) {}
+        fn on_recv_ack(&mut self, _: &mut Context, _: EndpointId, _: Message) {}
+        fn on_recv_timeout(&mut self, _: &mut Context) {}
+        fn on_recv_ready(&mut self, _: &mut Context, _: EndpointId) {}
+        fn can_recv(&self) -> bool {false}
+        fn close(&mut self, _: &mut Context) {}
+    }
+
+    fn millis(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn retry_ivl_is_constant_without_max() {
+        let config = Config::default();
+
+        assert_eq!(millis(100), next_retry_ivl(&config, None));
+        assert_eq!(millis(100), next_retry_ivl(&config, Some(millis(100))));
+    }
+
+    #[test]
+    fn retry_ivl_is_doubled_up_to_max() {
+        let mut config = Config::default();
+
+        config.retry_ivl_max = Some(millis(300));
+
+        assert_eq!(millis(100), next_retry_ivl(&config, None));
+        assert_eq!(millis(200), next_retry_ivl(&config, Some(millis(100))));
+        assert_eq!(millis(300), next_retry_ivl(&config, Some(millis(200))));
+        assert_eq!(millis(300), next_retry_ivl(&config, Some(millis(300))));
+    }
+
+    #[test]
+    fn retry_ivl_max_is_ignored_when_less_than_retry_ivl() {
+        let mut config = Config::default();
+
+        config.retry_ivl_max = Some(millis(50));
+
+        assert_eq!(millis(100), next_retry_ivl(&config, Some(millis(100))));
+    }
+
+    #[test]
+    fn randomized_retry_ivl_is_between_half_and_whole_ivl() {
+        for _ in 0..100 {
+            let delay = randomize_retry_ivl(millis(100));
+
+            assert!(delay >= millis(50));
+            assert!(delay <= millis(100));
+        }
+    }
+
+    #[test]
+    fn when_reconnect_fails_retry_ivl_grows_until_handshake_succeeds() {
+        let (tx, _rx) = mpsc::channel();
+        let proto = Box::new(HoldingProto::default()) as Box<Protocol>;
+        let mut network = RetryingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let eid = EndpointId::from(1);
+
+        socket.config.retry_ivl_max = Some(millis(400));
+        socket.connect(&mut network, String::from("test://fake"), EndpointOptions::default());
+        socket.on_pipe_error(&mut network, eid, other_io_error("broken"));
+
+        network.reconnect_fails = true;
+        for _ in 0..3 {
+            let spec = network.retries.pop().unwrap();
+            socket.reconnect(&mut network, spec);
+        }
+
+        let ivls: Vec<_> = network.retries.iter().map(|spec| spec.retry_ivl).collect();
+        assert_eq!(vec![Some(millis(400))], ivls);
+        assert!(network.delays[0] <= millis(100));
+        assert!(network.delays[1] <= millis(200));
+        assert!(network.delays[2] <= millis(400));
+        assert!(network.delays[3] >= millis(200));
+
+        network.reconnect_fails = false;
+        let spec = network.retries.pop().unwrap();
+        socket.reconnect(&mut network, spec);
+        socket.on_pipe_opened(&mut network, eid);
+        socket.on_pipe_error(&mut network, eid, other_io_error("broken"));
+
+        assert_eq!(Some(millis(100)), network.retries[0].retry_ivl);
+    }
+
+    #[test]
+    fn when_reconnecting_endpoint_options_are_kept() {
+        let (tx, _rx) = mpsc::channel();
+        let proto = Box::new(HoldingProto::default()) as Box<Protocol>;
+        let mut network = RetryingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let eid = EndpointId::from(1);
+        let options = EndpointOptions::new()
+            .with(ConfigOption::SendPriority(3))
+            .with(ConfigOption::RetryIvl(millis(30)));
+
+        socket.connect(&mut network, String::from("test://fake"), options.clone());
+        socket.on_pipe_error(&mut network, eid, other_io_error("broken"));
+
+        let spec = network.retries.pop().unwrap();
+        assert_eq!(3, spec.send_priority);
+        assert_eq!(8, spec.recv_priority);
+        assert_eq!(Some(millis(30)), spec.retry_ivl);
+        assert_eq!(options, spec.options);
+
+        socket.reconnect(&mut network, spec);
+        socket.on_pipe_opened(&mut network, eid);
+        socket.on_pipe_error(&mut network, eid, other_io_error("broken"));
+
+        assert_eq!(3, network.retries[0].send_priority);
+        assert_eq!(Some(millis(30)), network.retries[0].retry_ivl);
+    }
+
+    #[test]
+    fn when_connecting_with_socket_wide_options_an_error_is_replied() {
+        let (tx, rx) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = RetryingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let options = EndpointOptions::new().with(ConfigOption::Linger(millis(0)));
+
+        socket.connect(&mut network, String::from("test://fake"), options);
+
+        match rx.recv().expect("Socket should have sent a reply to the connect request") {
+            Reply::Err(e) => assert_eq!(io::ErrorKind::InvalidInput, e.kind()),
+            _ => assert!(false, "Socket should have replied an error to the connect request")
+        }
+    }
+
+    fn describe_endpoints(socket: &mut Socket, rx: &mpsc::Receiver<Reply>, pipes: Vec<(EndpointId, PipeStatus)>) -> Vec<EndpointInfo> {
+        socket.endpoints(pipes);
+
+        match rx.recv() {
+            Ok(Reply::Endpoints(infos)) => infos,
+            _ => panic!("Socket should have replied the endpoints")
+        }
+    }
+
+    #[test]
+    fn endpoints_report_their_state_and_accepted_pipes() {
+        let (tx, rx) = mpsc::channel();
+        let proto = Box::new(HoldingProto::default()) as Box<Protocol>;
+        let mut network = RetryingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let (connected, bound, accepted) = (EndpointId::from(1), EndpointId::from(2), EndpointId::from(3));
+
+        socket.connect(&mut network, String::from("test://remote"), EndpointOptions::default());
+        socket.bind(&mut network, String::from("test://local"), EndpointOptions::default());
+        socket.on_pipe_accepted(&mut network, bound, accepted);
+        let _ = rx.try_iter().count();
+
+        let pipes = vec![
+            (connected, PipeStatus { state: EndpointState::Handshaking, peer_addr: None }),
+            (accepted, PipeStatus { state: EndpointState::Connecting, peer_addr: Some(String::from("peer")) })];
+        let infos = describe_endpoints(&mut socket, &rx, pipes);
+
+        assert_eq!(2, infos.len());
+        assert_eq!(connected, infos[0].id);
+        assert_eq!("test://remote", infos[0].url);
+        assert_eq!(EndpointState::Handshaking, infos[0].state);
+        assert_eq!(bound, infos[1].id);
+        assert_eq!(EndpointState::Listening, infos[1].state);
+        assert_eq!(1, infos[1].accepted_pipes);
+        assert_eq!(vec![String::from("peer")], infos[1].peer_addrs);
+
+        socket.on_pipe_opened(&mut network, connected);
+        let infos = describe_endpoints(&mut socket, &rx, Vec::new());
+        assert_eq!(EndpointState::Active, infos[0].state);
+
+        socket.on_pipe_error(&mut network, connected, other_io_error("broken"));
+        let infos = describe_endpoints(&mut socket, &rx, Vec::new());
+        match infos[0].state {
+            EndpointState::Retrying(due) => assert!(due > Instant::now()),
+            _ => panic!("Endpoint should be waiting for a retry")
+        }
+    }
+
+    #[test]
+    fn failed_accepted_pipes_are_no_longer_reported() {
+        let (tx, rx) = mpsc::channel();
+        let proto = Box::new(HoldingProto::default()) as Box<Protocol>;
+        let mut network = RetryingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let (bound, accepted) = (EndpointId::from(2), EndpointId::from(3));
+
+        socket.bind(&mut network, String::from("test://local"), EndpointOptions::default());
+        socket.on_pipe_accepted(&mut network, bound, accepted);
+        socket.on_pipe_opened(&mut network, accepted);
+        let _ = rx.try_iter().count();
+
+        socket.on_pipe_error(&mut network, accepted, other_io_error("broken"));
+        let infos = describe_endpoints(&mut socket, &rx, Vec::new());
+
+        assert_eq!(1, infos.len());
+        assert_eq!(bound, infos[0].id);
+        assert_eq!(0, infos[0].accepted_pipes);
+        assert!(infos[0].peer_addrs.is_empty());
+    }
+
+    #[test]
+    fn when_endpoint_is_closed_reply_is_sent_once_pipe_is_closed() {
+        let (tx, _rx) = mpsc::channel();
+        let (reply_tx, reply_rx) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = ClosingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let eid = EndpointId::from(1);
+
+        socket.connect(&mut network, String::from("test://fake"), EndpointOptions::default());
+        socket.close_endpoint(&mut network, eid, true, reply_tx);
+
+        assert_eq!(vec![eid, eid], network.closed_pipes);
+        assert!(reply_rx.try_recv().is_err());
+
+        socket.on_pipe_closed(&mut network, eid);
+
+        match reply_rx.try_recv() {
+            Ok(endpoint::Reply::Close) => {},
+            _ => assert!(false, "Socket should have replied to the close request")
+        }
+    }
+
+    #[test]
+    fn when_bound_endpoint_is_closed_accepted_pipes_are_closed() {
+        let (tx, _rx) = mpsc::channel();
+        let (reply_tx, reply_rx) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = ClosingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let (bound, accepted) = (EndpointId::from(2), EndpointId::from(3));
+
+        socket.bind(&mut network, String::from("test://fake"), EndpointOptions::default());
+        socket.on_pipe_accepted(&mut network, bound, accepted);
+        socket.close_endpoint(&mut network, bound, false, reply_tx);
+
+        assert_eq!(vec![bound, accepted, accepted], network.closed_pipes);
+        assert!(reply_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn when_endpoint_is_closed_pending_reconnect_is_cancelled() {
+        let (tx, _rx) = mpsc::channel();
+        let (reply_tx, reply_rx) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = RetryingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let eid = EndpointId::from(1);
+
+        socket.connect(&mut network, String::from("test://fake"), EndpointOptions::default());
+        socket.on_pipe_error(&mut network, eid, other_io_error("broken"));
+        socket.close_endpoint(&mut network, eid, true, reply_tx);
+
+        match reply_rx.try_recv() {
+            Ok(endpoint::Reply::Close) => {},
+            _ => assert!(false, "Socket should have replied to the close request")
+        }
+    }
+
+    #[test]
+    fn when_unknown_endpoint_is_closed() {
+        let (tx, _rx) = mpsc::channel();
+        let (reply_tx, reply_rx) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = ClosingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+
+        socket.close_endpoint(&mut network, EndpointId::from(7), false, reply_tx);
+
+        match reply_rx.try_recv() {
+            Ok(endpoint::Reply::Err(e)) => assert_eq!(io::ErrorKind::InvalidInput, e.kind()),
+            _ => assert!(false, "Socket should have replied an error to the close request")
+        }
+    }
+
+    fn start_monitor(socket: &mut Socket, rx: &mpsc::Receiver<Reply>) -> mpsc::Receiver<MonitorEvent> {
+        socket.monitor();
+
+        match rx.try_recv() {
+            Ok(Reply::Monitor(monitor)) => monitor,
+            _ => panic!("Socket should have replied a monitor")
+        }
+    }
+
+    #[test]
+    fn monitor_reports_opened_pipe_failure_and_reconnect() {
+        let (tx, rx) = mpsc::channel();
+        let proto = Box::new(HoldingProto::default()) as Box<Protocol>;
+        let mut network = RetryingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let eid = EndpointId::from(1);
+
+        socket.connect(&mut network, String::from("test://fake"), EndpointOptions::default());
+        let _ = rx.try_recv();
+        let monitor = start_monitor(&mut socket, &rx);
+
+        socket.on_pipe_opened(&mut network, eid);
+        socket.on_pipe_error(&mut network, eid, other_io_error("broken"));
+
+        match monitor.try_recv() {
+            Ok(MonitorEvent::Opened(id)) => assert_eq!(eid, id),
+            _ => assert!(false, "Monitor should have received an opened event")
+        }
+        match monitor.try_recv() {
+            Ok(MonitorEvent::Closed(id, e)) => {
+                assert_eq!(eid, id);
+                assert_eq!(io::ErrorKind::Other, e.kind());
+            },
+            _ => assert!(false, "Monitor should have received a closed event")
+        }
+        match monitor.try_recv() {
+            Ok(MonitorEvent::ReconnectScheduled(id, delay)) => {
+                assert_eq!(eid, id);
+                assert_eq!(network.delays[0], delay);
+            },
+            _ => assert!(false, "Monitor should have received a reconnect event")
+        }
+    }
+
+    #[test]
+    fn monitor_reports_rejected_handshake() {
+        let (tx, rx) = mpsc::channel();
+        let proto = Box::new(HoldingProto::default()) as Box<Protocol>;
+        let mut network = RetryingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let eid = EndpointId::from(1);
+
+        socket.connect(&mut network, String::from("test://fake"), EndpointOptions::default());
+        let _ = rx.try_recv();
+        let monitor = start_monitor(&mut socket, &rx);
+
+        socket.on_pipe_error(&mut network, eid, invalid_data_io_error("bad handshake"));
+
+        match monitor.try_recv() {
+            Ok(MonitorEvent::HandshakeRejected(id, _)) => assert_eq!(eid, id),
+            _ => assert!(false, "Monitor should have received a handshake event")
+        }
+    }
+
+    #[test]
+    fn monitor_reports_bound_and_accepted_endpoints() {
+        let (tx, rx) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = WorkingNetwork(EndpointId::from(2));
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let monitor = start_monitor(&mut socket, &rx);
+
+        socket.bind(&mut network, String::from("test://fake"), EndpointOptions::default());
+        socket.on_pipe_accepted(&mut network, EndpointId::from(2), EndpointId::from(3));
+
+        match monitor.try_recv() {
+            Ok(MonitorEvent::Bound(id, url)) => {
+                assert_eq!(EndpointId::from(2), id);
+                assert_eq!("test://fake", url);
+            },
+            _ => assert!(false, "Monitor should have received a bound event")
+        }
+        match monitor.try_recv() {
+            Ok(MonitorEvent::Accepted(aid, eid)) => {
+                assert_eq!(EndpointId::from(2), aid);
+                assert_eq!(EndpointId::from(3), eid);
+            },
+            _ => assert!(false, "Monitor should have received an accepted event")
+        }
+    }
+
+    #[test]
+    fn dropped_monitor_is_forgotten() {
+        let (tx, rx) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = WorkingNetwork(EndpointId::from(2));
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+
+        drop(start_monitor(&mut socket, &rx));
+        socket.bind(&mut network, String::from("test://fake"), EndpointOptions::default());
+
+        assert!(socket.monitors.is_empty());
+    }
+
+    fn get_statistics(socket: &mut Socket, rx: &mpsc::Receiver<Reply>, traffic: Vec<(EndpointId, Traffic)>) -> Statistics {
+        socket.statistics(traffic);
+
+        match rx.try_recv() {
+            Ok(Reply::Statistics(stats)) => stats,
+            _ => panic!("Socket should have replied statistics")
+        }
+    }
+
+    #[test]
+    fn statistics_count_connection_lifecycle() {
+        let (tx, rx) = mpsc::channel();
+        let proto = Box::new(HoldingProto::default()) as Box<Protocol>;
+        let mut network = RetryingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let eid = EndpointId::from(1);
+
+        socket.connect(&mut network, String::from("test://fake"), EndpointOptions::default());
+        let _ = rx.try_recv();
+
+        let stats = get_statistics(&mut socket, &rx, vec![]);
+        assert_eq!(1, stats.inprogress_connections);
+        assert_eq!(0, stats.current_connections);
+
+        socket.on_pipe_opened(&mut network, eid);
+
+        let stats = get_statistics(&mut socket, &rx, vec![]);
+        assert_eq!(1, stats.established_connections);
+        assert_eq!(0, stats.inprogress_connections);
+        assert_eq!(1, stats.current_connections);
+
+        socket.on_pipe_error(&mut network, eid, other_io_error("broken"));
+
+        let stats = get_statistics(&mut socket, &rx, vec![]);
+        assert_eq!(1, stats.broken_connections);
+        assert_eq!(0, stats.current_connections);
+        assert_eq!(1, stats.current_ep_errors);
+
+        network.reconnect_fails = true;
+        let spec = network.retries.pop().unwrap();
+        socket.reconnect(&mut network, spec);
+
+        let stats = get_statistics(&mut socket, &rx, vec![]);
+        assert_eq!(1, stats.connect_errors);
+    }
+
+    #[test]
+    fn statistics_distinguish_rejected_handshake_from_connect_error() {
+        let (tx, rx) = mpsc::channel();
+        let proto = Box::new(HoldingProto::default()) as Box<Protocol>;
+        let mut network = RetryingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let eid = EndpointId::from(1);
+
+        socket.connect(&mut network, String::from("test://fake"), EndpointOptions::default());
+        let _ = rx.try_recv();
+        socket.on_pipe_error(&mut network, eid, other_io_error("refused"));
+
+        let spec = network.retries.pop().unwrap();
+        socket.reconnect(&mut network, spec);
+        socket.on_pipe_error(&mut network, eid, invalid_data_io_error("bad handshake"));
+
+        let stats = get_statistics(&mut socket, &rx, vec![]);
+        assert_eq!(1, stats.connect_errors);
+        assert_eq!(1, stats.dropped_connections);
+        assert_eq!(0, stats.broken_connections);
+    }
+
+    #[test]
+    fn statistics_include_traffic_of_live_and_closed_pipes() {
+        let (tx, rx) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let mut traffic = Traffic::default();
+
+        traffic.on_sent(&Message::from_body(vec![1, 2, 3]));
+        socket.on_pipe_traffic(EndpointId::from(2), traffic);
+
+        let stats = get_statistics(&mut socket, &rx, vec![(EndpointId::from(3), traffic)]);
+        assert_eq!(2, stats.messages_sent);
+        assert_eq!(6, stats.bytes_sent);
+
+        let stats = get_statistics(&mut socket, &rx, vec![]);
+        assert_eq!(1, stats.messages_sent);
+        assert_eq!(3, stats.bytes_sent);
+    }
+
+    #[test]
+    fn endpoint_statistics_include_accepted_pipes() {
+        let (tx, rx) = mpsc::channel();
+        let (reply_tx, reply_rx) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = WorkingNetwork(EndpointId::from(2));
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let aid = EndpointId::from(2);
+        let eid = EndpointId::from(3);
+        let mut traffic = Traffic::default();
+
+        socket.bind(&mut network, String::from("test://fake"), EndpointOptions::default());
+        let _ = rx.try_recv();
+        socket.on_pipe_accepted(&mut network, aid, eid);
+        socket.on_pipe_opened(&mut network, eid);
+
+        traffic.on_received(&Message::from_body(vec![1, 2]));
+        socket.endpoint_statistics(aid, vec![(eid, traffic), (EndpointId::from(4), traffic)], reply_tx.clone());
+
+        match reply_rx.try_recv() {
+            Ok(endpoint::Reply::Statistics(stats)) => {
+                assert_eq!(1, stats.accepted_connections);
+                assert_eq!(0, stats.established_connections);
+                assert_eq!(1, stats.current_connections);
+                assert_eq!(1, stats.messages_received);
+                assert_eq!(2, stats.bytes_received);
+            },
+            _ => assert!(false, "Socket should have replied the endpoint statistics")
+        }
+
+        socket.endpoint_statistics(EndpointId::from(7), vec![], reply_tx);
+
+        match reply_rx.try_recv() {
+            Ok(endpoint::Reply::Err(e)) => assert_eq!(io::ErrorKind::InvalidInput, e.kind()),
+            _ => assert!(false, "Socket should have replied an error for an unknown endpoint")
+        }
+    }
+
+    #[test]
+    fn get_option_is_answered_by_config_or_protocol() {
+        let (tx, rx) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+
+        socket.config.recv_priority = 3;
+        socket.get_option(ConfigOptionKind::RecvPriority);
+
+        match rx.try_recv() {
+            Ok(Reply::GetOption(ConfigOption::RecvPriority(3))) => {},
+            _ => assert!(false, "Socket should have replied the option value")
+        }
+
+        socket.get_option(ConfigOptionKind::SurveyDeadline);
+
+        match rx.try_recv() {
+            Ok(Reply::Err(e)) => assert_eq!(io::ErrorKind::InvalidInput, e.kind()),
+            _ => assert!(false, "Socket should have replied an error")
+        }
+    }
+
+    #[derive(Default)]
+    struct ContextProto(HashMap<ContextId, Sender<Reply>>);
+
+    impl Protocol for ContextProto {
+        fn id(&self) -> u16 {0}
+        fn peer_id(&self) -> u16 {0}
+        fn add_pipe(&mut self, _: &mut Context, _: EndpointId, _: Pipe) {}
+        fn remove_pipe(&mut self, _: &mut Context, _: EndpointId) -> Option<Pipe> {None}
+        fn send(&mut self, _: &mut Context, _: Message, _: Option<Scheduled>) {}
+        fn try_send(&mut self, _: &mut Context, _: Message) {}
+        fn on_send_ack(&mut self, _: &mut Context, _: EndpointId) {}
+        fn on_send_timeout(&mut self, _: &mut Context) {}
+        fn on_send_ready(&mut self, _: &mut Context, _: EndpointId) {}
+        fn can_send(&self) -> bool {false}
+        fn recv(&mut self, _: &mut Context, _: Option<Scheduled>) {}
+        fn try_recv(&mut self, _: &mut Context) {}
+        fn on_recv_ack(&mut self, _: &mut Context, _: EndpointId, _: Message) {}
+        fn on_recv_timeout(&mut self, _: &mut Context) {}
+        fn on_recv_ready(&mut self, _: &mut Context, _: EndpointId) {}
+        fn can_recv(&self) -> bool {false}
+        fn create_context(&mut self, cid: ContextId, reply_tx: Sender<Reply>) -> io::Result<()> {
+            self.0.insert(cid, reply_tx);
+            Ok(())
+        }
+        fn context_recv(&mut self, _: &mut Context, cid: ContextId, _: Option<Scheduled>) {
+            if let Some(reply_tx) = self.0.get(&cid) {
+                let _ = reply_tx.send(Reply::Recv(Message::from_body(vec![65])));
+            }
+        }
+        fn close_context(&mut self, _: &mut Context, cid: ContextId) {
+            self.0.remove(&cid);
+        }
+        fn close(&mut self, _: &mut Context) {}
+    }
+
+    #[test]
+    fn create_context_replies_an_error_when_not_supported_by_the_protocol() {
+        let (tx, rx) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+
+        socket.create_context();
+
+        match rx.try_recv() {
+            Ok(Reply::Err(e)) => assert_eq!(io::ErrorKind::Other, e.kind()),
+            _ => assert!(false, "Socket should have replied an error")
+        }
+    }
+
+    #[test]
+    fn collect_replies_an_error_when_not_supported_by_the_protocol() {
+        let (tx, rx) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = RetryingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+
+        socket.collect(&mut network, Duration::from_millis(100));
+
+        match rx.try_recv() {
+            Ok(Reply::Err(e)) => assert_eq!(io::ErrorKind::Other, e.kind()),
+            _ => assert!(false, "Socket should have replied an error")
+        }
+    }
+
+    #[test]
+    fn context_operations_are_replied_through_the_context_channel() {
+        let (tx, rx) = mpsc::channel();
+        let proto = Box::new(ContextProto::default()) as Box<Protocol>;
+        let mut network = RetryingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+
+        socket.create_context();
+
+        let (cid, context_rx) = match rx.try_recv() {
+            Ok(Reply::ContextCreated(cid, context_rx)) => (cid, context_rx),
+            _ => panic!("Socket should have replied the created context")
+        };
+
+        socket.context_recv(&mut network, cid);
+
+        match context_rx.try_recv() {
+            Ok(Reply::Recv(msg)) => assert_eq!(&[65], msg.get_body()),
+            _ => assert!(false, "Context should have received a message")
+        }
+        assert!(rx.try_recv().is_err());
+
+        socket.close_context(&mut network, cid);
+        socket.context_recv(&mut network, cid);
+
+        assert!(context_rx.try_recv().is_err());
+    }
 }
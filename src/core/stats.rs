@@ -0,0 +1,106 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use super::Message;
+
+/// Counters of a socket or of one of its endpoints, mirroring the nanomsg `NN_STAT_*` set.
+/// The counters of an endpoint created by `bind` include the connections it accepted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Statistics {
+    /// Number of connections successfully established by connecting endpoints.
+    pub established_connections: u64,
+    /// Number of connections accepted by listening endpoints.
+    pub accepted_connections: u64,
+    /// Number of connections that failed before the end of the handshake.
+    pub dropped_connections: u64,
+    /// Number of established connections that later failed.
+    pub broken_connections: u64,
+    /// Number of failed attempts to connect.
+    pub connect_errors: u64,
+    /// Number of failed attempts to bind.
+    pub bind_errors: u64,
+    /// Number of listening endpoints that failed while accepting connections.
+    pub accept_errors: u64,
+    /// Number of connections currently established.
+    pub current_connections: u64,
+    /// Number of connections currently performing the handshake.
+    pub inprogress_connections: u64,
+    /// Number of endpoints currently waiting to retry after an error.
+    pub current_ep_errors: u64,
+    /// Number of messages written by the transports.
+    pub messages_sent: u64,
+    /// Number of messages received from the transports.
+    pub messages_received: u64,
+    /// Number of bytes written by the transports, headers included.
+    pub bytes_sent: u64,
+    /// Number of bytes received from the transports, headers included.
+    pub bytes_received: u64
+}
+
+/// Message counters of a single pipe, maintained by the backend.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Traffic {
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64
+}
+
+impl Statistics {
+    pub fn add_traffic(&mut self, traffic: &Traffic) {
+        self.messages_sent += traffic.messages_sent;
+        self.messages_received += traffic.messages_received;
+        self.bytes_sent += traffic.bytes_sent;
+        self.bytes_received += traffic.bytes_received;
+    }
+}
+
+impl Traffic {
+    pub fn on_sent(&mut self, msg: &Message) {
+        self.messages_sent += 1;
+        self.bytes_sent += msg.len() as u64;
+    }
+
+    pub fn on_received(&mut self, msg: &Message) {
+        self.messages_received += 1;
+        self.bytes_received += msg.len() as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::Message;
+
+    use super::*;
+
+    #[test]
+    fn traffic_counts_messages_and_bytes() {
+        let mut traffic = Traffic::default();
+
+        traffic.on_sent(&Message::from_header_and_body(vec![1, 2], vec![3, 4, 5]));
+        traffic.on_sent(&Message::from_body(vec![6]));
+        traffic.on_received(&Message::from_body(vec![7, 8]));
+
+        assert_eq!(2, traffic.messages_sent);
+        assert_eq!(6, traffic.bytes_sent);
+        assert_eq!(1, traffic.messages_received);
+        assert_eq!(2, traffic.bytes_received);
+    }
+
+    #[test]
+    fn traffic_is_added_to_statistics() {
+        let mut stats = Statistics::default();
+        let mut traffic = Traffic::default();
+
+        traffic.on_sent(&Message::from_body(vec![1, 2, 3]));
+        stats.add_traffic(&traffic);
+        stats.add_traffic(&traffic);
+
+        assert_eq!(2, stats.messages_sent);
+        assert_eq!(6, stats.bytes_sent);
+        assert_eq!(0, stats.messages_received);
+    }
+}
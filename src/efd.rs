@@ -0,0 +1,143 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::io;
+use std::os::unix::io::RawFd;
+
+use libc;
+
+/// A file descriptor that is readable while it is signaled,
+/// so it can be registered in an event loop the library does not control.
+/// It is implemented with a non-blocking pipe holding at most one byte.
+pub struct Efd {
+    rx: RawFd,
+    tx: RawFd,
+    signaled: bool
+}
+
+impl Efd {
+    pub fn new() -> io::Result<Efd> {
+        let mut fds: [libc::c_int; 2] = [0; 2];
+
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let efd = Efd {
+            rx: fds[0],
+            tx: fds[1],
+            signaled: false
+        };
+
+        try!(set_nonblocking_cloexec(efd.rx));
+        try!(set_nonblocking_cloexec(efd.tx));
+
+        Ok(efd)
+    }
+
+    pub fn get_fd(&self) -> RawFd {
+        self.rx
+    }
+
+    pub fn set(&mut self, signaled: bool) {
+        if signaled {
+            self.signal();
+        } else {
+            self.unsignal();
+        }
+    }
+
+    fn signal(&mut self) {
+        if self.signaled {
+            return;
+        }
+
+        let buffer = [1u8];
+        let ptr = buffer.as_ptr() as *const libc::c_void;
+
+        unsafe { libc::write(self.tx, ptr, 1) };
+        self.signaled = true;
+    }
+
+    fn unsignal(&mut self) {
+        if self.signaled == false {
+            return;
+        }
+
+        let mut buffer = [0u8; 8];
+        let ptr = buffer.as_mut_ptr() as *mut libc::c_void;
+
+        while unsafe { libc::read(self.rx, ptr, buffer.len()) } > 0 {}
+        self.signaled = false;
+    }
+}
+
+impl Drop for Efd {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.rx);
+            libc::close(self.tx);
+        }
+    }
+}
+
+fn set_nonblocking_cloexec(fd: RawFd) -> io::Result<()> {
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        if flags == -1 || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let fd_flags = libc::fcntl(fd, libc::F_GETFD);
+        if fd_flags == -1 || libc::fcntl(fd, libc::F_SETFD, fd_flags | libc::FD_CLOEXEC) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::io::RawFd;
+
+    use libc;
+
+    use super::Efd;
+
+    fn is_readable(fd: RawFd) -> bool {
+        let mut pollfd = libc::pollfd { fd: fd, events: libc::POLLIN, revents: 0 };
+
+        unsafe { libc::poll(&mut pollfd, 1, 0) == 1 }
+    }
+
+    #[test]
+    fn new_efd_is_not_readable() {
+        let efd = Efd::new().unwrap();
+
+        assert!(!is_readable(efd.get_fd()));
+    }
+
+    #[test]
+    fn signaled_efd_is_readable() {
+        let mut efd = Efd::new().unwrap();
+
+        efd.set(true);
+        efd.set(true);
+
+        assert!(is_readable(efd.get_fd()));
+    }
+
+    #[test]
+    fn unsignaled_efd_is_not_readable_anymore() {
+        let mut efd = Efd::new().unwrap();
+
+        efd.set(true);
+        efd.set(false);
+
+        assert!(!is_readable(efd.get_fd()));
+    }
+}
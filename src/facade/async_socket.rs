@@ -0,0 +1,258 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use futures::{Async, AsyncSink, Future, Poll, Sink, StartSend, Stream};
+use futures::task::{self, Task};
+
+use core::Message;
+use core::socket::{Request, Reply};
+use super::socket::Socket;
+
+enum Operation {
+    Send,
+    Recv
+}
+
+/// Non-blocking counterpart of [Socket](struct.Socket.html), for use in async code.
+///
+/// It is built from a socket once its endpoints and options are set,
+/// and exchanges messages through futures that complete when the backend replies.
+/// It is also a `Stream` of received messages and a `Sink` of messages to send.
+/// Like with `Socket`, operations are performed one at a time:
+/// an operation started while another one is pending waits for it to complete.
+/// An operation abandoned before completion is not cancelled,
+/// the message it receives is returned by the next receive operation.
+/// With `futures::Sink` in scope, `send` resolves to the sink method, call `AsyncSocket::send` instead.
+pub struct AsyncSocket {
+    socket: Socket,
+    task: Arc<Mutex<Option<Task>>>,
+    pending: Option<Operation>,
+    sent: Option<io::Result<()>>,
+    received: Option<io::Result<Message>>
+}
+
+impl AsyncSocket {
+
+    /// Takes over the socket and registers to be notified by the backend.
+    pub fn new(socket: Socket) -> io::Result<AsyncSocket> {
+        let task: Arc<Mutex<Option<Task>>> = Arc::new(Mutex::new(None));
+        let slot = task.clone();
+        let notifier = Box::new(move || {
+            if let Ok(mut task) = slot.lock() {
+                if let Some(task) = task.take() {
+                    task.unpark();
+                }
+            }
+        });
+
+        try!(socket.set_notifier(notifier));
+
+        Ok(AsyncSocket {
+            socket: socket,
+            task: task,
+            pending: None,
+            sent: None,
+            received: None
+        })
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* send                                                                      */
+/*                                                                           */
+/*****************************************************************************/
+
+    /// Sends a buffer, the returned future completes once the message is sent.
+    pub fn send<'a>(&'a mut self, buffer: Vec<u8>) -> SendFuture<'a> {
+        self.send_msg(Message::from_body(buffer))
+    }
+
+    /// Sends a message, the returned future completes once the message is sent.
+    pub fn send_msg<'a>(&'a mut self, msg: Message) -> SendFuture<'a> {
+        SendFuture {
+            socket: self,
+            msg: Some(msg)
+        }
+    }
+
+    fn poll_send(&mut self, msg: &mut Option<Message>) -> Poll<(), io::Error> {
+        if msg.is_some() {
+            try_ready!(self.poll_idle());
+
+            if let Some(msg) = msg.take() {
+                try!(self.start_send_msg(msg));
+            }
+        }
+
+        self.poll_sent()
+    }
+
+    fn start_send_msg(&mut self, msg: Message) -> io::Result<()> {
+        self.sent = None;
+        self.start(Request::Send(msg), Operation::Send)
+    }
+
+    fn poll_sent(&mut self) -> Poll<(), io::Error> {
+        loop {
+            if let Some(res) = self.sent.take() {
+                return res.map(Async::Ready);
+            }
+
+            match self.pending {
+                Some(Operation::Send) => try_ready!(self.poll_pending()),
+                _ => return Ok(Async::Ready(()))
+            }
+        }
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* recv                                                                      */
+/*                                                                           */
+/*****************************************************************************/
+
+    /// Receives a buffer, the returned future completes once a message is received.
+    pub fn recv<'a>(&'a mut self) -> RecvFuture<'a> {
+        RecvFuture { socket: self }
+    }
+
+    fn poll_recv(&mut self) -> Poll<Message, io::Error> {
+        loop {
+            try_ready!(self.poll_idle());
+
+            if let Some(res) = self.received.take() {
+                return res.map(Async::Ready);
+            }
+
+            try!(self.start(Request::Recv, Operation::Recv));
+        }
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* backend                                                                   */
+/*                                                                           */
+/*****************************************************************************/
+
+    fn start(&mut self, request: Request, operation: Operation) -> io::Result<()> {
+        try!(self.socket.send_request(request));
+
+        self.pending = Some(operation);
+        Ok(())
+    }
+
+    fn poll_idle(&mut self) -> Poll<(), io::Error> {
+        while self.pending.is_some() {
+            try_ready!(self.poll_pending());
+        }
+
+        Ok(Async::Ready(()))
+    }
+
+    /// Stores the outcome of the pending operation, for whichever future is interested in.
+    fn poll_pending(&mut self) -> Poll<(), io::Error> {
+        let reply = try_ready!(self.poll_reply());
+
+        match self.pending.take() {
+            Some(Operation::Send) => self.sent = Some(self.socket.on_send_reply(reply)),
+            Some(Operation::Recv) => self.received = Some(self.socket.on_recv_reply(reply)),
+            None => {}
+        }
+
+        Ok(Async::Ready(()))
+    }
+
+    /// The current task is registered before checking again,
+    /// so a reply sent in between can not be missed.
+    fn poll_reply(&mut self) -> Poll<Reply, io::Error> {
+        if let Some(reply) = try!(self.socket.try_recv_reply()) {
+            return Ok(Async::Ready(reply));
+        }
+
+        if let Ok(mut task) = self.task.lock() {
+            *task = Some(task::park());
+        }
+
+        match try!(self.socket.try_recv_reply()) {
+            Some(reply) => Ok(Async::Ready(reply)),
+            None => Ok(Async::NotReady)
+        }
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* futures                                                                   */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Future returned by [AsyncSocket::send](struct.AsyncSocket.html#method.send).
+pub struct SendFuture<'a> {
+    socket: &'a mut AsyncSocket,
+    msg: Option<Message>
+}
+
+impl<'a> Future for SendFuture<'a> {
+    type Item = ();
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(), io::Error> {
+        self.socket.poll_send(&mut self.msg)
+    }
+}
+
+/// Future returned by [AsyncSocket::recv](struct.AsyncSocket.html#method.recv).
+pub struct RecvFuture<'a> {
+    socket: &'a mut AsyncSocket
+}
+
+impl<'a> Future for RecvFuture<'a> {
+    type Item = Vec<u8>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Vec<u8>, io::Error> {
+        let msg = try_ready!(self.socket.poll_recv());
+
+        Ok(Async::Ready(msg.into()))
+    }
+}
+
+impl Stream for AsyncSocket {
+    type Item = Message;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Message>, io::Error> {
+        let msg = try_ready!(self.poll_recv());
+
+        Ok(Async::Ready(Some(msg)))
+    }
+}
+
+impl Sink for AsyncSocket {
+    type SinkItem = Message;
+    type SinkError = io::Error;
+
+    /// The outcome of the previous send is reported before accepting another message.
+    fn start_send(&mut self, msg: Message) -> StartSend<Message, io::Error> {
+        if let Async::NotReady = try!(self.poll_idle()) {
+            return Ok(AsyncSink::NotReady(msg));
+        }
+        if let Some(Err(e)) = self.sent.take() {
+            return Err(e);
+        }
+
+        try!(self.start_send_msg(msg));
+
+        Ok(AsyncSink::Ready)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), io::Error> {
+        self.poll_sent()
+    }
+}
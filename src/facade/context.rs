@@ -0,0 +1,118 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::sync::mpsc;
+use std::io;
+use std::time::Duration;
+
+use super::*;
+use reactor;
+use core::{SocketId, ContextId, Message};
+use core::socket::{Request, Reply};
+use io_error::*;
+
+#[doc(hidden)]
+pub struct RequestSender {
+    req_tx: EventLoopRequestSender,
+    socket_id: SocketId,
+    id: ContextId
+}
+
+impl RequestSender {
+    pub fn new(tx: EventLoopRequestSender, sid: SocketId, cid: ContextId) -> RequestSender {
+        RequestSender {
+            req_tx: tx,
+            socket_id: sid,
+            id: cid
+        }
+    }
+    fn send(&self, req: Request) -> io::Result<()> {
+        self.req_tx.send(reactor::Request::Socket(self.socket_id, req)).map_err(from_send_error)
+    }
+}
+
+/// Context of a socket.
+///
+/// Obtained via the socket [create_context](struct.Socket.html#method.create_context) method.
+/// A context runs its own sequence of operations, independently of the socket and of the other contexts,
+/// while sharing the socket endpoints. With a `Req` socket, each context has its own pending request,
+/// so that several requests can be outstanding at the same time over the same connections.
+/// With a `Rep` or `Respondent` socket, each context keeps the backtrace of the request it received,
+/// so that several requests can be handled concurrently and answered in any order.
+/// With a `Surveyor` socket, each context has its own survey and deadline,
+/// and receives only the responses to that survey.
+/// Contexts can be moved to other threads, the send and recv timeouts of the socket apply.
+/// Dropping the context abandons its pending operation.
+pub struct Context {
+    request_sender: RequestSender,
+    reply_receiver: mpsc::Receiver<Reply>
+}
+
+impl Context {
+    #[doc(hidden)]
+    pub fn new(request_tx: RequestSender, reply_rx: mpsc::Receiver<Reply>) -> Context {
+        Context {
+            request_sender: request_tx,
+            reply_receiver: reply_rx
+        }
+    }
+
+    /// Sends a buffer on behalf of the context.
+    pub fn send(&mut self, buffer: Vec<u8>) -> io::Result<()> {
+        self.send_msg(Message::from_body(buffer))
+    }
+
+    /// Sends a message on behalf of the context.
+    pub fn send_msg(&mut self, msg: Message) -> io::Result<()> {
+        let request = Request::ContextSend(self.request_sender.id, msg);
+
+        match try!(self.call(request)) {
+            Reply::Send => Ok(()),
+            Reply::Err(e) => Err(e),
+            _ => Err(other_io_error("unexpected reply"))
+        }
+    }
+
+    /// Receives a buffer on behalf of the context.
+    pub fn recv(&mut self) -> io::Result<Vec<u8>> {
+        self.recv_msg().map(|msg| msg.into())
+    }
+
+    /// Receives a message on behalf of the context.
+    pub fn recv_msg(&mut self) -> io::Result<Message> {
+        let request = Request::ContextRecv(self.request_sender.id);
+
+        match try!(self.call(request)) {
+            Reply::Recv(msg) => Ok(msg),
+            Reply::Err(e) => Err(e),
+            _ => Err(other_io_error("unexpected reply"))
+        }
+    }
+
+    /// Receives all the responses to the survey of the context until the deadline expires.
+    /// See [Socket::collect](struct.Socket.html#method.collect).
+    pub fn collect(&mut self, deadline: Duration) -> io::Result<Vec<Message>> {
+        let request = Request::ContextCollect(self.request_sender.id, deadline);
+
+        match try!(self.call(request)) {
+            Reply::Collect(msgs) => Ok(msgs),
+            Reply::Err(e) => Err(e),
+            _ => Err(other_io_error("unexpected reply"))
+        }
+    }
+
+    fn call(&self, request: Request) -> io::Result<Reply> {
+        try!(self.request_sender.send(request));
+
+        self.reply_receiver.receive()
+    }
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        let _ = self.request_sender.send(Request::CloseContext(self.request_sender.id));
+    }
+}
@@ -4,12 +4,14 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
+use std::sync::mpsc;
 use std::io;
 
 use super::*;
 use reactor;
 use core::{SocketId, EndpointId};
-use core::endpoint::Request;
+use core::endpoint::{Request, Reply, EndpointInfo};
+use core::stats::Statistics;
 use io_error::*;
 
 #[doc(hidden)]
@@ -36,7 +38,7 @@ impl RequestSender {
 ///   
 /// Obtained via the socket [bind](struct.Socket.html#method.bind) or 
 /// [connect](struct.Socket.html#method.connect) methods.  
-/// Can only be used to close an endpoint.  
+/// Can be used to close an endpoint, or to inspect it.  
 /// Note that `drop(Endpoint)` will **NOT** close it.
 pub struct Endpoint {
     request_sender: RequestSender,
@@ -51,7 +53,46 @@ impl Endpoint {
         }
     }
 
+    /// Removes the endpoint from the socket, cancelling any pending reconnection.
+    /// Returns once the underlying connection or listener is closed,
+    /// messages still waiting to be sent on that endpoint are dropped.
+    /// Closing a bound endpoint also closes the connections it accepted.
     pub fn close(self) -> io::Result<()> {
-        self.request_sender.send(Request::Close(self.remote))
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        try!(self.request_sender.send(Request::Close(self.remote, reply_tx)));
+
+        match try!(reply_rx.receive()) {
+            Reply::Close => Ok(()),
+            Reply::Err(e) => Err(e),
+            _ => Err(other_io_error("unexpected reply"))
+        }
+    }
+
+    /// Retrieves the counters of the endpoint.
+    /// The counters of an endpoint created by `bind` include the connections it accepted.
+    pub fn statistics(&self) -> io::Result<Statistics> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        try!(self.request_sender.send(Request::Statistics(reply_tx)));
+
+        match try!(reply_rx.receive()) {
+            Reply::Statistics(stats) => Ok(stats),
+            Reply::Err(e) => Err(e),
+            _ => Err(other_io_error("unexpected reply"))
+        }
+    }
+
+    /// Describes the endpoint: its url, its state and the connections it accepted.
+    pub fn info(&self) -> io::Result<EndpointInfo> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        try!(self.request_sender.send(Request::Info(reply_tx)));
+
+        match try!(reply_rx.receive()) {
+            Reply::Info(info) => Ok(info),
+            Reply::Err(e) => Err(e),
+            _ => Err(other_io_error("unexpected reply"))
+        }
     }
 }
\ No newline at end of file
@@ -7,7 +7,10 @@
 pub mod session;
 pub mod socket;
 pub mod endpoint;
+pub mod context;
 pub mod device;
+#[cfg(feature = "futures")]
+pub mod async_socket;
 
 use std::sync::mpsc;
 use std::io;
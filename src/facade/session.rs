@@ -7,6 +7,7 @@
 use std::io;
 use std::thread;
 use std::sync::mpsc;
+use std::time::Duration;
 
 use mio;
 
@@ -16,6 +17,7 @@ use reactor::dispatcher;
 use core::session::{Request, Reply};
 use core::socket::{Protocol, ProtocolCtor};
 use core;
+use transport::Transport;
 use io_error::*;
 
 #[doc(hidden)]
@@ -42,25 +44,88 @@ impl RequestSender {
 }
 
 /// Creates the session and starts the I/O thread.
+/// Additional transports can be registered before the session is started,
+/// they are then available to the `connect` and `bind` methods of the sockets.
 #[derive(Default)]
-pub struct SessionBuilder;
+pub struct SessionBuilder {
+    transports: Vec<(String, Box<Transport + Send>)>
+}
 
 impl SessionBuilder {
 
     pub fn new() -> SessionBuilder {
-        SessionBuilder
+        SessionBuilder::default()
     }
 
+    /// Creates a session with the built-in transports only.
     pub fn build() -> io::Result<Session> {
+        SessionBuilder::new().start()
+    }
+
+    /// Registers a transport that will handle the urls starting with `scheme://`.
+    /// A transport registered with the scheme of a built-in one replaces it.
+    pub fn with_transport(mut self, scheme: &str, transport: Box<Transport + Send>) -> SessionBuilder {
+        self.transports.push((scheme.to_owned(), transport));
+        self
+    }
 
+    /// Creates the session with the registered transports.
+    pub fn start(self) -> io::Result<Session> {
         let (reply_tx, reply_rx) = mpsc::channel();
         let (request_tx, request_rx) = mio::channel::channel();
         let session = Session::new(RequestSender::new(request_tx), reply_rx);
+        let transports = self.transports;
 
-        thread::spawn(move || dispatcher::Dispatcher::dispatch(request_rx, reply_tx));
+        thread::spawn(move || dispatcher::Dispatcher::dispatch(request_rx, reply_tx, transports));
 
         Ok(session)
-    }}
+    }
+}
+
+/// Selects the operations [poll](struct.Session.html#method.poll) should check on a socket,
+/// and once the poll returns, tells which of them can be performed without blocking.
+#[derive(Clone, Copy)]
+pub struct PollRequest {
+    socket_id: core::SocketId,
+    send: bool,
+    recv: bool,
+    can_send: bool,
+    can_recv: bool
+}
+
+impl PollRequest {
+
+    /// Creates a request checking if the socket can send and/or receive.
+    pub fn new(socket: &socket::Socket, send: bool, recv: bool) -> PollRequest {
+        PollRequest {
+            socket_id: socket.id(),
+            send: send,
+            recv: recv,
+            can_send: false,
+            can_recv: false
+        }
+    }
+
+    /// Creates a request checking if the socket can send.
+    pub fn new_for_send(socket: &socket::Socket) -> PollRequest {
+        PollRequest::new(socket, true, false)
+    }
+
+    /// Creates a request checking if the socket can receive.
+    pub fn new_for_recv(socket: &socket::Socket) -> PollRequest {
+        PollRequest::new(socket, false, true)
+    }
+
+    /// Returns `true` if a message can be sent without blocking.
+    pub fn can_send(&self) -> bool {
+        self.can_send
+    }
+
+    /// Returns `true` if a message can be received without blocking.
+    pub fn can_recv(&self) -> bool {
+        self.can_recv
+    }
+}
 
 /// Creates sockets and devices.
 pub struct Session {
@@ -97,6 +162,22 @@ impl Session {
         self.call(request, |reply| self.on_create_socket_reply(reply))
     }
 
+    /// Creates a raw socket with the specified protocol.
+    /// Raw sockets deliver and accept messages with their protocol header intact,
+    /// so that routers and proxies can be written on top of 
+    /// [recv_msg](struct.Socket.html#method.recv_msg) and [send_msg](struct.Socket.html#method.send_msg).
+    /// The header of a received request holds its backtrace, the request id being the last 4 bytes.
+    /// `Rep` and `Respondent` raw sockets prepend the 4 bytes id of the pipe the request came from,
+    /// this id must be left at the front of the header of the reply so it can be routed back.
+    pub fn create_raw_socket<T>(&mut self) -> io::Result<socket::Socket>
+    where T : Protocol + From<mpsc::Sender<core::socket::Reply>> + 'static
+    {
+        let protocol_ctor = Session::create_raw_protocol_ctor::<T>();
+        let request = Request::CreateSocket(protocol_ctor);
+
+        self.call(request, |reply| self.on_create_socket_reply(reply))
+    }
+
     fn create_protocol_ctor<T>() -> ProtocolCtor 
     where T : Protocol + From<mpsc::Sender<core::socket::Reply>> + 'static
     {
@@ -105,6 +186,18 @@ impl Session {
         })
     }
 
+    fn create_raw_protocol_ctor<T>() -> ProtocolCtor 
+    where T : Protocol + From<mpsc::Sender<core::socket::Reply>> + 'static
+    {
+        Box::new(move |sender: mpsc::Sender<core::socket::Reply>| {
+            let mut protocol = T::from(sender);
+
+            protocol.set_raw();
+
+            Box::new(protocol) as Box<Protocol>
+        })
+    }
+
     fn on_create_socket_reply(&self, reply: Reply) -> io::Result<socket::Socket> {
         match reply {
             Reply::SocketCreated(id, rx) => {
@@ -150,6 +243,43 @@ impl Session {
         }
     }
 
+/*****************************************************************************/
+/*                                                                           */
+/* Poll                                                                      */
+/*                                                                           */
+/*****************************************************************************/
+
+    /// Checks a set of sockets and waits until at least one of them is ready for the requested operations.
+    /// The readiness of each socket is then available from the [requests](struct.PollRequest.html).
+    /// Returns the number of requests having at least one ready operation,
+    /// which is zero if the timeout elapsed first.
+    pub fn poll(&mut self, requests: &mut [PollRequest], timeout: Duration) -> io::Result<usize> {
+        let interests = requests.iter().map(|r| (r.socket_id, r.send, r.recv)).collect();
+        let request = Request::Poll(interests, timeout);
+
+        self.call(request, |reply| self.on_poll_reply(reply, requests))
+    }
+
+    fn on_poll_reply(&self, reply: Reply, requests: &mut [PollRequest]) -> io::Result<usize> {
+        match reply {
+            Reply::Poll(readiness) => {
+                let mut count = 0;
+
+                for (request, (can_send, can_recv)) in requests.iter_mut().zip(readiness) {
+                    request.can_send = can_send;
+                    request.can_recv = can_recv;
+
+                    if can_send || can_recv {
+                        count += 1;
+                    }
+                }
+
+                Ok(count)
+            },
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
 
 /*****************************************************************************/
 /*                                                                           */
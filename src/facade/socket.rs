@@ -7,12 +7,16 @@
 use std::sync::mpsc;
 use std::io;
 use std::time::Duration;
+#[cfg(not(windows))]
+use std::os::unix::io::RawFd;
 
 use super::*;
 use reactor;
 use core::{SocketId, Message};
-use core::socket::{Request, Reply};
-use core::config::ConfigOption;
+use core::socket::{Request, Reply, MonitorEvent};
+use core::config::{ConfigOption, ConfigOptionKind, EndpointOptions};
+use core::stats::Statistics;
+use core::endpoint::EndpointInfo;
 use core;
 use io_error::*;
 
@@ -35,6 +39,9 @@ impl RequestSender {
     fn child_sender(&self, eid: core::EndpointId) -> endpoint::RequestSender {
         endpoint::RequestSender::new(self.req_tx.clone(), self.socket_id, eid)
     }
+    fn context_sender(&self, cid: core::ContextId) -> context::RequestSender {
+        context::RequestSender::new(self.req_tx.clone(), self.socket_id, cid)
+    }
     fn send(&self, req: Request) -> io::Result<()> {
         self.req_tx.send(reactor::Request::Socket(self.socket_id, req)).map_err(from_send_error)
     }
@@ -78,7 +85,13 @@ impl Socket {
     /// thus allowing the socket to communicate with multiple heterogeneous endpoints.
     /// On success, returns an [Endpoint](struct.Endpoint.html) that can be later used to remove the endpoint from the socket.
     pub fn connect(&mut self, url: &str) -> io::Result<endpoint::Endpoint> {
-        let request = Request::Connect(From::from(url));
+        self.connect_with(url, EndpointOptions::default())
+    }
+
+    /// Adds a remote endpoint to the socket, like [connect](#method.connect) does,
+    /// with options overriding the socket ones for that endpoint only.
+    pub fn connect_with(&mut self, url: &str, options: EndpointOptions) -> io::Result<endpoint::Endpoint> {
+        let request = Request::Connect(From::from(url), options);
 
         self.call(request, |reply| self.on_connect_reply(reply))
     }
@@ -110,7 +123,13 @@ impl Socket {
     /// thus allowing the socket to communicate with multiple heterogeneous endpoints.
     /// On success, returns an [Endpoint](struct.Endpoint.html) that can be later used to remove the endpoint from the socket.
     pub fn bind(&mut self, url: &str) -> io::Result<endpoint::Endpoint> {
-        let request = Request::Bind(From::from(url));
+        self.bind_with(url, EndpointOptions::default())
+    }
+
+    /// Adds a local endpoint to the socket, like [bind](#method.bind) does,
+    /// with options overriding the socket ones for that endpoint and the connections it accepts.
+    pub fn bind_with(&mut self, url: &str, options: EndpointOptions) -> io::Result<endpoint::Endpoint> {
+        let request = Request::Bind(From::from(url), options);
 
         self.call(request, |reply| self.on_bind_reply(reply))
     }
@@ -148,7 +167,22 @@ impl Socket {
         self.call(request, |reply| self.on_send_reply(reply))
     }
 
-    fn on_send_reply(&self, reply: Reply) -> io::Result<()> {
+    /// Sends a buffer without waiting for a peer to be ready.
+    /// If no peer can accept the message right now, an error with the kind `WouldBlock` is returned.
+    pub fn try_send(&mut self, buffer: Vec<u8>) -> io::Result<()> {
+        self.try_send_msg(Message::from_body(buffer))
+    }
+
+    /// Sends a message without waiting for a peer to be ready.
+    /// If no peer can accept the message right now, an error with the kind `WouldBlock` is returned.
+    pub fn try_send_msg(&mut self, msg: Message) -> io::Result<()> {
+        let request = Request::TrySend(msg);
+
+        self.call(request, |reply| self.on_send_reply(reply))
+    }
+
+    #[doc(hidden)]
+    pub fn on_send_reply(&self, reply: Reply) -> io::Result<()> {
         match reply {
             Reply::Send => Ok(()),
             Reply::Err(e) => Err(e),
@@ -174,7 +208,22 @@ impl Socket {
         self.call(request, |reply| self.on_recv_reply(reply))
     }
 
-    fn on_recv_reply(&self, reply: Reply) -> io::Result<Message> {
+    /// Receives a buffer without waiting for a peer to be ready.
+    /// If no message can be received right now, an error with the kind `WouldBlock` is returned.
+    pub fn try_recv(&mut self) -> io::Result<Vec<u8>> {
+        self.try_recv_msg().map(|msg| msg.into())
+    }
+
+    /// Receives a message without waiting for a peer to be ready.
+    /// If no message can be received right now, an error with the kind `WouldBlock` is returned.
+    pub fn try_recv_msg(&mut self) -> io::Result<Message> {
+        let request = Request::TryRecv;
+
+        self.call(request, |reply| self.on_recv_reply(reply))
+    }
+
+    #[doc(hidden)]
+    pub fn on_recv_reply(&self, reply: Reply) -> io::Result<Message> {
         match reply {
             Reply::Recv(msg) => Ok(msg),
             Reply::Err(e) => Err(e),
@@ -182,6 +231,23 @@ impl Socket {
         }
     }
 
+    /// Receives all the responses to the current survey until the deadline expires.
+    /// The deadline replaces the `SurveyDeadline` of the survey, and the recv timeout does not apply.
+    /// Only supported by the `Surveyor` protocol.
+    pub fn collect(&mut self, deadline: Duration) -> io::Result<Vec<Message>> {
+        let request = Request::Collect(deadline);
+
+        self.call(request, |reply| self.on_collect_reply(reply))
+    }
+
+    fn on_collect_reply(&self, reply: Reply) -> io::Result<Vec<Message>> {
+        match reply {
+            Reply::Collect(msgs) => Ok(msgs),
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
 /*****************************************************************************/
 /*                                                                           */
 /* options                                                                   */
@@ -246,6 +312,142 @@ impl Socket {
         }
     }
 
+    /// Retrieves the current value of a socket option.
+    /// See [ConfigOptionKind](core/config/enum.ConfigOptionKind.html) to get the list of readable options.
+    pub fn get_option(&mut self, kind: ConfigOptionKind) -> io::Result<ConfigOption> {
+        self.call(Request::GetOption(kind), |reply| self.on_get_option_reply(reply))
+    }
+
+    fn on_get_option_reply(&self, reply: Reply) -> io::Result<ConfigOption> {
+        match reply {
+            Reply::GetOption(opt) => Ok(opt),
+            Reply::Err(e)         => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* readiness fds                                                             */
+/*                                                                           */
+/*****************************************************************************/
+
+    /// Retrieves a file descriptor that is readable when a message can be sent without blocking.  
+    /// It is meant to be registered in an external event loop, the only valid operation on it is polling:
+    /// it must not be read from, written to or closed. It stays valid until the socket is dropped.
+    #[cfg(not(windows))]
+    pub fn send_fd(&mut self) -> io::Result<RawFd> {
+        self.call(Request::SendFd, |reply| self.on_fd_reply(reply))
+    }
+
+    /// Retrieves a file descriptor that is readable when a message can be received without blocking.  
+    /// It is meant to be registered in an external event loop, the only valid operation on it is polling:
+    /// it must not be read from, written to or closed. It stays valid until the socket is dropped.
+    #[cfg(not(windows))]
+    pub fn recv_fd(&mut self) -> io::Result<RawFd> {
+        self.call(Request::RecvFd, |reply| self.on_fd_reply(reply))
+    }
+
+    #[cfg(not(windows))]
+    fn on_fd_reply(&self, reply: Reply) -> io::Result<RawFd> {
+        match reply {
+            Reply::Fd(fd) => Ok(fd),
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* monitor                                                                   */
+/*                                                                           */
+/*****************************************************************************/
+
+    /// Retrieves a receiver of the lifecycle events of the socket endpoints:
+    /// bound listeners, accepted and opened pipes, pipe failures and scheduled reconnections.
+    /// Events are only sent to the monitors that exist when they happen.
+    /// Several monitors can be created, dropping the receiver stops the monitoring.
+    /// See [MonitorEvent](enum.MonitorEvent.html) to get the list of events.
+    pub fn monitor(&mut self) -> io::Result<mpsc::Receiver<MonitorEvent>> {
+        self.call(Request::Monitor, |reply| self.on_monitor_reply(reply))
+    }
+
+    fn on_monitor_reply(&self, reply: Reply) -> io::Result<mpsc::Receiver<MonitorEvent>> {
+        match reply {
+            Reply::Monitor(rx) => Ok(rx),
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* statistics                                                                */
+/*                                                                           */
+/*****************************************************************************/
+
+    /// Retrieves the counters of the socket, accumulated over all its endpoints.
+    /// See [Statistics](struct.Statistics.html) to get the list of counters.
+    pub fn statistics(&mut self) -> io::Result<Statistics> {
+        self.call(Request::Statistics, |reply| self.on_statistics_reply(reply))
+    }
+
+    fn on_statistics_reply(&self, reply: Reply) -> io::Result<Statistics> {
+        match reply {
+            Reply::Statistics(stats) => Ok(stats),
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* endpoints                                                                 */
+/*                                                                           */
+/*****************************************************************************/
+
+    /// Describes the endpoints created by `connect` and `bind`, ordered by id.
+    /// See [EndpointInfo](struct.EndpointInfo.html) for what is reported.
+    pub fn endpoints(&mut self) -> io::Result<Vec<EndpointInfo>> {
+        self.call(Request::Endpoints, |reply| self.on_endpoints_reply(reply))
+    }
+
+    fn on_endpoints_reply(&self, reply: Reply) -> io::Result<Vec<EndpointInfo>> {
+        match reply {
+            Reply::Endpoints(infos) => Ok(infos),
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* contexts                                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+    /// Creates a context, that sends and receives independently of the socket and of the other contexts.
+    /// This is supported by the `Req` protocol, where each context has its own pending request
+    /// and replies are matched to the contexts by request id, by the `Rep` and `Respondent` protocols,
+    /// where each context receives a request and sends the reply back to its originator,
+    /// and by the `Surveyor` protocol, where each context has its own survey and responses are matched by survey id.
+    /// See [Context](struct.Context.html) for details.
+    pub fn create_context(&mut self) -> io::Result<context::Context> {
+        self.call(Request::CreateContext, |reply| self.on_create_context_reply(reply))
+    }
+
+    fn on_create_context_reply(&self, reply: Reply) -> io::Result<context::Context> {
+        match reply {
+            Reply::ContextCreated(id, rx) => {
+                let request_tx = self.request_sender.context_sender(id);
+
+                Ok(context::Context::new(request_tx, rx))
+            },
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
 /*****************************************************************************/
 /*                                                                           */
 /* backend                                                                   */
@@ -260,7 +462,21 @@ impl Socket {
         self.send_request(request).and_then(|_| self.recv_reply())
     }
 
-    fn send_request(&self, request: Request) -> io::Result<()> {
+    #[doc(hidden)]
+    pub fn set_notifier(&self, notifier: core::socket::Notifier) -> io::Result<()> {
+        self.call(Request::SetNotifier(notifier), |reply| self.on_set_notifier_reply(reply))
+    }
+
+    fn on_set_notifier_reply(&self, reply: Reply) -> io::Result<()> {
+        match reply {
+            Reply::SetNotifier => Ok(()),
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn send_request(&self, request: Request) -> io::Result<()> {
         self.request_sender.send(request)
     }
 
@@ -268,6 +484,15 @@ impl Socket {
         self.reply_receiver.receive()
     }
 
+    #[doc(hidden)]
+    pub fn try_recv_reply(&self) -> io::Result<Option<Reply>> {
+        match self.reply_receiver.try_recv() {
+            Ok(reply) => Ok(Some(reply)),
+            Err(mpsc::TryRecvError::Empty) => Ok(None),
+            Err(mpsc::TryRecvError::Disconnected) => Err(other_io_error("evt channel closed"))
+        }
+    }
+
     fn unexpected_reply<T>(&self) -> io::Result<T> {
         Err(other_io_error("unexpected reply"))
     }
@@ -5,6 +5,7 @@
 // This file may not be copied, modified, or distributed except according to those terms.
 
 use std::error;
+use std::fmt;
 use std::io;
 
 use mio;
@@ -29,6 +30,43 @@ pub fn timedout_io_error<E>(msg: E) -> io::Error where E: Into<Box<error::Error
     io::Error::new(io::ErrorKind::TimedOut, msg)
 }
 
+pub fn conn_aborted_io_error<E>(msg: E) -> io::Error where E: Into<Box<error::Error + Send + Sync>> {
+    io::Error::new(io::ErrorKind::ConnectionAborted, msg)
+}
+
+pub fn addr_in_use_io_error<E>(msg: E) -> io::Error where E: Into<Box<error::Error + Send + Sync>> {
+    io::Error::new(io::ErrorKind::AddrInUse, msg)
+}
+
+/// Error of a pipe receiving a message larger than the `RecvMaxSize` option.
+#[derive(Debug)]
+struct MsgTooLong;
+
+impl fmt::Display for MsgTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("message is too long")
+    }
+}
+
+impl error::Error for MsgTooLong {
+    fn description(&self) -> &str {
+        "message is too long"
+    }
+}
+
+pub fn msg_too_long_io_error() -> io::Error {
+    invalid_data_io_error(MsgTooLong)
+}
+
+pub fn is_msg_too_long(err: &io::Error) -> bool {
+    err.get_ref().map_or(false, |e| e.is::<MsgTooLong>())
+}
+
+/// `io::Error` is not `Clone`, this keeps the kind and the description.
+pub fn clone_io_error(err: &io::Error) -> io::Error {
+    io::Error::new(err.kind(), err.to_string())
+}
+
 pub fn from_send_error<T>(send_error: mio::channel::SendError<T>) -> io::Error {
     match send_error {
         mio::channel::SendError::Io(e) => e,
@@ -24,6 +24,8 @@
 //! To plug the sockets, use the [connect](struct.Socket.html#method.connect) and [bind](struct.Socket.html#method.bind) socket methods.  
 //! Finally, use the socket methods [send](struct.Socket.html#method.send) and
 //! [recv](struct.Socket.html#method.recv) to exchange messages between sockets.  
+//! With the `futures` feature enabled, a configured socket can be turned into an `AsyncSocket`,
+//! a `Stream` and `Sink` of messages whose operations return futures.  
 //! When in doubts, please refer to the [nanomsg manual](http://nanomsg.org/v1.0.0/nanomsg.7.html).  
 //!
 //! # Example
@@ -68,6 +70,17 @@ extern crate log;
 extern crate byteorder;
 extern crate mio;
 extern crate time;
+extern crate rand;
+extern crate sha1;
+extern crate base64;
+extern crate openssl;
+#[cfg(not(windows))]
+extern crate mio_uds;
+#[cfg(not(windows))]
+extern crate libc;
+#[cfg(feature = "futures")]
+#[macro_use]
+extern crate futures;
 
 #[doc(hidden)]
 pub mod core;
@@ -76,20 +89,30 @@ pub mod proto;
 mod reactor;
 #[doc(hidden)]
 mod facade;
-mod transport;
+pub mod transport;
 
 #[doc(hidden)]
 mod sequence;
 #[doc(hidden)]
 mod io_error;
+#[cfg(not(windows))]
+#[doc(hidden)]
+mod efd;
 
 pub use facade::session::SessionBuilder;
 pub use facade::session::Session;
+pub use facade::session::PollRequest;
 pub use facade::socket::Socket;
+#[cfg(feature = "futures")]
+pub use facade::async_socket::AsyncSocket;
 pub use facade::device::Device;
 pub use facade::endpoint::Endpoint;
+pub use facade::context::Context;
 pub use core::Message;
-pub use core::config::ConfigOption;
+pub use core::config::{ConfigOption, ConfigOptionKind, EndpointOptions};
+pub use core::socket::MonitorEvent;
+pub use core::endpoint::{EndpointInfo, EndpointState};
+pub use core::stats::Statistics;
 
 pub use proto::pair::Pair;
 pub use proto::publ::Pub;
@@ -6,6 +6,9 @@
 
 //! This is documentation for the `proto` module.
 
+#[macro_use]
+mod replier;
+
 pub mod pair;
 pub mod push;
 pub mod pull;
@@ -19,11 +22,91 @@ pub mod bus;
 
 mod priolist;
 
+use byteorder::*;
+
+use core::{EndpointId, Message};
 use core::context::Scheduled;
 
 #[doc(hidden)]
 pub type Timeout = Option<Scheduled>;
 
+/// Maximum number of hops of a backtrace.
+const DEFAULT_TTL: u8 = 8;
+
+/// Moves the backtrace from the body to the header of the message.
+/// The backtrace is made of 4 bytes hops and ends with the one having its most significant bit set.
+fn decode_backtrace(raw_msg: Message, ttl: u8) -> Option<Message> {
+    if raw_msg.get_header().is_empty() == false {
+        return check_backtrace(raw_msg, ttl);
+    }
+
+    let (mut header, mut body) = raw_msg.split();
+    let mut hops = 0;
+
+    loop {
+        if hops >= ttl {
+            return None;
+        }
+        hops += 1;
+
+        if body.len() < 4 {
+            return None;
+        }
+
+        let tail = body.split_off(4);
+        header.extend_from_slice(&body);
+
+        let position = header.len() - 4;
+        if header[position] & 0x80 != 0 {
+            return Some(Message::from_header_and_body(header, tail));
+        }
+        body = tail;
+    }
+}
+
+/// Transports handing over messages in memory keep the header apart from the body,
+/// it must then hold the whole backtrace, the last hop being the only one with the top bit set.
+fn check_backtrace(msg: Message, ttl: u8) -> Option<Message> {
+    let hops = msg.get_header().len() / 4;
+
+    if msg.get_header().len() % 4 != 0 || hops > ttl as usize {
+        return None;
+    }
+
+    let bottom = msg.get_header().chunks(4).position(|hop| hop[0] & 0x80 != 0);
+
+    if bottom == Some(hops - 1) {
+        Some(msg)
+    } else {
+        None
+    }
+}
+
+/// Raw sockets prepend the id of the pipe a request came from to its header,
+/// so that the reply can be routed back to this pipe.
+fn push_pipe_id(msg: Message, eid: EndpointId) -> Message {
+    let (header, body) = msg.split();
+    let mut raw_header = vec![0; 4];
+    let id: usize = eid.into();
+
+    BigEndian::write_u32(&mut raw_header, id as u32);
+    raw_header.extend_from_slice(&header);
+
+    Message::from_header_and_body(raw_header, body)
+}
+
+fn pop_pipe_id(msg: Message) -> Option<(EndpointId, Message)> {
+    if msg.get_header().len() < 4 {
+        return None;
+    }
+
+    let (mut header, body) = msg.split();
+    let backtrace = header.split_off(4);
+    let id = BigEndian::read_u32(&header) as usize;
+
+    Some((EndpointId::from(id), Message::from_header_and_body(backtrace, body)))
+}
+
 /// **One-to-one protocol**   
 ///   
 /// Pair protocol is the simplest and least scalable scalability protocol. 
@@ -145,3 +228,67 @@ pub const RESPONDENT: u16 = (6 * 16) + 3;
 /// Sent messages are distributed to all nodes in the topology. 
 /// Incoming messages from all other nodes in the topology are fair-queued in the socket.
 pub const BUS:        u16 = (7 * 16)    ;
+
+#[cfg(test)]
+mod tests {
+
+    use core::{EndpointId, Message};
+
+    use super::{decode_backtrace, push_pipe_id, pop_pipe_id};
+
+    #[test]
+    fn decode_backtrace_moves_hops_to_header() {
+        let raw_msg = Message::from_body(vec![0, 0, 0, 2, 128, 0, 0, 1, 65, 66]);
+        let msg = decode_backtrace(raw_msg, 8).unwrap();
+
+        assert_eq!(&[0, 0, 0, 2, 128, 0, 0, 1], msg.get_header());
+        assert_eq!(&[65, 66], msg.get_body());
+    }
+
+    #[test]
+    fn decode_backtrace_accepts_header_kept_apart() {
+        let raw_msg = Message::from_header_and_body(vec![0, 0, 0, 2, 128, 0, 0, 1], vec![65, 66]);
+        let msg = decode_backtrace(raw_msg, 8).unwrap();
+
+        assert_eq!(&[0, 0, 0, 2, 128, 0, 0, 1], msg.get_header());
+        assert_eq!(&[65, 66], msg.get_body());
+    }
+
+    #[test]
+    fn decode_backtrace_rejects_incomplete_header_kept_apart() {
+        let unterminated = Message::from_header_and_body(vec![0, 0, 0, 2], vec![65, 66]);
+        let truncated = Message::from_header_and_body(vec![128, 0, 0], vec![65, 66]);
+
+        assert!(decode_backtrace(unterminated, 8).is_none());
+        assert!(decode_backtrace(truncated, 8).is_none());
+    }
+
+    #[test]
+    fn decode_backtrace_fails_when_ttl_is_exceeded() {
+        let raw_msg = Message::from_body(vec![0, 0, 0, 2, 128, 0, 0, 1, 65, 66]);
+
+        assert!(decode_backtrace(raw_msg, 1).is_none());
+    }
+
+    #[test]
+    fn pipe_id_can_be_pushed_then_popped() {
+        let eid = EndpointId::from(258);
+        let msg = Message::from_header_and_body(vec![128, 0, 0, 1], vec![65]);
+        let raw_msg = push_pipe_id(msg, eid);
+
+        assert_eq!(&[0, 0, 1, 2, 128, 0, 0, 1], raw_msg.get_header());
+
+        let (id, msg) = pop_pipe_id(raw_msg).unwrap();
+
+        assert_eq!(eid, id);
+        assert_eq!(&[128, 0, 0, 1], msg.get_header());
+        assert_eq!(&[65], msg.get_body());
+    }
+
+    #[test]
+    fn pipe_id_cannot_be_popped_from_short_header() {
+        let msg = Message::from_header_and_body(vec![0, 1], vec![65]);
+
+        assert!(pop_pipe_id(msg).is_none());
+    }
+}
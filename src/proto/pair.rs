@@ -95,6 +95,10 @@ impl Protocol for Pair {
     fn send(&mut self, ctx: &mut Context, msg: Message, timeout: Timeout) {
         self.apply(ctx, |s, ctx, inner| s.send(ctx, inner, Rc::new(msg), timeout))
     }
+    fn try_send(&mut self, ctx: &mut Context, msg: Message) {
+        self.send(ctx, msg, None);
+        self.apply(ctx, |s, _, inner| s.dont_wait(inner))
+    }
     fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId) {
         self.apply(ctx, |s, ctx, inner| s.on_send_ack(ctx, inner, eid))
     }
@@ -104,9 +108,16 @@ impl Protocol for Pair {
     fn on_send_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
         self.apply(ctx, |s, ctx, inner| s.on_send_ready(ctx, inner, eid))
     }
+    fn can_send(&self) -> bool {
+        self.inner.send_ready
+    }
     fn recv(&mut self, ctx: &mut Context, timeout: Timeout) {
         self.apply(ctx, |s, ctx, inner| s.recv(ctx, inner, timeout))
     }
+    fn try_recv(&mut self, ctx: &mut Context) {
+        self.recv(ctx, None);
+        self.apply(ctx, |s, _, inner| s.dont_wait(inner))
+    }
     fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, msg: Message) {
         self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, msg))
     }
@@ -116,6 +127,9 @@ impl Protocol for Pair {
     fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
         self.apply(ctx, |s, ctx, inner| s.on_recv_ready(ctx, inner, eid))
     }
+    fn can_recv(&self) -> bool {
+        self.inner.recv_ready
+    }
     fn close(&mut self, ctx: &mut Context) {
         self.inner.close(ctx)
     }
@@ -160,6 +174,20 @@ impl State {
         }
     }
 
+    fn dont_wait(self, inner: &mut Inner) -> State {
+        match self {
+            State::SendOnHold(_, _) => {
+                inner.on_send_would_block();
+                State::Idle
+            },
+            State::RecvOnHold(_) => {
+                inner.on_recv_would_block();
+                State::Idle
+            },
+            any => any
+        }
+    }
+
 /*****************************************************************************/
 /*                                                                           */
 /* send                                                                      */
@@ -196,7 +224,10 @@ impl State {
 
         match self {
             State::SendOnHold(msg, timeout) => State::Idle.send(ctx, inner, msg, timeout),
-            any => any
+            any => {
+                ctx.raise(Event::CanSend);
+                any
+            }
         }
     }
 
@@ -293,6 +324,10 @@ impl Inner {
         let error = timedout_io_error("Send timed out");
         let _ = self.reply_tx.send(Reply::Err(error));
     }
+    fn on_send_would_block(&self) {
+        let error = would_block_io_error("Send would block");
+        let _ = self.reply_tx.send(Reply::Err(error));
+    }
 
     fn recv(&mut self, ctx: &mut Context) -> Option<EndpointId> {
         if self.recv_ready == false {
@@ -320,6 +355,10 @@ impl Inner {
         let error = timedout_io_error("Recv timed out");
         let _ = self.reply_tx.send(Reply::Err(error));
     }
+    fn on_recv_would_block(&self) {
+        let error = would_block_io_error("Recv would block");
+        let _ = self.reply_tx.send(Reply::Err(error));
+    }
     fn close(&mut self, ctx: &mut Context) {
         self.pipe.take().map(|(_, pipe)| pipe.close(ctx));
     }
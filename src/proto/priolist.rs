@@ -140,6 +140,34 @@ impl Priolist {
         }
     }
 
+    /// Deactivates an item without taking it in turn, returns whether it was active.
+    pub fn deactivate(&mut self, id: &EndpointId) -> bool {
+        match self.find_by_id_in_all(id) {
+            Some(index) => self.deactivate_at_index(index),
+            None => false
+        }
+    }
+
+    fn deactivate_at_index(&mut self, index: usize) -> bool {
+        if !self.is_index_active(index) {
+            return false;
+        }
+
+        let priority = self.items[index].priority;
+
+        self.set_index_active(index, false);
+
+        if self.current == Some((index, priority)) {
+            self.compute_next(index, priority);
+        }
+
+        true
+    }
+
+    pub fn is_active(&self, id: &EndpointId) -> bool {
+        self.find_by_id_in_all(id).map_or(false, |index| self.is_index_active(index))
+    }
+
     fn is_index_active(&self, index: usize) -> bool {
         self.items[index].active
     }
@@ -148,6 +176,10 @@ impl Priolist {
         self.items[index].active = active;
     }
 
+    pub fn has_active(&self) -> bool {
+        self.current.is_some()
+    }
+
     pub fn pop(&mut self) -> Option<EndpointId> {
         if let Some((index, priority)) = self.current.take() {
             self.set_index_active(index, false);
@@ -253,6 +285,19 @@ mod tests {
         assert_eq!(Some(eid), priolist.pop());
     }
 
+    #[test]
+    fn has_active_until_all_items_are_popped() {
+        let mut priolist = Priolist::new();
+        let eid = EndpointId::from(0);
+
+        priolist.insert(eid, 8);
+        assert!(!priolist.has_active());
+        priolist.activate(&eid);
+        assert!(priolist.has_active());
+        priolist.pop();
+        assert!(!priolist.has_active());
+    }
+
     #[test]
     fn activate_does_not_change_existing_next() {
         let mut priolist = Priolist::new();
@@ -314,6 +359,25 @@ mod tests {
         assert_eq!(None, priolist.pop());
     }
 
+    #[test]
+    fn deactivate_current_moves_forward() {
+        let mut priolist = Priolist::new();
+        let one = EndpointId::from(0);
+        let two = EndpointId::from(1);
+
+        priolist.insert(one, 8);
+        priolist.insert(two, 8);
+        priolist.activate(&one);
+        priolist.activate(&two);
+
+        assert_eq!(true, priolist.deactivate(&one));
+        assert_eq!(false, priolist.deactivate(&one));
+        assert_eq!(false, priolist.is_active(&one));
+        assert_eq!(true, priolist.is_active(&two));
+        assert_eq!(Some(two), priolist.pop());
+        assert_eq!(None, priolist.pop());
+    }
+
     #[test]
     fn next_can_skip_lower_priorities() {
         let mut priolist = Priolist::new();
@@ -66,6 +66,9 @@ impl Protocol for Pub {
             ctx.cancel(sched);
         }
     }
+    fn try_send(&mut self, ctx: &mut Context, msg: Message) {
+        self.send(ctx, msg, None)
+    }
     fn on_send_ack(&mut self, _: &mut Context, _: EndpointId) {
     }
     fn on_send_timeout(&mut self, _: &mut Context) {
@@ -73,6 +76,9 @@ impl Protocol for Pub {
     fn on_send_ready(&mut self, _: &mut Context, eid: EndpointId) {
         self.bc.insert(eid);
     }
+    fn can_send(&self) -> bool {
+        true
+    }
     fn recv(&mut self, ctx: &mut Context, timeout: Timeout) {
         let error = other_io_error("Recv is not supported by pub protocol");
         let _ = self.reply_tx.send(Reply::Err(error));
@@ -80,12 +86,18 @@ impl Protocol for Pub {
             ctx.cancel(sched);
         }
     }
+    fn try_recv(&mut self, ctx: &mut Context) {
+        self.recv(ctx, None)
+    }
     fn on_recv_ack(&mut self, _: &mut Context, _: EndpointId, _: Message) {
     }
     fn on_recv_timeout(&mut self, _: &mut Context) {
     }
     fn on_recv_ready(&mut self, _: &mut Context, _: EndpointId) {
     }
+    fn can_recv(&self) -> bool {
+        false
+    }
     fn close(&mut self, ctx: &mut Context) {
         for (_, pipe) in self.pipes.drain() {
             pipe.close(ctx);
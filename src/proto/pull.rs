@@ -93,6 +93,10 @@ impl Protocol for Pull {
     fn send(&mut self, ctx: &mut Context, msg: Message, timeout: Timeout) {
         self.apply(ctx, |s, ctx, inner| s.send(ctx, inner, Rc::new(msg), timeout))
     }
+    fn try_send(&mut self, ctx: &mut Context, msg: Message) {
+        self.send(ctx, msg, None);
+        self.apply(ctx, |s, _, inner| s.dont_wait(inner))
+    }
     fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId) {
         self.apply(ctx, |s, ctx, inner| s.on_send_ack(ctx, inner, eid))
     }
@@ -102,9 +106,16 @@ impl Protocol for Pull {
     fn on_send_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
         self.apply(ctx, |s, ctx, inner| s.on_send_ready(ctx, inner, eid))
     }
+    fn can_send(&self) -> bool {
+        false
+    }
     fn recv(&mut self, ctx: &mut Context, timeout: Timeout) {
         self.apply(ctx, |s, ctx, inner| s.recv(ctx, inner, timeout))
     }
+    fn try_recv(&mut self, ctx: &mut Context) {
+        self.recv(ctx, None);
+        self.apply(ctx, |s, _, inner| s.dont_wait(inner))
+    }
     fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, msg: Message) {
         self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, msg))
     }
@@ -114,6 +125,9 @@ impl Protocol for Pull {
     fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
         self.apply(ctx, |s, ctx, inner| s.on_recv_ready(ctx, inner, eid))
     }
+    fn can_recv(&self) -> bool {
+        self.inner.can_recv()
+    }
     fn close(&mut self, ctx: &mut Context) {
         self.inner.close(ctx)
     }
@@ -149,6 +163,16 @@ impl State {
         }
     }
 
+    fn dont_wait(self, inner: &mut Inner) -> State {
+        match self {
+            State::RecvOnHold(_) => {
+                inner.on_recv_would_block();
+                State::Idle
+            },
+            any => any
+        }
+    }
+
 /*****************************************************************************/
 /*                                                                           */
 /* send                                                                      */
@@ -246,6 +270,9 @@ impl Inner {
     fn on_recv_ready(&mut self, eid: EndpointId) {
         self.fq.activate(&eid)
     }
+    fn can_recv(&self) -> bool {
+        self.fq.has_active()
+    }
     fn on_recv_ack(&self, ctx: &mut Context, timeout: Timeout, msg: Message) {
         let _ = self.reply_tx.send(Reply::Recv(msg));
         if let Some(sched) = timeout {
@@ -256,6 +283,10 @@ impl Inner {
         let error = timedout_io_error("Recv timed out");
         let _ = self.reply_tx.send(Reply::Err(error));
     }
+    fn on_recv_would_block(&self) {
+        let error = would_block_io_error("Recv would block");
+        let _ = self.reply_tx.send(Reply::Err(error));
+    }
     fn close(&mut self, ctx: &mut Context) {
         for (_, pipe) in self.pipes.drain() {
             pipe.close(ctx);
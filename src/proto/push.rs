@@ -11,7 +11,7 @@ use std::sync::mpsc::Sender;
 use core::{EndpointId, Message};
 use core::socket::{Protocol, Reply};
 use core::endpoint::Pipe;
-use core::context::Context;
+use core::context::{Context, Event};
 use super::priolist::Priolist;
 use super::{Timeout, PUSH, PULL};
 use io_error::*;
@@ -93,6 +93,10 @@ impl Protocol for Push {
     fn send(&mut self, ctx: &mut Context, msg: Message, timeout: Timeout) {
         self.apply(ctx, |s, ctx, inner| s.send(ctx, inner, Rc::new(msg), timeout))
     }
+    fn try_send(&mut self, ctx: &mut Context, msg: Message) {
+        self.send(ctx, msg, None);
+        self.apply(ctx, |s, _, inner| s.dont_wait(inner))
+    }
     fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId) {
         self.apply(ctx, |s, ctx, inner| s.on_send_ack(ctx, inner, eid))
     }
@@ -102,9 +106,16 @@ impl Protocol for Push {
     fn on_send_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
         self.apply(ctx, |s, ctx, inner| s.on_send_ready(ctx, inner, eid))
     }
+    fn can_send(&self) -> bool {
+        self.inner.can_send()
+    }
     fn recv(&mut self, ctx: &mut Context, timeout: Timeout) {
         self.apply(ctx, |s, ctx, inner| s.recv(ctx, inner, timeout))
     }
+    fn try_recv(&mut self, ctx: &mut Context) {
+        self.recv(ctx, None);
+        self.apply(ctx, |s, _, inner| s.dont_wait(inner))
+    }
     fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, msg: Message) {
         self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, msg))
     }
@@ -114,6 +125,9 @@ impl Protocol for Push {
     fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
         self.apply(ctx, |s, ctx, inner| s.on_recv_ready(ctx, inner, eid))
     }
+    fn can_recv(&self) -> bool {
+        false
+    }
     fn close(&mut self, ctx: &mut Context) {
         self.inner.close(ctx)
     }
@@ -149,6 +163,16 @@ impl State {
         }
     }
 
+    fn dont_wait(self, inner: &mut Inner) -> State {
+        match self {
+            State::SendOnHold(_, _) => {
+                inner.on_send_would_block();
+                State::Idle
+            },
+            any => any
+        }
+    }
+
 /*****************************************************************************/
 /*                                                                           */
 /* send                                                                      */
@@ -185,7 +209,10 @@ impl State {
 
         match self {
             State::SendOnHold(msg, timeout) => State::Idle.send(ctx, inner, msg, timeout),
-            any => any
+            any => {
+                ctx.raise(Event::CanSend);
+                any
+            }
         }
     }
 
@@ -237,6 +264,9 @@ impl Inner {
     fn on_send_ready(&mut self, eid: EndpointId) {
         self.lb.activate(&eid)
     }
+    fn can_send(&self) -> bool {
+        self.lb.has_active()
+    }
     fn on_send_ack(&self, ctx: &mut Context, timeout: Timeout) {
         let _ = self.reply_tx.send(Reply::Send);
         if let Some(sched) = timeout {
@@ -247,6 +277,10 @@ impl Inner {
         let error = timedout_io_error("Send timed out");
         let _ = self.reply_tx.send(Reply::Err(error));
     }
+    fn on_send_would_block(&self) {
+        let error = would_block_io_error("Send would block");
+        let _ = self.reply_tx.send(Reply::Err(error));
+    }
 
     fn recv(&mut self, ctx: &mut Context, timeout: Timeout) {
         let error = other_io_error("Recv is not supported by push protocol");
@@ -4,389 +4,26 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::collections::HashMap;
-use std::rc::Rc;
 use std::sync::mpsc::Sender;
+use std::io;
 
-use core::{EndpointId, Message};
+use core::{EndpointId, ContextId, Message};
 use core::socket::{Protocol, Reply};
 use core::endpoint::Pipe;
-use core::context::{Context, Event};
-use super::priolist::Priolist;
+use core::context::Context;
+use super::replier::Replier;
 use super::{Timeout, REQ, REP};
-use io_error::*;
 
 pub struct Rep {
-    inner: Inner,
-    state: Option<State>
-}
-
-enum State {
-    Idle,
-    Receiving(EndpointId, Timeout),
-    RecvOnHold(Timeout),
-    Active(EndpointId),
-    Sending(EndpointId, Rc<Message>, Timeout),
-    SendOnHold(EndpointId, Rc<Message>, Timeout)
-}
-
-struct Inner {
-    reply_tx: Sender<Reply>,
-    pipes: HashMap<EndpointId, Pipe>,
-    fq: Priolist,
-    ttl: u8,
-    backtrace: Vec<u8>,
-    is_device_item: bool
-}
-
-/*****************************************************************************/
-/*                                                                           */
-/* Rep                                                                      */
-/*                                                                           */
-/*****************************************************************************/
-
-impl Rep {
-
-    fn apply<F>(&mut self, ctx: &mut Context, transition: F) where F : FnOnce(State, &mut Context, &mut Inner) -> State {
-        if let Some(old_state) = self.state.take() {
-            #[cfg(debug_assertions)] let old_name = old_state.name();
-            let new_state = transition(old_state, ctx, &mut self.inner);
-            #[cfg(debug_assertions)] let new_name = new_state.name();
-
-            self.state = Some(new_state);
-
-            #[cfg(debug_assertions)] debug!("[{:?}] switch from {} to {}", ctx, old_name, new_name);
-        }
-    }
-
+    replier: Replier
 }
 
 impl From<Sender<Reply>> for Rep {
     fn from(tx: Sender<Reply>) -> Rep {
         Rep {
-            inner: Inner::new(tx),
-            state: Some(State::Idle)
-        }
-    }
-}
-
-/*****************************************************************************/
-/*                                                                           */
-/* Protocol                                                                  */
-/*                                                                           */
-/*****************************************************************************/
-
-impl Protocol for Rep {
-    fn id(&self)      -> u16 { REP }
-    fn peer_id(&self) -> u16 { REQ }
-
-    fn add_pipe(&mut self, _: &mut Context, eid: EndpointId, pipe: Pipe) {
-        self.inner.add_pipe(eid, pipe)
-    }
-    fn remove_pipe(&mut self, ctx: &mut Context, eid: EndpointId) -> Option<Pipe> {
-        let pipe = self.inner.remove_pipe(eid);
-
-        if pipe.is_some() {
-            self.apply(ctx, |s, ctx, inner| s.on_pipe_removed(ctx, inner, eid));
-        }
-
-        pipe
-    }
-    fn send(&mut self, ctx: &mut Context, msg: Message, timeout: Timeout) {
-        let raw_msg = self.inner.msg_to_raw_msg(msg);
-
-        self.apply(ctx, |s, ctx, inner| s.send(ctx, inner, Rc::new(raw_msg), timeout))
-    }
-    fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.inner.clear_backtrace();
-        
-        self.apply(ctx, |s, ctx, inner| s.on_send_ack(ctx, inner, eid))
-    }
-    fn on_send_timeout(&mut self, ctx: &mut Context) {
-        self.apply(ctx, |s, ctx, inner| s.on_send_timeout(ctx, inner))
-    }
-    fn on_send_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_send_ready(ctx, inner, eid))
-    }
-    fn recv(&mut self, ctx: &mut Context, timeout: Timeout) {
-        self.apply(ctx, |s, ctx, inner| s.recv(ctx, inner, timeout))
-    }
-    fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, raw_msg: Message) {
-        if let Some(msg) = self.inner.raw_msg_to_msg(raw_msg) {
-            self.inner.set_backtrace(msg.get_header());
-
-            self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, msg))
-        } else {
-            self.inner.on_recv_ack_malformed(ctx)
+            replier: Replier::new(REP, REQ, tx)
         }
     }
-    fn on_recv_timeout(&mut self, ctx: &mut Context) {
-        self.apply(ctx, |s, ctx, inner| s.on_recv_timeout(ctx, inner))
-    }
-    fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_recv_ready(ctx, inner, eid))
-    }
-    fn on_device_plugged(&mut self, _: &mut Context) {
-        self.inner.is_device_item = true;
-    }
-    fn close(&mut self, ctx: &mut Context) {
-        self.inner.close(ctx)
-    }
 }
 
-/*****************************************************************************/
-/*                                                                           */
-/* State                                                                     */
-/*                                                                           */
-/*****************************************************************************/
-
-impl State {
-
-    #[cfg(debug_assertions)]
-    fn name(&self) -> &'static str {
-        match *self {
-            State::Idle             => "Idle",
-            State::Sending(_, _, _) => "Sending",
-            State::SendOnHold(_, _, _) => "SendOnHold",
-            State::Active(_)        => "Active",
-            State::Receiving(_, _)  => "Receiving",
-            State::RecvOnHold(_)    => "RecvOnHold"
-        }
-    }
-
-    fn on_pipe_removed(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
-        match self {
-            State::Receiving(id, timeout) => {
-                if id == eid {
-                    State::Idle.recv(ctx, inner, timeout)
-                } else {
-                    State::Receiving(id, timeout)
-                }
-            },
-            any => any
-        }
-    }
-
-/*****************************************************************************/
-/*                                                                           */
-/* send                                                                      */
-/*                                                                           */
-/*****************************************************************************/
-
-    fn send(self, ctx: &mut Context, inner: &mut Inner, msg: Rc<Message>, timeout: Timeout) -> State {
-        if let State::Active(eid) = self {
-            State::Idle.send_reply_to(ctx, inner, msg, timeout, eid)
-        } else {
-            inner.send_when_inactive(ctx, timeout);
-
-            State::Idle
-        }
-    }
-    fn send_reply_to(self, ctx: &mut Context, inner: &mut Inner, msg: Rc<Message>, timeout: Timeout, eid: EndpointId) -> State {
-        if inner.send_to(ctx, msg.clone(), eid) {
-            State::Sending(eid, msg, timeout)
-        } else {
-            State::SendOnHold(eid, msg, timeout)
-        }
-    }
-    fn on_send_ack(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
-        match self {
-            State::Sending(id, msg, timeout) => {
-                if id == eid {
-                    inner.on_send_ack(ctx, timeout);
-                    State::Idle
-                } else {
-                    State::Sending(id, msg, timeout)
-                }
-            },
-            any => any
-        }
-    }
-    fn on_send_timeout(self, _: &mut Context, inner: &mut Inner) -> State {
-        inner.on_send_timeout();
-
-        State::Idle
-    }
-    fn on_send_ready(self, _: &mut Context, _: &mut Inner, _: EndpointId) -> State {
-        self
-    }
-
-/*****************************************************************************/
-/*                                                                           */
-/* recv                                                                      */
-/*                                                                           */
-/*****************************************************************************/
-
-    fn recv(self, ctx: &mut Context, inner: &mut Inner, timeout: Timeout) -> State {
-        inner.recv(ctx).map_or_else(
-            |   | State::RecvOnHold(timeout),
-            |eid| State::Receiving(eid, timeout))
-    }
-    fn on_recv_ack(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId, msg: Message) -> State {
-        match self {
-            State::Receiving(id, timeout) => {
-                if id == eid {
-                    inner.on_recv_ack(ctx, timeout, msg);
-                    State::Active(eid)
-                } else {
-                    State::Receiving(id, timeout)
-                }
-            },
-            any => any
-        }
-    }
-    fn on_recv_timeout(self, _: &mut Context, inner: &mut Inner) -> State {
-        inner.on_recv_timeout();
-
-        State::Idle
-    }
-    fn on_recv_ready(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
-        inner.on_recv_ready(eid);
-
-        match self {
-            State::RecvOnHold(timeout) => State::Idle.recv(ctx, inner, timeout),
-            any => {
-                ctx.raise(Event::CanRecv);
-                any
-            }
-        }
-    }
-}
-
-/*****************************************************************************/
-/*                                                                           */
-/* Inner                                                                     */
-/*                                                                           */
-/*****************************************************************************/
-
-impl Inner {
-    fn new(tx: Sender<Reply>) -> Inner {
-        Inner {
-            reply_tx: tx,
-            pipes: HashMap::new(),
-            fq: Priolist::new(),
-            ttl: 8,
-            backtrace: Vec::new(),
-            is_device_item: false
-        }
-    }
-    fn add_pipe(&mut self, eid: EndpointId, pipe: Pipe) {
-        self.fq.insert(eid, pipe.get_recv_priority());
-        self.pipes.insert(eid, pipe);
-    }
-    fn remove_pipe(&mut self, eid: EndpointId) -> Option<Pipe> {
-        self.fq.remove(&eid);
-        self.pipes.remove(&eid)
-    }
-
-    fn send_to(&mut self, ctx: &mut Context, msg: Rc<Message>, eid: EndpointId) -> bool {
-        self.pipes.get_mut(&eid).map(|pipe| pipe.send(ctx, msg)).is_some()
-    }
-    fn on_send_ack(&self, ctx: &mut Context, timeout: Timeout) {
-        let _ = self.reply_tx.send(Reply::Send);
-        if let Some(sched) = timeout {
-            ctx.cancel(sched);
-        }
-    }
-    fn send_when_inactive(&mut self, ctx: &mut Context, timeout: Timeout) {
-        let error = other_io_error("Can't send: no active request");
-        let _ = self.reply_tx.send(Reply::Err(error));
-        if let Some(sched) = timeout {
-            ctx.cancel(sched);
-        }
-    }
-    fn on_send_timeout(&self) {
-        let error = timedout_io_error("Send timed out");
-        let _ = self.reply_tx.send(Reply::Err(error));
-    }
-
-    fn recv(&mut self, ctx: &mut Context) -> Option<EndpointId> {
-        self.fq.pop().map_or(None, |eid| self.recv_from(ctx, eid))
-    }
-    fn recv_from(&mut self, ctx: &mut Context, eid: EndpointId) -> Option<EndpointId> {
-        self.pipes.get_mut(&eid).map_or(None, |pipe| {
-            pipe.recv(ctx); 
-            Some(eid)
-        })
-    }
-    fn on_recv_ready(&mut self, eid: EndpointId) {
-        self.fq.activate(&eid)
-    }
-    fn on_recv_ack(&self, ctx: &mut Context, timeout: Timeout, msg: Message) {
-        let _ = self.reply_tx.send(Reply::Recv(msg));
-        if let Some(sched) = timeout {
-            ctx.cancel(sched);
-        }
-    }
-    fn on_recv_timeout(&self) {
-        let error = timedout_io_error("Recv timed out");
-        let _ = self.reply_tx.send(Reply::Err(error));
-    }
-    fn on_recv_ack_malformed(&self, _: &mut Context) {
-        let error = invalid_data_io_error("Received request without id");
-        let _ = self.reply_tx.send(Reply::Err(error));
-    }
-
-    fn raw_msg_to_msg(&self, raw_msg: Message) -> Option<Message> {
-        if self.is_device_item {
-            Some(raw_msg)
-        } else {
-            decode(raw_msg, self.ttl)
-        }
-    }
-    fn msg_to_raw_msg(&self, msg: Message) -> Message {
-        if self.is_device_item {
-            msg
-        } else {
-            encode(msg, self.get_backtrace())
-        }
-    }
-    fn set_backtrace(&mut self, bt: &[u8]) {
-        self.backtrace.clear();
-        self.backtrace.extend_from_slice(bt);
-    }
-    fn get_backtrace(&self) -> &[u8] {
-        &self.backtrace
-    }
-    fn clear_backtrace(&mut self) {
-        self.backtrace.clear();
-    }
-    fn close(&mut self, ctx: &mut Context) {
-        for (_, pipe) in self.pipes.drain() {
-            pipe.close(ctx);
-        }
-    }
-}
-
-fn decode(raw_msg: Message, ttl: u8) -> Option<Message> {
-    let (mut header, mut body) = raw_msg.split();
-    let mut hops = 0;
-
-    loop {
-        if hops >= ttl {
-            return None;
-        }
-        hops += 1;
-
-        if body.len() < 4 {
-            return None;
-        }
-
-        let tail = body.split_off(4);
-        header.extend_from_slice(&body);
-
-        let position = header.len() - 4;
-        if header[position] & 0x80 != 0 {
-            return Some(Message::from_header_and_body(header, tail));
-        }
-        body = tail;
-    }
-}
-
-fn encode(msg: Message, backtrace: &[u8]) -> Message {
-    let (mut header, body) = msg.split();
-
-    header.extend_from_slice(backtrace);
-
-    Message::from_header_and_body(header, body)
-}
+replier_protocol!(Rep);
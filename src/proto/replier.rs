@@ -0,0 +1,675 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::sync::mpsc::Sender;
+use std::io;
+
+use core::{EndpointId, ContextId, Message};
+use core::socket::{Protocol, Reply};
+use core::endpoint::Pipe;
+use core::context::{Context, Event};
+use super::priolist::Priolist;
+use super::Timeout;
+use super::{decode_backtrace, push_pipe_id, pop_pipe_id};
+use io_error::*;
+
+/// Implements `Protocol` for a socket type holding a `Replier` in its `replier` field.
+macro_rules! replier_protocol {
+    ($name:ident) => {
+        impl Protocol for $name {
+            fn id(&self)      -> u16 { self.replier.id() }
+            fn peer_id(&self) -> u16 { self.replier.peer_id() }
+
+            fn add_pipe(&mut self, ctx: &mut Context, eid: EndpointId, pipe: Pipe) {
+                self.replier.add_pipe(ctx, eid, pipe)
+            }
+            fn remove_pipe(&mut self, ctx: &mut Context, eid: EndpointId) -> Option<Pipe> {
+                self.replier.remove_pipe(ctx, eid)
+            }
+            fn send(&mut self, ctx: &mut Context, msg: Message, timeout: Timeout) {
+                self.replier.send(ctx, msg, timeout)
+            }
+            fn try_send(&mut self, ctx: &mut Context, msg: Message) {
+                self.replier.try_send(ctx, msg)
+            }
+            fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId) {
+                self.replier.on_send_ack(ctx, eid)
+            }
+            fn on_send_timeout(&mut self, ctx: &mut Context) {
+                self.replier.on_send_timeout(ctx)
+            }
+            fn on_send_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
+                self.replier.on_send_ready(ctx, eid)
+            }
+            fn can_send(&self) -> bool {
+                self.replier.can_send()
+            }
+            fn recv(&mut self, ctx: &mut Context, timeout: Timeout) {
+                self.replier.recv(ctx, timeout)
+            }
+            fn try_recv(&mut self, ctx: &mut Context) {
+                self.replier.try_recv(ctx)
+            }
+            fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, raw_msg: Message) {
+                self.replier.on_recv_ack(ctx, eid, raw_msg)
+            }
+            fn on_recv_timeout(&mut self, ctx: &mut Context) {
+                self.replier.on_recv_timeout(ctx)
+            }
+            fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
+                self.replier.on_recv_ready(ctx, eid)
+            }
+            fn can_recv(&self) -> bool {
+                self.replier.can_recv()
+            }
+            fn set_raw(&mut self) {
+                self.replier.set_raw()
+            }
+            fn create_context(&mut self, cid: ContextId, reply_tx: Sender<Reply>) -> io::Result<()> {
+                self.replier.create_context(cid, reply_tx)
+            }
+            fn context_send(&mut self, ctx: &mut Context, cid: ContextId, msg: Message, timeout: Timeout) {
+                self.replier.context_send(ctx, cid, msg, timeout)
+            }
+            fn on_context_send_timeout(&mut self, ctx: &mut Context, cid: ContextId) {
+                self.replier.on_context_send_timeout(ctx, cid)
+            }
+            fn context_recv(&mut self, ctx: &mut Context, cid: ContextId, timeout: Timeout) {
+                self.replier.context_recv(ctx, cid, timeout)
+            }
+            fn on_context_recv_timeout(&mut self, ctx: &mut Context, cid: ContextId) {
+                self.replier.on_context_recv_timeout(ctx, cid)
+            }
+            fn close_context(&mut self, ctx: &mut Context, cid: ContextId) {
+                self.replier.close_context(ctx, cid)
+            }
+            fn close(&mut self, ctx: &mut Context) {
+                self.replier.close(ctx)
+            }
+        }
+    }
+}
+
+/// Requests are answered either by the socket itself, identified by `None`, or by one of its contexts.
+/// Each of them has its own state and backtrace, while the pipes and the fair queue are shared.
+type SlotId = Option<ContextId>;
+
+/// Replying side of both request/reply and survey protocols, which only differ by their ids.
+pub struct Replier {
+    id: u16,
+    peer_id: u16,
+    inner: Inner,
+    slots: HashMap<SlotId, Slot>
+}
+
+enum State {
+    Idle,
+    Receiving(EndpointId, Timeout),
+    RecvOnHold(Timeout),
+    Active(EndpointId),
+    Sending(EndpointId, Rc<Message>, Timeout),
+    SendOnHold(EndpointId, Rc<Message>, Timeout)
+}
+
+struct Slot {
+    id: SlotId,
+    reply_tx: Sender<Reply>,
+    backtrace: Vec<u8>,
+    state: Option<State>
+}
+
+struct Inner {
+    pipes: HashMap<EndpointId, Pipe>,
+    fq: Priolist,
+    sending: HashSet<EndpointId>,
+    ttl: u8,
+    raw: bool
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Replier                                                                   */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Replier {
+
+    pub fn new(id: u16, peer_id: u16, tx: Sender<Reply>) -> Replier {
+        let mut slots = HashMap::new();
+
+        slots.insert(None, Slot::new(None, tx));
+
+        Replier {
+            id: id,
+            peer_id: peer_id,
+            inner: Inner::new(),
+            slots: slots
+        }
+    }
+
+    fn apply<F>(&mut self, ctx: &mut Context, id: SlotId, transition: F) where F : FnOnce(State, &mut Context, &mut Inner, &mut Slot) -> State {
+        if let Some(slot) = self.slots.get_mut(&id) {
+            if let Some(old_state) = slot.state.take() {
+                #[cfg(debug_assertions)] let old_name = old_state.name();
+                let new_state = transition(old_state, ctx, &mut self.inner, slot);
+                #[cfg(debug_assertions)] let new_name = new_state.name();
+
+                slot.state = Some(new_state);
+
+                #[cfg(debug_assertions)] debug!("[{:?}] {:?} switch from {} to {}", ctx, id, old_name, new_name);
+            }
+        }
+    }
+
+    fn find<P>(&self, predicate: P) -> Option<SlotId> where P : Fn(&State) -> bool {
+        self.slots.values().
+            find(|slot| slot.state.as_ref().map_or(false, |s| predicate(s))).
+            map(|slot| slot.id)
+    }
+
+    fn find_all<P>(&self, predicate: P) -> Vec<SlotId> where P : Fn(&State) -> bool {
+        self.slots.values().
+            filter(|slot| slot.state.as_ref().map_or(false, |s| predicate(s))).
+            map(|slot| slot.id).
+            collect()
+    }
+
+    fn send_from(&mut self, ctx: &mut Context, id: SlotId, msg: Message, timeout: Timeout) {
+        self.apply(ctx, id, |s, ctx, inner, slot| {
+            let raw_msg = encode(msg, &slot.backtrace);
+
+            s.send(ctx, inner, slot, Rc::new(raw_msg), timeout)
+        })
+    }
+
+    /// In raw mode, the reply is routed to the pipe whose id heads the header,
+    /// and is dropped when this pipe is gone.
+    fn send_raw(&mut self, ctx: &mut Context, msg: Message, timeout: Timeout) {
+        match pop_pipe_id(msg) {
+            Some((eid, raw_msg)) => {
+                if self.inner.pipes.contains_key(&eid) {
+                    self.apply(ctx, None, |_, ctx, inner, _| State::Idle.send_reply_to(ctx, inner, Rc::new(raw_msg), timeout, eid))
+                } else {
+                    self.apply(ctx, None, |s, ctx, _, slot| { slot.on_send_ack(ctx, timeout); s })
+                }
+            },
+            None => self.apply(ctx, None, |s, ctx, _, slot| { slot.send_without_pipe_id(ctx, timeout); s })
+        }
+    }
+
+    fn recv_from(&mut self, ctx: &mut Context, id: SlotId, timeout: Timeout) {
+        self.apply(ctx, id, |s, ctx, inner, _| s.recv(ctx, inner, timeout))
+    }
+
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Protocol                                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Protocol for Replier {
+    fn id(&self)      -> u16 { self.id }
+    fn peer_id(&self) -> u16 { self.peer_id }
+
+    fn add_pipe(&mut self, _: &mut Context, eid: EndpointId, pipe: Pipe) {
+        self.inner.add_pipe(eid, pipe)
+    }
+    fn remove_pipe(&mut self, ctx: &mut Context, eid: EndpointId) -> Option<Pipe> {
+        let pipe = self.inner.remove_pipe(eid);
+
+        if pipe.is_some() {
+            for id in self.find_all(|s| s.is_using(eid)) {
+                self.apply(ctx, id, |s, ctx, inner, slot| s.on_pipe_removed(ctx, inner, slot, eid));
+            }
+        }
+
+        pipe
+    }
+    fn send(&mut self, ctx: &mut Context, msg: Message, timeout: Timeout) {
+        if self.inner.raw {
+            self.send_raw(ctx, msg, timeout)
+        } else {
+            self.send_from(ctx, None, msg, timeout)
+        }
+    }
+    fn try_send(&mut self, ctx: &mut Context, msg: Message) {
+        self.send(ctx, msg, None);
+        self.apply(ctx, None, |s, _, _, slot| s.dont_wait(slot))
+    }
+    fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId) {
+        self.inner.on_send_ack(eid);
+
+        if let Some(id) = self.find(|s| s.is_sending_to(eid)) {
+            self.apply(ctx, id, |s, ctx, _, slot| s.on_send_ack(ctx, slot, eid));
+        }
+        if let Some(id) = self.find(|s| s.is_send_on_hold_for(eid)) {
+            self.apply(ctx, id, |s, ctx, inner, _| s.on_pipe_available(ctx, inner));
+        }
+    }
+    fn on_send_timeout(&mut self, ctx: &mut Context) {
+        self.apply(ctx, None, |s, ctx, _, slot| s.on_send_timeout(ctx, slot))
+    }
+    fn on_send_ready(&mut self, _: &mut Context, _: EndpointId) {
+    }
+    fn can_send(&self) -> bool {
+        if self.inner.raw {
+            !self.inner.pipes.is_empty()
+        } else {
+            self.slots.get(&None).and_then(|slot| slot.state.as_ref()).map_or(false, State::can_send)
+        }
+    }
+    fn recv(&mut self, ctx: &mut Context, timeout: Timeout) {
+        self.recv_from(ctx, None, timeout)
+    }
+    fn try_recv(&mut self, ctx: &mut Context) {
+        self.recv(ctx, None);
+        self.apply(ctx, None, |s, _, _, slot| s.dont_wait(slot))
+    }
+    fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, raw_msg: Message) {
+        if let Some(id) = self.find(|s| s.is_receiving_from(eid)) {
+            self.apply(ctx, id, |s, ctx, inner, slot| s.on_recv_ack(ctx, inner, slot, eid, raw_msg))
+        }
+    }
+    fn on_recv_timeout(&mut self, ctx: &mut Context) {
+        self.apply(ctx, None, |s, ctx, _, slot| s.on_recv_timeout(ctx, slot))
+    }
+    fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
+        self.inner.on_recv_ready(eid);
+
+        match self.find(State::is_recv_on_hold) {
+            Some(id) => self.apply(ctx, id, |s, ctx, inner, _| s.on_recv_ready(ctx, inner)),
+            None => ctx.raise(Event::CanRecv)
+        }
+    }
+    fn can_recv(&self) -> bool {
+        self.inner.can_recv()
+    }
+    fn set_raw(&mut self) {
+        self.inner.raw = true;
+    }
+    fn create_context(&mut self, cid: ContextId, reply_tx: Sender<Reply>) -> io::Result<()> {
+        if self.inner.raw {
+            return Err(other_io_error("contexts not supported by raw sockets"));
+        }
+
+        self.slots.insert(Some(cid), Slot::new(Some(cid), reply_tx));
+        Ok(())
+    }
+    fn context_send(&mut self, ctx: &mut Context, cid: ContextId, msg: Message, timeout: Timeout) {
+        self.send_from(ctx, Some(cid), msg, timeout)
+    }
+    fn on_context_send_timeout(&mut self, ctx: &mut Context, cid: ContextId) {
+        self.apply(ctx, Some(cid), |s, ctx, _, slot| s.on_send_timeout(ctx, slot))
+    }
+    fn context_recv(&mut self, ctx: &mut Context, cid: ContextId, timeout: Timeout) {
+        self.recv_from(ctx, Some(cid), timeout)
+    }
+    fn on_context_recv_timeout(&mut self, ctx: &mut Context, cid: ContextId) {
+        self.apply(ctx, Some(cid), |s, ctx, _, slot| s.on_recv_timeout(ctx, slot))
+    }
+    fn close_context(&mut self, ctx: &mut Context, cid: ContextId) {
+        if let Some(state) = self.slots.remove(&Some(cid)).and_then(|mut slot| slot.state.take()) {
+            state.cancel(ctx);
+        }
+    }
+    fn close(&mut self, ctx: &mut Context) {
+        self.inner.close(ctx)
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* State                                                                     */
+/*                                                                           */
+/*****************************************************************************/
+
+impl State {
+
+    #[cfg(debug_assertions)]
+    fn name(&self) -> &'static str {
+        match *self {
+            State::Idle             => "Idle",
+            State::Sending(_, _, _) => "Sending",
+            State::SendOnHold(_, _, _) => "SendOnHold",
+            State::Active(_)        => "Active",
+            State::Receiving(_, _)  => "Receiving",
+            State::RecvOnHold(_)    => "RecvOnHold"
+        }
+    }
+
+    fn is_sending_to(&self, eid: EndpointId) -> bool {
+        match *self {
+            State::Sending(id, _, _) => id == eid,
+            _ => false
+        }
+    }
+
+    fn is_send_on_hold_for(&self, eid: EndpointId) -> bool {
+        match *self {
+            State::SendOnHold(id, _, _) => id == eid,
+            _ => false
+        }
+    }
+
+    fn is_receiving_from(&self, eid: EndpointId) -> bool {
+        match *self {
+            State::Receiving(id, _) => id == eid,
+            _ => false
+        }
+    }
+
+    fn is_using(&self, eid: EndpointId) -> bool {
+        match *self {
+            State::Receiving(id, _) |
+            State::Sending(id, _, _) |
+            State::SendOnHold(id, _, _) => id == eid,
+            _ => false
+        }
+    }
+
+    fn is_recv_on_hold(&self) -> bool {
+        match *self {
+            State::RecvOnHold(_) => true,
+            _ => false
+        }
+    }
+
+    /// A pending receive moves on to another pipe, while a pending reply can no longer be delivered.
+    fn on_pipe_removed(self, ctx: &mut Context, inner: &mut Inner, slot: &mut Slot, eid: EndpointId) -> State {
+        match self {
+            State::Receiving(id, timeout) if id == eid => State::Idle.recv(ctx, inner, timeout),
+            State::Sending(id, _, timeout) |
+            State::SendOnHold(id, _, timeout) if id == eid => {
+                slot.send_when_pipe_removed(ctx, timeout);
+                State::Idle
+            },
+            any => any
+        }
+    }
+
+    fn dont_wait(self, slot: &mut Slot) -> State {
+        match self {
+            State::SendOnHold(eid, _, _) => {
+                slot.on_send_would_block();
+                State::Active(eid)
+            },
+            State::RecvOnHold(_) => {
+                slot.on_recv_would_block();
+                State::Idle
+            },
+            any => any
+        }
+    }
+
+    fn can_send(&self) -> bool {
+        match *self {
+            State::Active(_) => true,
+            _ => false
+        }
+    }
+
+    fn cancel(self, ctx: &mut Context) {
+        match self {
+            State::Receiving(_, timeout) |
+            State::RecvOnHold(timeout) |
+            State::Sending(_, _, timeout) |
+            State::SendOnHold(_, _, timeout) => cancel(ctx, timeout),
+            _ => {}
+        }
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* send                                                                      */
+/*                                                                           */
+/*****************************************************************************/
+
+    fn send(self, ctx: &mut Context, inner: &mut Inner, slot: &mut Slot, msg: Rc<Message>, timeout: Timeout) -> State {
+        if let State::Active(eid) = self {
+            if inner.pipes.contains_key(&eid) {
+                State::Idle.send_reply_to(ctx, inner, msg, timeout, eid)
+            } else {
+                slot.send_when_pipe_removed(ctx, timeout);
+                State::Idle
+            }
+        } else {
+            slot.send_when_inactive(ctx, timeout);
+
+            State::Idle
+        }
+    }
+    fn send_reply_to(self, ctx: &mut Context, inner: &mut Inner, msg: Rc<Message>, timeout: Timeout, eid: EndpointId) -> State {
+        if inner.send_to(ctx, msg.clone(), eid) {
+            State::Sending(eid, msg, timeout)
+        } else {
+            State::SendOnHold(eid, msg, timeout)
+        }
+    }
+    fn on_send_ack(self, ctx: &mut Context, slot: &mut Slot, eid: EndpointId) -> State {
+        match self {
+            State::Sending(id, msg, timeout) => {
+                if id == eid {
+                    slot.on_send_ack(ctx, timeout);
+                    State::Idle
+                } else {
+                    State::Sending(id, msg, timeout)
+                }
+            },
+            any => any
+        }
+    }
+    fn on_send_timeout(self, _: &mut Context, slot: &mut Slot) -> State {
+        slot.on_send_timeout();
+
+        State::Idle
+    }
+    fn on_pipe_available(self, ctx: &mut Context, inner: &mut Inner) -> State {
+        match self {
+            State::SendOnHold(eid, msg, timeout) => State::Idle.send_reply_to(ctx, inner, msg, timeout, eid),
+            any => any
+        }
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* recv                                                                      */
+/*                                                                           */
+/*****************************************************************************/
+
+    fn recv(self, ctx: &mut Context, inner: &mut Inner, timeout: Timeout) -> State {
+        inner.recv(ctx).map_or_else(
+            |   | State::RecvOnHold(timeout),
+            |eid| State::Receiving(eid, timeout))
+    }
+    fn on_recv_ack(self, ctx: &mut Context, inner: &mut Inner, slot: &mut Slot, eid: EndpointId, raw_msg: Message) -> State {
+        match self {
+            State::Receiving(id, timeout) => {
+                if id == eid {
+                    match inner.raw_msg_to_msg(raw_msg, eid) {
+                        Some(msg) => {
+                            slot.on_recv_ack(ctx, timeout, msg);
+                            State::Active(eid)
+                        },
+                        None => {
+                            slot.on_recv_ack_malformed(ctx, timeout);
+                            State::Idle
+                        }
+                    }
+                } else {
+                    State::Receiving(id, timeout)
+                }
+            },
+            any => any
+        }
+    }
+    fn on_recv_timeout(self, _: &mut Context, slot: &mut Slot) -> State {
+        slot.on_recv_timeout();
+
+        State::Idle
+    }
+    fn on_recv_ready(self, ctx: &mut Context, inner: &mut Inner) -> State {
+        match self {
+            State::RecvOnHold(timeout) => State::Idle.recv(ctx, inner, timeout),
+            any => any
+        }
+    }
+}
+
+fn cancel(ctx: &mut Context, timeout: Timeout) {
+    if let Some(sched) = timeout {
+        ctx.cancel(sched);
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Slot                                                                      */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Slot {
+    fn new(id: SlotId, tx: Sender<Reply>) -> Slot {
+        Slot {
+            id: id,
+            reply_tx: tx,
+            backtrace: Vec::new(),
+            state: Some(State::Idle)
+        }
+    }
+    fn send_reply(&self, reply: Reply) {
+        let _ = self.reply_tx.send(reply);
+    }
+    fn on_send_ack(&mut self, ctx: &mut Context, timeout: Timeout) {
+        self.backtrace.clear();
+        self.send_reply(Reply::Send);
+        cancel(ctx, timeout);
+    }
+    fn send_when_inactive(&self, ctx: &mut Context, timeout: Timeout) {
+        let error = other_io_error("Can't send: no active request");
+        self.send_reply(Reply::Err(error));
+        cancel(ctx, timeout);
+    }
+    fn send_when_pipe_removed(&mut self, ctx: &mut Context, timeout: Timeout) {
+        let error = conn_aborted_io_error("Can't send: the requester is gone");
+        self.backtrace.clear();
+        self.send_reply(Reply::Err(error));
+        cancel(ctx, timeout);
+    }
+    fn on_send_timeout(&self) {
+        let error = timedout_io_error("Send timed out");
+        self.send_reply(Reply::Err(error));
+    }
+    fn on_send_would_block(&self) {
+        let error = would_block_io_error("Send would block");
+        self.send_reply(Reply::Err(error));
+    }
+    fn send_without_pipe_id(&self, ctx: &mut Context, timeout: Timeout) {
+        let error = invalid_input_io_error("Can't send: no pipe id in header");
+        self.send_reply(Reply::Err(error));
+        cancel(ctx, timeout);
+    }
+    fn on_recv_ack(&mut self, ctx: &mut Context, timeout: Timeout, msg: Message) {
+        self.backtrace.clear();
+        self.backtrace.extend_from_slice(msg.get_header());
+        self.send_reply(Reply::Recv(msg));
+        cancel(ctx, timeout);
+    }
+    fn on_recv_timeout(&self) {
+        let error = timedout_io_error("Recv timed out");
+        self.send_reply(Reply::Err(error));
+    }
+    fn on_recv_would_block(&self) {
+        let error = would_block_io_error("Recv would block");
+        self.send_reply(Reply::Err(error));
+    }
+    fn on_recv_ack_malformed(&self, ctx: &mut Context, timeout: Timeout) {
+        let error = invalid_data_io_error("Received request without id");
+        self.send_reply(Reply::Err(error));
+        cancel(ctx, timeout);
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Inner                                                                     */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Inner {
+    fn new() -> Inner {
+        Inner {
+            pipes: HashMap::new(),
+            fq: Priolist::new(),
+            sending: HashSet::new(),
+            ttl: 8,
+            raw: false
+        }
+    }
+    fn add_pipe(&mut self, eid: EndpointId, pipe: Pipe) {
+        self.fq.insert(eid, pipe.get_recv_priority());
+        self.pipes.insert(eid, pipe);
+    }
+    fn remove_pipe(&mut self, eid: EndpointId) -> Option<Pipe> {
+        self.fq.remove(&eid);
+        self.sending.remove(&eid);
+        self.pipes.remove(&eid)
+    }
+
+    /// Replies of several slots to the same pipe are sent one after the other.
+    fn send_to(&mut self, ctx: &mut Context, msg: Rc<Message>, eid: EndpointId) -> bool {
+        if self.sending.contains(&eid) {
+            return false;
+        }
+
+        self.pipes.get_mut(&eid).map(|pipe| pipe.send(ctx, msg)).is_some() && self.sending.insert(eid)
+    }
+    fn on_send_ack(&mut self, eid: EndpointId) {
+        self.sending.remove(&eid);
+    }
+
+    fn recv(&mut self, ctx: &mut Context) -> Option<EndpointId> {
+        self.fq.pop().map_or(None, |eid| self.recv_from(ctx, eid))
+    }
+    fn recv_from(&mut self, ctx: &mut Context, eid: EndpointId) -> Option<EndpointId> {
+        self.pipes.get_mut(&eid).map_or(None, |pipe| {
+            pipe.recv(ctx);
+            Some(eid)
+        })
+    }
+    fn on_recv_ready(&mut self, eid: EndpointId) {
+        self.fq.activate(&eid)
+    }
+    fn can_recv(&self) -> bool {
+        self.fq.has_active()
+    }
+
+    fn raw_msg_to_msg(&self, raw_msg: Message, eid: EndpointId) -> Option<Message> {
+        decode_backtrace(raw_msg, self.ttl).map(|msg| {
+            if self.raw {
+                push_pipe_id(msg, eid)
+            } else {
+                msg
+            }
+        })
+    }
+    fn close(&mut self, ctx: &mut Context) {
+        for (_, pipe) in self.pipes.drain() {
+            pipe.close(ctx);
+        }
+    }
+}
+
+fn encode(msg: Message, backtrace: &[u8]) -> Message {
+    let (mut header, body) = msg.split();
+
+    header.extend_from_slice(backtrace);
+
+    Message::from_header_and_body(header, body)
+}
@@ -4,8 +4,9 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
+use std::cmp;
 use std::sync::mpsc::Sender;
 use std::io;
 use std::time::Duration;
@@ -14,61 +15,126 @@ use time;
 
 use byteorder::*;
 
-use core::{EndpointId, Message};
+use core::{EndpointId, ContextId, Message};
 use core::socket::{Protocol, Reply};
-use core::config::ConfigOption;
+use core::config::{ConfigOption, ConfigOptionKind};
 use core::endpoint::Pipe;
 use core::context::{Context, Schedulable, Event};
 use super::priolist::Priolist;
-use super::{Timeout, REQ, REP};
+use super::{Timeout, REQ, REP, DEFAULT_TTL};
+use super::decode_backtrace;
 use io_error::*;
 
+/// Requests are issued either by the socket itself, identified by `None`, or by one of its contexts.
+/// Each of them has its own state, while the pipes and the load balancer are shared.
+type SlotId = Option<ContextId>;
+
 pub struct Req {
     inner: Inner,
-    state: Option<State>
+    slots: HashMap<SlotId, Slot>
 }
 
 enum State {
     Idle,
-    Sending(EndpointId, Rc<Message>, Timeout, bool),
-    SendOnHold(Rc<Message>, Timeout, bool),
+    Sending(EndpointId, Rc<Message>, Timeout),
+    SendOnHold(Rc<Message>, Timeout),
     Active(PendingRequest),
     Receiving(PendingRequest, Timeout),
-    RecvOnHold(PendingRequest, Timeout)
+    RawReceiving(EndpointId, Timeout),
+    RawRecvOnHold(Timeout)
 }
 
-struct Inner {
+struct Slot {
+    id: SlotId,
     reply_tx: Sender<Reply>,
+    req_id: u32,
+    state: Option<State>
+}
+
+struct Inner {
     pipes: HashMap<EndpointId, Pipe>,
     lb: Priolist,
+    fq: Priolist,
+    reading: HashSet<EndpointId>,
     req_id_seq: u32,
-    is_device_item: bool,
-    resend_ivl: Duration
+    raw: bool,
+    resend_ivl: Duration,
+    resend_ivl_max: Option<Duration>,
+    resend_max: Option<u32>,
+    resend_to_other_peer: bool
 }
 
+/// A request that has been sent, and is resent until a reply is received.
+/// The reply is read as soon as the peer sends it, and kept until the user asks for it.
 struct PendingRequest {
     eid: EndpointId,
     req: Rc<Message>,
-    retry_timeout: Timeout
+    resend_count: u32,
+    resend_ivl: Duration,
+    retry_timeout: Timeout,
+    reply: Option<io::Result<Message>>
 }
 
 /*****************************************************************************/
 /*                                                                           */
-/* Req                                                                      */
+/* Req                                                                       */
 /*                                                                           */
 /*****************************************************************************/
 
 impl Req {
 
-    fn apply<F>(&mut self, ctx: &mut Context, transition: F) where F : FnOnce(State, &mut Context, &mut Inner) -> State {
-        if let Some(old_state) = self.state.take() {
-            #[cfg(debug_assertions)] let old_name = old_state.name();
-            let new_state = transition(old_state, ctx, &mut self.inner);
-            #[cfg(debug_assertions)] let new_name = new_state.name();
+    fn apply<F>(&mut self, ctx: &mut Context, id: SlotId, transition: F) where F : FnOnce(State, &mut Context, &mut Inner, &mut Slot) -> State {
+        if let Some(slot) = self.slots.get_mut(&id) {
+            if let Some(old_state) = slot.state.take() {
+                #[cfg(debug_assertions)] let old_name = old_state.name();
+                let new_state = transition(old_state, ctx, &mut self.inner, slot);
+                #[cfg(debug_assertions)] let new_name = new_state.name();
 
-            self.state = Some(new_state);
+                slot.state = Some(new_state);
 
-            #[cfg(debug_assertions)] debug!("[{:?}] switch from {} to {}", ctx, old_name, new_name);
+                #[cfg(debug_assertions)] debug!("[{:?}] {:?} switch from {} to {}", ctx, id, old_name, new_name);
+            }
+        }
+    }
+
+    fn find_all<P>(&self, predicate: P) -> Vec<SlotId> where P : Fn(&Slot, &State) -> bool {
+        self.slots.values().
+            filter(|slot| slot.state.as_ref().map_or(false, |s| predicate(slot, s))).
+            map(|slot| slot.id).
+            collect()
+    }
+
+    fn find<P>(&self, predicate: P) -> Option<SlotId> where P : Fn(&Slot, &State) -> bool {
+        self.find_all(predicate).into_iter().next()
+    }
+
+    fn send_from(&mut self, ctx: &mut Context, id: SlotId, msg: Message, timeout: Timeout) {
+        self.apply(ctx, id, |s, ctx, inner, slot| {
+            let raw_msg = inner.msg_to_raw_msg(slot, msg);
+
+            s.send(ctx, inner, Rc::new(raw_msg), timeout)
+        })
+    }
+
+    fn recv_from(&mut self, ctx: &mut Context, id: SlotId, timeout: Timeout) {
+        self.apply(ctx, id, |s, ctx, inner, slot| s.recv(ctx, inner, slot, timeout))
+    }
+
+    fn on_raw_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, raw_msg: Message) {
+        if let Some(msg) = decode_backtrace(raw_msg, DEFAULT_TTL) {
+            self.apply(ctx, None, |s, ctx, _, slot| s.on_raw_recv_ack(ctx, slot, eid, msg))
+        } else {
+            self.apply(ctx, None, |s, ctx, _, slot| s.on_raw_recv_ack_malformed(ctx, slot, eid))
+        }
+    }
+
+    /// Replies are read as soon as they are available, on behalf of all the requests
+    /// waiting for a reply from the pipe, instead of when the user asks for them.
+    fn prefetch(&mut self, ctx: &mut Context, eid: EndpointId) -> bool {
+        if self.find(|_, s| s.is_waiting_for(eid)).is_some() {
+            self.inner.recv_from(ctx, eid)
+        } else {
+            false
         }
     }
 
@@ -76,9 +142,13 @@ impl Req {
 
 impl From<Sender<Reply>> for Req {
     fn from(tx: Sender<Reply>) -> Req {
+        let mut slots = HashMap::new();
+
+        slots.insert(None, Slot::new(None, tx));
+
         Req {
-            inner: Inner::new(tx),
-            state: Some(State::Idle)
+            inner: Inner::new(),
+            slots: slots
         }
     }
 }
@@ -100,54 +170,133 @@ impl Protocol for Req {
         let pipe = self.inner.remove_pipe(eid);
 
         if pipe.is_some() {
-            self.apply(ctx, |s, ctx, inner| s.on_pipe_removed(ctx, inner, eid));
+            for id in self.find_all(|_, s| s.is_using(eid)) {
+                self.apply(ctx, id, |s, ctx, inner, _| s.on_pipe_removed(ctx, inner, eid));
+            }
         }
 
         pipe
     }
     fn send(&mut self, ctx: &mut Context, msg: Message, timeout: Timeout) {
-        let raw_msg = self.inner.msg_to_raw_msg(msg);
-
-        self.apply(ctx, |s, ctx, inner| s.send(ctx, inner, Rc::new(raw_msg), timeout, false))
+        self.send_from(ctx, None, msg, timeout)
+    }
+    fn try_send(&mut self, ctx: &mut Context, msg: Message) {
+        self.send(ctx, msg, None);
+        self.apply(ctx, None, |s, _, inner, slot| s.dont_wait(inner, slot))
     }
     fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_send_ack(ctx, inner, eid))
+        if let Some(id) = self.find(|_, s| s.is_sending_to(eid)) {
+            self.apply(ctx, id, |s, ctx, inner, slot| s.on_send_ack(ctx, inner, slot, eid))
+        }
     }
     fn on_send_timeout(&mut self, ctx: &mut Context) {
-        self.apply(ctx, |s, ctx, inner| s.on_send_timeout(ctx, inner))
+        self.apply(ctx, None, |s, ctx, _, slot| s.on_send_timeout(ctx, slot))
     }
     fn on_send_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_send_ready(ctx, inner, eid))
+        self.inner.on_send_ready(eid);
+
+        match self.find(|_, s| s.is_send_on_hold()) {
+            Some(id) => self.apply(ctx, id, |s, ctx, inner, _| s.on_send_ready(ctx, inner)),
+            None => ctx.raise(Event::CanSend)
+        }
+    }
+    fn can_send(&self) -> bool {
+        self.inner.can_send()
     }
     fn recv(&mut self, ctx: &mut Context, timeout: Timeout) {
-        self.apply(ctx, |s, ctx, inner| s.recv(ctx, inner, timeout))
+        self.recv_from(ctx, None, timeout)
+    }
+    fn try_recv(&mut self, ctx: &mut Context) {
+        self.recv(ctx, None);
+        self.apply(ctx, None, |s, _, inner, slot| s.dont_wait(inner, slot))
     }
     fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, raw_msg: Message) {
-        if let Some((msg, req_id)) = self.inner.raw_msg_to_msg(raw_msg) {
-            self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, msg, req_id))
+        self.inner.on_recv_ack(eid);
+
+        if self.inner.raw {
+            return self.on_raw_recv_ack(ctx, eid, raw_msg);
+        }
+
+        // A resent request can be answered by any of the peers it was sent to
+        if let Some((msg, req_id)) = decode(raw_msg) {
+            if let Some(id) = self.find(|slot, s| slot.req_id == req_id && s.is_waiting()) {
+                self.apply(ctx, id, |s, ctx, _, slot| s.on_recv_ack(ctx, slot, msg));
+            }
         } else {
-            self.inner.on_recv_ack_malformed(ctx)
+            for id in self.find_all(|_, s| s.is_receiving_from(eid)) {
+                self.apply(ctx, id, |s, ctx, _, slot| s.on_recv_ack_malformed(ctx, slot));
+            }
         }
+
+        self.prefetch(ctx, eid);
     }
     fn on_recv_timeout(&mut self, ctx: &mut Context) {
-        self.apply(ctx, |s, ctx, inner| s.on_recv_timeout(ctx, inner))
+        self.apply(ctx, None, |s, ctx, _, slot| s.on_recv_timeout(ctx, slot))
     }
     fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_recv_ready(ctx, inner, eid))
+        self.inner.on_recv_ready(eid);
+
+        if let Some(id) = self.find(|_, s| s.is_raw_recv_on_hold()) {
+            self.apply(ctx, id, |s, ctx, inner, _| s.on_recv_ready(ctx, inner));
+        } else if !self.prefetch(ctx, eid) {
+            ctx.raise(Event::CanRecv);
+        }
+    }
+    fn can_recv(&self) -> bool {
+        self.slots.get(&None).and_then(|slot| slot.state.as_ref()).map_or(false, |s| s.can_recv(&self.inner))
     }
     fn set_option(&mut self, opt: ConfigOption) -> io::Result<()> {
         match opt {
             ConfigOption::ReqResendIvl(ivl) => Ok(self.inner.set_resend_ivl(ivl)),
+            ConfigOption::ReqResendIvlMax(ivl) => Ok(self.inner.resend_ivl_max = ivl),
+            ConfigOption::ReqResendMax(max) => Ok(self.inner.resend_max = max),
+            ConfigOption::ReqResendToOtherPeer(other) => Ok(self.inner.resend_to_other_peer = other),
+            _ => Err(invalid_input_io_error("option not supported"))
+        }
+    }
+    fn get_option(&self, kind: ConfigOptionKind) -> io::Result<ConfigOption> {
+        match kind {
+            ConfigOptionKind::ReqResendIvl => Ok(ConfigOption::ReqResendIvl(self.inner.resend_ivl)),
+            ConfigOptionKind::ReqResendIvlMax => Ok(ConfigOption::ReqResendIvlMax(self.inner.resend_ivl_max)),
+            ConfigOptionKind::ReqResendMax => Ok(ConfigOption::ReqResendMax(self.inner.resend_max)),
+            ConfigOptionKind::ReqResendToOtherPeer => Ok(ConfigOption::ReqResendToOtherPeer(self.inner.resend_to_other_peer)),
             _ => Err(invalid_input_io_error("option not supported"))
         }
     }
     fn on_timer_tick(&mut self, ctx: &mut Context, task: Schedulable) {
-        if let Schedulable::ReqResend = task {
-            self.apply(ctx, |s, ctx, inner| s.on_retry_timeout(ctx, inner))
+        match task {
+            Schedulable::ReqResend => self.apply(ctx, None, |s, ctx, inner, slot| s.on_retry_timeout(ctx, inner, slot)),
+            Schedulable::ContextReqResend(cid) => self.apply(ctx, Some(cid), |s, ctx, inner, slot| s.on_retry_timeout(ctx, inner, slot)),
+            _ => {}
         }
     }
-    fn on_device_plugged(&mut self, _: &mut Context) {
-        self.inner.is_device_item = true;
+    fn set_raw(&mut self) {
+        self.inner.raw = true;
+    }
+    fn create_context(&mut self, cid: ContextId, reply_tx: Sender<Reply>) -> io::Result<()> {
+        if self.inner.raw {
+            return Err(other_io_error("contexts not supported by raw sockets"));
+        }
+
+        self.slots.insert(Some(cid), Slot::new(Some(cid), reply_tx));
+        Ok(())
+    }
+    fn context_send(&mut self, ctx: &mut Context, cid: ContextId, msg: Message, timeout: Timeout) {
+        self.send_from(ctx, Some(cid), msg, timeout)
+    }
+    fn on_context_send_timeout(&mut self, ctx: &mut Context, cid: ContextId) {
+        self.apply(ctx, Some(cid), |s, ctx, _, slot| s.on_send_timeout(ctx, slot))
+    }
+    fn context_recv(&mut self, ctx: &mut Context, cid: ContextId, timeout: Timeout) {
+        self.recv_from(ctx, Some(cid), timeout)
+    }
+    fn on_context_recv_timeout(&mut self, ctx: &mut Context, cid: ContextId) {
+        self.apply(ctx, Some(cid), |s, ctx, _, slot| s.on_recv_timeout(ctx, slot))
+    }
+    fn close_context(&mut self, ctx: &mut Context, cid: ContextId) {
+        if let Some(state) = self.slots.remove(&Some(cid)).and_then(|mut slot| slot.state.take()) {
+            state.cancel(ctx);
+        }
     }
     fn close(&mut self, ctx: &mut Context) {
         self.inner.close(ctx)
@@ -165,80 +314,180 @@ impl State {
     #[cfg(debug_assertions)]
     fn name(&self) -> &'static str {
         match *self {
-            State::Idle                    => "Idle",
-            State::Sending(_, _, _, false) => "Sending",
-            State::Sending(_, _, _, true)  => "Resending",
-            State::SendOnHold(_, _, _)     => "SendOnHold",
-            State::Active(_)               => "Active",
-            State::Receiving(_, _)         => "Receiving",
-            State::RecvOnHold(_, _)        => "RecvOnHold"
+            State::Idle               => "Idle",
+            State::Sending(_, _, _)   => "Sending",
+            State::SendOnHold(_, _)   => "SendOnHold",
+            State::Active(_)          => "Active",
+            State::Receiving(_, _)    => "Receiving",
+            State::RawReceiving(_, _) => "RawReceiving",
+            State::RawRecvOnHold(_)   => "RawRecvOnHold"
+        }
+    }
+
+    fn is_using(&self, eid: EndpointId) -> bool {
+        match *self {
+            State::Sending(id, _, _) |
+            State::RawReceiving(id, _) => id == eid,
+            State::Active(ref p) |
+            State::Receiving(ref p, _) => p.eid == eid,
+            _ => false
+        }
+    }
+
+    fn is_sending_to(&self, eid: EndpointId) -> bool {
+        match *self {
+            State::Sending(id, _, _) => id == eid,
+            _ => false
+        }
+    }
+
+    fn is_send_on_hold(&self) -> bool {
+        match *self {
+            State::SendOnHold(_, _) => true,
+            _ => false
+        }
+    }
+
+    fn is_waiting(&self) -> bool {
+        match *self {
+            State::Active(ref p) |
+            State::Receiving(ref p, _) => p.is_waiting(),
+            _ => false
+        }
+    }
+
+    fn is_waiting_for(&self, eid: EndpointId) -> bool {
+        match *self {
+            State::Active(ref p) |
+            State::Receiving(ref p, _) => p.eid == eid && p.is_waiting(),
+            _ => false
+        }
+    }
+
+    fn is_receiving_from(&self, eid: EndpointId) -> bool {
+        match *self {
+            State::Receiving(ref p, _) => p.eid == eid,
+            _ => false
+        }
+    }
+
+    fn is_raw_recv_on_hold(&self) -> bool {
+        match *self {
+            State::RawRecvOnHold(_) => true,
+            _ => false
         }
     }
 
     fn on_pipe_removed(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
         match self {
-            State::Sending(id, msg, timeout, retry) => {
+            State::Sending(id, msg, timeout) => {
                 if id == eid {
-                    State::Idle.send(ctx, inner, msg, timeout, retry)
+                    State::Idle.send(ctx, inner, msg, timeout)
                 } else {
-                    State::Sending(id, msg, timeout, retry)
+                    State::Sending(id, msg, timeout)
                 }
             },
-            State::Receiving(p, timeout) => {
-                if p.eid == eid {
-                    State::Idle.recv(ctx, inner, timeout)
+            State::Active(p) => State::Active(p.on_pipe_removed(ctx, inner, eid)),
+            State::Receiving(p, timeout) => State::Receiving(p.on_pipe_removed(ctx, inner, eid), timeout),
+            State::RawReceiving(id, timeout) => {
+                if id == eid {
+                    State::Idle.recv_any(ctx, inner, timeout)
                 } else {
+                    State::RawReceiving(id, timeout)
+                }
+            },
+            any => any
+        }
+    }
+
+    fn dont_wait(self, inner: &Inner, slot: &mut Slot) -> State {
+        match self {
+            State::SendOnHold(_, _) => {
+                slot.on_send_would_block();
+                State::Idle
+            },
+            State::Receiving(p, timeout) => {
+                if inner.is_reading(p.eid) {
                     State::Receiving(p, timeout)
+                } else {
+                    slot.on_recv_would_block();
+                    State::Active(p)
                 }
             },
+            State::RawRecvOnHold(_) => {
+                slot.on_recv_would_block();
+                State::Idle
+            },
             any => any
         }
     }
 
+    fn can_recv(&self, inner: &Inner) -> bool {
+        match *self {
+            State::Active(ref p) => p.reply.is_some() || inner.can_recv_from(p.eid),
+            State::Idle => inner.raw && inner.can_recv_any(),
+            _ => false
+        }
+    }
+
+    fn cancel(self, ctx: &mut Context) {
+        match self {
+            State::Sending(_, _, timeout) |
+            State::SendOnHold(_, timeout) |
+            State::RawReceiving(_, timeout) |
+            State::RawRecvOnHold(timeout) => cancel(ctx, timeout),
+            State::Active(p) => cancel(ctx, p.retry_timeout),
+            State::Receiving(p, timeout) => {
+                cancel(ctx, timeout);
+                cancel(ctx, p.retry_timeout);
+            },
+            State::Idle => {}
+        }
+    }
+
 /*****************************************************************************/
 /*                                                                           */
 /* send                                                                      */
 /*                                                                           */
 /*****************************************************************************/
 
-    fn send(self, ctx: &mut Context, inner: &mut Inner, msg: Rc<Message>, timeout: Timeout, retry: bool) -> State {
+    fn send(self, ctx: &mut Context, inner: &mut Inner, msg: Rc<Message>, timeout: Timeout) -> State {
         if let State::Active(p) = self {
-            inner.cancel(ctx, p);
+            cancel(ctx, p.retry_timeout);
         }
         if let Some(eid) = inner.send(ctx, msg.clone()) {
-            State::Sending(eid, msg, timeout, retry)
+            State::Sending(eid, msg, timeout)
         } else {
-            State::SendOnHold(msg, timeout, retry)
+            State::SendOnHold(msg, timeout)
         }
     }
-    fn on_send_ack(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
+    fn on_send_ack(self, ctx: &mut Context, inner: &mut Inner, slot: &mut Slot, eid: EndpointId) -> State {
         match self {
-            State::Sending(id, msg, timeout, retry) => {
+            State::Sending(id, msg, timeout) => {
                 if id == eid {
-                    let retry_timeout = inner.on_send_ack(ctx, timeout, retry);
+                    slot.on_send_ack(ctx, timeout);
 
-                    State::Active(PendingRequest {
-                        eid: eid,
-                        req: msg,
-                        retry_timeout: retry_timeout
-                    })
+                    if inner.raw {
+                        State::Idle
+                    } else {
+                        inner.recv_from(ctx, eid);
+                        State::Active(PendingRequest::new(ctx, inner, slot, eid, msg))
+                    }
                 } else {
-                    State::Sending(id, msg, timeout, retry)
+                    State::Sending(id, msg, timeout)
                 }
             },
             any => any
         }
     }
-    fn on_send_timeout(self, _: &mut Context, inner: &mut Inner) -> State {
-        inner.on_send_timeout();
+    fn on_send_timeout(self, _: &mut Context, slot: &mut Slot) -> State {
+        slot.on_send_timeout();
 
         State::Idle
     }
-    fn on_send_ready(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
-        inner.on_send_ready(eid);
-
+    fn on_send_ready(self, ctx: &mut Context, inner: &mut Inner) -> State {
         match self {
-            State::SendOnHold(msg, timeout, retry) => State::Idle.send(ctx, inner, msg, timeout, retry),
+            State::SendOnHold(msg, timeout) => State::Idle.send(ctx, inner, msg, timeout),
             any => any
         }
     }
@@ -249,68 +498,258 @@ impl State {
 /*                                                                           */
 /*****************************************************************************/
 
-    fn recv(self, ctx: &mut Context, inner: &mut Inner, timeout: Timeout) -> State {
+    fn recv(self, ctx: &mut Context, inner: &mut Inner, slot: &mut Slot, timeout: Timeout) -> State {
         if let State::Active(p) = self {
-            State::Idle.recv_reply_for(ctx, inner, timeout, p)
+            State::Idle.recv_reply_for(ctx, inner, slot, timeout, p)
+        } else if inner.raw {
+            State::Idle.recv_any(ctx, inner, timeout)
         } else {
-            inner.recv_when_inactive(ctx, timeout);
+            slot.recv_when_inactive(ctx, timeout);
 
             State::Idle
         }
     }
-    fn recv_reply_for(self, ctx: &mut Context, inner: &mut Inner, timeout: Timeout, p: PendingRequest) -> State {
-        if inner.recv_from(ctx, p.eid) {
-            State::Receiving(p, timeout)
-        } else {
-            State::RecvOnHold(p, timeout)
+    fn recv_any(self, ctx: &mut Context, inner: &mut Inner, timeout: Timeout) -> State {
+        match inner.recv_any(ctx) {
+            Some(eid) => State::RawReceiving(eid, timeout),
+            None => State::RawRecvOnHold(timeout)
         }
     }
-    fn on_recv_ack(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId, msg: Message, req_id: u32) -> State {
+    fn recv_reply_for(self, ctx: &mut Context, inner: &mut Inner, slot: &mut Slot, timeout: Timeout, mut p: PendingRequest) -> State {
+        match p.reply.take() {
+            Some(Ok(msg)) => {
+                slot.on_recv_ack(ctx, timeout, msg, p.retry_timeout);
+                State::Idle
+            },
+            Some(Err(error)) => {
+                slot.on_recv_error(ctx, timeout, error);
+                State::Idle
+            },
+            None => {
+                inner.recv_from(ctx, p.eid);
+                State::Receiving(p, timeout)
+            }
+        }
+    }
+    fn on_recv_ack(self, ctx: &mut Context, slot: &mut Slot, msg: Message) -> State {
         match self {
             State::Receiving(p, timeout) => {
-                if p.eid == eid {
-                    if inner.cur_req_id() == req_id {
-                        inner.on_recv_ack(ctx, timeout, msg, p.retry_timeout);
-                        State::Idle
-                    } else {
-                        State::Idle.recv_reply_for(ctx, inner, timeout, p)
-                    }
+                slot.on_recv_ack(ctx, timeout, msg, p.retry_timeout);
+                State::Idle
+            },
+            State::Active(mut p) => {
+                cancel(ctx, p.retry_timeout.take());
+                p.reply = Some(Ok(msg));
+                slot.on_reply_stored(ctx);
+                State::Active(p)
+            },
+            any => any
+        }
+    }
+    fn on_recv_ack_malformed(self, ctx: &mut Context, slot: &mut Slot) -> State {
+        match self {
+            State::Receiving(p, timeout) => {
+                slot.on_recv_ack_malformed(ctx, timeout);
+                State::Active(p)
+            },
+            any => any
+        }
+    }
+    fn on_raw_recv_ack(self, ctx: &mut Context, slot: &mut Slot, eid: EndpointId, msg: Message) -> State {
+        match self {
+            State::RawReceiving(id, timeout) => {
+                if id == eid {
+                    slot.on_recv_ack(ctx, timeout, msg, None);
+                    State::Idle
                 } else {
-                    State::Receiving(p, timeout)
+                    State::RawReceiving(id, timeout)
                 }
             },
             any => any
         }
     }
-    fn on_recv_timeout(self, ctx: &mut Context, inner: &mut Inner) -> State {
+    fn on_raw_recv_ack_malformed(self, ctx: &mut Context, slot: &mut Slot, eid: EndpointId) -> State {
         match self {
-            State::Receiving(p, _) |
-            State::RecvOnHold(p, _) => inner.on_recv_timeout(ctx, p.retry_timeout),
+            State::RawReceiving(id, timeout) => {
+                if id == eid {
+                    slot.on_recv_ack_malformed(ctx, timeout);
+                    State::Idle
+                } else {
+                    State::RawReceiving(id, timeout)
+                }
+            },
+            any => any
+        }
+    }
+    fn on_recv_timeout(self, ctx: &mut Context, slot: &mut Slot) -> State {
+        match self {
+            State::Receiving(p, _) => slot.on_recv_timeout(ctx, p.retry_timeout),
+            State::RawReceiving(_, _) |
+            State::RawRecvOnHold(_) => slot.on_recv_timeout(ctx, None),
             _ => {}
         }
 
         State::Idle
     }
-    fn on_recv_ready(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
+    fn on_recv_ready(self, ctx: &mut Context, inner: &mut Inner) -> State {
+        match self {
+            State::RawRecvOnHold(timeout) => State::Idle.recv_any(ctx, inner, timeout),
+            any => any
+        }
+    }
+    fn on_retry_timeout(self, ctx: &mut Context, inner: &mut Inner, slot: &mut Slot) -> State {
         match self {
-            State::RecvOnHold(p, timeout) => {
-                if eid == p.eid {
-                    State::Active(p).recv(ctx, inner, timeout)
+            State::Active(mut p) => {
+                p.retry_timeout = None;
+
+                if !p.is_waiting() {
+                    State::Active(p)
+                } else if p.is_resend_exhausted(inner) {
+                    p.reply = Some(Err(resend_exhausted_io_error()));
+                    slot.on_reply_stored(ctx);
+                    State::Active(p)
                 } else {
-                    State::RecvOnHold(p, timeout)
+                    State::Active(p.resend(ctx, inner, slot))
                 }
             },
-            any => {
-                ctx.raise(Event::CanRecv);
-                any
+            State::Receiving(mut p, timeout) => {
+                p.retry_timeout = None;
+
+                if p.is_resend_exhausted(inner) {
+                    slot.on_recv_error(ctx, timeout, resend_exhausted_io_error());
+                    State::Idle
+                } else {
+                    State::Receiving(p.resend(ctx, inner, slot), timeout)
+                }
+            },
+            any => any
+        }
+    }
+}
+
+fn cancel(ctx: &mut Context, timeout: Timeout) {
+    if let Some(sched) = timeout {
+        ctx.cancel(sched);
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* PendingRequest                                                            */
+/*                                                                           */
+/*****************************************************************************/
+
+impl PendingRequest {
+    fn new(ctx: &mut Context, inner: &Inner, slot: &Slot, eid: EndpointId, req: Rc<Message>) -> PendingRequest {
+        PendingRequest {
+            eid: eid,
+            req: req,
+            resend_count: 0,
+            resend_ivl: inner.resend_ivl,
+            retry_timeout: inner.schedule_resend(ctx, slot, inner.resend_ivl),
+            reply: None
+        }
+    }
+    fn is_waiting(&self) -> bool {
+        self.reply.is_none()
+    }
+    fn is_resend_exhausted(&self, inner: &Inner) -> bool {
+        inner.resend_max.map_or(false, |max| self.resend_count >= max)
+    }
+    fn resend(mut self, ctx: &mut Context, inner: &mut Inner, slot: &Slot) -> PendingRequest {
+        let avoided = if inner.resend_to_other_peer { Some(self.eid) } else { None };
+
+        // When no pipe is available, the request is resent at the next timeout
+        if let Some(eid) = inner.send_avoiding(ctx, self.req.clone(), avoided) {
+            self.eid = eid;
+            self.resend_count += 1;
+            self.resend_ivl = inner.next_resend_ivl(self.resend_ivl);
+            inner.recv_from(ctx, eid);
+        }
+
+        self.retry_timeout = inner.schedule_resend(ctx, slot, self.resend_ivl);
+        self
+    }
+    /// The request is resent right away to another pipe, without waiting for the timeout.
+    fn on_pipe_removed(mut self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> PendingRequest {
+        if self.eid == eid && self.is_waiting() {
+            if let Some(id) = inner.send(ctx, self.req.clone()) {
+                self.eid = id;
+                inner.recv_from(ctx, id);
             }
         }
+
+        self
     }
-    fn on_retry_timeout(self, ctx: &mut Context, inner: &mut Inner) -> State {
-        if let State::Active(p) = self {
-            State::Idle.send(ctx, inner, p.req, None, true)
-        } else {
-            self
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Slot                                                                      */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Slot {
+    fn new(id: SlotId, tx: Sender<Reply>) -> Slot {
+        Slot {
+            id: id,
+            reply_tx: tx,
+            req_id: 0,
+            state: Some(State::Idle)
+        }
+    }
+    fn send_reply(&self, reply: Reply) {
+        let _ = self.reply_tx.send(reply);
+    }
+    fn on_send_ack(&self, ctx: &mut Context, timeout: Timeout) {
+        self.send_reply(Reply::Send);
+        cancel(ctx, timeout);
+    }
+    fn on_send_timeout(&self) {
+        let error = timedout_io_error("Send timed out");
+        self.send_reply(Reply::Err(error));
+    }
+    fn on_send_would_block(&self) {
+        let error = would_block_io_error("Send would block");
+        self.send_reply(Reply::Err(error));
+    }
+    fn on_recv_would_block(&self) {
+        let error = would_block_io_error("Recv would block");
+        self.send_reply(Reply::Err(error));
+    }
+    fn recv_when_inactive(&self, ctx: &mut Context, timeout: Timeout) {
+        let error = other_io_error("Can't recv: no active request");
+        self.send_reply(Reply::Err(error));
+        cancel(ctx, timeout);
+    }
+    fn on_recv_ack(&self, ctx: &mut Context, timeout: Timeout, msg: Message, retry_timeout: Timeout) {
+        self.send_reply(Reply::Recv(msg));
+        cancel(ctx, timeout);
+        cancel(ctx, retry_timeout);
+    }
+    fn on_recv_error(&self, ctx: &mut Context, timeout: Timeout, error: io::Error) {
+        self.send_reply(Reply::Err(error));
+        cancel(ctx, timeout);
+    }
+    fn on_reply_stored(&self, ctx: &mut Context) {
+        if self.id.is_none() {
+            ctx.raise(Event::CanRecv);
+        }
+    }
+    fn on_recv_timeout(&self, ctx: &mut Context, retry_timeout: Timeout) {
+        let error = timedout_io_error("Recv timed out");
+        self.send_reply(Reply::Err(error));
+        cancel(ctx, retry_timeout);
+    }
+    fn on_recv_ack_malformed(&self, ctx: &mut Context, timeout: Timeout) {
+        let error = invalid_data_io_error("Received reply without req id");
+        self.send_reply(Reply::Err(error));
+        cancel(ctx, timeout);
+    }
+    fn resend_task(&self) -> Schedulable {
+        match self.id {
+            None => Schedulable::ReqResend,
+            Some(cid) => Schedulable::ContextReqResend(cid)
         }
     }
 }
@@ -322,106 +761,115 @@ impl State {
 /*****************************************************************************/
 
 impl Inner {
-    fn new(tx: Sender<Reply>) -> Inner {
+    fn new() -> Inner {
         Inner {
-            reply_tx: tx,
             pipes: HashMap::new(),
             lb: Priolist::new(),
+            fq: Priolist::new(),
+            reading: HashSet::new(),
             req_id_seq: time::get_time().nsec as u32,
-            is_device_item: false,
-            resend_ivl: Duration::from_secs(60)
+            raw: false,
+            resend_ivl: Duration::from_secs(60),
+            resend_ivl_max: None,
+            resend_max: None,
+            resend_to_other_peer: false
         }
     }
     fn add_pipe(&mut self, eid: EndpointId, pipe: Pipe) {
         self.lb.insert(eid, pipe.get_send_priority());
+        self.fq.insert(eid, pipe.get_recv_priority());
         self.pipes.insert(eid, pipe);
     }
     fn remove_pipe(&mut self, eid: EndpointId) -> Option<Pipe> {
         self.lb.remove(&eid);
+        self.fq.remove(&eid);
+        self.reading.remove(&eid);
         self.pipes.remove(&eid)
     }
     fn send(&mut self, ctx: &mut Context, msg: Rc<Message>) -> Option<EndpointId> {
         self.lb.pop().map_or(None, |eid| self.send_to(ctx, msg, eid))
     }
+    /// Picks another pipe than the avoided one, unless it is the only one available.
+    fn send_avoiding(&mut self, ctx: &mut Context, msg: Rc<Message>, avoided: Option<EndpointId>) -> Option<EndpointId> {
+        match self.lb.pop() {
+            Some(eid) if Some(eid) == avoided && self.lb.has_active() => {
+                let other = self.lb.pop();
+
+                self.lb.activate(&eid);
+                other.and_then(|other| self.send_to(ctx, msg, other))
+            },
+            popped => popped.and_then(|eid| self.send_to(ctx, msg, eid))
+        }
+    }
     fn send_to(&mut self, ctx: &mut Context, msg: Rc<Message>, eid: EndpointId) -> Option<EndpointId> {
         self.pipes.get_mut(&eid).map_or(None, |pipe| {
-            pipe.send(ctx, msg); 
+            pipe.send(ctx, msg);
             Some(eid)
         })
     }
     fn on_send_ready(&mut self, eid: EndpointId) {
         self.lb.activate(&eid)
     }
-    fn on_send_ack(&self, ctx: &mut Context, timeout: Timeout, retry: bool) -> Timeout {
-        if !retry {
-        let _ = self.reply_tx.send(Reply::Send);
-        }
-        if let Some(sched) = timeout {
-            ctx.cancel(sched);
-        }
-        ctx.schedule(Schedulable::ReqResend, self.resend_ivl).ok()
+    fn can_send(&self) -> bool {
+        self.lb.has_active()
     }
-    fn on_send_timeout(&self) {
-        let error = timedout_io_error("Send timed out");
-        let _ = self.reply_tx.send(Reply::Err(error));
+    fn schedule_resend(&self, ctx: &mut Context, slot: &Slot, ivl: Duration) -> Timeout {
+        ctx.schedule(slot.resend_task(), ivl).ok()
     }
-    fn cancel(&self, ctx: &mut Context, p: PendingRequest) {
-        if let Some(sched) = p.retry_timeout {
-            ctx.cancel(sched);
+
+    /// Doubles the previous interval up to `resend_ivl_max`, if it is greater than `resend_ivl`.
+    fn next_resend_ivl(&self, previous: Duration) -> Duration {
+        match self.resend_ivl_max {
+            Some(ivl_max) if ivl_max > self.resend_ivl => cmp::min(previous.checked_mul(2).unwrap_or(ivl_max), ivl_max),
+            _ => self.resend_ivl
         }
     }
 
+    /// Several requests can wait for a reply from the same pipe,
+    /// in which case a single read is made on their behalf.
     fn recv_from(&mut self, ctx: &mut Context, eid: EndpointId) -> bool {
-        self.pipes.get_mut(&eid).map(|pipe| pipe.recv(ctx)).is_some()
-    }
-    fn recv_when_inactive(&mut self, ctx: &mut Context, timeout: Timeout) {
-        let error = other_io_error("Can't recv: no active request");
-        let _ = self.reply_tx.send(Reply::Err(error));
-        if let Some(sched) = timeout {
-            ctx.cancel(sched);
+        if self.reading.contains(&eid) {
+            true
+        } else if self.fq.deactivate(&eid) {
+            self.read(ctx, eid)
+        } else {
+            false
         }
     }
-    fn on_recv_ack(&self, ctx: &mut Context, timeout: Timeout, msg: Message, retry_timeout: Timeout) {
-        let _ = self.reply_tx.send(Reply::Recv(msg));
-        if let Some(sched) = timeout {
-            ctx.cancel(sched);
-        }
-        if let Some(sched) = retry_timeout {
-            ctx.cancel(sched);
-        }
+    fn read(&mut self, ctx: &mut Context, eid: EndpointId) -> bool {
+        self.pipes.get_mut(&eid).map(|pipe| pipe.recv(ctx)).is_some() && self.reading.insert(eid)
     }
-    fn on_recv_timeout(&self, ctx: &mut Context, retry_timeout: Timeout) {
-        let error = timedout_io_error("Recv timed out");
-        let _ = self.reply_tx.send(Reply::Err(error));
-        if let Some(sched) = retry_timeout {
-            ctx.cancel(sched);
-        }
+    fn on_recv_ready(&mut self, eid: EndpointId) {
+        self.fq.activate(&eid);
     }
-    fn on_recv_ack_malformed(&self, _: &mut Context) {
-        let error = invalid_data_io_error("Received reply without req id");
-        let _ = self.reply_tx.send(Reply::Err(error));
+    fn on_recv_ack(&mut self, eid: EndpointId) {
+        self.reading.remove(&eid);
     }
+    fn is_reading(&self, eid: EndpointId) -> bool {
+        self.reading.contains(&eid)
+    }
+    fn can_recv_from(&self, eid: EndpointId) -> bool {
+        self.fq.is_active(&eid)
+    }
+    /// Raw sockets take the replies in turn from the pipes that can be read, like the other fair queues.
+    fn recv_any(&mut self, ctx: &mut Context) -> Option<EndpointId> {
+        let ready = self.fq.pop();
 
-    fn msg_to_raw_msg(&mut self, msg: Message) -> Message {
-        if self.is_device_item {
-            msg
-        } else {
-            encode(msg, self.next_req_id())
-        }
+        ready.and_then(|eid| if self.reading.contains(&eid) || self.read(ctx, eid) { Some(eid) } else { None })
+    }
+    fn can_recv_any(&self) -> bool {
+        self.fq.has_active()
     }
 
-    fn raw_msg_to_msg(&self, raw_msg: Message) -> Option<(Message, u32)> {
-        if self.is_device_item {
-            Some((raw_msg, self.cur_req_id()))
+    fn msg_to_raw_msg(&mut self, slot: &mut Slot, msg: Message) -> Message {
+        if self.raw {
+            msg
         } else {
-            decode(raw_msg)
+            slot.req_id = self.next_req_id();
+            encode(msg, slot.req_id)
         }
     }
 
-    fn cur_req_id(&self) -> u32 {
-        self.req_id_seq | 0x80000000
-    }
-
     fn next_req_id(&mut self) -> u32 {
         self.req_id_seq += 1;
         self.req_id_seq | 0x80000000
@@ -436,6 +884,10 @@ impl Inner {
     }
 }
 
+fn resend_exhausted_io_error() -> io::Error {
+    conn_aborted_io_error("Recv aborted: no reply after the last resend")
+}
+
 fn encode(msg: Message, req_id: u32) -> Message {
     let mut raw_msg = msg;
     let mut req_id_bytes: [u8; 4] = [0; 4];
@@ -448,6 +900,13 @@ fn encode(msg: Message, req_id: u32) -> Message {
 }
 
 fn decode(raw_msg: Message) -> Option<(Message, u32)> {
+    // the header kept apart by the inproc transport already starts with the id
+    if raw_msg.get_header().len() >= 4 {
+        let req_id = BigEndian::read_u32(raw_msg.get_header());
+
+        return Some((raw_msg, req_id));
+    }
+
     if raw_msg.get_body().len() < 4 {
         return None;
     }
@@ -464,3 +923,60 @@ fn decode(raw_msg: Message) -> Option<(Message, u32)> {
 
     Some((Message::from_header_and_body(header, body), req_id))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use core::Message;
+    use super::{Inner, decode};
+
+    fn millis(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn resend_ivl_is_constant_without_max() {
+        let mut inner = Inner::new();
+
+        inner.set_resend_ivl(millis(100));
+
+        assert_eq!(millis(100), inner.next_resend_ivl(millis(100)));
+    }
+
+    #[test]
+    fn resend_ivl_is_doubled_up_to_max() {
+        let mut inner = Inner::new();
+
+        inner.set_resend_ivl(millis(100));
+        inner.resend_ivl_max = Some(millis(300));
+
+        assert_eq!(millis(200), inner.next_resend_ivl(millis(100)));
+        assert_eq!(millis(300), inner.next_resend_ivl(millis(200)));
+        assert_eq!(millis(300), inner.next_resend_ivl(millis(300)));
+    }
+
+    #[test]
+    fn resend_ivl_max_is_ignored_when_less_than_resend_ivl() {
+        let mut inner = Inner::new();
+
+        inner.set_resend_ivl(millis(100));
+        inner.resend_ivl_max = Some(millis(50));
+
+        assert_eq!(millis(100), inner.next_resend_ivl(millis(100)));
+    }
+
+    #[test]
+    fn reply_id_is_decoded_from_body_or_from_header_kept_apart() {
+        let read = Message::from_body(vec![128, 0, 0, 7, 65]);
+        let handed_over = Message::from_header_and_body(vec![128, 0, 0, 7], vec![65]);
+
+        for raw_msg in vec![read, handed_over] {
+            let (msg, req_id) = decode(raw_msg).unwrap();
+
+            assert_eq!(0x80000007, req_id);
+            assert_eq!(&[128, 0, 0, 7], msg.get_header());
+            assert_eq!(&[65], msg.get_body());
+        }
+    }
+}
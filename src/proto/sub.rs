@@ -11,7 +11,7 @@ use std::io;
 
 use core::{EndpointId, Message};
 use core::socket::{Protocol, Reply};
-use core::config::ConfigOption;
+use core::config::{ConfigOption, ConfigOptionKind};
 use core::endpoint::Pipe;
 use core::context::{Context, Event};
 use super::priolist::Priolist;
@@ -97,6 +97,10 @@ impl Protocol for Sub {
     fn send(&mut self, ctx: &mut Context, msg: Message, timeout: Timeout) {
         self.apply(ctx, |s, ctx, inner| s.send(ctx, inner, Rc::new(msg), timeout))
     }
+    fn try_send(&mut self, ctx: &mut Context, msg: Message) {
+        self.send(ctx, msg, None);
+        self.apply(ctx, |s, _, inner| s.dont_wait(inner))
+    }
     fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId) {
         self.apply(ctx, |s, ctx, inner| s.on_send_ack(ctx, inner, eid))
     }
@@ -106,9 +110,16 @@ impl Protocol for Sub {
     fn on_send_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
         self.apply(ctx, |s, ctx, inner| s.on_send_ready(ctx, inner, eid))
     }
+    fn can_send(&self) -> bool {
+        false
+    }
     fn recv(&mut self, ctx: &mut Context, timeout: Timeout) {
         self.apply(ctx, |s, ctx, inner| s.recv(ctx, inner, timeout))
     }
+    fn try_recv(&mut self, ctx: &mut Context) {
+        self.recv(ctx, None);
+        self.apply(ctx, |s, _, inner| s.dont_wait(inner))
+    }
     fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, msg: Message) {
         self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, msg))
     }
@@ -118,6 +129,9 @@ impl Protocol for Sub {
     fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
         self.apply(ctx, |s, ctx, inner| s.on_recv_ready(ctx, inner, eid))
     }
+    fn can_recv(&self) -> bool {
+        self.inner.can_recv()
+    }
     fn set_option(&mut self, opt: ConfigOption) -> io::Result<()> {
         match opt {
             ConfigOption::Subscribe(x)   => Ok(self.inner.subscribe(x)),
@@ -125,6 +139,12 @@ impl Protocol for Sub {
             _ => Err(invalid_input_io_error("option not supported"))
         }
     }
+    fn get_option(&self, kind: ConfigOptionKind) -> io::Result<ConfigOption> {
+        match kind {
+            ConfigOptionKind::Subscriptions => Ok(ConfigOption::Subscriptions(self.inner.get_subscriptions())),
+            _ => Err(invalid_input_io_error("option not supported"))
+        }
+    }
     fn close(&mut self, ctx: &mut Context) {
         self.inner.close(ctx)
     }
@@ -160,6 +180,16 @@ impl State {
         }
     }
 
+    fn dont_wait(self, inner: &mut Inner) -> State {
+        match self {
+            State::RecvOnHold(_) => {
+                inner.on_recv_would_block();
+                State::Idle
+            },
+            any => any
+        }
+    }
+
 /*****************************************************************************/
 /*                                                                           */
 /* send                                                                      */
@@ -261,6 +291,9 @@ impl Inner {
     fn on_recv_ready(&mut self, eid: EndpointId) {
         self.fq.activate(&eid)
     }
+    fn can_recv(&self) -> bool {
+        self.fq.has_active()
+    }
     fn on_recv_ack(&self, ctx: &mut Context, timeout: Timeout, msg: Message) {
         let _ = self.reply_tx.send(Reply::Recv(msg));
         if let Some(sched) = timeout {
@@ -271,6 +304,10 @@ impl Inner {
         let error = timedout_io_error("Recv timed out");
         let _ = self.reply_tx.send(Reply::Err(error));
     }
+    fn on_recv_would_block(&self) {
+        let error = would_block_io_error("Recv would block");
+        let _ = self.reply_tx.send(Reply::Err(error));
+    }
 
     fn subscribe(&mut self, subscription :String) {
         self.subscriptions.insert(subscription.into_bytes());
@@ -280,6 +317,15 @@ impl Inner {
         self.subscriptions.remove(&subscription.into_bytes());
     }
 
+    fn get_subscriptions(&self) -> Vec<String> {
+        let mut subscriptions: Vec<String> = self.subscriptions.iter().
+            map(|s| String::from_utf8_lossy(s).into_owned()).
+            collect();
+
+        subscriptions.sort();
+        subscriptions
+    }
+
     fn accept(&self, msg: &Message) -> bool {
         let payload = msg.get_body();
         
@@ -4,7 +4,7 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::rc::Rc;
 use std::sync::mpsc::Sender;
 use std::time::Duration;
@@ -14,59 +14,113 @@ use time;
 
 use byteorder::*;
 
-use core::{EndpointId, Message};
+use core::{EndpointId, ContextId, Message};
 use core::socket::{Protocol, Reply};
-use core::config::ConfigOption;
+use core::config::{ConfigOption, ConfigOptionKind};
 use core::endpoint::Pipe;
 use core::context::{Context, Schedulable, Event};
 use super::priolist::Priolist;
-use super::{Timeout, SURVEYOR, RESPONDENT};
+use super::{Timeout, SURVEYOR, RESPONDENT, DEFAULT_TTL};
+use super::decode_backtrace;
 use io_error::*;
 
+/// Surveys are issued either by the socket itself, identified by `None`, or by one of its contexts.
+/// Each of them has its own survey id and deadline, while the pipes are shared.
+type SlotId = Option<ContextId>;
+
 pub struct Surveyor {
     inner: Inner,
-    state: Option<State>
+    slots: HashMap<SlotId, Slot>
 }
 
 enum State {
     Idle,
     Active(PendingSurvey),
-    Receiving(EndpointId, PendingSurvey, Timeout),
-    RecvOnHold(PendingSurvey, Timeout)
+    Receiving(PendingSurvey, Timeout),
+    Collecting(PendingSurvey)
 }
 
-struct Inner {
+struct Slot {
+    id: SlotId,
     reply_tx: Sender<Reply>,
+    state: Option<State>
+}
+
+struct Inner {
     pipes: HashMap<EndpointId, Pipe>,
     bc: HashSet<EndpointId>,
+    backlog: HashMap<EndpointId, VecDeque<(SlotId, Rc<Message>)>>,
     fq: Priolist,
+    reading: HashSet<EndpointId>,
     survey_id_seq: u32,
-    is_device_item: bool,
+    raw: bool,
     deadline: Duration
 }
 
+/// A survey whose deadline is not reached yet. The responses read on behalf
+/// of another survey, or while the user was not asking for them, are kept until requested.
 struct PendingSurvey {
     id: u32,
-    timeout: Timeout
+    timeout: Timeout,
+    responses: VecDeque<Message>
 }
 
 /*****************************************************************************/
 /*                                                                           */
-/* Surveyor                                                                      */
+/* Surveyor                                                                  */
 /*                                                                           */
 /*****************************************************************************/
 
 impl Surveyor {
 
-    fn apply<F>(&mut self, ctx: &mut Context, transition: F) where F : FnOnce(State, &mut Context, &mut Inner) -> State {
-        if let Some(old_state) = self.state.take() {
-            #[cfg(debug_assertions)] let old_name = old_state.name();
-            let new_state = transition(old_state, ctx, &mut self.inner);
-            #[cfg(debug_assertions)] let new_name = new_state.name();
+    fn apply<F>(&mut self, ctx: &mut Context, id: SlotId, transition: F) where F : FnOnce(State, &mut Context, &mut Inner, &mut Slot) -> State {
+        if let Some(slot) = self.slots.get_mut(&id) {
+            if let Some(old_state) = slot.state.take() {
+                #[cfg(debug_assertions)] let old_name = old_state.name();
+                let new_state = transition(old_state, ctx, &mut self.inner, slot);
+                #[cfg(debug_assertions)] let new_name = new_state.name();
 
-            self.state = Some(new_state);
+                slot.state = Some(new_state);
 
-            #[cfg(debug_assertions)] debug!("[{:?}] switch from {} to {}", ctx, old_name, new_name);
+                #[cfg(debug_assertions)] debug!("[{:?}] {:?} switch from {} to {}", ctx, id, old_name, new_name);
+            }
+        }
+    }
+
+    fn find_all<P>(&self, predicate: P) -> Vec<SlotId> where P : Fn(&State) -> bool {
+        self.slots.values().
+            filter(|slot| slot.state.as_ref().map_or(false, |s| predicate(s))).
+            map(|slot| slot.id).
+            collect()
+    }
+
+    fn find<P>(&self, predicate: P) -> Option<SlotId> where P : Fn(&State) -> bool {
+        self.find_all(predicate).into_iter().next()
+    }
+
+    fn send_from(&mut self, ctx: &mut Context, id: SlotId, msg: Message, timeout: Timeout) {
+        let raw_msg = self.inner.msg_to_raw_msg(msg);
+
+        self.apply(ctx, id, |s, ctx, inner, slot| s.send(ctx, inner, slot, Rc::new(raw_msg), timeout))
+    }
+
+    fn recv_from(&mut self, ctx: &mut Context, id: SlotId, timeout: Timeout) {
+        self.apply(ctx, id, |s, ctx, inner, slot| s.recv(ctx, inner, slot, timeout));
+        self.recv_for_surveys(ctx);
+    }
+
+    fn collect_from(&mut self, ctx: &mut Context, id: SlotId, deadline: Duration) {
+        self.apply(ctx, id, |s, ctx, inner, slot| s.collect(ctx, inner, slot, deadline));
+        self.recv_for_surveys(ctx);
+    }
+
+    /// Responses are read one at a time as long as a survey is waiting for them,
+    /// and dispatched to the survey they answer.
+    fn recv_for_surveys(&mut self, ctx: &mut Context) -> bool {
+        if self.find(|s| s.is_waiting()).is_some() {
+            self.inner.recv(ctx)
+        } else {
+            false
         }
     }
 
@@ -74,9 +128,13 @@ impl Surveyor {
 
 impl From<Sender<Reply>> for Surveyor {
     fn from(tx: Sender<Reply>) -> Surveyor {
+        let mut slots = HashMap::new();
+
+        slots.insert(None, Slot::new(None, tx));
+
         Surveyor {
-            inner: Inner::new(tx),
-            state: Some(State::Idle)
+            inner: Inner::new(),
+            slots: slots
         }
     }
 }
@@ -98,40 +156,65 @@ impl Protocol for Surveyor {
         let pipe = self.inner.remove_pipe(eid);
 
         if pipe.is_some() {
-            self.apply(ctx, |s, ctx, inner| s.on_pipe_removed(ctx, inner, eid));
+            self.recv_for_surveys(ctx);
         }
 
         pipe
     }
     fn send(&mut self, ctx: &mut Context, msg: Message, timeout: Timeout) {
-        let raw_msg = self.inner.msg_to_raw_msg(msg);
-
-        self.apply(ctx, |s, ctx, inner| s.send(ctx, inner, Rc::new(raw_msg), timeout))
+        self.send_from(ctx, None, msg, timeout)
+    }
+    fn try_send(&mut self, ctx: &mut Context, msg: Message) {
+        self.send(ctx, msg, None)
     }
-    fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_send_ack(ctx, inner, eid))
+    fn on_send_ack(&mut self, _: &mut Context, _: EndpointId) {
     }
-    fn on_send_timeout(&mut self, ctx: &mut Context) {
-        self.apply(ctx, |s, ctx, inner| s.on_send_timeout(ctx, inner))
+    fn on_send_timeout(&mut self, _: &mut Context) {
     }
     fn on_send_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_send_ready(ctx, inner, eid))
+        self.inner.on_send_ready(ctx, eid)
+    }
+    fn can_send(&self) -> bool {
+        true
     }
     fn recv(&mut self, ctx: &mut Context, timeout: Timeout) {
-        self.apply(ctx, |s, ctx, inner| s.recv(ctx, inner, timeout))
+        self.recv_from(ctx, None, timeout)
+    }
+    fn try_recv(&mut self, ctx: &mut Context) {
+        self.recv(ctx, None);
+        self.apply(ctx, None, |s, _, inner, slot| s.dont_wait(inner, slot))
     }
     fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, raw_msg: Message) {
+        self.inner.on_recv_ack(eid);
+
         if let Some((msg, survey_id)) = self.inner.raw_msg_to_msg(raw_msg) {
-            self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, msg, survey_id))
+            if let Some(id) = self.find(|s| s.is_pending(survey_id)) {
+                self.apply(ctx, id, |s, ctx, _, slot| s.on_recv_ack(ctx, slot, msg));
+            }
         } else {
-            self.inner.on_recv_ack_malformed(ctx)
+            for id in self.find_all(|s| s.is_receiving()) {
+                self.apply(ctx, id, |s, ctx, _, slot| s.on_recv_ack_malformed(ctx, slot));
+            }
         }
+
+        self.recv_for_surveys(ctx);
     }
     fn on_recv_timeout(&mut self, ctx: &mut Context) {
-        self.apply(ctx, |s, ctx, inner| s.on_recv_timeout(ctx, inner))
+        self.apply(ctx, None, |s, ctx, _, slot| s.on_recv_timeout(ctx, slot))
     }
     fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_recv_ready(ctx, inner, eid))
+        self.inner.on_recv_ready(eid);
+
+        if !self.recv_for_surveys(ctx) {
+            ctx.raise(Event::CanRecv);
+        }
+    }
+    fn can_recv(&self) -> bool {
+        self.slots.get(&None).and_then(|slot| slot.state.as_ref()).map_or(false, |s| s.can_recv(&self.inner))
+    }
+    fn collect(&mut self, ctx: &mut Context, deadline: Duration) -> io::Result<()> {
+        self.collect_from(ctx, None, deadline);
+        Ok(())
     }
     fn set_option(&mut self, opt: ConfigOption) -> io::Result<()> {
         match opt {
@@ -139,13 +222,47 @@ impl Protocol for Surveyor {
             _ => Err(invalid_input_io_error("option not supported"))
         }
     }
+    fn get_option(&self, kind: ConfigOptionKind) -> io::Result<ConfigOption> {
+        match kind {
+            ConfigOptionKind::SurveyDeadline => Ok(ConfigOption::SurveyDeadline(self.inner.deadline)),
+            _ => Err(invalid_input_io_error("option not supported"))
+        }
+    }
     fn on_timer_tick(&mut self, ctx: &mut Context, task: Schedulable) {
-        if let Schedulable::SurveyCancel = task {
-            self.apply(ctx, |s, ctx, inner| s.on_survey_timeout(ctx, inner))
+        match task {
+            Schedulable::SurveyCancel => self.apply(ctx, None, |s, ctx, _, slot| s.on_survey_timeout(ctx, slot)),
+            Schedulable::ContextSurveyCancel(cid) => self.apply(ctx, Some(cid), |s, ctx, _, slot| s.on_survey_timeout(ctx, slot)),
+            _ => {}
         }
     }
-    fn on_device_plugged(&mut self, _: &mut Context) {
-        self.inner.is_device_item = true;
+    fn set_raw(&mut self) {
+        self.inner.raw = true;
+    }
+    fn create_context(&mut self, cid: ContextId, reply_tx: Sender<Reply>) -> io::Result<()> {
+        if self.inner.raw {
+            return Err(other_io_error("contexts not supported by raw sockets"));
+        }
+
+        self.slots.insert(Some(cid), Slot::new(Some(cid), reply_tx));
+        Ok(())
+    }
+    fn context_send(&mut self, ctx: &mut Context, cid: ContextId, msg: Message, timeout: Timeout) {
+        self.send_from(ctx, Some(cid), msg, timeout)
+    }
+    fn context_recv(&mut self, ctx: &mut Context, cid: ContextId, timeout: Timeout) {
+        self.recv_from(ctx, Some(cid), timeout)
+    }
+    fn on_context_recv_timeout(&mut self, ctx: &mut Context, cid: ContextId) {
+        self.apply(ctx, Some(cid), |s, ctx, _, slot| s.on_recv_timeout(ctx, slot))
+    }
+    fn context_collect(&mut self, ctx: &mut Context, cid: ContextId, deadline: Duration) -> io::Result<()> {
+        self.collect_from(ctx, Some(cid), deadline);
+        Ok(())
+    }
+    fn close_context(&mut self, ctx: &mut Context, cid: ContextId) {
+        if let Some(state) = self.slots.remove(&Some(cid)).and_then(|mut slot| slot.state.take()) {
+            state.cancel(ctx);
+        }
     }
     fn close(&mut self, ctx: &mut Context) {
         self.inner.close(ctx)
@@ -163,50 +280,85 @@ impl State {
     #[cfg(debug_assertions)]
     fn name(&self) -> &'static str {
         match *self {
-            State::Idle               => "Idle",
-            State::Active(_)          => "Active",
-            State::Receiving(_, _, _) => "Receiving",
-            State::RecvOnHold(_, _)   => "RecvOnHold"
+            State::Idle            => "Idle",
+            State::Active(_)       => "Active",
+            State::Receiving(_, _) => "Receiving",
+            State::Collecting(_)   => "Collecting"
+        }
+    }
+
+    fn is_pending(&self, survey_id: u32) -> bool {
+        match *self {
+            State::Active(ref p)       |
+            State::Receiving(ref p, _) |
+            State::Collecting(ref p)   => p.id == survey_id,
+            State::Idle => false
+        }
+    }
+
+    fn is_waiting(&self) -> bool {
+        match *self {
+            State::Receiving(_, _) |
+            State::Collecting(_)   => true,
+            _ => false
+        }
+    }
+
+    fn is_receiving(&self) -> bool {
+        match *self {
+            State::Receiving(_, _) => true,
+            _ => false
         }
     }
 
-    fn on_pipe_removed(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
+    fn dont_wait(self, inner: &Inner, slot: &mut Slot) -> State {
         match self {
-            State::Receiving(id, p, timeout) => {
-                if id == eid {
-                    State::Idle.recv(ctx, inner, timeout)
+            State::Receiving(p, timeout) => {
+                if inner.is_reading() {
+                    State::Receiving(p, timeout)
                 } else {
-                    State::Receiving(id, p, timeout)
+                    slot.on_recv_would_block();
+                    State::Active(p)
                 }
             },
             any => any
         }
     }
 
+    fn can_recv(&self, inner: &Inner) -> bool {
+        match *self {
+            State::Active(ref p) => !p.responses.is_empty() || inner.can_recv(),
+            _ => false
+        }
+    }
+
+    fn cancel(self, ctx: &mut Context) {
+        match self {
+            State::Active(p) |
+            State::Collecting(p) => cancel(ctx, p.timeout),
+            State::Receiving(p, timeout) => {
+                cancel(ctx, timeout);
+                cancel(ctx, p.timeout);
+            },
+            State::Idle => {}
+        }
+    }
+
 /*****************************************************************************/
 /*                                                                           */
 /* send                                                                      */
 /*                                                                           */
 /*****************************************************************************/
 
-    fn send(self, ctx: &mut Context, inner: &mut Inner, msg: Rc<Message>, timeout: Timeout) -> State {
+    fn send(self, ctx: &mut Context, inner: &mut Inner, slot: &mut Slot, msg: Rc<Message>, timeout: Timeout) -> State {
         if let State::Active(p) = self {
-            inner.cancel(ctx, p);
+            cancel(ctx, p.timeout);
         }
 
-        let pending_survey = inner.send(ctx, msg, timeout);
+        inner.send(ctx, slot.id, msg);
+        slot.on_send_ack(ctx, timeout);
 
-        State::Active(pending_survey)
-    }
-    fn on_send_ack(self, _: &mut Context, _: &mut Inner, _: EndpointId) -> State {
-        self
-    }
-    fn on_send_timeout(self, _: &mut Context, _: &mut Inner) -> State {
-        self
-    }
-    fn on_send_ready(self, _: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
-        inner.on_send_ready(eid);
-        self
+        State::Active(inner.new_survey(ctx, slot))
     }
 
 /*****************************************************************************/
@@ -215,67 +367,180 @@ impl State {
 /*                                                                           */
 /*****************************************************************************/
 
-    fn recv(self, ctx: &mut Context, inner: &mut Inner, timeout: Timeout) -> State {
+    fn recv(self, ctx: &mut Context, inner: &mut Inner, slot: &mut Slot, timeout: Timeout) -> State {
         if let State::Active(p) = self {
-            State::Idle.recv_reply_for(ctx, inner, timeout, p)
+            State::Idle.recv_response_for(ctx, slot, timeout, p)
+        } else if inner.raw {
+            let p = inner.raw_survey();
+
+            State::Idle.recv_response_for(ctx, slot, timeout, p)
         } else {
-            inner.recv_when_inactive(ctx, timeout);
+            slot.recv_when_inactive(ctx, timeout);
 
             State::Idle
         }
     }
-    fn recv_reply_for(self, ctx: &mut Context, inner: &mut Inner, timeout: Timeout, p: PendingSurvey) -> State {
-        if let Some(eid) = inner.recv(ctx) {
-            State::Receiving(eid, p, timeout)
+    fn recv_response_for(self, ctx: &mut Context, slot: &mut Slot, timeout: Timeout, mut p: PendingSurvey) -> State {
+        if let Some(msg) = p.responses.pop_front() {
+            slot.on_recv_ack(ctx, timeout, msg);
+            State::Active(p)
         } else {
-            State::RecvOnHold(p, timeout)
+            State::Receiving(p, timeout)
         }
     }
-    fn on_recv_ack(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId, msg: Message, survey_id: u32) -> State {
+    fn on_recv_ack(self, ctx: &mut Context, slot: &mut Slot, msg: Message) -> State {
         match self {
-            State::Receiving(id, p, timeout) => {
-                if id == eid {
-                    if p.id == survey_id {
-                        inner.on_recv_ack(ctx, timeout, msg);
-                        State::Active(p)
-                    } else {
-                        State::Idle.recv_reply_for(ctx, inner, timeout, p)
-                    }
-                } else {
-                    State::Receiving(id, p, timeout)
-                }
+            State::Receiving(p, timeout) => {
+                slot.on_recv_ack(ctx, timeout, msg);
+                State::Active(p)
+            },
+            State::Active(mut p) => {
+                p.responses.push_back(msg);
+                slot.on_response_stored(ctx);
+                State::Active(p)
+            },
+            State::Collecting(mut p) => {
+                p.responses.push_back(msg);
+                State::Collecting(p)
             },
             any => any
         }
     }
-    fn on_recv_timeout(self, _: &mut Context, inner: &mut Inner) -> State {
-        inner.on_recv_timeout();
-
+    fn on_recv_ack_malformed(self, ctx: &mut Context, slot: &mut Slot) -> State {
         match self {
-            State::Receiving(_, p, _) |
-            State::RecvOnHold(p, _)   => State::Active(p),
-            _ => State::Idle
+            State::Receiving(p, timeout) => {
+                slot.on_recv_ack_malformed(ctx, timeout);
+                State::Active(p)
+            },
+            any => any
         }
     }
-    fn on_recv_ready(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
-        inner.on_recv_ready(eid);
-
+    fn on_recv_timeout(self, _: &mut Context, slot: &mut Slot) -> State {
         match self {
-            State::RecvOnHold(p, timeout) => State::Active(p).recv(ctx, inner, timeout),
-            any => {
-                ctx.raise(Event::CanRecv);
-                any
-            }
+            State::Receiving(p, _) => {
+                slot.on_recv_timeout();
+                State::Active(p)
+            },
+            any => any
         }
     }
-    fn on_survey_timeout(self, _: &mut Context, _: &mut Inner) -> State {
-        if let State::Active(_) = self {
-            State::Idle
-        } else {
-            self
+
+/*****************************************************************************/
+/*                                                                           */
+/* deadline                                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+    /// The deadline of the survey is replaced by the one of the collect.
+    fn collect(self, ctx: &mut Context, inner: &mut Inner, slot: &mut Slot, deadline: Duration) -> State {
+        let p = match self {
+            State::Active(p) | State::Collecting(p) => {
+                cancel(ctx, p.timeout);
+                p
+            },
+            State::Receiving(p, timeout) => {
+                cancel(ctx, timeout);
+                cancel(ctx, p.timeout);
+                p
+            },
+            State::Idle => {
+                if inner.raw {
+                    inner.raw_survey()
+                } else {
+                    slot.collect_when_inactive();
+                    return State::Idle;
+                }
+            }
+        };
+
+        State::Collecting(PendingSurvey {
+            timeout: ctx.schedule(slot.survey_cancel_task(), deadline).ok(),
+            ..p
+        })
+    }
+    fn on_survey_timeout(self, ctx: &mut Context, slot: &mut Slot) -> State {
+        match self {
+            State::Receiving(_, timeout) => slot.on_survey_timeout(ctx, timeout),
+            State::Collecting(p) => slot.on_collect(p.responses.into_iter().collect()),
+            _ => {}
         }
+
+        State::Idle
     }
+}
+
+fn cancel(ctx: &mut Context, timeout: Timeout) {
+    if let Some(sched) = timeout {
+        ctx.cancel(sched);
+    }
+}
 
+/*****************************************************************************/
+/*                                                                           */
+/* Slot                                                                      */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Slot {
+    fn new(id: SlotId, tx: Sender<Reply>) -> Slot {
+        Slot {
+            id: id,
+            reply_tx: tx,
+            state: Some(State::Idle)
+        }
+    }
+    fn send_reply(&self, reply: Reply) {
+        let _ = self.reply_tx.send(reply);
+    }
+    fn on_send_ack(&self, ctx: &mut Context, timeout: Timeout) {
+        self.send_reply(Reply::Send);
+        cancel(ctx, timeout);
+    }
+    fn recv_when_inactive(&self, ctx: &mut Context, timeout: Timeout) {
+        let error = other_io_error("Can't recv: no active survey");
+        self.send_reply(Reply::Err(error));
+        cancel(ctx, timeout);
+    }
+    fn on_recv_ack(&self, ctx: &mut Context, timeout: Timeout, msg: Message) {
+        self.send_reply(Reply::Recv(msg));
+        cancel(ctx, timeout);
+    }
+    fn on_response_stored(&self, ctx: &mut Context) {
+        if self.id.is_none() {
+            ctx.raise(Event::CanRecv);
+        }
+    }
+    fn on_recv_timeout(&self) {
+        let error = timedout_io_error("Recv timed out");
+        self.send_reply(Reply::Err(error));
+    }
+    fn on_recv_would_block(&self) {
+        let error = would_block_io_error("Recv would block");
+        self.send_reply(Reply::Err(error));
+    }
+    fn on_recv_ack_malformed(&self, ctx: &mut Context, timeout: Timeout) {
+        let error = invalid_data_io_error("Received response without survey id");
+        self.send_reply(Reply::Err(error));
+        cancel(ctx, timeout);
+    }
+    fn collect_when_inactive(&self) {
+        let error = other_io_error("Can't collect: no active survey");
+        self.send_reply(Reply::Err(error));
+    }
+    fn on_collect(&self, responses: Vec<Message>) {
+        self.send_reply(Reply::Collect(responses));
+    }
+    fn on_survey_timeout(&self, ctx: &mut Context, timeout: Timeout) {
+        let error = timedout_io_error("Survey deadline reached");
+        self.send_reply(Reply::Err(error));
+        cancel(ctx, timeout);
+    }
+    fn survey_cancel_task(&self) -> Schedulable {
+        match self.id {
+            None => Schedulable::SurveyCancel,
+            Some(cid) => Schedulable::ContextSurveyCancel(cid)
+        }
+    }
 }
 
 /*****************************************************************************/
@@ -285,14 +550,15 @@ impl State {
 /*****************************************************************************/
 
 impl Inner {
-    fn new(tx: Sender<Reply>) -> Inner {
+    fn new() -> Inner {
         Inner {
-            reply_tx: tx,
             pipes: HashMap::new(),
             bc: HashSet::new(),
+            backlog: HashMap::new(),
             fq: Priolist::new(),
+            reading: HashSet::new(),
             survey_id_seq: time::get_time().nsec as u32,
-            is_device_item: false,
+            raw: false,
             deadline: Duration::from_secs(1)
         }
     }
@@ -302,70 +568,82 @@ impl Inner {
     }
     fn remove_pipe(&mut self, eid: EndpointId) -> Option<Pipe> {
         self.bc.remove(&eid);
+        self.backlog.remove(&eid);
         self.fq.remove(&eid);
+        self.reading.remove(&eid);
         self.pipes.remove(&eid)
     }
-    fn send(&mut self, ctx: &mut Context, msg: Rc<Message>, timeout: Timeout) -> PendingSurvey {
-        for id in self.bc.drain() {
-            self.pipes.get_mut(&id).map(|pipe| pipe.send(ctx, msg.clone()));
+    /// A pipe still busy sending a previous survey queues the new one, unless it comes from the same slot,
+    /// in which case it replaces it. Pipes that never were ready to send are skipped.
+    fn send(&mut self, ctx: &mut Context, id: SlotId, msg: Rc<Message>) {
+        for (eid, pipe) in self.pipes.iter_mut() {
+            if self.bc.remove(eid) {
+                pipe.send(ctx, msg.clone());
+                self.backlog.insert(*eid, VecDeque::new());
+            } else if let Some(queue) = self.backlog.get_mut(eid) {
+                queue.retain(|&(slot_id, _)| slot_id != id);
+                queue.push_back((id, msg.clone()));
+            }
         }
-
-        let _ = self.reply_tx.send(Reply::Send);
-        if let Some(sched) = timeout {
-            ctx.cancel(sched);
+    }
+    fn new_survey(&self, ctx: &mut Context, slot: &Slot) -> PendingSurvey {
+        if self.raw {
+            self.raw_survey()
+        } else {
+            PendingSurvey {
+                id: self.cur_survey_id(),
+                timeout: ctx.schedule(slot.survey_cancel_task(), self.deadline).ok(),
+                responses: VecDeque::new()
+            }
         }
-
+    }
+    /// In raw mode, responses are accepted at any time, whatever the survey they answer.
+    fn raw_survey(&self) -> PendingSurvey {
         PendingSurvey {
             id: self.cur_survey_id(),
-            timeout: ctx.schedule(Schedulable::SurveyCancel, self.deadline).ok()
+            timeout: None,
+            responses: VecDeque::new()
         }
     }
-    fn on_send_ready(&mut self, eid: EndpointId) {
-        self.bc.insert(eid);
-    }
+    fn on_send_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
+        let queued = self.backlog.get_mut(&eid).and_then(|queue| queue.pop_front());
 
-    fn recv(&mut self, ctx: &mut Context) -> Option<EndpointId> {
-        self.fq.pop().map_or(None, |eid| self.recv_from(ctx, eid))
-    }
-    fn recv_from(&mut self, ctx: &mut Context, eid: EndpointId) -> Option<EndpointId> {
-        self.pipes.get_mut(&eid).map_or(None, |pipe| {
-            pipe.recv(ctx); 
-            Some(eid)
-        })
+        if let Some((_, msg)) = queued {
+            self.pipes.get_mut(&eid).map(|pipe| pipe.send(ctx, msg));
+        } else {
+            self.backlog.remove(&eid);
+            self.bc.insert(eid);
+        }
     }
-    fn recv_when_inactive(&mut self, ctx: &mut Context, timeout: Timeout) {
-        let error = other_io_error("Can't recv: no active survey");
-        let _ = self.reply_tx.send(Reply::Err(error));
-        if let Some(sched) = timeout {
-            ctx.cancel(sched);
+
+    fn recv(&mut self, ctx: &mut Context) -> bool {
+        if self.is_reading() {
+            true
+        } else {
+            self.fq.pop().map_or(false, |eid| self.recv_from(ctx, eid))
         }
     }
-    fn on_recv_ready(&mut self, eid: EndpointId) {
-        self.fq.activate(&eid)
+    fn recv_from(&mut self, ctx: &mut Context, eid: EndpointId) -> bool {
+        self.pipes.get_mut(&eid).map_or(false, |pipe| {
+            pipe.recv(ctx);
+            true
+        }) && self.reading.insert(eid)
     }
-    fn on_recv_ack(&self, ctx: &mut Context, timeout: Timeout, msg: Message) {
-        let _ = self.reply_tx.send(Reply::Recv(msg));
-        if let Some(sched) = timeout {
-            ctx.cancel(sched);
-        }
+    fn is_reading(&self) -> bool {
+        !self.reading.is_empty()
     }
-    fn on_recv_timeout(&self) {
-        let error = timedout_io_error("Recv timed out");
-        let _ = self.reply_tx.send(Reply::Err(error));
+    fn on_recv_ack(&mut self, eid: EndpointId) {
+        self.reading.remove(&eid);
     }
-    fn on_recv_ack_malformed(&self, _: &mut Context) {
-        let error = invalid_data_io_error("Received response without survey id");
-        let _ = self.reply_tx.send(Reply::Err(error));
+    fn on_recv_ready(&mut self, eid: EndpointId) {
+        self.fq.activate(&eid)
     }
-
-    fn cancel(&self, ctx: &mut Context, mut pending_survey: PendingSurvey) {
-        if let Some(timeout) = pending_survey.timeout.take() {
-            ctx.cancel(timeout);
-        }
+    fn can_recv(&self) -> bool {
+        self.fq.has_active()
     }
 
     fn msg_to_raw_msg(&mut self, msg: Message) -> Message {
-        if self.is_device_item {
+        if self.raw {
             msg
         } else {
             encode(msg, self.next_survey_id())
@@ -373,8 +651,8 @@ impl Inner {
     }
 
     fn raw_msg_to_msg(&self, raw_msg: Message) -> Option<(Message, u32)> {
-        if self.is_device_item {
-            Some((raw_msg, self.cur_survey_id()))
+        if self.raw {
+            decode_backtrace(raw_msg, DEFAULT_TTL).map(|msg| (msg, self.cur_survey_id()))
         } else {
             decode(raw_msg)
         }
@@ -411,6 +689,13 @@ fn encode(msg: Message, survey_id: u32) -> Message {
 }
 
 fn decode(raw_msg: Message) -> Option<(Message, u32)> {
+    // the header kept apart by the inproc transport already starts with the id
+    if raw_msg.get_header().len() >= 4 {
+        let survey_id = BigEndian::read_u32(raw_msg.get_header());
+
+        return Some((raw_msg, survey_id));
+    }
+
     if raw_msg.get_body().len() < 4 {
         return None;
     }
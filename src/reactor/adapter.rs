@@ -6,7 +6,7 @@
 
 use std::fmt;
 use std::rc::Rc;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::io;
 use std::time::Duration;
 
@@ -16,12 +16,15 @@ use mio::timer::{Timer, Timeout};
 use core::context;
 use core::network::Network;
 use core::{SocketId, EndpointId, Message};
+use core::config::Config;
+use core::stats::Traffic;
+use core::endpoint::PipeStatus;
 use transport::Transport;
 use transport::endpoint::*;
 use transport::pipe;
 use transport::acceptor;
-use transport::tcp::Tcp;
 use super::bus::EventLoopBus;
+use super::buffer::{PipeBuffer, BufferSizes, Action, buffer_sizes};
 use super::{Signal, Task};
 use sequence::Sequence;
 use io_error::*;
@@ -38,7 +41,8 @@ pub struct SocketEventLoopContext<'a> {
     signal_tx: &'a mut EventLoopBus<Signal>,
     endpoints: &'a mut EndpointCollection,
     schedule: &'a mut Schedule,
-    timer: &'a mut Timer<Task>
+    timer: &'a mut Timer<Task>,
+    transports: &'a HashMap<String, Box<Transport>>
 }
 
 pub struct EndpointEventLoopContext<'a, 'b> {
@@ -51,13 +55,25 @@ pub struct EndpointEventLoopContext<'a, 'b> {
 pub struct PipeController {
     socket_id: SocketId,
     endpoint_id: EndpointId,
-    pipe: Box<pipe::Pipe>
+    pipe: Box<pipe::Pipe>,
+    traffic: Traffic,
+    /// The message handed to the transport pipe, counted once the pipe acknowledges it.
+    sending: Option<Rc<Message>>,
+    buffer: Option<PipeBuffer>
 }
 
 pub struct AcceptorController {
     socket_id: SocketId,
     endpoint_id: EndpointId,
-    acceptor: Box<acceptor::Acceptor>
+    acceptor: Box<acceptor::Acceptor>,
+    buffer_sizes: BufferSizes
+}
+
+/// Collects the events raised by a transport pipe,
+/// so that they go through the pipe buffer before reaching the socket.
+struct CapturingContext<'x> {
+    inner: &'x mut pipe::Context,
+    events: Vec<pipe::Event>
 }
 
 pub struct EndpointCollection {
@@ -92,19 +108,46 @@ impl Registrar for EventLoop {
 impl PipeController {
     pub fn ready(&mut self, registrar: &mut Registrar, signal_bus: &mut EventLoopBus<Signal>, events: Ready) {
         let mut ctx = self.create_context(registrar, signal_bus);
+        let pipe = &mut self.pipe;
+        let sent = &mut Sent { traffic: &mut self.traffic, sending: &mut self.sending };
+        let raised = capture(&mut ctx, |ctx| pipe.ready(ctx, events));
+
+        match self.buffer {
+            Some(ref mut buffer) => {
+                for evt in raised {
+                    sent.on_event(&evt);
 
-        self.pipe.ready(&mut ctx, events);
+                    let actions = buffer.on_event(evt);
+
+                    run_buffered(pipe, buffer, sent, &mut ctx, actions);
+                }
+            },
+            None => forward(sent, &mut ctx, raised)
+        }
     }
 
     pub fn process(&mut self, registrar: &mut Registrar, signal_bus: &mut EventLoopBus<Signal>, cmd: pipe::Command) {
         let mut ctx = self.create_context(registrar, signal_bus);
+        let pipe = &mut self.pipe;
+        let sent = &mut Sent { traffic: &mut self.traffic, sending: &mut self.sending };
 
-        match cmd {
-            pipe::Command::Open      => self.pipe.open(&mut ctx),
-            pipe::Command::Close     => self.pipe.close(&mut ctx),
-            pipe::Command::Send(msg) => self.pipe.send(&mut ctx, msg),
-            pipe::Command::Recv      => self.pipe.recv(&mut ctx)
+        if let Some(ref mut buffer) = self.buffer {
+            let actions = buffer.on_command(cmd);
+
+            return run_buffered(pipe, buffer, sent, &mut ctx, actions);
         }
+
+        let raised = match cmd {
+            pipe::Command::Open      => capture(&mut ctx, |ctx| pipe.open(ctx)),
+            pipe::Command::Close     => capture(&mut ctx, |ctx| pipe.close(ctx)),
+            pipe::Command::Send(msg) => {
+                sent.on_send(&msg);
+                capture(&mut ctx, |ctx| pipe.send(ctx, msg))
+            },
+            pipe::Command::Recv      => capture(&mut ctx, |ctx| pipe.recv(ctx))
+        };
+
+        forward(sent, &mut ctx, raised);
     }
 
     fn create_context<'a, 'b>(&self, registrar: &'b mut Registrar, signal_bus: &'a mut EventLoopBus<Signal>) -> EndpointEventLoopContext<'a, 'b> {
@@ -115,6 +158,83 @@ impl PipeController {
             registrar: registrar
         }
     }
+
+    pub fn on_received(&mut self, msg: &Message) {
+        self.traffic.on_received(msg);
+    }
+
+    fn status(&self) -> PipeStatus {
+        PipeStatus {
+            state: self.pipe.state(),
+            peer_addr: self.pipe.peer_addr()
+        }
+    }
+}
+
+/// Counts the messages written by the transport pipe,
+/// a queued message is not sent yet and may still be dropped when the pipe is closed.
+struct Sent<'x> {
+    traffic: &'x mut Traffic,
+    sending: &'x mut Option<Rc<Message>>
+}
+
+impl<'x> Sent<'x> {
+    fn on_send(&mut self, msg: &Rc<Message>) {
+        *self.sending = Some(msg.clone());
+    }
+
+    fn on_event(&mut self, evt: &pipe::Event) {
+        if let pipe::Event::Sent = *evt {
+            if let Some(msg) = self.sending.take() {
+                self.traffic.on_sent(&msg);
+            }
+        }
+    }
+}
+
+/// Passes the events raised by an unbuffered transport pipe to the socket.
+fn forward(sent: &mut Sent, ctx: &mut pipe::Context, events: Vec<pipe::Event>) {
+    for evt in events {
+        sent.on_event(&evt);
+        ctx.raise(evt);
+    }
+}
+
+/// Performs the actions requested by the buffer,
+/// feeding it back with the events raised by the transport pipe in the process.
+fn run_buffered(pipe: &mut Box<pipe::Pipe>, buffer: &mut PipeBuffer, sent: &mut Sent, ctx: &mut pipe::Context, actions: Vec<Action>) {
+    let mut pending: VecDeque<Action> = actions.into_iter().collect();
+
+    while let Some(action) = pending.pop_front() {
+        let events = match action {
+            Action::Raise(evt) => {
+                ctx.raise(evt);
+                continue;
+            },
+            Action::Open      => capture(ctx, |ctx| pipe.open(ctx)),
+            Action::Close     => capture(ctx, |ctx| pipe.close(ctx)),
+            Action::Send(msg) => {
+                sent.on_send(&msg);
+                capture(ctx, |ctx| pipe.send(ctx, msg))
+            },
+            Action::Recv      => capture(ctx, |ctx| pipe.recv(ctx))
+        };
+
+        for evt in events {
+            sent.on_event(&evt);
+            pending.extend(buffer.on_event(evt));
+        }
+    }
+}
+
+fn capture<F : FnOnce(&mut pipe::Context)>(ctx: &mut pipe::Context, f: F) -> Vec<pipe::Event> {
+    let mut capturing = CapturingContext {
+        inner: ctx,
+        events: Vec::new()
+    };
+
+    f(&mut capturing);
+    capturing.events
 }
 
 impl AcceptorController {
@@ -156,45 +276,75 @@ impl EndpointCollection {
         self.pipes.get_mut(&eid)
     }
 
-    pub fn insert_pipe(&mut self, sid: SocketId, pipe: Box<pipe::Pipe>) -> EndpointId {
+    pub fn insert_pipe(&mut self, sid: SocketId, pipe: Box<pipe::Pipe>, sizes: BufferSizes) -> EndpointId {
         let eid = EndpointId::from(self.ids.next());
         
-        self.insert_pipe_controller(sid, eid, pipe);
+        self.insert_pipe_controller(sid, eid, pipe, sizes);
 
         eid
     }
 
-    fn insert_pipe_controller(&mut self, sid: SocketId, eid: EndpointId, pipe: Box<pipe::Pipe>) {
+    /// Accepted pipes get the buffer sizes that were configured when the acceptor was bound.
+    pub fn insert_accepted_pipe(&mut self, sid: SocketId, aid: EndpointId, pipe: Box<pipe::Pipe>) -> EndpointId {
+        let sizes = self.acceptors.get(&aid).map_or((0, 0), |acceptor| acceptor.buffer_sizes);
+
+        self.insert_pipe(sid, pipe, sizes)
+    }
+
+    fn insert_pipe_controller(&mut self, sid: SocketId, eid: EndpointId, pipe: Box<pipe::Pipe>, sizes: BufferSizes) {
         let controller = PipeController {
             socket_id: sid,
             endpoint_id: eid,
-            pipe: pipe
+            pipe: pipe,
+            traffic: Traffic::default(),
+            sending: None,
+            buffer: PipeBuffer::from_sizes(sizes)
         };
 
         self.pipes.insert(eid, controller);
     }
 
-    pub fn remove_pipe(&mut self, eid: EndpointId) {
-        self.pipes.remove(&eid);
+    /// Returns the traffic of the removed pipe, so the socket can keep track of it.
+    pub fn remove_pipe(&mut self, eid: EndpointId) -> Option<Traffic> {
+        self.pipes.remove(&eid).map(|pipe| pipe.traffic)
+    }
+
+    pub fn get_pipes_traffic(&self, sid: SocketId) -> Vec<(EndpointId, Traffic)> {
+        self.pipes.values().
+            filter(|pipe| pipe.socket_id == sid).
+            map(|pipe| (pipe.endpoint_id, pipe.traffic)).
+            collect()
+    }
+
+    pub fn get_pipes_status(&self, sid: SocketId) -> Vec<(EndpointId, PipeStatus)> {
+        self.pipes.values().
+            filter(|pipe| pipe.socket_id == sid).
+            map(|pipe| (pipe.endpoint_id, pipe.status())).
+            collect()
     }
 
     pub fn get_acceptor_mut(&mut self, eid: EndpointId) -> Option<&mut AcceptorController> {
         self.acceptors.get_mut(&eid)
     }
 
-    fn insert_acceptor(&mut self, sid: SocketId, acceptor: Box<acceptor::Acceptor>) -> EndpointId {
+    pub fn remove_acceptor(&mut self, eid: EndpointId) {
+        self.acceptors.remove(&eid);
+    }
+
+    fn insert_acceptor(&mut self, sid: SocketId, acceptor: Box<acceptor::Acceptor>, sizes: BufferSizes) -> EndpointId {
         let eid = EndpointId::from(self.ids.next());
 
-        self.insert_acceptor_controller(sid, eid, acceptor);
+        self.insert_acceptor_controller(sid, eid, acceptor, sizes);
 
         eid
     }
 
-    fn insert_acceptor_controller(&mut self, sid: SocketId, eid: EndpointId, acceptor: Box<acceptor::Acceptor>) {
+    fn insert_acceptor_controller(&mut self, sid: SocketId, eid: EndpointId, acceptor: Box<acceptor::Acceptor>, sizes: BufferSizes) {
         let controller = AcceptorController {
             socket_id: sid,
             endpoint_id: eid,
-            acceptor: acceptor
+            acceptor: acceptor,
+            buffer_sizes: sizes
         };
 
         self.acceptors.insert(eid, controller);
@@ -230,13 +380,15 @@ impl<'a> SocketEventLoopContext<'a> {
         tx: &'a mut EventLoopBus<Signal>,
         eps: &'a mut EndpointCollection,
         sched: &'a mut Schedule,
-        timer: &'a mut Timer<Task>) -> SocketEventLoopContext<'a> {
+        timer: &'a mut Timer<Task>,
+        transports: &'a HashMap<String, Box<Transport>>) -> SocketEventLoopContext<'a> {
         SocketEventLoopContext {
             socket_id: sid,
             signal_tx: tx,
             endpoints: eps,
             schedule: sched,
-            timer: timer
+            timer: timer,
+            transports: transports
         }
     }
 
@@ -262,14 +414,14 @@ impl<'a> SocketEventLoopContext<'a> {
         self.send_signal(signal);
     }
 
-    fn get_transport(&self, scheme: &str) -> io::Result<Box<Transport>> {
-        match scheme {
-            "tcp" => Ok(Box::new(Tcp)),
-            _ => Err(invalid_input_io_error(scheme.to_owned()))
+    fn get_transport(&self, scheme: &str) -> io::Result<&Transport> {
+        match self.transports.get(scheme) {
+            Some(transport) => Ok(transport.as_ref()),
+            None => Err(invalid_input_io_error(scheme.to_owned()))
         }
     }
 
-    fn connect(&mut self, url: &str, pids: (u16, u16)) -> io::Result<Box<pipe::Pipe>> {
+    fn connect(&mut self, url: &str, pids: (u16, u16), config: &Config) -> io::Result<Box<pipe::Pipe>> {
         let index = match url.find("://") {
             Some(x) => x,
             None => return Err(invalid_input_io_error(url.to_owned()))
@@ -279,10 +431,10 @@ impl<'a> SocketEventLoopContext<'a> {
         let addr = &remainder[3..];
         let transport = try!(self.get_transport(scheme));
 
-        transport.connect(addr, pids)
+        transport.connect(addr, pids, config)
     }
 
-    fn bind(&mut self, url: &str, pids: (u16, u16)) -> io::Result<Box<acceptor::Acceptor>> {
+    fn bind(&mut self, url: &str, pids: (u16, u16), config: &Config) -> io::Result<Box<acceptor::Acceptor>> {
         let index = match url.find("://") {
             Some(x) => x,
             None => return Err(invalid_input_io_error(url.to_owned()))
@@ -292,33 +444,33 @@ impl<'a> SocketEventLoopContext<'a> {
         let addr = &remainder[3..];
         let transport = try!(self.get_transport(scheme));
 
-        transport.bind(addr, pids)
+        transport.bind(addr, pids, config)
     }
 }
 
 impl<'a> Network for SocketEventLoopContext<'a> {
 
-    fn connect(&mut self, sid: SocketId, url: &str, pids: (u16, u16)) -> io::Result<EndpointId> {
-        let pipe = try!(self.connect(url, pids));
-        let eid = self.endpoints.insert_pipe(sid, pipe);
+    fn connect(&mut self, sid: SocketId, url: &str, pids: (u16, u16), config: &Config) -> io::Result<EndpointId> {
+        let pipe = try!(self.connect(url, pids, config));
+        let eid = self.endpoints.insert_pipe(sid, pipe, buffer_sizes(config));
 
         Ok(eid)
     }
-    fn bind(&mut self, sid: SocketId, url: &str, pids: (u16, u16)) -> io::Result<EndpointId> {
-        let acceptor = try!(self.bind(url, pids));
-        let eid = self.endpoints.insert_acceptor(sid, acceptor);
+    fn bind(&mut self, sid: SocketId, url: &str, pids: (u16, u16), config: &Config) -> io::Result<EndpointId> {
+        let acceptor = try!(self.bind(url, pids, config));
+        let eid = self.endpoints.insert_acceptor(sid, acceptor, buffer_sizes(config));
 
         Ok(eid)
     }
-    fn reconnect(&mut self, sid: SocketId, eid: EndpointId, url: &str, pids: (u16, u16)) -> io::Result<()> {
-        let pipe = try!(self.connect(url, pids));
+    fn reconnect(&mut self, sid: SocketId, eid: EndpointId, url: &str, pids: (u16, u16), config: &Config) -> io::Result<()> {
+        let pipe = try!(self.connect(url, pids, config));
 
-        Ok(self.endpoints.insert_pipe_controller(sid, eid, pipe))
+        Ok(self.endpoints.insert_pipe_controller(sid, eid, pipe, buffer_sizes(config)))
     }
-    fn rebind(&mut self, sid: SocketId, eid: EndpointId, url: &str, pids: (u16, u16)) -> io::Result<()> {
-        let acceptor = try!(self.bind(url, pids));
+    fn rebind(&mut self, sid: SocketId, eid: EndpointId, url: &str, pids: (u16, u16), config: &Config) -> io::Result<()> {
+        let acceptor = try!(self.bind(url, pids, config));
 
-        Ok(self.endpoints.insert_acceptor_controller(sid, eid, acceptor))
+        Ok(self.endpoints.insert_acceptor_controller(sid, eid, acceptor, buffer_sizes(config)))
     }
     fn open(&mut self, endpoint_id: EndpointId, remote: bool) {
         if remote {
@@ -409,6 +561,30 @@ impl<'a, 'b> pipe::Context for EndpointEventLoopContext<'a, 'b> {
     }
 }
 
+impl<'x> EndpointRegistrar for CapturingContext<'x> {
+    fn register(&mut self, io: &Evented, interest: Ready, opt: PollOpt) {
+        self.inner.register(io, interest, opt)
+    }
+    fn reregister(&mut self, io: &Evented, interest: Ready, opt: PollOpt) {
+        self.inner.reregister(io, interest, opt)
+    }
+    fn deregister(&mut self, io: &Evented) {
+        self.inner.deregister(io)
+    }
+}
+
+impl<'x> pipe::Context for CapturingContext<'x> {
+    fn raise(&mut self, evt: pipe::Event) {
+        self.events.push(evt);
+    }
+}
+
+impl<'x> fmt::Debug for CapturingContext<'x> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
 impl<'a, 'b> fmt::Debug for EndpointEventLoopContext<'a, 'b> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Socket:{:?} Pipe:{:?}", self.socket_id, self.endpoint_id)
@@ -440,3 +616,35 @@ impl From<Token> for EndpointId {
         EndpointId::from(tok.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use core::Message;
+    use core::stats::Traffic;
+    use transport::pipe::Event;
+
+    use super::Sent;
+
+    #[test]
+    fn message_is_counted_once_the_pipe_acknowledges_it() {
+        let mut traffic = Traffic::default();
+        let mut sending = None;
+        {
+            let mut sent = Sent { traffic: &mut traffic, sending: &mut sending };
+
+            sent.on_send(&Rc::new(Message::from_body(vec![65, 66, 67])));
+            sent.on_event(&Event::CanRecv);
+        }
+        assert_eq!(0, traffic.messages_sent);
+        {
+            let mut sent = Sent { traffic: &mut traffic, sending: &mut sending };
+
+            sent.on_event(&Event::Sent);
+            sent.on_event(&Event::Sent);
+        }
+        assert_eq!(1, traffic.messages_sent);
+        assert_eq!(3, traffic.bytes_sent);
+    }
+}
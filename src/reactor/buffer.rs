@@ -0,0 +1,389 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::rc::Rc;
+use std::collections::VecDeque;
+
+use core::Message;
+use core::config::Config;
+use transport::pipe::{Command, Event};
+
+/// Sizes of the outbound and inbound queues, in bytes.
+pub type BufferSizes = (usize, usize);
+
+pub fn buffer_sizes(config: &Config) -> BufferSizes {
+    (config.send_buffer_size, config.recv_buffer_size)
+}
+
+/// What the controller has to do on behalf of the buffer:
+/// either raise an event to the socket or forward a command to the transport pipe.
+pub enum Action {
+    Raise(Event),
+    Open,
+    Close,
+    Send(Rc<Message>),
+    Recv
+}
+
+/// Message queues standing between the socket and a transport pipe.
+/// The socket sees a pipe that acknowledges a send as soon as the message is queued,
+/// and that is ready to receive as soon as a prefetched message is available.
+/// Meanwhile the queues are drained and filled whenever the transport pipe is ready.
+/// A limit of zero disables the corresponding queue, the commands and events are then passed through.
+pub struct PipeBuffer {
+    send_hwm: usize,
+    send_queue: VecDeque<Rc<Message>>,
+    send_queued: usize,
+    /// The transport pipe can accept a message.
+    can_send: bool,
+    /// The socket was told it can send a message, and has not done it yet.
+    send_announced: bool,
+    opened: bool,
+    closing: bool,
+    recv_hwm: usize,
+    recv_queue: VecDeque<Message>,
+    recv_queued: usize,
+    /// The transport pipe has a message to deliver.
+    can_recv: bool,
+    receiving: bool,
+    /// The socket was told it can receive a message, and has not done it yet.
+    recv_announced: bool,
+    /// The socket is waiting for a message that is not received yet.
+    recv_requested: bool
+}
+
+impl PipeBuffer {
+    pub fn new(send_hwm: usize, recv_hwm: usize) -> PipeBuffer {
+        PipeBuffer {
+            send_hwm: send_hwm,
+            send_queue: VecDeque::new(),
+            send_queued: 0,
+            can_send: false,
+            send_announced: false,
+            opened: false,
+            closing: false,
+            recv_hwm: recv_hwm,
+            recv_queue: VecDeque::new(),
+            recv_queued: 0,
+            can_recv: false,
+            receiving: false,
+            recv_announced: false,
+            recv_requested: false
+        }
+    }
+
+    /// Returns a buffer only when at least one of the queues is enabled.
+    pub fn from_sizes(sizes: BufferSizes) -> Option<PipeBuffer> {
+        match sizes {
+            (0, 0) => None,
+            (send, recv) => Some(PipeBuffer::new(send, recv))
+        }
+    }
+
+    fn is_send_buffered(&self) -> bool {
+        self.send_hwm > 0
+    }
+
+    fn is_recv_buffered(&self) -> bool {
+        self.recv_hwm > 0
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* commands from the socket                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+    pub fn on_command(&mut self, cmd: Command) -> Vec<Action> {
+        let mut actions = Vec::new();
+
+        match cmd {
+            Command::Open      => actions.push(Action::Open),
+            Command::Close     => self.close(&mut actions),
+            Command::Send(msg) => self.send(msg, &mut actions),
+            Command::Recv      => self.recv(&mut actions)
+        }
+
+        actions
+    }
+
+    /// Queued messages are still sent when the socket lingers,
+    /// unless the pipe is closed a second time.
+    fn close(&mut self, actions: &mut Vec<Action>) {
+        if self.closing || self.send_queue.is_empty() {
+            self.send_queue.clear();
+            self.send_queued = 0;
+            actions.push(Action::Close);
+        } else {
+            self.closing = true;
+        }
+    }
+
+    fn send(&mut self, msg: Rc<Message>, actions: &mut Vec<Action>) {
+        if !self.is_send_buffered() {
+            return actions.push(Action::Send(msg));
+        }
+
+        self.send_announced = false;
+        self.send_queued += msg.len();
+        self.send_queue.push_back(msg);
+
+        actions.push(Action::Raise(Event::Sent));
+        self.flush(actions);
+        self.announce_send(actions);
+    }
+
+    fn recv(&mut self, actions: &mut Vec<Action>) {
+        if !self.is_recv_buffered() {
+            return actions.push(Action::Recv);
+        }
+
+        self.recv_announced = false;
+
+        match self.recv_queue.pop_front() {
+            Some(msg) => {
+                self.recv_queued -= msg.len();
+                actions.push(Action::Raise(Event::Received(msg)));
+                self.announce_recv(actions);
+            },
+            None => self.recv_requested = true
+        }
+
+        self.prefetch(actions);
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* events from the transport pipe                                            */
+/*                                                                           */
+/*****************************************************************************/
+
+    pub fn on_event(&mut self, evt: Event) -> Vec<Action> {
+        let mut actions = Vec::new();
+
+        match evt {
+            Event::Opened        => self.on_opened(&mut actions),
+            Event::CanSend       => self.on_can_send(&mut actions),
+            Event::Sent          => self.on_sent(&mut actions),
+            Event::CanRecv       => self.on_can_recv(&mut actions),
+            Event::Received(msg) => self.on_received(msg, &mut actions),
+            other                => actions.push(Action::Raise(other))
+        }
+
+        actions
+    }
+
+    fn on_opened(&mut self, actions: &mut Vec<Action>) {
+        self.opened = true;
+        actions.push(Action::Raise(Event::Opened));
+        self.announce_send(actions);
+    }
+
+    fn on_can_send(&mut self, actions: &mut Vec<Action>) {
+        if !self.is_send_buffered() {
+            return actions.push(Action::Raise(Event::CanSend));
+        }
+
+        self.can_send = true;
+        self.flush(actions);
+        self.announce_send(actions);
+    }
+
+    /// The transport pipe will tell when it can accept the next message.
+    fn on_sent(&mut self, actions: &mut Vec<Action>) {
+        if !self.is_send_buffered() {
+            actions.push(Action::Raise(Event::Sent));
+        }
+    }
+
+    fn on_can_recv(&mut self, actions: &mut Vec<Action>) {
+        if !self.is_recv_buffered() {
+            return actions.push(Action::Raise(Event::CanRecv));
+        }
+
+        self.can_recv = true;
+        self.prefetch(actions);
+    }
+
+    fn on_received(&mut self, msg: Message, actions: &mut Vec<Action>) {
+        if !self.is_recv_buffered() {
+            return actions.push(Action::Raise(Event::Received(msg)));
+        }
+
+        self.receiving = false;
+
+        if self.recv_requested {
+            self.recv_requested = false;
+            actions.push(Action::Raise(Event::Received(msg)));
+        } else {
+            self.recv_queued += msg.len();
+            self.recv_queue.push_back(msg);
+            self.announce_recv(actions);
+        }
+
+        self.prefetch(actions);
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* queues                                                                    */
+/*                                                                           */
+/*****************************************************************************/
+
+    fn flush(&mut self, actions: &mut Vec<Action>) {
+        if !self.can_send {
+            return;
+        }
+
+        if let Some(msg) = self.send_queue.pop_front() {
+            self.can_send = false;
+            self.send_queued -= msg.len();
+            actions.push(Action::Send(msg));
+
+            if self.closing && self.send_queue.is_empty() {
+                actions.push(Action::Close);
+            }
+        }
+    }
+
+    /// The queue accepts messages while it holds less than the limit,
+    /// so a message larger than the limit can still be sent.
+    fn announce_send(&mut self, actions: &mut Vec<Action>) {
+        if self.opened && !self.closing && !self.send_announced && self.send_queued < self.send_hwm {
+            self.send_announced = true;
+            actions.push(Action::Raise(Event::CanSend));
+        }
+    }
+
+    fn prefetch(&mut self, actions: &mut Vec<Action>) {
+        if self.can_recv && !self.receiving && (self.recv_requested || self.recv_queued < self.recv_hwm) {
+            self.can_recv = false;
+            self.receiving = true;
+            actions.push(Action::Recv);
+        }
+    }
+
+    fn announce_recv(&mut self, actions: &mut Vec<Action>) {
+        if !self.recv_announced && !self.recv_queue.is_empty() {
+            self.recv_announced = true;
+            actions.push(Action::Raise(Event::CanRecv));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use core::Message;
+    use transport::pipe::{Command, Event};
+
+    use super::*;
+
+    fn names(actions: &[Action]) -> Vec<&'static str> {
+        actions.iter().map(|action| match *action {
+            Action::Raise(ref evt) => evt.name(),
+            Action::Open           => "Open",
+            Action::Close          => "Close",
+            Action::Send(_)        => "Send",
+            Action::Recv           => "Recv"
+        }).collect()
+    }
+
+    fn msg(len: usize) -> Rc<Message> {
+        Rc::new(Message::from_body(vec![0; len]))
+    }
+
+    #[test]
+    fn can_send_is_announced_when_opened() {
+        let mut buffer = PipeBuffer::new(16, 0);
+
+        assert_eq!(vec!["Opened", "CanSend"], names(&buffer.on_event(Event::Opened)));
+    }
+
+    #[test]
+    fn send_is_acknowledged_before_the_transport_can_send() {
+        let mut buffer = PipeBuffer::new(16, 0);
+
+        buffer.on_event(Event::Opened);
+
+        assert_eq!(vec!["Sent", "CanSend"], names(&buffer.on_command(Command::Send(msg(4)))));
+        assert_eq!(vec!["Send"], names(&buffer.on_event(Event::CanSend)));
+        assert!(buffer.on_event(Event::Sent).is_empty());
+    }
+
+    #[test]
+    fn can_send_is_not_announced_above_the_limit() {
+        let mut buffer = PipeBuffer::new(8, 0);
+
+        buffer.on_event(Event::Opened);
+        buffer.on_command(Command::Send(msg(4)));
+
+        assert_eq!(vec!["Sent"], names(&buffer.on_command(Command::Send(msg(4)))));
+        assert_eq!(vec!["Send", "CanSend"], names(&buffer.on_event(Event::CanSend)));
+    }
+
+    #[test]
+    fn queued_messages_are_sent_before_closing() {
+        let mut buffer = PipeBuffer::new(16, 0);
+
+        buffer.on_event(Event::Opened);
+        buffer.on_command(Command::Send(msg(4)));
+        buffer.on_command(Command::Send(msg(4)));
+
+        assert!(buffer.on_command(Command::Close).is_empty());
+        assert_eq!(vec!["Send"], names(&buffer.on_event(Event::CanSend)));
+        assert_eq!(vec!["Send", "Close"], names(&buffer.on_event(Event::CanSend)));
+    }
+
+    #[test]
+    fn closing_twice_drops_queued_messages() {
+        let mut buffer = PipeBuffer::new(16, 0);
+
+        buffer.on_event(Event::Opened);
+        buffer.on_command(Command::Send(msg(4)));
+        buffer.on_command(Command::Close);
+
+        assert_eq!(vec!["Close"], names(&buffer.on_command(Command::Close)));
+        assert!(buffer.on_event(Event::CanSend).is_empty());
+    }
+
+    #[test]
+    fn messages_are_prefetched_up_to_the_limit() {
+        let mut buffer = PipeBuffer::new(0, 8);
+
+        assert_eq!(vec!["Recv"], names(&buffer.on_event(Event::CanRecv)));
+        assert_eq!(vec!["CanRecv"], names(&buffer.on_event(Event::Received(Message::from_body(vec![0; 4])))));
+        assert_eq!(vec!["Recv"], names(&buffer.on_event(Event::CanRecv)));
+        assert!(buffer.on_event(Event::Received(Message::from_body(vec![0; 4]))).is_empty());
+        assert!(buffer.on_event(Event::CanRecv).is_empty());
+
+        assert_eq!(vec!["Received", "CanRecv", "Recv"], names(&buffer.on_command(Command::Recv)));
+        assert_eq!(vec!["Received"], names(&buffer.on_command(Command::Recv)));
+    }
+
+    #[test]
+    fn pending_recv_is_completed_by_the_next_message() {
+        let mut buffer = PipeBuffer::new(0, 8);
+
+        assert!(buffer.on_command(Command::Recv).is_empty());
+        assert_eq!(vec!["Recv"], names(&buffer.on_event(Event::CanRecv)));
+        assert_eq!(vec!["Received"], names(&buffer.on_event(Event::Received(Message::new()))));
+    }
+
+    #[test]
+    fn disabled_queues_pass_through() {
+        let mut buffer = PipeBuffer::new(0, 0);
+
+        assert_eq!(vec!["Opened"], names(&buffer.on_event(Event::Opened)));
+        assert_eq!(vec!["CanSend"], names(&buffer.on_event(Event::CanSend)));
+        assert_eq!(vec!["Send"], names(&buffer.on_command(Command::Send(msg(4)))));
+        assert_eq!(vec!["Sent"], names(&buffer.on_event(Event::Sent)));
+        assert_eq!(vec!["CanRecv"], names(&buffer.on_event(Event::CanRecv)));
+        assert_eq!(vec!["Recv"], names(&buffer.on_command(Command::Recv)));
+        assert_eq!(vec!["Received"], names(&buffer.on_event(Event::Received(Message::new()))));
+    }
+}
@@ -6,14 +6,16 @@
 
 use std::sync::mpsc::Sender;
 use std::io;
+use std::collections::HashMap;
 use std::time::Duration;
 
 use mio::{Token, Ready, PollOpt};
-use mio::timer::{Timer, Builder};
+use mio::timer::{Timer, Timeout, Builder};
 use mio::channel::{Receiver};
 
-use core::{SocketId, EndpointId, DeviceId, session, socket, context, device};
-use transport::{pipe, acceptor};
+use core::{SocketId, EndpointId, DeviceId, session, socket, endpoint, context, device};
+use transport::{self, pipe, acceptor};
+use transport::Transport;
 use super::{Signal, Request, Task};
 use super::event_loop::{EventLoop, EventHandler};
 use super::bus::EventLoopBus;
@@ -33,16 +35,19 @@ pub struct Dispatcher {
     // request handlers
     sockets: session::Session,
     endpoints: EndpointCollection,
-    schedule: Schedule
+    schedule: Schedule,
+    transports: HashMap<String, Box<Transport>>,
+    poll_timeout: Option<Timeout>,
+    shutdown: bool
 }
 
 impl Dispatcher {
-    pub fn dispatch(rx: Receiver<Request>, tx: Sender<session::Reply>) -> io::Result<()> {
-        let mut dispatcher = Dispatcher::new(rx, tx);
+    pub fn dispatch(rx: Receiver<Request>, tx: Sender<session::Reply>, transports: Vec<(String, Box<Transport + Send>)>) -> io::Result<()> {
+        let mut dispatcher = Dispatcher::new(rx, tx, transports);
 
         dispatcher.run()
     }
-    pub fn new(rx: Receiver<Request>, tx: Sender<session::Reply>) -> Dispatcher {
+    pub fn new(rx: Receiver<Request>, tx: Sender<session::Reply>, transports: Vec<(String, Box<Transport + Send>)>) -> Dispatcher {
         let id_seq = Sequence::new();
         let timeout_eq = Sequence::new();
         let clock = Builder::default().
@@ -57,7 +62,10 @@ impl Dispatcher {
             timer: clock,
             sockets: session::Session::new(id_seq.clone(), tx),
             endpoints: EndpointCollection::new(id_seq.clone()),
-            schedule: Schedule::new(timeout_eq)
+            schedule: Schedule::new(timeout_eq),
+            transports: create_transports(transports),
+            poll_timeout: None,
+            shutdown: false
         }
     }
 
@@ -110,6 +118,7 @@ impl Dispatcher {
         match request {
             Request::Session(req) => self.process_session_request(el, req),
             Request::Socket(id, req) => self.process_socket_request(el, id, req),
+            Request::Endpoint(sid, eid, req) => self.process_endpoint_request(el, sid, eid, req),
             Request::Device(id, req) => self.process_device_request(el, id, req),
             _ => {}
         }
@@ -131,7 +140,11 @@ impl Dispatcher {
 /*****************************************************************************/
     fn process_tick(&mut self, _: &mut EventLoop, task: Task) {
         match task {
-            Task::Socket(sid, schedulable) => self.process_socket_task(sid, schedulable)
+            Task::Socket(sid, schedulable) => self.process_socket_task(sid, schedulable),
+            Task::Poll => {
+                self.poll_timeout = None;
+                self.sockets.on_poll_timeout();
+            }
         }
     }
 
@@ -141,6 +154,9 @@ impl Dispatcher {
             context::Schedulable::Rebind(spec)    => self.apply_on_socket(sid, |socket, ctx| socket.rebind(ctx, spec)),
             context::Schedulable::SendTimeout     => self.apply_on_socket(sid, |socket, ctx| socket.on_send_timeout(ctx)),
             context::Schedulable::RecvTimeout     => self.apply_on_socket(sid, |socket, ctx| socket.on_recv_timeout(ctx)),
+            context::Schedulable::ContextSendTimeout(cid) => self.apply_on_socket(sid, |socket, ctx| socket.on_context_send_timeout(ctx, cid)),
+            context::Schedulable::ContextRecvTimeout(cid) => self.apply_on_socket(sid, |socket, ctx| socket.on_context_recv_timeout(ctx, cid)),
+            context::Schedulable::Linger          => self.apply_on_socket(sid, |socket, ctx| socket.on_linger_timeout(ctx)),
             other                                 => self.apply_on_socket(sid, |socket, ctx| socket.on_timer_tick(ctx, other))
         }
     }
@@ -179,19 +195,89 @@ impl Dispatcher {
                 self.apply_on_socket(r, |socket, ctx| socket.on_device_plugged(ctx));
                 self.sockets.add_device(l, r);
             },
-            session::Request::Shutdown => el.shutdown()
+            session::Request::Poll(requests, timeout) => self.poll(requests, timeout),
+            session::Request::Shutdown => {
+                self.shutdown = true;
+                self.check_shutdown(el);
+            }
+        }
+    }
+
+    fn poll(&mut self, requests: Vec<(SocketId, bool, bool)>, timeout: Duration) {
+        if self.sockets.poll(requests) {
+            return;
+        }
+
+        if timeout == Duration::from_millis(0) {
+            return self.sockets.on_poll_timeout();
+        }
+
+        match self.timer.set_timeout(timeout, Task::Poll) {
+            Ok(handle) => self.poll_timeout = Some(handle),
+            Err(_) => self.sockets.on_poll_timeout()
+        }
+    }
+
+    fn on_socket_ready(&mut self, sid: SocketId) {
+        if self.sockets.on_socket_ready(sid) {
+            if let Some(handle) = self.poll_timeout.take() {
+                self.timer.cancel_timeout(&handle);
+            }
+        }
+    }
+
+    /// Shutdown is postponed until the sockets that are lingering are closed.
+    fn check_shutdown(&mut self, el: &mut EventLoop) {
+        if self.shutdown && self.sockets.has_closing_sockets() == false {
+            el.shutdown();
         }
     }
     fn process_socket_request(&mut self, _: &mut EventLoop, id: SocketId, request: socket::Request) {
         match request {
-            socket::Request::Connect(url) => self.apply_on_socket(id, |socket, ctx| socket.connect(ctx, url)),
-            socket::Request::Bind(url)    => self.apply_on_socket(id, |socket, ctx| socket.bind(ctx, url)),
+            socket::Request::Connect(url, opts) => self.apply_on_socket(id, |socket, ctx| socket.connect(ctx, url, opts)),
+            socket::Request::Bind(url, opts)    => self.apply_on_socket(id, |socket, ctx| socket.bind(ctx, url, opts)),
             socket::Request::Send(msg)    => self.apply_on_socket(id, |socket, ctx| socket.send(ctx, msg)),
+            socket::Request::TrySend(msg) => self.apply_on_socket(id, |socket, ctx| socket.try_send(ctx, msg)),
             socket::Request::Recv         => self.apply_on_socket(id, |socket, ctx| socket.recv(ctx)),
+            socket::Request::TryRecv      => self.apply_on_socket(id, |socket, ctx| socket.try_recv(ctx)),
+            socket::Request::Collect(d)   => self.apply_on_socket(id, |socket, ctx| socket.collect(ctx, d)),
             socket::Request::SetOption(x) => self.apply_on_socket(id, |socket, ctx| socket.set_option(ctx, x)),
+            socket::Request::GetOption(x) => self.apply_on_socket(id, |socket, _| socket.get_option(x)),
+            #[cfg(not(windows))]
+            socket::Request::SendFd       => self.apply_on_socket(id, |socket, _| socket.send_fd()),
+            #[cfg(not(windows))]
+            socket::Request::RecvFd       => self.apply_on_socket(id, |socket, _| socket.recv_fd()),
+            socket::Request::SetNotifier(n) => self.apply_on_socket(id, |socket, _| socket.set_notifier(n)),
+            socket::Request::Monitor      => self.apply_on_socket(id, |socket, _| socket.monitor()),
+            socket::Request::Statistics   => {
+                let traffic = self.endpoints.get_pipes_traffic(id);
+                self.apply_on_socket(id, |socket, _| socket.statistics(traffic))
+            },
+            socket::Request::Endpoints    => {
+                let pipes = self.endpoints.get_pipes_status(id);
+                self.apply_on_socket(id, |socket, _| socket.endpoints(pipes))
+            },
+            socket::Request::CreateContext     => self.apply_on_socket(id, |socket, _| socket.create_context()),
+            socket::Request::ContextSend(c, m) => self.apply_on_socket(id, |socket, ctx| socket.context_send(ctx, c, m)),
+            socket::Request::ContextRecv(c)    => self.apply_on_socket(id, |socket, ctx| socket.context_recv(ctx, c)),
+            socket::Request::ContextCollect(c, d) => self.apply_on_socket(id, |socket, ctx| socket.context_collect(ctx, c, d)),
+            socket::Request::CloseContext(c)   => self.apply_on_socket(id, |socket, ctx| socket.close_context(ctx, c)),
             socket::Request::Close        => self.apply_on_socket(id, |socket, ctx| socket.close(ctx)),
         }
     }
+    fn process_endpoint_request(&mut self, _: &mut EventLoop, sid: SocketId, eid: EndpointId, request: endpoint::Request) {
+        match request {
+            endpoint::Request::Close(remote, tx) => self.apply_on_socket(sid, |socket, ctx| socket.close_endpoint(ctx, eid, remote, tx)),
+            endpoint::Request::Statistics(tx)    => {
+                let traffic = self.endpoints.get_pipes_traffic(sid);
+                self.apply_on_socket(sid, |socket, _| socket.endpoint_statistics(eid, traffic, tx))
+            },
+            endpoint::Request::Info(tx)          => {
+                let pipes = self.endpoints.get_pipes_status(sid);
+                self.apply_on_socket(sid, |socket, _| socket.endpoint_info(eid, pipes, tx))
+            }
+        }
+    }
     fn process_device_request(&mut self, _: &mut EventLoop, id: DeviceId, request: device::Request) {
         if let device::Request::Check = request { 
             self.apply_on_device(id, |device| device.check()) 
@@ -219,31 +305,62 @@ impl Dispatcher {
             pipe::Event::CanSend       => self.apply_on_socket(sid, |socket, ctx| socket.on_send_ready(ctx, eid)),
             pipe::Event::Sent          => self.apply_on_socket(sid, |socket, ctx| socket.on_send_ack(ctx, eid)),
             pipe::Event::CanRecv       => self.apply_on_socket(sid, |socket, ctx| socket.on_recv_ready(ctx, eid)),
-            pipe::Event::Received(msg) => self.apply_on_socket(sid, |socket, ctx| socket.on_recv_ack(ctx, eid, msg)),
-            pipe::Event::Error(err)    => self.apply_on_socket(sid, |socket, ctx| socket.on_pipe_error(ctx, eid, err)),
-            pipe::Event::Closed        => self.endpoints.remove_pipe(eid)
+            pipe::Event::Received(msg) => {
+                if let Some(pipe) = self.endpoints.get_pipe_mut(eid) {
+                    pipe.on_received(&msg);
+                }
+                self.apply_on_socket(sid, |socket, ctx| socket.on_recv_ack(ctx, eid, msg))
+            },
+            pipe::Event::Error(err)    => {
+                // a failed pipe does not raise `Closed`, and may be reconnected under the same id
+                let traffic = self.endpoints.remove_pipe(eid);
+                self.apply_on_socket(sid, |socket, ctx| {
+                    if let Some(traffic) = traffic {
+                        socket.on_pipe_traffic(eid, traffic);
+                    }
+                    socket.on_pipe_error(ctx, eid, err)
+                })
+            },
+            pipe::Event::Closed        => {
+                let traffic = self.endpoints.remove_pipe(eid);
+                self.apply_on_socket(sid, |socket, ctx| {
+                    if let Some(traffic) = traffic {
+                        socket.on_pipe_traffic(eid, traffic);
+                    }
+                    socket.on_pipe_closed(ctx, eid)
+                })
+            }
         }
     }
     fn process_acceptor_evt(&mut self, _: &mut EventLoop, sid: SocketId, aid: EndpointId, evt: acceptor::Event) {
         match evt {
-            // Maybe the controller should be removed from the endpoint collection
             acceptor::Event::Error(e) => self.apply_on_socket(sid, |socket, ctx| socket.on_acceptor_error(ctx, aid, e)),
             acceptor::Event::Accepted(pipes) => {
                 for pipe in pipes {
-                    let pipe_id = self.endpoints.insert_pipe(sid, pipe);
+                    let pipe_id = self.endpoints.insert_accepted_pipe(sid, aid, pipe);
 
                     self.apply_on_socket(sid, |socket, ctx| socket.on_pipe_accepted(ctx, aid, pipe_id));
                 }
             },
-            _ => {}
+            acceptor::Event::Closed => {
+                self.endpoints.remove_acceptor(aid);
+                self.apply_on_socket(sid, |socket, ctx| socket.on_acceptor_closed(ctx, aid))
+            },
+            acceptor::Event::Opened => {}
         }
     }
 
-    fn process_socket_evt(&mut self, _: &mut EventLoop, sid: SocketId, evt: context::Event) {
+    fn process_socket_evt(&mut self, el: &mut EventLoop, sid: SocketId, evt: context::Event) {
         match evt {
-            context::Event::CanRecv => self.apply_on_device_link(sid, |device| device.on_socket_can_recv(sid)),
-            context::Event::CanSend => {},
-            context::Event::Closed => self.sockets.remove_socket(sid)
+            context::Event::CanRecv => {
+                self.apply_on_device_link(sid, |device| device.on_socket_can_recv(sid));
+                self.on_socket_ready(sid);
+            },
+            context::Event::CanSend => self.on_socket_ready(sid),
+            context::Event::Closed => {
+                self.sockets.remove_socket(sid);
+                self.check_shutdown(el);
+            }
         }
     }
 
@@ -255,9 +372,14 @@ impl Dispatcher {
                 &mut self.bus,
                 &mut self.endpoints,
                 &mut self.schedule,
-                &mut self.timer);
+                &mut self.timer,
+                &self.transports);
 
             f(socket, &mut ctx);
+
+            // whatever was done, the readiness may have changed
+            #[cfg(not(windows))] socket.update_fds();
+            socket.notify();
         }
     }
 
@@ -291,3 +413,14 @@ impl EventHandler for Dispatcher {
         self.process_io(el, token, events)
     }
 }
+
+/// Built-in transports are registered first so that user supplied ones can replace them.
+fn create_transports(custom: Vec<(String, Box<Transport + Send>)>) -> HashMap<String, Box<Transport>> {
+    let mut transports = transport::builtins();
+
+    for (scheme, transport) in custom {
+        transports.insert(scheme, transport);
+    }
+
+    transports
+}
@@ -8,6 +8,7 @@ pub mod dispatcher;
 mod event_loop;
 mod bus;
 mod adapter;
+mod buffer;
 
 use core::{SocketId, EndpointId, DeviceId, context, session, socket, endpoint, device};
 use transport::{pipe, acceptor};
@@ -32,5 +33,6 @@ pub enum Request {
 }
 
 pub enum Task {
-    Socket(SocketId, context::Schedulable)
+    Socket(SocketId, context::Schedulable),
+    Poll
 }
@@ -23,6 +23,8 @@ pub enum Event {
     Error(io::Error)
 }
 
+/// Listening endpoint, driven by the I/O thread.
+/// Incoming connections are reported by raising `Event::Accepted` with the new pipes.
 pub trait Acceptor {
     fn ready(&mut self, ctx: &mut Context, events: Ready);
     fn open(&mut self, ctx: &mut Context);
@@ -12,7 +12,7 @@ use mio::{Ready, PollOpt};
 use core::Message;
 use transport::async::stub::*;
 use transport::async::state::*;
-use transport::async::dead::Dead; 
+use transport::async::linger::Linger;
 use transport::pipe::{Event, Context};
 use io_error::*;
 
@@ -41,6 +41,9 @@ impl<S : AsyncPipeStub> Active<S> {
         if events.is_writable() == false {
             return Ok(());
         }
+
+        try!(self.stub.flush_output());
+
         if self.stub.has_pending_send() {
             let progress = self.stub.resume_send();
 
@@ -89,17 +92,30 @@ impl<S : AsyncPipeStub> Active<S> {
     }
 }
 
+impl<S : AsyncPipeStub> Into<Linger<S>> for Active<S> {
+    fn into(self) -> Linger<S> {
+        Linger::new(self.stub)
+    }
+}
+
 impl<S : AsyncPipeStub + 'static> PipeState<S> for Active<S> {
     fn name(&self) -> &'static str {"Active"}
+    fn peer_addr(&self) -> Option<String> {
+        self.stub.peer_addr()
+    }
 
     fn enter(&self, ctx: &mut Context) {
         ctx.reregister(self.stub.deref(), Ready::all(), PollOpt::edge());
         ctx.raise(Event::Opened);
     }
     fn close(self: Box<Self>, ctx: &mut Context) -> Box<PipeState<S>> {
+        if self.stub.has_pending_send() {
+            return transition::<Active<S>, Linger<S>, S>(self, ctx);
+        }
+
         ctx.deregister(self.stub.deref());
 
-        box Dead
+        dead(ctx)
     }
     fn send(mut self: Box<Self>, ctx: &mut Context, msg: Rc<Message>) -> Box<PipeState<S>> {
         let progress = self.stub.start_send(msg);
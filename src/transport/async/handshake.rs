@@ -4,15 +4,15 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::io::Result;
+use std::io::{Result, ErrorKind};
 
 use mio::{Ready, PollOpt};
 
 use transport::async::stub::*;
 use transport::async::state::*;
 use transport::async::active::Active; 
-use transport::async::dead::Dead; 
 use transport::pipe::Context;
+use core::endpoint::EndpointState;
 
 pub struct HandshakeTx<S : AsyncPipeStub + 'static> {
     stub: S,
@@ -42,6 +42,12 @@ impl<S : AsyncPipeStub> Into<HandshakeRx<S>> for HandshakeTx<S> {
 
 impl<S : AsyncPipeStub> PipeState<S> for HandshakeTx<S> {
     fn name(&self) -> &'static str {"HandshakeTx"}
+    fn endpoint_state(&self) -> EndpointState {
+        EndpointState::Connecting
+    }
+    fn peer_addr(&self) -> Option<String> {
+        self.stub.peer_addr()
+    }
 
     fn enter(&self, ctx: &mut Context) {
         ctx.register(self.stub.deref(), Ready::writable(), PollOpt::level());
@@ -49,12 +55,19 @@ impl<S : AsyncPipeStub> PipeState<S> for HandshakeTx<S> {
     fn close(self: Box<Self>, ctx: &mut Context) -> Box<PipeState<S>> {
         ctx.deregister(self.stub.deref());
 
-        box Dead
+        dead(ctx)
     }
     fn ready(mut self: Box<Self>, ctx: &mut Context, events: Ready) -> Box<PipeState<S>> {
         if events.is_writable() {
             let res = self.send_handshake();
 
+            // some transports need several writes to send the whole handshake
+            if let Err(ref e) = res {
+                if e.kind() == ErrorKind::WouldBlock {
+                    return self;
+                }
+            }
+
             transition_if_ok::<HandshakeTx<S>, HandshakeRx<S>, S>(self, ctx, res)
         } else {
             self
@@ -91,18 +104,36 @@ impl<S : AsyncPipeStub> Into<Active<S>> for HandshakeRx<S> {
 impl<S : AsyncPipeStub + 'static> PipeState<S> for HandshakeRx<S> {
 
     fn name(&self) -> &'static str {"HandshakeRx"}
+    fn endpoint_state(&self) -> EndpointState {
+        EndpointState::Handshaking
+    }
+    fn peer_addr(&self) -> Option<String> {
+        self.stub.peer_addr()
+    }
 
     fn enter(&self, ctx: &mut Context) {
-        ctx.reregister(self.stub.deref(), Ready::readable(), PollOpt::level());
+        ctx.reregister(self.stub.deref(), self.stub.handshake_interest(), PollOpt::level());
     }
     fn close(self: Box<Self>, ctx: &mut Context) -> Box<PipeState<S>> {
         ctx.deregister(self.stub.deref());
 
-        box Dead
+        dead(ctx)
     }
     fn ready(mut self: Box<Self>, ctx: &mut Context, events: Ready) -> Box<PipeState<S>> {
-        if events.is_readable() {
+        if events.is_readable() || events.is_writable() {
+            let interest = self.stub.handshake_interest();
             let res = self.recv_handshake();
+
+            // some transports need several reads to receive the whole handshake,
+            // and may have to wait for the socket to be writable in between
+            if let Err(ref e) = res {
+                if e.kind() == ErrorKind::WouldBlock {
+                    if self.stub.handshake_interest() != interest {
+                        self.enter(ctx);
+                    }
+                    return self;
+                }
+            }
             
             /*if events.is_hup() && res.is_ok() {
                 let active = transition::<HandshakeRx<S>, Active<S>, S>(self, ctx);
@@ -181,6 +212,31 @@ mod tests {
         assert_eq!("HandshakeRx", new_state.name());
     }
 
+    #[test]
+    fn on_writable_the_handshake_can_be_partially_sent() {
+        let sensor_srv = TestStepStreamSensor::new();
+        let sensor = Rc::new(RefCell::new(sensor_srv));
+        let mut stub = TestStepStream::with_sensor(sensor.clone());
+        stub.set_send_handshake_pending(true);
+        let state = box HandshakeTx::new(stub, (4, 2));
+        let mut ctx = TestPipeContext::new();
+        let events = mio::Ready::writable();
+        let new_state = state.ready(&mut ctx, events);
+
+        assert_eq!(1, sensor.borrow().get_sent_handshakes().len());
+        assert_eq!(0, ctx.get_raised_events().len());
+        assert_eq!("HandshakeTx", new_state.name());
+    }
+
+    #[test]
+    fn endpoint_state_should_tell_connecting_from_handshaking() {
+        let tx_state = box HandshakeTx::new(TestStepStream::new(), (4, 2));
+        let rx_state = box HandshakeRx::new(TestStepStream::new(), (4, 2));
+
+        assert_eq!(EndpointState::Connecting, tx_state.endpoint_state());
+        assert_eq!(EndpointState::Handshaking, rx_state.endpoint_state());
+    }
+
     #[test]
     fn on_enter_rx_should_reregister() {
         let stub = TestStepStream::new();
@@ -229,4 +285,50 @@ mod tests {
         assert_eq!(1, sensor.borrow().get_received_handshakes());
         assert_eq!("Active", new_state.name());
     }
+
+    #[test]
+    fn readable_the_handshake_can_be_partially_received() {
+        let sensor_srv = TestStepStreamSensor::new();
+        let sensor = Rc::new(RefCell::new(sensor_srv));
+        let mut stub = TestStepStream::with_sensor(sensor.clone());
+        stub.set_recv_handshake_pending(true);
+        let pids = (6, 6);
+        let state = box HandshakeRx::new(stub, pids);
+        let mut ctx = TestPipeContext::new();
+        let events = mio::Ready::readable();
+        let new_state = state.ready(&mut ctx, events);
+
+        assert_eq!(1, sensor.borrow().get_received_handshakes());
+        assert_eq!(0, ctx.get_raised_events().len());
+        assert_eq!("HandshakeRx", new_state.name());
+    }
+
+    #[test]
+    fn on_enter_rx_should_reregister_the_stub_interest() {
+        let mut stub = TestStepStream::new();
+        stub.set_handshake_interest(mio::Ready::writable());
+        let state = box HandshakeRx::new(stub, (4, 2));
+        let mut ctx = TestPipeContext::new();
+
+        state.enter(&mut ctx);
+
+        let (ref interest, _) = ctx.get_reregistrations()[0];
+
+        assert_eq!(&mio::Ready::writable(), interest);
+    }
+
+    #[test]
+    fn writable_the_handshake_should_be_received() {
+        let sensor_srv = TestStepStreamSensor::new();
+        let sensor = Rc::new(RefCell::new(sensor_srv));
+        let mut stub = TestStepStream::with_sensor(sensor.clone());
+        stub.set_handshake_interest(mio::Ready::writable());
+        let state = box HandshakeRx::new(stub, (6, 6));
+        let mut ctx = TestPipeContext::new();
+        let events = mio::Ready::writable();
+        let new_state = state.ready(&mut ctx, events);
+
+        assert_eq!(1, sensor.borrow().get_received_handshakes());
+        assert_eq!("Active", new_state.name());
+    }
 }
@@ -8,6 +8,7 @@ use transport::async::stub::*;
 use transport::async::state::*;
 use transport::async::handshake::HandshakeTx; 
 use transport::pipe::Context;
+use core::endpoint::EndpointState;
 
 pub struct Initial<S : AsyncPipeStub> {
     stub: S,
@@ -32,6 +33,12 @@ impl<S : AsyncPipeStub> Into<HandshakeTx<S>> for Initial<S> {
 impl<S : AsyncPipeStub + 'static> PipeState<S> for Initial<S> {
 
     fn name(&self) -> &'static str {"Initial"}
+    fn endpoint_state(&self) -> EndpointState {
+        EndpointState::Connecting
+    }
+    fn peer_addr(&self) -> Option<String> {
+        self.stub.peer_addr()
+    }
     
     fn open(self: Box<Self>, ctx: &mut Context) -> Box<PipeState<S>> {
         transition::<Initial<S>, HandshakeTx<S>, S>(self, ctx)
@@ -41,6 +48,9 @@ impl<S : AsyncPipeStub + 'static> PipeState<S> for Initial<S> {
 
 #[cfg(test)]
 mod tests {
+    use std::rc::Rc;
+    use std::cell::RefCell;
+
     use transport::tests::*;
     use transport::async::state::*;
     use transport::async::tests::*;
@@ -73,4 +83,17 @@ mod tests {
 
         assert_eq!("Dead", new_state.name());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn peer_addr_is_asked_to_the_stub_once_known() {
+        let sensor = Rc::new(RefCell::new(TestStepStreamSensor::new()));
+        let stub = TestStepStream::with_sensor(sensor.clone());
+        let state = box Initial::new(stub, (1, 1));
+
+        assert_eq!(None, state.peer_addr());
+
+        sensor.borrow_mut().set_peer_addr(Some(String::from("127.0.0.1:5454")));
+
+        assert_eq!(Some(String::from("127.0.0.1:5454")), state.peer_addr());
+    }
+}
@@ -0,0 +1,122 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use mio::{Ready, PollOpt};
+
+use transport::async::stub::*;
+use transport::async::state::*;
+use transport::pipe::Context;
+
+/// Closed pipe that keeps writing the message it was sending when asked to close.
+/// Closing it again, when the linger period is over, disposes of it immediately.
+pub struct Linger<S> {
+    stub: S
+}
+
+impl<S : AsyncPipeStub> Linger<S> {
+    pub fn new(s: S) -> Linger<S> {
+        Linger { stub: s }
+    }
+}
+
+impl<S : AsyncPipeStub + 'static> PipeState<S> for Linger<S> {
+    fn name(&self) -> &'static str {"Linger"}
+    fn peer_addr(&self) -> Option<String> {
+        self.stub.peer_addr()
+    }
+
+    fn enter(&self, ctx: &mut Context) {
+        ctx.reregister(self.stub.deref(), Ready::writable(), PollOpt::edge());
+    }
+    fn close(self: Box<Self>, ctx: &mut Context) -> Box<PipeState<S>> {
+        ctx.deregister(self.stub.deref());
+
+        dead(ctx)
+    }
+    fn ready(mut self: Box<Self>, ctx: &mut Context, events: Ready) -> Box<PipeState<S>> {
+        if events.is_hup() {
+            return self.close(ctx);
+        }
+        if events.is_writable() {
+            // Whether the message is sent or can't be, there is nothing more to wait for
+            return match self.stub.resume_send() {
+                Ok(false) => self,
+                _ => self.close(ctx)
+            };
+        }
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::cell::RefCell;
+
+    use mio;
+
+    use transport::*;
+    use transport::tests::*;
+    use transport::async::state::*;
+    use transport::async::tests::*;
+    use transport::async::linger::*;
+
+    #[test]
+    fn on_enter_should_reregister_for_writable() {
+        let stub = TestStepStream::new();
+        let state = box Linger::new(stub);
+        let mut ctx = TestPipeContext::new();
+
+        state.enter(&mut ctx);
+
+        assert_eq!(1, ctx.get_reregistrations().len());
+
+        let (ref interest, ref poll_opt) = ctx.get_reregistrations()[0];
+
+        assert_eq!(&mio::Ready::writable(), interest);
+        assert_eq!(&mio::PollOpt::edge(), poll_opt);
+    }
+
+    #[test]
+    fn when_writable_the_pending_send_should_be_resumed() {
+        let sensor_srv = TestStepStreamSensor::new();
+        let sensor = Rc::new(RefCell::new(sensor_srv));
+        let stub = TestStepStream::with_sensor(sensor.clone());
+        let state = box Linger::new(stub);
+        let mut ctx = TestPipeContext::new();
+
+        sensor.borrow_mut().set_resume_send_result(Some(false));
+        let state = state.ready(&mut ctx, mio::Ready::writable());
+        assert_eq!("Linger", state.name());
+        assert_eq!(0, ctx.get_raised_events().len());
+
+        sensor.borrow_mut().set_resume_send_result(Some(true));
+        let state = state.ready(&mut ctx, mio::Ready::writable());
+        assert_eq!("Dead", state.name());
+        assert_eq!(1, ctx.get_deregistrations());
+        assert_eq!(1, ctx.get_raised_events().len());
+
+        let is_closed = match ctx.get_raised_events()[0] {
+            pipe::Event::Closed => true,
+            _ => false,
+        };
+
+        assert!(is_closed);
+    }
+
+    #[test]
+    fn close_should_deregister_and_cause_a_transition_to_dead() {
+        let stub = TestStepStream::new();
+        let state = box Linger::new(stub);
+        let mut ctx = TestPipeContext::new();
+        let new_state = state.close(&mut ctx);
+
+        assert_eq!(1, ctx.get_deregistrations());
+        assert_eq!(1, ctx.get_raised_events().len());
+        assert_eq!("Dead", new_state.name());
+    }
+}
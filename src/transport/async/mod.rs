@@ -7,10 +7,12 @@
 /// This module provides a pipe implementation built upon mio async streams.
 
 pub mod stub;
+pub mod signal;
 mod state;
 mod initial;
 mod handshake;
 mod active;
+mod linger;
 mod dead;
 
 #[cfg(test)]
@@ -21,6 +23,7 @@ use std::rc::Rc;
 use mio;
 
 use core::Message;
+use core::endpoint::EndpointState;
 use transport::*;
 use transport::pipe::*;
 
@@ -37,7 +40,9 @@ impl<S : AsyncPipeStub + 'static> AsyncPipe<S> {
     pub fn new(stub: S, pids: (u16, u16)) -> AsyncPipe<S> {
         let initial_state = box initial::Initial::new(stub, pids);
 
-        AsyncPipe { state: Some(initial_state) }
+        AsyncPipe {
+            state: Some(initial_state)
+        }
     }
 
     fn apply<F>(&mut self, ctx: &mut Context, transition: F) where F : FnOnce(Box<PipeState<S>>, &mut Context) -> Box<PipeState<S>> {
@@ -74,4 +79,13 @@ impl<S : AsyncPipeStub> pipe::Pipe for AsyncPipe<S> {
     fn recv(&mut self, ctx: &mut Context) {
         self.apply(ctx, |s, ctx| s.recv(ctx))
     }
+
+    fn state(&self) -> EndpointState {
+        self.state.as_ref().map_or(EndpointState::Connecting, |s| s.endpoint_state())
+    }
+
+    /// Asked to the stub each time, since a connecting stream has no peer address yet.
+    fn peer_addr(&self) -> Option<String> {
+        self.state.as_ref().and_then(|s| s.peer_addr())
+    }
 }
@@ -0,0 +1,74 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::cell::{Cell, RefCell};
+use std::io;
+
+use mio::{Registration, SetReadiness, Evented, Poll, Token, Ready, PollOpt};
+
+use io_error::*;
+
+/// User space readiness source, for pipes and acceptors that are not backed by a file descriptor,
+/// or that can have data ready to be read while their file descriptor is not readable.
+pub struct Signal {
+    registration: RefCell<Option<Registration>>,
+    readiness: RefCell<Option<SetReadiness>>,
+    ready: Cell<Ready>
+}
+
+impl Signal {
+    pub fn new() -> Signal {
+        Signal {
+            registration: RefCell::new(None),
+            readiness: RefCell::new(None),
+            ready: Cell::new(Ready::none())
+        }
+    }
+
+    #[cfg(test)]
+    pub fn get(&self) -> Ready {
+        self.ready.get()
+    }
+
+    pub fn set(&self, ready: Ready) {
+        self.ready.set(ready);
+
+        if let Some(ref readiness) = *self.readiness.borrow() {
+            let _ = readiness.set_readiness(ready);
+        }
+    }
+}
+
+impl Evented for Signal {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        if self.registration.borrow().is_some() {
+            return Err(other_io_error("signal already registered"));
+        }
+
+        let (registration, readiness) = Registration::new(poll, token, interest, opts);
+
+        let _ = readiness.set_readiness(self.ready.get());
+
+        *self.registration.borrow_mut() = Some(registration);
+        *self.readiness.borrow_mut() = Some(readiness);
+
+        Ok(())
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        match *self.registration.borrow_mut() {
+            Some(ref registration) => registration.update(poll, token, interest, opts),
+            None => Err(other_io_error("signal not registered")),
+        }
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        match *self.registration.borrow_mut() {
+            Some(ref registration) => registration.deregister(poll),
+            None => Err(other_io_error("signal not registered")),
+        }
+    }
+}
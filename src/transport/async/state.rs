@@ -10,6 +10,7 @@ use std::io::{Result, Error};
 use mio::Ready;
 
 use core::Message;
+use core::endpoint::EndpointState;
 use transport::async::stub::*;
 use transport::async::dead::*;
 use transport::pipe::{Event, Context};
@@ -17,11 +18,17 @@ use transport::pipe::{Event, Context};
 pub trait PipeState<S : AsyncPipeStub + 'static> {
 
     fn name(&self) -> &'static str;
+    fn endpoint_state(&self) -> EndpointState {
+        EndpointState::Active
+    }
+    fn peer_addr(&self) -> Option<String> {
+        None
+    }
     fn open(self: Box<Self>, _: &mut Context) -> Box<PipeState<S>> {
         box Dead
     }
-    fn close(self: Box<Self>, _: &mut Context) -> Box<PipeState<S>> {
-        box Dead
+    fn close(self: Box<Self>, ctx: &mut Context) -> Box<PipeState<S>> {
+        dead(ctx)
     }
     fn send(self: Box<Self>, _: &mut Context, _: Rc<Message>) -> Box<PipeState<S>> {
         box Dead
@@ -43,6 +50,14 @@ pub trait PipeState<S : AsyncPipeStub + 'static> {
     }
 }
 
+/// Closing a pipe ends in the dead state, entering it notifies that the pipe can be disposed of.
+pub fn dead<S : AsyncPipeStub + 'static>(ctx: &mut Context) -> Box<PipeState<S>> {
+    let state: Box<PipeState<S>> = box Dead;
+
+    state.enter(ctx);
+    state
+}
+
 pub fn transition<F, T, S>(old_state: Box<F>, ctx: &mut Context) -> Box<T> where
     F : PipeState<S>,
     F : Into<T>,
@@ -10,18 +10,28 @@ use std::io::{Result, Read, Write, ErrorKind};
 
 use byteorder::{ BigEndian, ByteOrder };
 
-use mio::Evented;
+use mio::{Evented, Ready};
 
 use core::Message;
 use io_error::*;
 
+/// Non-blocking stream that can be wrapped into an `AsyncPipe`,
+/// which takes care of the handshake and readiness state machine.
 pub trait AsyncPipeStub : Sender + Receiver + Handshake + Deref<Target=Evented> {
+    fn peer_addr(&self) -> Option<String> {
+        None
+    }
 }
 
 pub trait Sender {
     fn start_send(&mut self, msg: Rc<Message>) -> Result<bool>;
     fn resume_send(&mut self) -> Result<bool>;
     fn has_pending_send(&self) -> bool;
+
+    /// Writes the bytes owed to the peer outside of any message, such as protocol control frames.
+    fn flush_output(&mut self) -> Result<()> {
+        Ok(())
+    }
 }
 
 pub trait Receiver {
@@ -33,6 +43,12 @@ pub trait Receiver {
 pub trait Handshake {
     fn send_handshake(&mut self, pids: (u16, u16)) -> Result<()>;
     fn recv_handshake(&mut self, pids: (u16, u16)) -> Result<()>;
+
+    /// The readiness needed to make progress while receiving the handshake,
+    /// transports running their own handshake beforehand may also need to write.
+    fn handshake_interest(&self) -> Ready {
+        Ready::readable()
+    }
 }
 
 pub fn send_and_check_handshake<T:Write>(stream: &mut T, pids: (u16, u16)) -> Result<()> {
@@ -58,7 +74,7 @@ pub fn recv_and_check_handshake<T:Read>(stream: &mut T, pids: (u16, u16)) -> Res
     stream.read(&mut handshake).and_then(|_| check_handshake(pids, &handshake))
 }
 
-fn check_handshake(pids: (u16, u16), handshake: &[u8; 8]) -> Result<()> {
+pub fn check_handshake(pids: (u16, u16), handshake: &[u8; 8]) -> Result<()> {
     let (_, proto_id) = pids;
     let expected_handshake = create_handshake(proto_id);
 
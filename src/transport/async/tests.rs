@@ -21,7 +21,8 @@ pub struct TestStepStreamSensor {
     start_send_result: Option<bool>,
     resume_send_result: Option<bool>,
     start_recv_result: Option<Message>,
-    resume_recv_result: Option<Message>
+    resume_recv_result: Option<Message>,
+    peer_addr: Option<String>
 }
 
 impl TestStepStreamSensor {
@@ -32,7 +33,8 @@ impl TestStepStreamSensor {
             start_send_result: Some(true),
             resume_send_result: None,
             start_recv_result: None,
-            resume_recv_result: None
+            resume_recv_result: None,
+            peer_addr: None
         }
     }
 
@@ -83,12 +85,19 @@ impl TestStepStreamSensor {
     pub fn set_resume_recv_result(&mut self, res: Option<Message>) {
         self.resume_recv_result = res;
     }
+
+    pub fn set_peer_addr(&mut self, addr: Option<String>) {
+        self.peer_addr = addr;
+    }
 }
 
 pub struct TestStepStream {
     sensor: Rc<RefCell<TestStepStreamSensor>>,
     send_handshake_ok: bool,
+    send_handshake_pending: bool,
     recv_handshake_ok: bool,
+    recv_handshake_pending: bool,
+    handshake_interest: mio::Ready,
     pending_send: bool,
     pending_recv: bool
 }
@@ -102,7 +111,10 @@ impl TestStepStream {
         TestStepStream {
             sensor: sensor,
             send_handshake_ok: true,
+            send_handshake_pending: false,
             recv_handshake_ok: true,
+            recv_handshake_pending: false,
+            handshake_interest: mio::Ready::readable(),
             pending_send: false,
             pending_recv: false
         }
@@ -110,9 +122,21 @@ impl TestStepStream {
     pub fn set_send_handshake_ok(&mut self, send_handshake_ok: bool) {
         self.send_handshake_ok = send_handshake_ok;
     }
+    pub fn set_send_handshake_pending(&mut self, send_handshake_pending: bool) {
+        self.send_handshake_pending = send_handshake_pending;
+    }
+    pub fn set_recv_handshake_pending(&mut self, recv_handshake_pending: bool) {
+        self.recv_handshake_pending = recv_handshake_pending;
+    }
+    pub fn set_handshake_interest(&mut self, handshake_interest: mio::Ready) {
+        self.handshake_interest = handshake_interest;
+    }
 }
 
 impl stub::AsyncPipeStub for TestStepStream {
+    fn peer_addr(&self) -> Option<String> {
+        self.sensor.borrow().peer_addr.clone()
+    }
 }
 
 impl mio::Evented for TestStepStream {
@@ -137,12 +161,17 @@ impl Deref for TestStepStream {
 impl stub::Handshake for TestStepStream {
     fn send_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
         self.sensor.borrow_mut().push_sent_handshake(pids);
+        if self.send_handshake_pending { return Err(would_block_io_error("test")); }
         if self.send_handshake_ok { Ok(()) } else { Err(other_io_error("test")) }
     }
     fn recv_handshake(&mut self, _: (u16, u16)) -> io::Result<()> {
         self.sensor.borrow_mut().push_received_handshake();
+        if self.recv_handshake_pending { return Err(would_block_io_error("test")); }
         if self.recv_handshake_ok { Ok(()) } else { Err(other_io_error("test")) }
     }
+    fn handshake_interest(&self) -> mio::Ready {
+        self.handshake_interest
+    }
 }
 
 impl stub::Sender for TestStepStream {
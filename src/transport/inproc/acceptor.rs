@@ -0,0 +1,77 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use mio;
+
+use transport::*;
+use transport::acceptor::*;
+use transport::async::AsyncPipe;
+use transport::async::signal::Signal;
+use super::{Inproc, Binding};
+
+pub struct InprocAcceptor {
+    inproc: Inproc,
+    name: String,
+    binding: Rc<RefCell<Binding>>,
+    signal: Rc<Signal>,
+    proto_ids: (u16, u16),
+    recv_max_size: u64
+}
+
+impl InprocAcceptor {
+
+    pub fn new(inproc: Inproc, name: String, binding: Rc<RefCell<Binding>>, pids: (u16, u16), recv_max_size: u64) -> InprocAcceptor {
+        let signal = binding.borrow().signal.clone();
+
+        InprocAcceptor {
+            inproc: inproc,
+            name: name,
+            binding: binding,
+            signal: signal,
+            proto_ids: pids,
+            recv_max_size: recv_max_size
+        }
+    }
+
+    fn accept(&mut self, ctx: &mut Context) {
+        let pids = self.proto_ids;
+        let recv_max_size = self.recv_max_size;
+        let stubs = self.binding.borrow_mut().drain();
+        let pipes: Vec<Box<pipe::Pipe>> = stubs.
+            into_iter().
+            map(|mut stub| {
+                stub.set_recv_max_size(recv_max_size);
+                box AsyncPipe::new(stub, pids) as Box<pipe::Pipe>
+            }).
+            collect();
+
+        if pipes.is_empty() == false {
+            ctx.raise(Event::Accepted(pipes));
+        }
+    }
+}
+
+impl acceptor::Acceptor for InprocAcceptor {
+    fn ready(&mut self, ctx: &mut Context, events: mio::Ready) {
+        if events.is_readable() {
+            self.accept(ctx);
+        }
+    }
+
+    fn open(&mut self, ctx: &mut Context) {
+        ctx.register(&*self.signal, mio::Ready::readable(), mio::PollOpt::edge());
+        ctx.raise(Event::Opened);
+    }
+
+    fn close(&mut self, ctx: &mut Context) {
+        ctx.deregister(&*self.signal);
+        self.inproc.remove_binding(&self.name);
+        ctx.raise(Event::Closed);
+    }
+}
@@ -0,0 +1,106 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+mod stub;
+mod acceptor;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io;
+
+use mio::Ready;
+
+use self::stub::InprocPipeStub;
+use self::acceptor::InprocAcceptor;
+use core::config::Config;
+use transport::{Transport, recv_max_size};
+use transport::pipe::Pipe;
+use transport::acceptor::Acceptor;
+use transport::async::AsyncPipe;
+use transport::async::signal::Signal;
+use io_error::*;
+
+/// Size in bytes of the messages that can wait in a mailbox before the sender stops being writable.
+const MAILBOX_CAPACITY: usize = 128 * 1024;
+
+/// Session wide registry of the bound inproc endpoints.
+#[derive(Clone, Default)]
+pub struct Inproc {
+    bindings: Rc<RefCell<HashMap<String, Rc<RefCell<Binding>>>>>
+}
+
+/// Bound side of an inproc endpoint, keeps the connected pipes until they are accepted.
+/// Pipe ends are pairs of in-memory mailboxes, so messages are handed over without any system call.
+pub struct Binding {
+    pending: Vec<InprocPipeStub>,
+    signal: Rc<Signal>
+}
+
+impl Inproc {
+    pub fn new() -> Inproc {
+        Inproc::default()
+    }
+
+    fn find_binding(&self, name: &str) -> Option<Rc<RefCell<Binding>>> {
+        self.bindings.borrow().get(name).cloned()
+    }
+
+    fn remove_binding(&self, name: &str) {
+        self.bindings.borrow_mut().remove(name);
+    }
+}
+
+impl Binding {
+    fn new() -> Binding {
+        Binding {
+            pending: Vec::new(),
+            signal: Rc::new(Signal::new())
+        }
+    }
+
+    fn push(&mut self, stub: InprocPipeStub) {
+        self.pending.push(stub);
+        self.signal.set(Ready::readable());
+    }
+
+    fn drain(&mut self) -> Vec<InprocPipeStub> {
+        self.signal.set(Ready::none());
+        self.pending.drain(..).collect()
+    }
+}
+
+impl Transport for Inproc {
+    fn connect(&self, url: &str, pids: (u16, u16), config: &Config) -> io::Result<Box<Pipe>> {
+        let (mut local, remote) = stub::pair(MAILBOX_CAPACITY);
+
+        local.set_recv_max_size(recv_max_size(config));
+
+        let pipe = box AsyncPipe::new(local, pids);
+
+        // When nothing is bound yet, the remote end is just dropped:
+        // the handshake will fail and the pipe will be reconnected later,
+        // just like a refused tcp connection.
+        if let Some(binding) = self.find_binding(url) {
+            binding.borrow_mut().push(remote);
+        }
+
+        Ok(pipe)
+    }
+
+    fn bind(&self, url: &str, pids: (u16, u16), config: &Config) -> io::Result<Box<Acceptor>> {
+        if self.bindings.borrow().contains_key(url) {
+            return Err(addr_in_use_io_error(url.to_owned()));
+        }
+
+        let binding = Rc::new(RefCell::new(Binding::new()));
+        let acceptor = box InprocAcceptor::new(self.clone(), url.to_owned(), binding.clone(), pids, recv_max_size(config));
+
+        self.bindings.borrow_mut().insert(url.to_owned(), binding);
+
+        Ok(acceptor)
+    }
+}
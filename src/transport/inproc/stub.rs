@@ -0,0 +1,460 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::ops::Deref;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io;
+
+use mio;
+use mio::Ready;
+
+use core::Message;
+use transport::async::stub::*;
+use transport::async::signal::Signal;
+use io_error::*;
+
+/*****************************************************************************/
+/*                                                                           */
+/* Channel                                                                   */
+/*                                                                           */
+/*****************************************************************************/
+
+/// State shared by the two ends of an inproc pipe,
+/// each side owns the mailbox where its peer drops the messages.
+struct Channel {
+    capacity: usize,
+    sides: [Side; 2]
+}
+
+struct Side {
+    handshake: Option<u16>,
+    inbox: VecDeque<Rc<Message>>,
+    inbox_size: usize,
+    closed: bool,
+    signal: Rc<Signal>
+}
+
+impl Side {
+    fn new(signal: Rc<Signal>) -> Side {
+        Side {
+            handshake: None,
+            inbox: VecDeque::new(),
+            inbox_size: 0,
+            closed: false,
+            signal: signal
+        }
+    }
+}
+
+impl Side {
+    fn push(&mut self, msg: Rc<Message>) {
+        self.inbox_size += msg.len();
+        self.inbox.push_back(msg);
+    }
+
+    fn pop(&mut self) -> Option<Rc<Message>> {
+        self.inbox.pop_front().map(|msg| {
+            self.inbox_size -= msg.len();
+            msg
+        })
+    }
+
+    fn clear(&mut self) {
+        self.inbox.clear();
+        self.inbox_size = 0;
+    }
+}
+
+impl Channel {
+    /// A mailbox accepts messages as long as its size is below the capacity,
+    /// so a single message can never block a pipe, whatever its size.
+    fn can_send_to(&self, side: usize) -> bool {
+        self.sides[side].inbox_size < self.capacity
+    }
+
+    fn refresh(&self, side: usize) {
+        let peer = 1 - side;
+        let mut ready = Ready::none();
+
+        if self.sides[side].handshake.is_some() || self.sides[side].inbox.is_empty() == false {
+            ready.insert(Ready::readable());
+        }
+        if self.can_send_to(peer) {
+            ready.insert(Ready::writable());
+        }
+        if self.sides[peer].closed {
+            ready.insert(Ready::hup());
+        }
+
+        self.sides[side].signal.set(ready);
+    }
+}
+
+/// Creates the two ends of a pipe, `capacity` is the size in bytes of each mailbox.
+pub fn pair(capacity: usize) -> (InprocPipeStub, InprocPipeStub) {
+    let local = Rc::new(Signal::new());
+    let remote = Rc::new(Signal::new());
+    let channel = Channel {
+        capacity: capacity,
+        sides: [Side::new(local.clone()), Side::new(remote.clone())]
+    };
+    let channel = Rc::new(RefCell::new(channel));
+
+    channel.borrow().refresh(0);
+    channel.borrow().refresh(1);
+
+    (InprocPipeStub::new(channel.clone(), 0, local), InprocPipeStub::new(channel, 1, remote))
+}
+
+/// The message is moved to the receiver when it is not shared with other pipes,
+/// as with Pair, Push, Pull and replies, otherwise it is copied.
+/// The header is handed over apart from the body, instead of at the start of the body
+/// as for a message read from a stream based transport, the protocols decode both layouts.
+fn into_received_message(msg: Rc<Message>) -> Message {
+    match Rc::try_unwrap(msg) {
+        Ok(msg) => msg,
+        Err(msg) => Message::from_header_and_body(msg.get_header().to_vec(), msg.get_body().to_vec())
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* InprocPipeStub                                                            */
+/*                                                                           */
+/*****************************************************************************/
+
+pub struct InprocPipeStub {
+    channel: Rc<RefCell<Channel>>,
+    side: usize,
+    signal: Rc<Signal>,
+    recv_max_size: u64,
+    pending_send: Option<Rc<Message>>,
+    pending_recv: bool
+}
+
+impl Deref for InprocPipeStub {
+    type Target = mio::Evented;
+    fn deref(&self) -> &Self::Target {
+        &*self.signal
+    }
+}
+
+impl InprocPipeStub {
+    fn new(channel: Rc<RefCell<Channel>>, side: usize, signal: Rc<Signal>) -> InprocPipeStub {
+        InprocPipeStub {
+            channel: channel,
+            side: side,
+            signal: signal,
+            recv_max_size: u64::max_value(),
+            pending_send: None,
+            pending_recv: false
+        }
+    }
+
+    /// The receiving end is known only when the pipe is accepted,
+    /// so the limit is set after the pair has been created.
+    pub fn set_recv_max_size(&mut self, recv_max_size: u64) {
+        self.recv_max_size = recv_max_size;
+    }
+
+    fn peer(&self) -> usize {
+        1 - self.side
+    }
+
+    fn send(&mut self, msg: Rc<Message>) -> io::Result<bool> {
+        let (side, peer) = (self.side, self.peer());
+        let mut channel = self.channel.borrow_mut();
+
+        if channel.sides[peer].closed {
+            return Err(other_io_error("peer closed"));
+        }
+
+        if channel.can_send_to(peer) == false {
+            self.pending_send = Some(msg);
+            return Ok(false);
+        }
+
+        channel.sides[peer].push(msg);
+        channel.refresh(peer);
+        channel.refresh(side);
+
+        Ok(true)
+    }
+
+    fn recv(&mut self) -> io::Result<Option<Message>> {
+        let (side, peer) = (self.side, self.peer());
+        let mut channel = self.channel.borrow_mut();
+
+        match channel.sides[side].pop() {
+            Some(msg) => {
+                channel.refresh(side);
+                channel.refresh(peer);
+                self.pending_recv = false;
+
+                if msg.len() as u64 > self.recv_max_size {
+                    return Err(msg_too_long_io_error());
+                }
+
+                Ok(Some(into_received_message(msg)))
+            },
+            None => {
+                self.pending_recv = true;
+
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Drop for InprocPipeStub {
+    fn drop(&mut self) {
+        let (side, peer) = (self.side, self.peer());
+        let mut channel = self.channel.borrow_mut();
+
+        channel.sides[side].closed = true;
+        channel.sides[side].clear();
+        channel.refresh(peer);
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Sender for InprocPipeStub                                                 */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Sender for InprocPipeStub {
+    fn start_send(&mut self, msg: Rc<Message>) -> io::Result<bool> {
+        self.send(msg)
+    }
+
+    fn resume_send(&mut self) -> io::Result<bool> {
+        if let Some(msg) = self.pending_send.take() {
+            self.send(msg)
+        } else {
+            Err(other_io_error("Cannot resume send: no pending operation"))
+        }
+    }
+
+    fn has_pending_send(&self) -> bool {
+        self.pending_send.is_some()
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Receiver for InprocPipeStub                                               */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Receiver for InprocPipeStub {
+    fn start_recv(&mut self) -> io::Result<Option<Message>> {
+        self.recv()
+    }
+
+    fn resume_recv(&mut self) -> io::Result<Option<Message>> {
+        if self.pending_recv {
+            self.recv()
+        } else {
+            Err(other_io_error("Cannot resume recv: no pending operation"))
+        }
+    }
+
+    fn has_pending_recv(&self) -> bool {
+        self.pending_recv
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Handshake for InprocPipeStub                                              */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Handshake for InprocPipeStub {
+    fn send_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
+        let (proto_id, _) = pids;
+        let peer = self.peer();
+        let mut channel = self.channel.borrow_mut();
+
+        if channel.sides[peer].closed {
+            return Err(other_io_error("peer closed"));
+        }
+
+        channel.sides[peer].handshake = Some(proto_id);
+        channel.refresh(peer);
+
+        Ok(())
+    }
+    fn recv_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
+        let (_, proto_id) = pids;
+        let side = self.side;
+        let mut channel = self.channel.borrow_mut();
+        let handshake = channel.sides[side].handshake.take();
+
+        channel.refresh(side);
+
+        match handshake {
+            Some(x) if x == proto_id => Ok(()),
+            Some(_) => Err(invalid_data_io_error("received bad handshake")),
+            None => Err(would_block_io_error("handshake not received yet"))
+        }
+    }
+}
+
+impl AsyncPipeStub for InprocPipeStub {
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use mio::Ready;
+
+    use core::Message;
+    use transport::async::stub::*;
+    use super::pair;
+
+    fn readiness(stub: &super::InprocPipeStub) -> Ready {
+        stub.signal.get()
+    }
+
+    #[test]
+    fn handshake_is_checked_against_peer_protocol() {
+        let (mut local, mut remote) = pair(4);
+
+        local.send_handshake((16, 17)).unwrap();
+        remote.send_handshake((17, 16)).unwrap();
+
+        assert!(readiness(&local).is_readable());
+        assert!(readiness(&remote).is_readable());
+
+        local.recv_handshake((16, 17)).unwrap();
+        assert!(remote.recv_handshake((17, 18)).is_err());
+    }
+
+    #[test]
+    fn message_without_header_is_handed_over() {
+        let (mut local, mut remote) = pair(4);
+        let msg = Message::from_body(vec![65, 66, 67]);
+
+        assert_eq!(true, local.start_send(Rc::new(msg)).unwrap());
+        assert!(readiness(&remote).is_readable());
+
+        let received = remote.start_recv().unwrap().unwrap();
+
+        assert_eq!(vec![65, 66, 67], received.body);
+        assert!(received.header.is_empty());
+        assert_eq!(false, readiness(&remote).is_readable());
+    }
+
+    #[test]
+    fn message_body_is_moved_when_not_shared() {
+        let (mut local, mut remote) = pair(4);
+        let body = vec![65, 66, 67];
+        let buffer = body.as_ptr();
+
+        local.start_send(Rc::new(Message::from_body(body))).unwrap();
+
+        let received = remote.start_recv().unwrap().unwrap();
+
+        assert_eq!(buffer, received.body.as_ptr());
+    }
+
+    #[test]
+    fn message_body_is_copied_when_shared() {
+        let (mut local, mut remote) = pair(4);
+        let msg = Rc::new(Message::from_body(vec![65, 66, 67]));
+
+        local.start_send(msg.clone()).unwrap();
+
+        let received = remote.start_recv().unwrap().unwrap();
+
+        assert_eq!(vec![65, 66, 67], received.body);
+        assert!(msg.get_body().as_ptr() != received.body.as_ptr());
+    }
+
+    #[test]
+    fn message_header_is_handed_over_apart_from_body() {
+        let (mut local, mut remote) = pair(4);
+        let (header, body) = (vec![1, 4, 3, 2], vec![65, 66, 67]);
+        let buffers = (header.as_ptr(), body.as_ptr());
+
+        local.start_send(Rc::new(Message::from_header_and_body(header, body))).unwrap();
+
+        let received = remote.start_recv().unwrap().unwrap();
+
+        assert_eq!(vec![1, 4, 3, 2], received.header);
+        assert_eq!(vec![65, 66, 67], received.body);
+        assert_eq!(buffers, (received.header.as_ptr(), received.body.as_ptr()));
+    }
+
+    #[test]
+    fn message_header_is_copied_apart_from_body_when_shared() {
+        let (mut local, mut remote) = pair(4);
+        let msg = Rc::new(Message::from_header_and_body(vec![1, 4, 3, 2], vec![65, 66, 67]));
+
+        local.start_send(msg.clone()).unwrap();
+
+        let received = remote.start_recv().unwrap().unwrap();
+
+        assert_eq!(vec![1, 4, 3, 2], received.header);
+        assert_eq!(vec![65, 66, 67], received.body);
+    }
+
+    #[test]
+    fn recv_is_pending_until_peer_sends() {
+        let (mut local, mut remote) = pair(4);
+
+        assert!(remote.start_recv().unwrap().is_none());
+        assert!(remote.has_pending_recv());
+
+        local.start_send(Rc::new(Message::from_body(vec![1]))).unwrap();
+
+        assert_eq!(vec![1], remote.resume_recv().unwrap().unwrap().body);
+        assert_eq!(false, remote.has_pending_recv());
+    }
+
+    #[test]
+    fn send_is_pending_when_peer_mailbox_is_full() {
+        let (mut local, mut remote) = pair(2);
+
+        assert_eq!(true, local.start_send(Rc::new(Message::from_body(vec![1, 1]))).unwrap());
+        assert_eq!(false, readiness(&local).is_writable());
+        assert_eq!(false, local.start_send(Rc::new(Message::from_body(vec![2]))).unwrap());
+        assert!(local.has_pending_send());
+
+        remote.start_recv().unwrap().unwrap();
+
+        assert!(readiness(&local).is_writable());
+        assert_eq!(true, local.resume_send().unwrap());
+        assert_eq!(vec![2], remote.start_recv().unwrap().unwrap().body);
+    }
+
+    #[test]
+    fn dropping_one_end_hangs_up_the_other() {
+        let (mut local, remote) = pair(4);
+
+        drop(remote);
+
+        assert!(readiness(&local).is_hup());
+        assert!(local.start_send(Rc::new(Message::new())).is_err());
+    }
+
+    #[test]
+    fn recv_fails_when_message_exceeds_max_size() {
+        let (mut local, mut remote) = pair(16);
+
+        remote.set_recv_max_size(2);
+        local.start_send(Rc::new(Message::from_body(vec![1, 2]))).unwrap();
+        local.start_send(Rc::new(Message::from_body(vec![1, 2, 3]))).unwrap();
+
+        assert_eq!(vec![1, 2], remote.start_recv().unwrap().unwrap().body);
+        assert!(remote.start_recv().is_err());
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::fs;
+use std::path::PathBuf;
+
+use mio;
+use mio_uds::{UnixListener, UnixStream};
+
+use transport::*;
+use transport::acceptor::*;
+use transport::async::AsyncPipe;
+use transport::tcp::stub::StreamPipeStub;
+use super::MSG_TYPE_NORMAL;
+
+/// The socket file is removed once the listener is dropped,
+/// so that the same path can be bound again.
+pub struct IpcAcceptor {
+    listener: UnixListener,
+    path: PathBuf,
+    proto_ids: (u16, u16),
+    recv_max_size: u64
+}
+
+impl IpcAcceptor {
+
+    pub fn new(l: UnixListener, path: PathBuf, pids: (u16, u16), recv_max_size: u64) -> IpcAcceptor {
+        IpcAcceptor {
+            listener: l,
+            path: path,
+            proto_ids: pids,
+            recv_max_size: recv_max_size
+        }
+    }
+
+    fn accept(&mut self, ctx: &mut Context) {
+        let mut pipes = Vec::new();
+
+        loop {
+            match self.listener.accept() {
+                Ok(Some((stream, _))) => {
+                    let pipe = self.create_pipe(stream);
+
+                    pipes.push(pipe);
+                },
+                Ok(None) => break,
+                Err(e) => {
+                    ctx.raise(Event::Error(e));
+                    break;
+                }
+            }
+        }
+
+        if pipes.is_empty() == false {
+            ctx.raise(Event::Accepted(pipes));
+        }
+    }
+
+    fn create_pipe(&self, stream: UnixStream) -> Box<pipe::Pipe> {
+        let pids = self.proto_ids;
+        let stub = StreamPipeStub::with_msg_type(stream, Some(MSG_TYPE_NORMAL), self.recv_max_size);
+
+        box AsyncPipe::new(stub, pids)
+    }
+}
+
+impl acceptor::Acceptor for IpcAcceptor {
+    fn ready(&mut self, ctx: &mut Context, events: mio::Ready) {
+        if events.is_readable() {
+            self.accept(ctx);
+        }
+    }
+
+    fn open(&mut self, ctx: &mut Context) {
+        ctx.register(&self.listener, mio::Ready::readable(), mio::PollOpt::edge());
+        ctx.raise(Event::Opened);
+    }
+
+    fn close(&mut self, ctx: &mut Context) {
+        ctx.deregister(&self.listener);
+        ctx.raise(Event::Closed);
+    }
+}
+
+impl Drop for IpcAcceptor {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
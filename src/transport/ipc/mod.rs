@@ -0,0 +1,76 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+mod acceptor;
+
+use std::path;
+use std::fs;
+use std::io;
+use std::net::Shutdown;
+use std::os::unix::net;
+
+use mio_uds::{UnixListener, UnixStream};
+
+use self::acceptor::IpcAcceptor;
+use core::config::Config;
+use transport::{Transport, recv_max_size};
+use transport::pipe::Pipe;
+use transport::acceptor::Acceptor;
+use transport::async::AsyncPipe;
+use transport::tcp::stub::{Stream, StreamPipeStub};
+
+/// Message type byte sent before the length of each message, as defined by nanomsg's IPC framing.
+const MSG_TYPE_NORMAL: u8 = 1;
+
+pub struct Ipc;
+
+impl Ipc {
+    fn connect(&self, addr: &path::Path, pids: (u16, u16), config: &Config) -> io::Result<Box<Pipe>> {
+        let stream = try!(UnixStream::connect(addr));
+        let stub = StreamPipeStub::with_msg_type(stream, Some(MSG_TYPE_NORMAL), recv_max_size(config));
+        let pipe = box AsyncPipe::new(stub, pids);
+
+        Ok(pipe)
+    }
+    fn bind(&self, addr: &path::Path, pids: (u16, u16), config: &Config) -> io::Result<Box<Acceptor>> {
+        remove_unused_socket_file(addr);
+
+        let listener = try!(UnixListener::bind(addr));
+        let acceptor = box IpcAcceptor::new(listener, addr.to_path_buf(), pids, recv_max_size(config));
+
+        Ok(acceptor)
+    }
+}
+
+/// Deletes the file left over by a previous run of the application, if any.
+/// When a process is still listening on it, the file is left untouched so that bind fails.
+fn remove_unused_socket_file(addr: &path::Path) {
+    if addr.exists() == false {
+        return;
+    }
+
+    if let Err(e) = net::UnixStream::connect(addr) {
+        if e.kind() == io::ErrorKind::ConnectionRefused {
+            let _ = fs::remove_file(addr);
+        }
+    }
+}
+
+impl Stream for UnixStream {
+    fn close(&self) -> io::Result<()> {
+        self.shutdown(Shutdown::Both)
+    }
+}
+
+impl Transport for Ipc {
+    fn connect(&self, url: &str, pids: (u16, u16), config: &Config) -> io::Result<Box<Pipe>> {
+        self.connect(path::Path::new(url), pids, config)
+    }
+
+    fn bind(&self, url: &str, pids: (u16, u16), config: &Config) -> io::Result<Box<Acceptor>> {
+        self.bind(path::Path::new(url), pids, config)
+    }
+}
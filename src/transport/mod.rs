@@ -4,19 +4,61 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-pub mod async;
-pub mod tcp;
+//! Transports move the messages between the sockets.
+//! Custom transports can be registered with
+//! [SessionBuilder::with_transport](../struct.SessionBuilder.html#method.with_transport).
+//! They create the `Pipe` and `Acceptor` endpoints, a non-blocking stream implementing
+//! `AsyncPipeStub` can be wrapped in an `AsyncPipe` to get the handshake handled.
+
+mod async;
+mod tcp;
+#[cfg(not(windows))]
+mod ipc;
+mod inproc;
+mod ws;
+mod tls;
 pub mod endpoint;
 pub mod pipe;
 pub mod acceptor;
 
+pub use self::async::AsyncPipe;
+pub use self::async::stub::{AsyncPipeStub, Sender, Receiver, Handshake};
+
+use std::collections::HashMap;
 use std::io::Result;
 
-pub const DEFAULT_RECV_MAX_SIZE: u64 = 1024 * 1024;
+use core::config::Config;
+
+/// Returns the maximum size of the messages that the pipes of a socket can receive,
+/// the `RecvMaxSize` option value being zero when the size is unlimited.
+pub fn recv_max_size(config: &Config) -> u64 {
+    match config.recv_max_size {
+        0 => u64::max_value(),
+        size => size
+    }
+}
+
+/// Returns the transports available in every session, indexed by url scheme.
+#[doc(hidden)]
+pub fn builtins() -> HashMap<String, Box<Transport>> {
+    let mut transports: HashMap<String, Box<Transport>> = HashMap::new();
+
+    transports.insert(From::from("tcp"), box tcp::Tcp);
+    #[cfg(not(windows))]
+    transports.insert(From::from("ipc"), box ipc::Ipc);
+    transports.insert(From::from("inproc"), box inproc::Inproc::new());
+    transports.insert(From::from("ws"), box ws::Ws);
+    transports.insert(From::from("tls+tcp"), box tls::Tls);
 
+    transports
+}
+
+/// Creates the pipes and acceptors for the urls of a given scheme.
+/// The address passed to `connect` and `bind` is the part of the url following `scheme://`,
+/// `pids` holds the protocol ids of the local and remote sockets.
 pub trait Transport {
-    fn connect(&self, url: &str, pids: (u16, u16)) -> Result<Box<pipe::Pipe>>;
-    fn bind(&self, url: &str, pids: (u16, u16)) -> Result<Box<acceptor::Acceptor>>;
+    fn connect(&self, url: &str, pids: (u16, u16), config: &Config) -> Result<Box<pipe::Pipe>>;
+    fn bind(&self, url: &str, pids: (u16, u16), config: &Config) -> Result<Box<acceptor::Acceptor>>;
 }
 
 #[cfg(test)]
@@ -25,6 +67,7 @@ mod tests {
 
     use mio;
 
+    use core::config::Config;
     use transport::*;
 
     pub struct TestPipeContext {
@@ -72,4 +115,14 @@ mod tests {
             self.raised_events.push(evt);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn recv_max_size_zero_means_unlimited() {
+        let mut config = Config::default();
+
+        assert_eq!(1024 * 1024, recv_max_size(&config));
+
+        config.recv_max_size = 0;
+        assert_eq!(u64::max_value(), recv_max_size(&config));
+    }
+}
@@ -11,6 +11,7 @@ use std::fmt;
 use mio::Ready;
 
 use core::Message;
+use core::endpoint::EndpointState;
 use transport::endpoint::*;
 
 pub enum Command {
@@ -30,12 +31,25 @@ pub enum Event {
     Error(io::Error)
 }
 
+/// Connection to a remote socket, driven by the I/O thread.
+/// Operations report their outcome asynchronously by raising events on the context.
 pub trait Pipe {
     fn ready(&mut self, ctx: &mut Context, events: Ready);
     fn open(&mut self, ctx: &mut Context);
     fn close(&mut self, ctx: &mut Context);
     fn send(&mut self, ctx: &mut Context, msg: Rc<Message>);
     fn recv(&mut self, ctx: &mut Context);
+
+    /// Reports whether the connection or the handshake is still in progress.
+    /// Only asked about pipes that have not been opened yet.
+    fn state(&self) -> EndpointState {
+        EndpointState::Connecting
+    }
+
+    /// Address of the remote peer, when the transport provides one.
+    fn peer_addr(&self) -> Option<String> {
+        None
+    }
 }
 
 pub trait Context : EndpointRegistrar + fmt::Debug {
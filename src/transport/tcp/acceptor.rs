@@ -12,19 +12,24 @@ use mio::tcp::{TcpListener, TcpStream};
 use transport::*;
 use transport::acceptor::*;
 use transport::async::AsyncPipe;
-use super::stub::TcpPipeStub;
+use super::stub::StreamPipeStub;
+use super::options::TcpOptions;
 
 pub struct TcpAcceptor {
     listener: TcpListener,
-    proto_ids: (u16, u16)
+    proto_ids: (u16, u16),
+    recv_max_size: u64,
+    options: TcpOptions
 }
 
 impl TcpAcceptor {
 
-    pub fn new(l: TcpListener, pids: (u16, u16)) -> TcpAcceptor {
+    pub fn new(l: TcpListener, pids: (u16, u16), recv_max_size: u64, options: TcpOptions) -> TcpAcceptor {
         TcpAcceptor {
             listener: l,
-            proto_ids: pids
+            proto_ids: pids,
+            recv_max_size: recv_max_size,
+            options: options
         }
     }
 
@@ -34,9 +39,10 @@ impl TcpAcceptor {
         loop {
             match self.listener.accept() {
                 Ok((stream, _)) => {
-                    let pipe = self.create_pipe(stream);
-
-                    pipes.push(pipe);
+                    match self.create_pipe(stream) {
+                        Ok(pipe) => pipes.push(pipe),
+                        Err(e) => ctx.raise(Event::Error(e))
+                    }
                 },
                 Err(e) => {
                     if e.kind() == io::ErrorKind::WouldBlock {
@@ -53,11 +59,14 @@ impl TcpAcceptor {
         }
     }
 
-    fn create_pipe(&self, stream: TcpStream) -> Box<pipe::Pipe> {
+    fn create_pipe(&self, stream: TcpStream) -> io::Result<Box<pipe::Pipe>> {
         let pids = self.proto_ids;
-        let stub = TcpPipeStub::new(stream);
 
-        box AsyncPipe::new(stub, pids)
+        try!(self.options.apply(&stream));
+
+        let stub = StreamPipeStub::new(stream, self.recv_max_size);
+
+        Ok(box AsyncPipe::new(stub, pids))
     }
 }
 
@@ -4,10 +4,11 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-mod stub;
-mod send;
-mod recv;
+pub mod stub;
+pub mod send;
+pub mod recv;
 mod acceptor;
+pub mod options;
 
 use std::str::FromStr;
 use std::io;
@@ -15,9 +16,11 @@ use std::net;
 
 use mio::tcp::{TcpListener, TcpStream};
 
-use self::stub::TcpPipeStub;
+use self::stub::StreamPipeStub;
 use self::acceptor::TcpAcceptor;
-use transport::Transport;
+use self::options::TcpOptions;
+use core::config::Config;
+use transport::{Transport, recv_max_size};
 use transport::pipe::Pipe;
 use transport::acceptor::Acceptor;
 use transport::async::AsyncPipe;
@@ -26,32 +29,35 @@ use io_error::*;
 pub struct Tcp;
 
 impl Tcp {
-    fn connect(&self, addr: &net::SocketAddr, pids: (u16, u16)) -> io::Result<Box<Pipe>> {
+    fn connect(&self, addr: &net::SocketAddr, pids: (u16, u16), config: &Config) -> io::Result<Box<Pipe>> {
         let stream = try!(TcpStream::connect(addr));
-        let stub = TcpPipeStub::new(stream);
+
+        try!(TcpOptions::from(config).apply(&stream));
+
+        let stub = StreamPipeStub::new(stream, recv_max_size(config));
         let pipe = box AsyncPipe::new(stub, pids);
 
         Ok(pipe)
     }
-    fn bind(&self, addr: &net::SocketAddr, pids: (u16, u16)) -> io::Result<Box<Acceptor>> {
+    fn bind(&self, addr: &net::SocketAddr, pids: (u16, u16), config: &Config) -> io::Result<Box<Acceptor>> {
         let listener = try!(TcpListener::bind(addr));
-        let acceptor = box TcpAcceptor::new(listener, pids);
+        let acceptor = box TcpAcceptor::new(listener, pids, recv_max_size(config), TcpOptions::from(config));
 
         Ok(acceptor)
     }
 }
 
 impl Transport for Tcp {
-    fn connect(&self, url: &str, pids: (u16, u16)) -> io::Result<Box<Pipe>> {
+    fn connect(&self, url: &str, pids: (u16, u16), config: &Config) -> io::Result<Box<Pipe>> {
         match net::SocketAddr::from_str(url) {
-            Ok(addr) => self.connect(&addr, pids),
+            Ok(addr) => self.connect(&addr, pids, config),
             Err(_) => Err(invalid_input_io_error(url))
         }
     }
 
-    fn bind(&self, url: &str, pids: (u16, u16)) -> io::Result<Box<Acceptor>> {
+    fn bind(&self, url: &str, pids: (u16, u16), config: &Config) -> io::Result<Box<Acceptor>> {
         match net::SocketAddr::from_str(url) {
-            Ok(addr) => self.bind(&addr, pids),
+            Ok(addr) => self.bind(&addr, pids, config),
             Err(_) => Err(invalid_input_io_error(url))
         }
     }
@@ -0,0 +1,148 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::io;
+use std::time::Duration;
+
+use mio::tcp::TcpStream;
+
+use core::config::Config;
+
+/// Socket level settings applied to each stream, connected or accepted,
+/// before the handshake is started. Shared by the transports running over tcp.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TcpOptions {
+    nodelay: bool,
+    keepalive: Option<Duration>,
+    keepalive_ivl: Option<Duration>,
+    keepalive_cnt: Option<u32>,
+    send_buffer_size: Option<usize>,
+    recv_buffer_size: Option<usize>
+}
+
+impl<'a> From<&'a Config> for TcpOptions {
+    fn from(config: &'a Config) -> TcpOptions {
+        TcpOptions {
+            nodelay: config.tcp_nodelay,
+            keepalive: config.tcp_keepalive,
+            keepalive_ivl: config.tcp_keepalive_ivl,
+            keepalive_cnt: config.tcp_keepalive_cnt,
+            send_buffer_size: config.tcp_send_buffer_size,
+            recv_buffer_size: config.tcp_recv_buffer_size
+        }
+    }
+}
+
+impl TcpOptions {
+    pub fn apply(&self, stream: &TcpStream) -> io::Result<()> {
+        try!(stream.set_nodelay(self.nodelay));
+
+        if let Some(size) = self.send_buffer_size {
+            try!(stream.set_send_buffer_size(size));
+        }
+        if let Some(size) = self.recv_buffer_size {
+            try!(stream.set_recv_buffer_size(size));
+        }
+
+        try!(stream.set_keepalive(self.keepalive));
+
+        if self.keepalive.is_some() {
+            try!(set_keepalive_probes(stream, self.keepalive_ivl, self.keepalive_cnt));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+fn set_keepalive_probes(stream: &TcpStream, ivl: Option<Duration>, cnt: Option<u32>) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let fd = stream.as_raw_fd();
+
+    if let Some(ivl) = ivl {
+        let secs = ::std::cmp::max(1, ivl.as_secs()) as ::libc::c_int;
+
+        try!(setsockopt(fd, ::libc::TCP_KEEPINTVL, secs));
+    }
+    if let Some(cnt) = cnt {
+        try!(setsockopt(fd, ::libc::TCP_KEEPCNT, cnt as ::libc::c_int));
+    }
+
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "freebsd")))]
+fn set_keepalive_probes(_: &TcpStream, _: Option<Duration>, _: Option<u32>) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+fn setsockopt(fd: ::libc::c_int, opt: ::libc::c_int, value: ::libc::c_int) -> io::Result<()> {
+    use std::mem;
+
+    let ptr = &value as *const ::libc::c_int as *const ::libc::c_void;
+    let len = mem::size_of::<::libc::c_int>() as ::libc::socklen_t;
+    let res = unsafe { ::libc::setsockopt(fd, ::libc::IPPROTO_TCP, opt, ptr, len) };
+
+    if res == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net;
+    use std::time::Duration;
+
+    use mio::tcp::TcpStream;
+
+    use core::config::{Config, ConfigOption};
+    use super::TcpOptions;
+
+    fn connected_stream() -> (net::TcpListener, TcpStream) {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let stream = TcpStream::connect(&addr).unwrap();
+
+        (listener, stream)
+    }
+
+    #[test]
+    fn default_options_disable_nodelay_and_keepalive() {
+        let options = TcpOptions::from(&Config::default());
+        let (_listener, stream) = connected_stream();
+
+        options.apply(&stream).unwrap();
+
+        assert_eq!(false, stream.nodelay().unwrap());
+        assert_eq!(None, stream.keepalive().unwrap());
+    }
+
+    #[test]
+    fn options_are_carried_from_config_to_stream() {
+        let mut config = Config::default();
+
+        config.set(ConfigOption::TcpNoDelay(true)).unwrap();
+        config.set(ConfigOption::TcpKeepAlive(Some(Duration::from_secs(30)))).unwrap();
+        config.set(ConfigOption::TcpKeepAliveInterval(Some(Duration::from_secs(5)))).unwrap();
+        config.set(ConfigOption::TcpKeepAliveCount(Some(3))).unwrap();
+        config.set(ConfigOption::TcpSendBufferSize(Some(64 * 1024))).unwrap();
+        config.set(ConfigOption::TcpRecvBufferSize(Some(64 * 1024))).unwrap();
+
+        let options = TcpOptions::from(&config);
+        let (_listener, stream) = connected_stream();
+
+        options.apply(&stream).unwrap();
+
+        assert_eq!(true, stream.nodelay().unwrap());
+        assert_eq!(Some(Duration::from_secs(30)), stream.keepalive().unwrap());
+        assert!(stream.send_buffer_size().unwrap() >= 64 * 1024);
+        assert!(stream.recv_buffer_size().unwrap() >= 64 * 1024);
+    }
+}
@@ -18,8 +18,13 @@ pub struct RecvOperation {
 
 impl RecvOperation {
     pub fn new(recv_max_size: u64) -> RecvOperation {
+        RecvOperation::with_msg_type(recv_max_size, None)
+    }
+
+    /// The message type, when specified, is expected before the message length.
+    pub fn with_msg_type(recv_max_size: u64, msg_type: Option<u8>) -> RecvOperation {
         RecvOperation {
-            step: Some(RecvOperationStep::Header([0; 8], 0, recv_max_size))
+            step: Some(RecvOperationStep::Header([0; 9], 0, recv_max_size, msg_type))
         }
     }
 
@@ -51,7 +56,7 @@ impl RecvOperation {
 }
 
 enum RecvOperationStep {
-    Header([u8; 8], usize, u64),
+    Header([u8; 9], usize, u64, Option<u8>),
     Payload(Vec<u8>, usize),
     Terminal(Message)
 }
@@ -59,27 +64,31 @@ enum RecvOperationStep {
 impl RecvOperationStep {
     fn advance<T:io::Read>(self, stream: &mut T) -> io::Result<(bool, RecvOperationStep)> {
         match self {
-            RecvOperationStep::Header(buffer, read, max_size) => read_header(stream, buffer, read, max_size),
+            RecvOperationStep::Header(buffer, read, max_size, msg_type) => read_header(stream, buffer, read, max_size, msg_type),
             RecvOperationStep::Payload(buffer, read) => read_payload(stream, buffer, read),
             RecvOperationStep::Terminal(_) => Err(other_io_error("Cannot advance terminal step of recv operation"))
         }
     }
 }
 
-fn read_header<T:io::Read>(stream: &mut T, mut buffer: [u8; 8], mut read: usize, max_size: u64) -> io::Result<(bool, RecvOperationStep)> {
-    read += try!(stream.read_buffer(&mut buffer[read..]));
+fn read_header<T:io::Read>(stream: &mut T, mut buffer: [u8; 9], mut read: usize, max_size: u64, msg_type: Option<u8>) -> io::Result<(bool, RecvOperationStep)> {
+    let len = if msg_type.is_some() { 9 } else { 8 };
 
-    if read == 8 {
-        let msg_len = BigEndian::read_u64(&buffer);
-        if msg_len > max_size {
-            Err(invalid_data_io_error("message is too long"))
+    read += try!(stream.read_buffer(&mut buffer[read..len]));
+
+    if read == len {
+        let msg_len = BigEndian::read_u64(&buffer[len - 8..len]);
+        if msg_type.map_or(false, |msg_type| msg_type != buffer[0]) {
+            Err(invalid_data_io_error("unsupported message type"))
+        } else if msg_len > max_size {
+            Err(msg_too_long_io_error())
         } else {
             let payload = vec![0u8; msg_len as usize];
 
             Ok((true, RecvOperationStep::Payload(payload, 0)))
         }
     } else {
-        Ok((false, RecvOperationStep::Header(buffer, read, max_size)))
+        Ok((false, RecvOperationStep::Header(buffer, read, max_size, msg_type)))
     }
 }
 
@@ -92,3 +101,49 @@ fn read_payload<T:io::Read>(stream: &mut T, mut buffer: Vec<u8>, mut read: usize
         Ok((false, RecvOperationStep::Payload(buffer, read)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn recv_in_one_run() {
+        let bytes = vec![0u8, 0, 0, 0, 0, 0, 0, 3, 65, 66, 67];
+        let mut stream = Cursor::new(bytes);
+        let mut operation = RecvOperation::new(1024);
+        let msg = operation.run(&mut stream).expect("recv should have succeeded").expect("recv should have completed");
+
+        assert_eq!(&[65, 66, 67], msg.get_body());
+    }
+
+    #[test]
+    fn recv_with_msg_type_in_one_run() {
+        let bytes = vec![1u8, 0, 0, 0, 0, 0, 0, 0, 3, 65, 66, 67];
+        let mut stream = Cursor::new(bytes);
+        let mut operation = RecvOperation::with_msg_type(1024, Some(1));
+        let msg = operation.run(&mut stream).expect("recv should have succeeded").expect("recv should have completed");
+
+        assert_eq!(&[65, 66, 67], msg.get_body());
+    }
+
+    #[test]
+    fn recv_with_bad_message_type_fails() {
+        let bytes = vec![2u8, 0, 0, 0, 0, 0, 0, 0, 3, 65, 66, 67];
+        let mut stream = Cursor::new(bytes);
+        let mut operation = RecvOperation::with_msg_type(1024, Some(1));
+
+        assert!(operation.run(&mut stream).is_err());
+    }
+
+    #[test]
+    fn recv_with_too_long_message_fails() {
+        let bytes = vec![1u8, 0, 0, 0, 0, 0, 0, 4, 1, 65, 66, 67];
+        let mut stream = Cursor::new(bytes);
+        let mut operation = RecvOperation::with_msg_type(1024, Some(1));
+        let err = operation.run(&mut stream).err().expect("recv should have failed");
+
+        assert_eq!(::std::io::ErrorKind::InvalidData, err.kind());
+    }
+}
@@ -19,8 +19,13 @@ pub struct SendOperation {
 
 impl SendOperation {
     pub fn new(msg: Rc<Message>) -> SendOperation {
+        SendOperation::with_msg_type(msg, None)
+    }
+
+    /// The message type, when specified, is written before the message length.
+    pub fn with_msg_type(msg: Rc<Message>, msg_type: Option<u8>) -> SendOperation {
         SendOperation { 
-            step: Some(SendOperationStep::TransportHdr(msg, 0))
+            step: Some(SendOperationStep::TransportHdr(msg, msg_type, 0))
         }
     }
 
@@ -52,7 +57,7 @@ impl SendOperation {
 }
 
 enum SendOperationStep {
-    TransportHdr(Rc<Message>, usize),
+    TransportHdr(Rc<Message>, Option<u8>, usize),
     ProtocolHdr(Rc<Message>, usize),
     UsrPayload(Rc<Message>, usize),
     Terminal
@@ -63,7 +68,7 @@ impl SendOperationStep {
     /// Returns whether the buffer was fully sent, and what is the next step.
     fn advance<T:io::Write>(self, stream: &mut T) -> io::Result<(bool, SendOperationStep)> {
         match self {
-            SendOperationStep::TransportHdr(msg, written) => write_transport_hdr(stream, msg, written),
+            SendOperationStep::TransportHdr(msg, msg_type, written) => write_transport_hdr(stream, msg, msg_type, written),
             SendOperationStep::ProtocolHdr(msg, written) => write_protocol_hdr(stream, msg, written),
             SendOperationStep::UsrPayload(msg, written) => write_usr_payload(stream, msg, written),
             SendOperationStep::Terminal => Err(other_io_error("Cannot advance terminal step of send operation"))
@@ -78,17 +83,25 @@ impl SendOperationStep {
     }
 }
 
-fn write_transport_hdr<T:io::Write>(stream: &mut T, msg: Rc<Message>, mut written: usize) -> io::Result<(bool, SendOperationStep)> {
+fn write_transport_hdr<T:io::Write>(stream: &mut T, msg: Rc<Message>, msg_type: Option<u8>, mut written: usize) -> io::Result<(bool, SendOperationStep)> {
     let msg_len = msg.len() as u64;
-    let mut buffer = [0u8; 8];
+    let mut buffer = [0u8; 9];
 
-    BigEndian::write_u64(&mut buffer, msg_len);
+    BigEndian::write_u64(&mut buffer[1..], msg_len);
 
-    let sent = try!(stream.write_buffer(&buffer, &mut written));
+    let header = match msg_type {
+        Some(msg_type) => {
+            buffer[0] = msg_type;
+            &buffer[..]
+        },
+        None => &buffer[1..]
+    };
+
+    let sent = try!(stream.write_buffer(header, &mut written));
     if sent {
         Ok((true, SendOperationStep::ProtocolHdr(msg, 0)))
     } else {
-        Ok((false, SendOperationStep::TransportHdr(msg, written)))
+        Ok((false, SendOperationStep::TransportHdr(msg, msg_type, written)))
     }
 }
 
@@ -139,4 +152,18 @@ mod tests {
         assert!(result);
         assert_eq!(&expected_bytes, stream.deref());
     }
+
+    #[test]
+    fn send_with_msg_type_in_one_run() {
+        let header = vec!(1, 4, 3, 2);
+        let payload = vec!(65, 66, 67, 69);
+        let msg = Message::from_header_and_body(header, payload);
+        let mut operation = SendOperation::with_msg_type(Rc::new(msg), Some(1));
+        let mut stream = Vec::new();
+        let result = operation.run(&mut stream).expect("send should have succeeded");
+        let expected_bytes = [1u8, 0, 0, 0, 0, 0, 0, 0, 8, 1, 4, 3, 2, 65, 66, 67, 69];
+
+        assert!(result);
+        assert_eq!(&expected_bytes, stream.deref());
+    }
 }
\ No newline at end of file
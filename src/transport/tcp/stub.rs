@@ -12,7 +12,6 @@ use mio;
 use mio::tcp::{TcpStream, Shutdown};
 
 use core::Message;
-use transport::DEFAULT_RECV_MAX_SIZE;
 use transport::tcp::send::SendOperation;
 use transport::tcp::recv::RecvOperation;
 use transport::async::stub::*;
@@ -20,27 +19,62 @@ use io_error::*;
 
 /*****************************************************************************/
 /*                                                                           */
-/* TcpPipeStub                                                               */
+/* Stream                                                                    */
 /*                                                                           */
 /*****************************************************************************/
 
-pub struct TcpPipeStub {
-    stream: TcpStream,
+/// A connected byte stream the messages are framed on, such as a tcp or unix stream.
+pub trait Stream : mio::Evented + io::Read + io::Write {
+    /// Shuts down both halves of the connection.
+    fn close(&self) -> io::Result<()>;
+
+    fn peer_addr(&self) -> Option<String> {
+        None
+    }
+}
+
+impl Stream for TcpStream {
+    fn close(&self) -> io::Result<()> {
+        self.shutdown(Shutdown::Both)
+    }
+
+    fn peer_addr(&self) -> Option<String> {
+        TcpStream::peer_addr(self).ok().map(|addr| addr.to_string())
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* StreamPipeStub                                                            */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Each message is preceded by its length, and by its type when one is specified.
+pub struct StreamPipeStub<S : Stream> {
+    stream: S,
+    msg_type: Option<u8>,
+    recv_max_size: u64,
     send_operation: Option<SendOperation>,
     recv_operation: Option<RecvOperation>
 }
 
-impl Deref for TcpPipeStub {
+impl<S : Stream + 'static> Deref for StreamPipeStub<S> {
     type Target = mio::Evented;
     fn deref(&self) -> &Self::Target {
         &self.stream
     }
 }
 
-impl TcpPipeStub {
-    pub fn new(stream: TcpStream) -> TcpPipeStub {
-        TcpPipeStub {
+impl<S : Stream> StreamPipeStub<S> {
+    pub fn new(stream: S, recv_max_size: u64) -> StreamPipeStub<S> {
+        StreamPipeStub::with_msg_type(stream, None, recv_max_size)
+    }
+
+    pub fn with_msg_type(stream: S, msg_type: Option<u8>, recv_max_size: u64) -> StreamPipeStub<S> {
+        StreamPipeStub {
             stream: stream,
+            msg_type: msg_type,
+            recv_max_size: recv_max_size,
             send_operation: None,
             recv_operation: None
         }
@@ -66,21 +100,21 @@ impl TcpPipeStub {
     }
 }
 
-impl Drop for TcpPipeStub {
+impl<S : Stream> Drop for StreamPipeStub<S> {
     fn drop(&mut self) {
-        let _ = self.stream.shutdown(Shutdown::Both);
+        let _ = self.stream.close();
     }
 }
 
 /*****************************************************************************/
 /*                                                                           */
-/* Sender for TcpPipeStub                                                    */
+/* Sender for StreamPipeStub                                                 */
 /*                                                                           */
 /*****************************************************************************/
 
-impl Sender for TcpPipeStub {
+impl<S : Stream> Sender for StreamPipeStub<S> {
     fn start_send(&mut self, msg: Rc<Message>) -> io::Result<bool> {
-        let send_operation = SendOperation::new(msg);
+        let send_operation = SendOperation::with_msg_type(msg, self.msg_type);
 
         self.run_send_operation(send_operation)
     }
@@ -100,13 +134,13 @@ impl Sender for TcpPipeStub {
 
 /*****************************************************************************/
 /*                                                                           */
-/* Receiver for TcpPipeStub                                                  */
+/* Receiver for StreamPipeStub                                               */
 /*                                                                           */
 /*****************************************************************************/
 
-impl Receiver for TcpPipeStub {
+impl<S : Stream> Receiver for StreamPipeStub<S> {
     fn start_recv(&mut self) -> io::Result<Option<Message>> {
-        let recv_operation = RecvOperation::new(DEFAULT_RECV_MAX_SIZE);
+        let recv_operation = RecvOperation::with_msg_type(self.recv_max_size, self.msg_type);
 
         self.run_recv_operation(recv_operation)
     }
@@ -126,11 +160,11 @@ impl Receiver for TcpPipeStub {
 
 /*****************************************************************************/
 /*                                                                           */
-/* Handshake for TcpPipeStub                                                 */
+/* Handshake for StreamPipeStub                                              */
 /*                                                                           */
 /*****************************************************************************/
 
-impl Handshake for TcpPipeStub {
+impl<S : Stream> Handshake for StreamPipeStub<S> {
     fn send_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
         send_and_check_handshake(&mut self.stream, pids)
     }
@@ -139,5 +173,8 @@ impl Handshake for TcpPipeStub {
     }
 }
 
-impl AsyncPipeStub for TcpPipeStub {
+impl<S : Stream + 'static> AsyncPipeStub for StreamPipeStub<S> {
+    fn peer_addr(&self) -> Option<String> {
+        self.stream.peer_addr()
+    }
 }
@@ -0,0 +1,87 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::io;
+
+use mio;
+use mio::tcp::{TcpListener, TcpStream};
+
+use openssl::ssl::SslAcceptor;
+
+use transport::*;
+use transport::acceptor::*;
+use transport::async::AsyncPipe;
+use transport::tcp::options::TcpOptions;
+use super::stub::TlsPipeStub;
+
+pub struct TlsAcceptor {
+    listener: TcpListener,
+    ssl_acceptor: SslAcceptor,
+    proto_ids: (u16, u16),
+    recv_max_size: u64,
+    options: TcpOptions
+}
+
+impl TlsAcceptor {
+
+    pub fn new(l: TcpListener, ssl_acceptor: SslAcceptor, pids: (u16, u16), recv_max_size: u64, options: TcpOptions) -> TlsAcceptor {
+        TlsAcceptor {
+            listener: l,
+            ssl_acceptor: ssl_acceptor,
+            proto_ids: pids,
+            recv_max_size: recv_max_size,
+            options: options
+        }
+    }
+
+    fn accept(&mut self, ctx: &mut Context) {
+        let mut pipes = Vec::new();
+
+        loop {
+            match self.listener.accept().and_then(|(stream, _)| self.create_pipe(stream)) {
+                Ok(pipe) => pipes.push(pipe),
+                Err(e) => {
+                    if e.kind() != io::ErrorKind::WouldBlock {
+                        ctx.raise(Event::Error(e));
+                    }
+                    break;
+                }
+            }
+        }
+
+        if pipes.is_empty() == false {
+            ctx.raise(Event::Accepted(pipes));
+        }
+    }
+
+    fn create_pipe(&self, stream: TcpStream) -> io::Result<Box<pipe::Pipe>> {
+        let pids = self.proto_ids;
+
+        try!(self.options.apply(&stream));
+
+        let stub = TlsPipeStub::server(stream, self.ssl_acceptor.clone(), self.recv_max_size);
+
+        Ok(box AsyncPipe::new(stub, pids))
+    }
+}
+
+impl acceptor::Acceptor for TlsAcceptor {
+    fn ready(&mut self, ctx: &mut Context, events: mio::Ready) {
+        if events.is_readable() {
+            self.accept(ctx);
+        }
+    }
+
+    fn open(&mut self, ctx: &mut Context) {
+        ctx.register(&self.listener, mio::Ready::readable(), mio::PollOpt::edge());
+        ctx.raise(Event::Opened);
+    }
+
+    fn close(&mut self, ctx: &mut Context) {
+        ctx.deregister(&self.listener);
+        ctx.raise(Event::Closed);
+    }
+}
@@ -0,0 +1,79 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::io;
+
+use openssl::ssl::{SslContextBuilder, SslConnector, SslConnectorBuilder, SslAcceptor, SslAcceptorBuilder, SslMethod};
+use openssl::ssl::{SSL_VERIFY_PEER, SSL_VERIFY_FAIL_IF_NO_PEER_CERT};
+use openssl::x509::X509;
+use openssl::pkey::PKey;
+
+use core::config::Config;
+use io_error::*;
+
+/// Creates the client side of the sessions, the server certificate is verified
+/// against the trusted authorities and the domain given when connecting, which can be an ip address.
+pub fn create_connector(config: &Config) -> io::Result<SslConnector> {
+    let mut builder = try!(SslConnectorBuilder::new(SslMethod::tls()).map_err(other_io_error));
+
+    try!(set_ca_certs(builder.builder_mut(), config));
+
+    if config.tls_cert.is_some() {
+        try!(set_certificate(builder.builder_mut(), config));
+    }
+
+    Ok(builder.build())
+}
+
+/// Creates the server side shared by all the sessions accepted by a server.
+pub fn create_acceptor(config: &Config) -> io::Result<SslAcceptor> {
+    let mut builder = try!(SslAcceptorBuilder::mozilla_intermediate_raw(SslMethod::tls()).map_err(other_io_error));
+
+    try!(set_certificate(builder.builder_mut(), config));
+    try!(set_ca_certs(builder.builder_mut(), config));
+
+    if config.tls_verify_client {
+        builder.builder_mut().set_verify(SSL_VERIFY_PEER | SSL_VERIFY_FAIL_IF_NO_PEER_CERT);
+    }
+
+    Ok(builder.build())
+}
+
+fn set_certificate(builder: &mut SslContextBuilder, config: &Config) -> io::Result<()> {
+    let (cert_pem, key_pem) = match (config.tls_cert.as_ref(), config.tls_private_key.as_ref()) {
+        (Some(cert), Some(key)) => (cert, key),
+        _ => return Err(invalid_input_io_error("tls certificate and private key are both required"))
+    };
+    let mut chain = try!(X509::stack_from_pem(cert_pem).map_err(other_io_error)).into_iter();
+    let key = try!(PKey::private_key_from_pem(key_pem).map_err(other_io_error));
+
+    match chain.next() {
+        Some(cert) => try!(builder.set_certificate(&cert).map_err(other_io_error)),
+        None => return Err(invalid_input_io_error("tls certificate is empty"))
+    }
+
+    for cert in chain {
+        try!(builder.add_extra_chain_cert(cert).map_err(other_io_error));
+    }
+
+    try!(builder.set_private_key(&key).map_err(other_io_error));
+
+    builder.check_private_key().map_err(other_io_error)
+}
+
+fn set_ca_certs(builder: &mut SslContextBuilder, config: &Config) -> io::Result<()> {
+    if let Some(ref ca_pem) = config.tls_ca_certs {
+        let certs = try!(X509::stack_from_pem(ca_pem).map_err(other_io_error));
+
+        for cert in certs {
+            try!(builder.cert_store_mut().add_cert(cert).map_err(other_io_error));
+        }
+    } else {
+        try!(builder.set_default_verify_paths().map_err(other_io_error));
+    }
+
+    Ok(())
+}
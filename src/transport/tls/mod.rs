@@ -0,0 +1,65 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+mod stub;
+mod context;
+mod acceptor;
+
+use std::str::FromStr;
+use std::io;
+use std::net;
+
+use mio::tcp::{TcpListener, TcpStream};
+
+use self::stub::TlsPipeStub;
+use self::acceptor::TlsAcceptor;
+use core::config::Config;
+use transport::tcp::options::TcpOptions;
+use transport::{Transport, recv_max_size};
+use transport::pipe::Pipe;
+use transport::acceptor::Acceptor;
+use transport::async::AsyncPipe;
+use io_error::*;
+
+/// TCP transport secured with TLS, the peer certificate is checked against the address it is connected to.
+pub struct Tls;
+
+impl Tls {
+    fn connect(&self, addr: &net::SocketAddr, pids: (u16, u16), config: &Config) -> io::Result<Box<Pipe>> {
+        let connector = try!(context::create_connector(config));
+        let stream = try!(TcpStream::connect(addr));
+
+        try!(TcpOptions::from(config).apply(&stream));
+
+        let stub = TlsPipeStub::client(stream, connector, addr.ip().to_string(), recv_max_size(config));
+        let pipe = box AsyncPipe::new(stub, pids);
+
+        Ok(pipe)
+    }
+    fn bind(&self, addr: &net::SocketAddr, pids: (u16, u16), config: &Config) -> io::Result<Box<Acceptor>> {
+        let ssl_acceptor = try!(context::create_acceptor(config));
+        let listener = try!(TcpListener::bind(addr));
+        let acceptor = box TlsAcceptor::new(listener, ssl_acceptor, pids, recv_max_size(config), TcpOptions::from(config));
+
+        Ok(acceptor)
+    }
+}
+
+impl Transport for Tls {
+    fn connect(&self, url: &str, pids: (u16, u16), config: &Config) -> io::Result<Box<Pipe>> {
+        match net::SocketAddr::from_str(url) {
+            Ok(addr) => self.connect(&addr, pids, config),
+            Err(_) => Err(invalid_input_io_error(url))
+        }
+    }
+
+    fn bind(&self, url: &str, pids: (u16, u16), config: &Config) -> io::Result<Box<Acceptor>> {
+        match net::SocketAddr::from_str(url) {
+            Ok(addr) => self.bind(&addr, pids, config),
+            Err(_) => Err(invalid_input_io_error(url))
+        }
+    }
+}
@@ -0,0 +1,345 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::ops::Deref;
+use std::rc::Rc;
+use std::mem;
+use std::io;
+use std::io::{Read, Write};
+
+use mio;
+use mio::{Evented, Poll, Token, Ready, PollOpt};
+use mio::tcp::{TcpStream, Shutdown};
+
+use openssl::ssl::{SslConnector, SslAcceptor, SslStream, MidHandshakeSslStream, HandshakeError};
+use openssl::ssl::Error as SslError;
+
+use core::Message;
+use transport::tcp::send::SendOperation;
+use transport::tcp::recv::RecvOperation;
+use transport::async::stub::*;
+use transport::async::signal::Signal;
+use io_error::*;
+
+/// The tcp stream is shared between the stub, that registers it, and the tls session.
+pub struct SharedStream(Rc<TcpStream>);
+
+impl Read for SharedStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&*self.0).read(buf)
+    }
+}
+
+impl Write for SharedStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self.0).write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        (&*self.0).flush()
+    }
+}
+
+/// A tls session reads and writes at most one record per call,
+/// this keeps going until the buffer is processed or the socket would block,
+/// otherwise the edge triggered readiness would not be raised again.
+struct Records<'a>(&'a mut SslStream<SharedStream>);
+
+impl<'a> Read for Records<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut total = 0;
+
+        while total < buf.len() {
+            match self.0.read(&mut buf[total..]) {
+                Ok(0) => break,
+                Ok(n) => total += n,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock && total > 0 => break,
+                Err(e) => return Err(e)
+            }
+        }
+
+        Ok(total)
+    }
+}
+
+impl<'a> Write for Records<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut total = 0;
+
+        while total < buf.len() {
+            match self.0.write(&buf[total..]) {
+                Ok(0) => break,
+                Ok(n) => total += n,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock && total > 0 => break,
+                Err(e) => return Err(e)
+            }
+        }
+
+        Ok(total)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+enum TlsState {
+    Connect(SslConnector, String),
+    Accept(SslAcceptor),
+    Handshake(MidHandshakeSslStream<SharedStream>),
+    Established(SslStream<SharedStream>),
+    Failed
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* TlsPipeStub                                                               */
+/*                                                                           */
+/*****************************************************************************/
+
+/// The tls handshake is performed before the scalability protocol handshake,
+/// both are driven by the handshake state, which waits for the socket to be writable
+/// whenever the tls session could not flush its handshake records.
+/// Decrypted data can remain in the tls session after a message has been received,
+/// the signal is there to notify that the stub is still readable in that case.
+pub struct TlsPipeStub {
+    stream: Rc<TcpStream>,
+    tls: TlsState,
+    signal: Signal,
+    handshake_sent: bool,
+    handshake: [u8; 8],
+    handshake_read: usize,
+    recv_max_size: u64,
+    send_operation: Option<SendOperation>,
+    recv_operation: Option<RecvOperation>
+}
+
+impl Deref for TlsPipeStub {
+    type Target = mio::Evented;
+    fn deref(&self) -> &Self::Target {
+        self
+    }
+}
+
+impl Evented for TlsPipeStub {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        try!(self.stream.register(poll, token, interest, opts));
+        self.signal.register(poll, token, interest, opts)
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        try!(self.stream.reregister(poll, token, interest, opts));
+        self.signal.reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        try!(self.stream.deregister(poll));
+        self.signal.deregister(poll)
+    }
+}
+
+impl TlsPipeStub {
+    pub fn client(stream: TcpStream, connector: SslConnector, domain: String, recv_max_size: u64) -> TlsPipeStub {
+        TlsPipeStub::new(stream, TlsState::Connect(connector, domain), recv_max_size)
+    }
+
+    pub fn server(stream: TcpStream, acceptor: SslAcceptor, recv_max_size: u64) -> TlsPipeStub {
+        TlsPipeStub::new(stream, TlsState::Accept(acceptor), recv_max_size)
+    }
+
+    fn new(stream: TcpStream, tls: TlsState, recv_max_size: u64) -> TlsPipeStub {
+        TlsPipeStub {
+            stream: Rc::new(stream),
+            tls: tls,
+            signal: Signal::new(),
+            handshake_sent: false,
+            handshake: [0; 8],
+            handshake_read: 0,
+            recv_max_size: recv_max_size,
+            send_operation: None,
+            recv_operation: None
+        }
+    }
+
+    /// Starts or resumes the tls handshake, returns a `WouldBlock` error until it is completed.
+    fn run_tls_handshake(&mut self) -> io::Result<()> {
+        let progress = match mem::replace(&mut self.tls, TlsState::Failed) {
+            TlsState::Connect(connector, domain) => connector.connect(&domain, SharedStream(self.stream.clone())),
+            TlsState::Accept(acceptor) => acceptor.accept(SharedStream(self.stream.clone())),
+            TlsState::Handshake(mid) => mid.handshake(),
+            TlsState::Established(stream) => Ok(stream),
+            TlsState::Failed => return Err(other_io_error("tls handshake failed"))
+        };
+
+        match progress {
+            Ok(stream) => {
+                self.tls = TlsState::Established(stream);
+                Ok(())
+            },
+            Err(HandshakeError::Interrupted(mid)) => {
+                self.tls = TlsState::Handshake(mid);
+                Err(would_block_io_error("tls handshake in progress"))
+            },
+            Err(HandshakeError::Failure(mid)) => Err(other_io_error(mid.error().to_string())),
+            Err(HandshakeError::SetupFailure(e)) => Err(other_io_error(e))
+        }
+    }
+
+    fn recv_sp_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
+        let stream = match self.tls {
+            TlsState::Established(ref mut stream) => stream,
+            _ => return Err(other_io_error("tls handshake not completed"))
+        };
+
+        if self.handshake_sent == false {
+            try!(send_and_check_handshake(stream, pids));
+            self.handshake_sent = true;
+        }
+
+        while self.handshake_read < 8 {
+            match try!(stream.read(&mut self.handshake[self.handshake_read..])) {
+                0 => return Err(other_io_error("connection closed during handshake")),
+                n => self.handshake_read += n
+            }
+        }
+
+        check_handshake(pids, &self.handshake)
+    }
+
+    fn refresh_signal(&self) {
+        let pending = match self.tls {
+            TlsState::Established(ref stream) => stream.ssl().pending() > 0,
+            _ => false
+        };
+
+        self.signal.set(if pending { Ready::readable() } else { Ready::none() });
+    }
+
+    fn run_send_operation(&mut self, mut send_operation: SendOperation) -> io::Result<bool> {
+        let done = match self.tls {
+            TlsState::Established(ref mut stream) => try!(send_operation.run(&mut Records(stream))),
+            _ => return Err(other_io_error("tls handshake not completed"))
+        };
+
+        if done {
+            Ok(true)
+        } else {
+            self.send_operation = Some(send_operation);
+            Ok(false)
+        }
+    }
+
+    fn run_recv_operation(&mut self, mut recv_operation: RecvOperation) -> io::Result<Option<Message>> {
+        let received = match self.tls {
+            TlsState::Established(ref mut stream) => try!(recv_operation.run(&mut Records(stream))),
+            _ => return Err(other_io_error("tls handshake not completed"))
+        };
+
+        self.refresh_signal();
+
+        match received {
+            Some(msg) => Ok(Some(msg)),
+            None => {
+                self.recv_operation = Some(recv_operation);
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Drop for TlsPipeStub {
+    fn drop(&mut self) {
+        if let TlsState::Established(ref mut stream) = self.tls {
+            let _ = stream.shutdown();
+        }
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Sender for TlsPipeStub                                                    */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Sender for TlsPipeStub {
+    fn start_send(&mut self, msg: Rc<Message>) -> io::Result<bool> {
+        let send_operation = SendOperation::new(msg);
+
+        self.run_send_operation(send_operation)
+    }
+
+    fn resume_send(&mut self) -> io::Result<bool> {
+        if let Some(send_operation) = self.send_operation.take() {
+            self.run_send_operation(send_operation)
+        } else {
+            Err(other_io_error("Cannot resume send: no pending operation"))
+        }
+    }
+
+    fn has_pending_send(&self) -> bool {
+        self.send_operation.is_some()
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Receiver for TlsPipeStub                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Receiver for TlsPipeStub {
+    fn start_recv(&mut self) -> io::Result<Option<Message>> {
+        let recv_operation = RecvOperation::new(self.recv_max_size);
+
+        self.run_recv_operation(recv_operation)
+    }
+
+    fn resume_recv(&mut self) -> io::Result<Option<Message>> {
+        if let Some(recv_operation) = self.recv_operation.take() {
+            self.run_recv_operation(recv_operation)
+        } else {
+            Err(other_io_error("Cannot resume recv: no pending operation"))
+        }
+    }
+
+    fn has_pending_recv(&self) -> bool {
+        self.recv_operation.is_some()
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Handshake for TlsPipeStub                                                 */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Handshake for TlsPipeStub {
+    fn send_handshake(&mut self, _: (u16, u16)) -> io::Result<()> {
+        match self.run_tls_handshake() {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            other => other
+        }
+    }
+    fn recv_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
+        try!(self.run_tls_handshake());
+
+        self.recv_sp_handshake(pids)
+    }
+    fn handshake_interest(&self) -> Ready {
+        match self.tls {
+            TlsState::Handshake(ref mid) => match *mid.error() {
+                SslError::WantWrite(_) => Ready::writable(),
+                _ => Ready::readable()
+            },
+            _ => Ready::readable()
+        }
+    }
+}
+
+impl AsyncPipeStub for TlsPipeStub {
+    fn peer_addr(&self) -> Option<String> {
+        self.stream.peer_addr().ok().map(|addr| addr.to_string())
+    }
+}
@@ -0,0 +1,90 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::io;
+
+use mio;
+use mio::tcp::{TcpListener, TcpStream};
+
+use transport::*;
+use transport::acceptor::*;
+use transport::async::AsyncPipe;
+use transport::tcp::options::TcpOptions;
+use super::stub::WsPipeStub;
+
+pub struct WsAcceptor {
+    listener: TcpListener,
+    proto_ids: (u16, u16),
+    recv_max_size: u64,
+    options: TcpOptions
+}
+
+impl WsAcceptor {
+
+    pub fn new(l: TcpListener, pids: (u16, u16), recv_max_size: u64, options: TcpOptions) -> WsAcceptor {
+        WsAcceptor {
+            listener: l,
+            proto_ids: pids,
+            recv_max_size: recv_max_size,
+            options: options
+        }
+    }
+
+    fn accept(&mut self, ctx: &mut Context) {
+        let mut pipes = Vec::new();
+
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    match self.create_pipe(stream) {
+                        Ok(pipe) => pipes.push(pipe),
+                        Err(e) => ctx.raise(Event::Error(e))
+                    }
+                },
+                Err(e) => {
+                    if e.kind() == io::ErrorKind::WouldBlock {
+                        break;
+                    } else {
+                        ctx.raise(Event::Error(e));
+                        break;
+                    }
+                }
+            }
+        }
+
+        if pipes.is_empty() == false {
+            ctx.raise(Event::Accepted(pipes));
+        }
+    }
+
+    fn create_pipe(&self, stream: TcpStream) -> io::Result<Box<pipe::Pipe>> {
+        let pids = self.proto_ids;
+
+        try!(self.options.apply(&stream));
+
+        let stub = WsPipeStub::server(stream, self.recv_max_size);
+
+        Ok(box AsyncPipe::new(stub, pids))
+    }
+}
+
+impl acceptor::Acceptor for WsAcceptor {
+    fn ready(&mut self, ctx: &mut Context, events: mio::Ready) {
+        if events.is_readable() {
+            self.accept(ctx);
+        }
+    }
+
+    fn open(&mut self, ctx: &mut Context) {
+        ctx.register(&self.listener, mio::Ready::readable(), mio::PollOpt::edge());
+        ctx.raise(Event::Opened);
+    }
+
+    fn close(&mut self, ctx: &mut Context) {
+        ctx.deregister(&self.listener);
+        ctx.raise(Event::Closed);
+    }
+}
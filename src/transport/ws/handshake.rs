@@ -0,0 +1,290 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::collections::HashMap;
+use std::io;
+use std::str;
+
+use base64;
+use sha1::Sha1;
+
+use mio::tcp::TcpStream;
+
+use proto;
+use io_error::*;
+
+const WEBSOCKET_GUID: &'static str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const HANDSHAKE_MAX_SIZE: usize = 8 * 1024;
+
+/// Name of the websocket sub-protocol matching a scalability protocol,
+/// as defined by the nanomsg websocket mapping.
+pub fn sub_protocol(proto_id: u16) -> io::Result<String> {
+    let name = match proto_id {
+        proto::PAIR       => "pair",
+        proto::PUB        => "pub",
+        proto::SUB        => "sub",
+        proto::REQ        => "req",
+        proto::REP        => "rep",
+        proto::PUSH       => "push",
+        proto::PULL       => "pull",
+        proto::SURVEYOR   => "surveyor",
+        proto::RESPONDENT => "respondent",
+        proto::BUS        => "bus",
+        _ => return Err(invalid_input_io_error("protocol not supported by websocket transport"))
+    };
+
+    Ok(format!("x-{}.sp.nanomsg.org", name))
+}
+
+pub fn accept_key(key: &str) -> String {
+    let mut sha1 = Sha1::new();
+
+    sha1.update(key.as_bytes());
+    sha1.update(WEBSOCKET_GUID.as_bytes());
+
+    base64::encode(&sha1.digest().bytes())
+}
+
+pub fn create_request(host: &str, path: &str, key: &str, protocol: &str) -> String {
+    format!("GET {} HTTP/1.1\r\n\
+        Host: {}\r\n\
+        Upgrade: websocket\r\n\
+        Connection: Upgrade\r\n\
+        Sec-WebSocket-Key: {}\r\n\
+        Sec-WebSocket-Version: 13\r\n\
+        Sec-WebSocket-Protocol: {}\r\n\r\n", path, host, key, protocol)
+}
+
+pub fn create_response(key: &str, protocol: &str) -> String {
+    format!("HTTP/1.1 101 Switching Protocols\r\n\
+        Upgrade: websocket\r\n\
+        Connection: Upgrade\r\n\
+        Sec-WebSocket-Accept: {}\r\n\
+        Sec-WebSocket-Protocol: {}\r\n\r\n", accept_key(key), protocol)
+}
+
+pub fn create_failure_response() -> String {
+    String::from("HTTP/1.1 400 Bad Request\r\n\r\n")
+}
+
+/// Checks the upgrade request sent by a client and returns its key.
+pub fn check_request(bytes: &[u8], protocol: &str) -> io::Result<String> {
+    let (start_line, headers) = try!(parse(bytes));
+
+    if start_line.starts_with("GET ") == false {
+        return Err(invalid_data_io_error("websocket handshake: bad request method"));
+    }
+
+    try!(check_upgrade_header(&headers));
+    try!(check_header(&headers, "sec-websocket-version", "13"));
+    try!(check_header(&headers, "sec-websocket-protocol", protocol));
+
+    match headers.get("sec-websocket-key") {
+        Some(key) => Ok(key.clone()),
+        None => Err(invalid_data_io_error("websocket handshake: missing key"))
+    }
+}
+
+/// Checks the response sent by a server to the upgrade request.
+pub fn check_response(bytes: &[u8], key: &str, protocol: &str) -> io::Result<()> {
+    let (start_line, headers) = try!(parse(bytes));
+
+    if start_line.starts_with("HTTP/1.1 101") == false {
+        return Err(invalid_data_io_error("websocket handshake: upgrade refused"));
+    }
+
+    try!(check_upgrade_header(&headers));
+    try!(check_header(&headers, "sec-websocket-accept", &accept_key(key)));
+    try!(check_header(&headers, "sec-websocket-protocol", protocol));
+
+    Ok(())
+}
+
+fn parse(bytes: &[u8]) -> io::Result<(String, HashMap<String, String>)> {
+    let text = try!(str::from_utf8(bytes).map_err(|_| invalid_data_io_error("websocket handshake: not utf8")));
+    let mut lines = text.split("\r\n").filter(|line| line.is_empty() == false);
+    let start_line = match lines.next() {
+        Some(line) => line.to_owned(),
+        None => return Err(invalid_data_io_error("websocket handshake: empty"))
+    };
+    let mut headers = HashMap::new();
+
+    for line in lines {
+        match line.find(':') {
+            Some(index) => {
+                let (name, value) = line.split_at(index);
+
+                headers.insert(name.trim().to_lowercase(), value[1..].trim().to_owned());
+            },
+            None => return Err(invalid_data_io_error("websocket handshake: malformed header"))
+        }
+    }
+
+    Ok((start_line, headers))
+}
+
+fn check_upgrade_header(headers: &HashMap<String, String>) -> io::Result<()> {
+    match headers.get("upgrade") {
+        Some(value) if value.to_lowercase() == "websocket" => Ok(()),
+        _ => Err(invalid_data_io_error("websocket handshake: bad or missing upgrade header"))
+    }
+}
+
+fn check_header(headers: &HashMap<String, String>, name: &str, expected: &str) -> io::Result<()> {
+    match headers.get(name) {
+        Some(value) if value == expected => Ok(()),
+        _ => Err(invalid_data_io_error(format!("websocket handshake: bad or missing {} header", name)))
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* HandshakeReader                                                           */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Stream whose incoming bytes can be looked at without being consumed.
+pub trait Peek : io::Read {
+    fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+}
+
+impl Peek for TcpStream {
+    fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        TcpStream::peek(self, buf)
+    }
+}
+
+/// Accumulates the bytes of an http handshake until the empty line.
+/// Incoming bytes are peeked by chunks, and only those up to the empty line are read,
+/// so nothing following the handshake is consumed.
+pub struct HandshakeReader {
+    buffer: Vec<u8>
+}
+
+impl HandshakeReader {
+    pub fn new() -> HandshakeReader {
+        HandshakeReader {
+            buffer: Vec::with_capacity(256)
+        }
+    }
+
+    /// Returns the whole handshake once received, or a `WouldBlock` error.
+    pub fn read<T:Peek>(&mut self, stream: &mut T) -> io::Result<&[u8]> {
+        let mut chunk = [0u8; 512];
+
+        while self.buffer.ends_with(b"\r\n\r\n") == false {
+            let available = HANDSHAKE_MAX_SIZE - self.buffer.len();
+
+            if available == 0 {
+                return Err(invalid_data_io_error("websocket handshake: too long"));
+            }
+
+            let limit = if available < chunk.len() { available } else { chunk.len() };
+            let peeked = match try!(stream.peek(&mut chunk[..limit])) {
+                0 => return Err(other_io_error("websocket handshake: connection closed")),
+                n => n
+            };
+            let len = self.end_in(&chunk[..peeked]).unwrap_or(peeked);
+            let read = try!(stream.read(&mut chunk[..len]));
+
+            self.buffer.extend_from_slice(&chunk[..read]);
+        }
+
+        Ok(&self.buffer)
+    }
+
+    /// Length of the part of the chunk completing the handshake, if it contains the empty line.
+    fn end_in(&self, chunk: &[u8]) -> Option<usize> {
+        let start = self.buffer.len().saturating_sub(3);
+        let mut tail = self.buffer[start..].to_vec();
+
+        tail.extend_from_slice(chunk);
+        tail.windows(4).
+            position(|w| w == b"\r\n\r\n").
+            map(|pos| pos + 4 - (self.buffer.len() - start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor, ErrorKind, Read};
+
+    use super::*;
+
+    impl Peek for Cursor<Vec<u8>> {
+        fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let position = self.position();
+            let peeked = try!(self.read(buf));
+
+            self.set_position(position);
+            Ok(peeked)
+        }
+    }
+
+    #[test]
+    fn accept_key_matches_rfc_sample() {
+        assert_eq!("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=", accept_key("dGhlIHNhbXBsZSBub25jZQ=="));
+    }
+
+    #[test]
+    fn sub_protocol_follows_nanomsg_mapping() {
+        assert_eq!("x-req.sp.nanomsg.org", sub_protocol(48).unwrap());
+        assert_eq!("x-surveyor.sp.nanomsg.org", sub_protocol(98).unwrap());
+        assert!(sub_protocol(1).is_err());
+    }
+
+    #[test]
+    fn request_is_accepted_by_server() {
+        let request = create_request("127.0.0.1:5454", "/", "dGhlIHNhbXBsZSBub25jZQ==", "x-req.sp.nanomsg.org");
+        let key = check_request(request.as_bytes(), "x-req.sp.nanomsg.org").unwrap();
+
+        assert_eq!("dGhlIHNhbXBsZSBub25jZQ==", key);
+    }
+
+    #[test]
+    fn request_with_other_protocol_is_rejected() {
+        let request = create_request("127.0.0.1:5454", "/", "dGhlIHNhbXBsZSBub25jZQ==", "x-pub.sp.nanomsg.org");
+
+        assert!(check_request(request.as_bytes(), "x-req.sp.nanomsg.org").is_err());
+    }
+
+    #[test]
+    fn response_is_accepted_by_client() {
+        let response = create_response("dGhlIHNhbXBsZSBub25jZQ==", "x-rep.sp.nanomsg.org");
+
+        check_response(response.as_bytes(), "dGhlIHNhbXBsZSBub25jZQ==", "x-rep.sp.nanomsg.org").unwrap();
+        assert!(check_response(response.as_bytes(), "AAAAAAAAAAAAAAAAAAAAAA==", "x-rep.sp.nanomsg.org").is_err());
+    }
+
+    #[test]
+    fn reader_stops_at_empty_line() {
+        let mut stream = Cursor::new(b"HTTP/1.1 101 OK\r\n\r\n\x82\x00".to_vec());
+        let mut reader = HandshakeReader::new();
+
+        assert_eq!(b"HTTP/1.1 101 OK\r\n\r\n", reader.read(&mut stream).unwrap());
+        assert_eq!(19, stream.position());
+    }
+
+    #[test]
+    fn reader_finds_empty_line_across_chunks() {
+        let mut request = vec![b'a'; 510];
+        request.extend_from_slice(b"\r\n\r\n\x82\x00");
+        let mut stream = Cursor::new(request);
+        let mut reader = HandshakeReader::new();
+
+        assert_eq!(514, reader.read(&mut stream).unwrap().len());
+        assert_eq!(514, stream.position());
+    }
+
+    #[test]
+    fn reader_fails_when_closed_before_the_end() {
+        let mut stream = Cursor::new(b"HTTP/1.1 101 OK\r\n".to_vec());
+        let mut reader = HandshakeReader::new();
+        let err = reader.read(&mut stream).unwrap_err();
+
+        assert!(err.kind() != ErrorKind::WouldBlock);
+    }
+}
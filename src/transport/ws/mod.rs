@@ -0,0 +1,97 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+mod stub;
+mod handshake;
+mod send;
+mod recv;
+mod acceptor;
+
+use std::str::FromStr;
+use std::io;
+use std::net;
+
+use mio::tcp::{TcpListener, TcpStream};
+
+use self::stub::WsPipeStub;
+use self::acceptor::WsAcceptor;
+use core::config::Config;
+use transport::tcp::options::TcpOptions;
+use transport::{Transport, recv_max_size};
+use transport::pipe::Pipe;
+use transport::acceptor::Acceptor;
+use transport::async::AsyncPipe;
+use io_error::*;
+
+const FIN: u8 = 0x80;
+const MASKED: u8 = 0x80;
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+/// Websocket transport, following the nanomsg mapping:
+/// each message is sent in a single binary frame.
+pub struct Ws;
+
+impl Ws {
+    fn connect(&self, addr: &net::SocketAddr, host: &str, path: &str, pids: (u16, u16), config: &Config) -> io::Result<Box<Pipe>> {
+        let stream = try!(TcpStream::connect(addr));
+
+        try!(TcpOptions::from(config).apply(&stream));
+
+        let stub = WsPipeStub::client(stream, host.to_owned(), path.to_owned(), recv_max_size(config));
+        let pipe = box AsyncPipe::new(stub, pids);
+
+        Ok(pipe)
+    }
+    fn bind(&self, addr: &net::SocketAddr, pids: (u16, u16), config: &Config) -> io::Result<Box<Acceptor>> {
+        let listener = try!(TcpListener::bind(addr));
+        let acceptor = box WsAcceptor::new(listener, pids, recv_max_size(config), TcpOptions::from(config));
+
+        Ok(acceptor)
+    }
+}
+
+/// Splits `host:port/path` into the address and the resource path.
+fn split_url(url: &str) -> (&str, &str) {
+    match url.find('/') {
+        Some(index) => url.split_at(index),
+        None => (url, "/")
+    }
+}
+
+impl Transport for Ws {
+    fn connect(&self, url: &str, pids: (u16, u16), config: &Config) -> io::Result<Box<Pipe>> {
+        let (host, path) = split_url(url);
+
+        match net::SocketAddr::from_str(host) {
+            Ok(addr) => self.connect(&addr, host, path, pids, config),
+            Err(_) => Err(invalid_input_io_error(url))
+        }
+    }
+
+    fn bind(&self, url: &str, pids: (u16, u16), config: &Config) -> io::Result<Box<Acceptor>> {
+        let (host, _) = split_url(url);
+
+        match net::SocketAddr::from_str(host) {
+            Ok(addr) => self.bind(&addr, pids, config),
+            Err(_) => Err(invalid_input_io_error(url))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_url;
+
+    #[test]
+    fn url_is_split_into_address_and_path() {
+        assert_eq!(("127.0.0.1:5454", "/"), split_url("127.0.0.1:5454"));
+        assert_eq!(("127.0.0.1:5454", "/a/b"), split_url("127.0.0.1:5454/a/b"));
+    }
+}
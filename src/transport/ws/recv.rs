@@ -0,0 +1,294 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::io;
+
+use byteorder::{ BigEndian, ByteOrder };
+
+use core::Message;
+use transport::async::stub::*;
+use io_error::*;
+use super::{FIN, MASKED, OPCODE_CONTINUATION, OPCODE_BINARY, OPCODE_CLOSE, OPCODE_PING, OPCODE_PONG};
+
+/// Receives a message made of one binary frame, or of several fragments.
+/// Frames sent by a client must be masked, and those sent by a server must not.
+/// Ping and close control frames are answered by the replies the stub has to send back.
+pub struct RecvOperation {
+    step: Option<RecvOperationStep>,
+    max_size: u64,
+    masked: bool,
+    fragments: Vec<u8>,
+    replies: Vec<(u8, Vec<u8>)>
+}
+
+impl RecvOperation {
+    pub fn new(recv_max_size: u64, masked: bool) -> RecvOperation {
+        RecvOperation {
+            step: Some(RecvOperationStep::Header([0; 14], 0)),
+            max_size: recv_max_size,
+            masked: masked,
+            fragments: Vec::new(),
+            replies: Vec::new()
+        }
+    }
+
+    /// Opcode and payload of the control frames to send in reply to the ones received so far.
+    pub fn take_replies(&mut self) -> Vec<(u8, Vec<u8>)> {
+        ::std::mem::replace(&mut self.replies, Vec::new())
+    }
+
+    pub fn run<T:io::Read>(&mut self, stream: &mut T) -> io::Result<Option<Message>> {
+        if let Some(step) = self.step.take() {
+            self.resume_at(stream, step)
+        } else {
+            Err(other_io_error("Cannot resume already finished recv operation"))
+        }
+    }
+
+    fn resume_at<T:io::Read>(&mut self, stream: &mut T, step: RecvOperationStep) -> io::Result<Option<Message>> {
+        let mut cur_step = step;
+
+        loop {
+            let (passed, next_step) = try!(cur_step.advance(stream, self.max_size, self.masked));
+
+            if !passed {
+                self.step = Some(next_step);
+                return Ok(None);
+            }
+
+            match next_step {
+                RecvOperationStep::Terminal(header, payload) => {
+                    if let Some(msg) = try!(self.on_frame_received(header, payload)) {
+                        return Ok(Some(msg));
+                    }
+
+                    cur_step = RecvOperationStep::Header([0; 14], 0);
+                },
+                other => cur_step = other
+            }
+        }
+    }
+
+    fn on_frame_received(&mut self, header: FrameHeader, mut payload: Vec<u8>) -> io::Result<Option<Message>> {
+        if header.opcode & 0x08 != 0 && (header.fin == false || header.len > 125) {
+            return Err(invalid_data_io_error("malformed websocket control frame"));
+        }
+
+        match header.opcode {
+            OPCODE_BINARY | OPCODE_CONTINUATION => {
+                if self.fragments.len() as u64 + header.len > self.max_size {
+                    return Err(msg_too_long_io_error());
+                }
+
+                if self.fragments.is_empty() {
+                    self.fragments = payload;
+                } else {
+                    self.fragments.append(&mut payload);
+                }
+
+                if header.fin {
+                    let body = ::std::mem::replace(&mut self.fragments, Vec::new());
+
+                    Ok(Some(Message::from_body(body)))
+                } else {
+                    Ok(None)
+                }
+            },
+            OPCODE_PING => {
+                self.replies.push((OPCODE_PONG, payload));
+                Ok(None)
+            },
+            OPCODE_PONG => Ok(None),
+            OPCODE_CLOSE => {
+                payload.truncate(2);
+                self.replies.push((OPCODE_CLOSE, payload));
+                Err(other_io_error("websocket closed by peer"))
+            },
+            _ => Err(invalid_data_io_error("unsupported websocket frame"))
+        }
+    }
+}
+
+struct FrameHeader {
+    fin: bool,
+    opcode: u8,
+    mask: Option<[u8; 4]>,
+    len: u64
+}
+
+enum RecvOperationStep {
+    Header([u8; 14], usize),
+    Payload(FrameHeader, Vec<u8>, usize),
+    Terminal(FrameHeader, Vec<u8>)
+}
+
+impl RecvOperationStep {
+    fn advance<T:io::Read>(self, stream: &mut T, max_size: u64, masked: bool) -> io::Result<(bool, RecvOperationStep)> {
+        match self {
+            RecvOperationStep::Header(buffer, read) => read_header(stream, buffer, read, max_size, masked),
+            RecvOperationStep::Payload(header, buffer, read) => read_payload(stream, header, buffer, read),
+            RecvOperationStep::Terminal(..) => Err(other_io_error("Cannot advance terminal step of recv operation"))
+        }
+    }
+}
+
+/// Size of the frame header, which is known once the first two bytes are read.
+fn header_len(buffer: &[u8; 14]) -> usize {
+    let mask_len = if buffer[1] & MASKED == 0 { 0 } else { 4 };
+    let ext_len = match buffer[1] & 0x7F {
+        126 => 2,
+        127 => 8,
+        _ => 0
+    };
+
+    2 + ext_len + mask_len
+}
+
+fn parse_header(buffer: &[u8; 14]) -> FrameHeader {
+    let (len, mut offset) = match buffer[1] & 0x7F {
+        126 => (BigEndian::read_u16(&buffer[2..4]) as u64, 4),
+        127 => (BigEndian::read_u64(&buffer[2..10]), 10),
+        x => (x as u64, 2)
+    };
+    let mask = if buffer[1] & MASKED == 0 {
+        None
+    } else {
+        let mut key = [0u8; 4];
+
+        key.copy_from_slice(&buffer[offset..offset + 4]);
+        offset += 4;
+
+        Some(key)
+    };
+
+    debug_assert_eq!(offset, header_len(buffer));
+
+    FrameHeader {
+        fin: buffer[0] & FIN != 0,
+        opcode: buffer[0] & 0x0F,
+        mask: mask,
+        len: len
+    }
+}
+
+fn read_header<T:io::Read>(stream: &mut T, mut buffer: [u8; 14], mut read: usize, max_size: u64, masked: bool) -> io::Result<(bool, RecvOperationStep)> {
+    let expected = if read < 2 { 2 } else { header_len(&buffer) };
+
+    read += try!(stream.read_buffer(&mut buffer[read..expected]));
+
+    if read < expected {
+        return Ok((false, RecvOperationStep::Header(buffer, read)));
+    }
+    if read < header_len(&buffer) {
+        return Ok((true, RecvOperationStep::Header(buffer, read)));
+    }
+
+    let header = parse_header(&buffer);
+
+    if header.mask.is_some() != masked {
+        return Err(invalid_data_io_error(if masked { "unmasked client frame" } else { "masked server frame" }));
+    }
+
+    if header.len > max_size {
+        Err(msg_too_long_io_error())
+    } else {
+        let payload = vec![0u8; header.len as usize];
+
+        Ok((true, RecvOperationStep::Payload(header, payload, 0)))
+    }
+}
+
+fn read_payload<T:io::Read>(stream: &mut T, header: FrameHeader, mut buffer: Vec<u8>, mut read: usize) -> io::Result<(bool, RecvOperationStep)> {
+    read += try!(stream.read_buffer(&mut buffer[read..]));
+
+    if read < buffer.len() {
+        return Ok((false, RecvOperationStep::Payload(header, buffer, read)));
+    }
+
+    if let Some(key) = header.mask {
+        for (i, b) in buffer.iter_mut().enumerate() {
+            *b ^= key[i % 4];
+        }
+    }
+
+    Ok((true, RecvOperationStep::Terminal(header, buffer)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use super::super::{OPCODE_CLOSE, OPCODE_PONG};
+
+    #[test]
+    fn recv_in_one_run() {
+        let buffer = vec![0x82, 7, 1, 4, 3, 65, 66, 67, 69];
+        let mut stream = Cursor::new(buffer);
+        let mut operation = RecvOperation::new(1024, false);
+        let msg = operation.run(&mut stream).unwrap().unwrap();
+
+        assert_eq!(&[1, 4, 3, 65, 66, 67, 69], msg.get_body());
+    }
+
+    #[test]
+    fn recv_masked_in_one_run() {
+        let buffer = vec![0x82, 0x88, 1, 2, 4, 8, 0, 6, 7, 10, 64, 64, 71, 77];
+        let mut stream = Cursor::new(buffer);
+        let mut operation = RecvOperation::new(1024, true);
+        let msg = operation.run(&mut stream).unwrap().unwrap();
+
+        assert_eq!(&[1, 4, 3, 2, 65, 66, 67, 69], msg.get_body());
+    }
+
+    #[test]
+    fn recv_fragments_and_answer_ping() {
+        let buffer = vec![0x02, 2, 1, 4, 0x89, 1, 7, 0x80, 2, 3, 2];
+        let mut stream = Cursor::new(buffer);
+        let mut operation = RecvOperation::new(1024, false);
+        let msg = operation.run(&mut stream).unwrap().unwrap();
+
+        assert_eq!(&[1, 4, 3, 2], msg.get_body());
+        assert_eq!(vec![(OPCODE_PONG, vec![7])], operation.take_replies());
+    }
+
+    #[test]
+    fn recv_unmasked_client_frame_fails() {
+        let buffer = vec![0x82, 2, 65, 66];
+        let mut stream = Cursor::new(buffer);
+        let mut operation = RecvOperation::new(1024, true);
+
+        assert!(operation.run(&mut stream).is_err());
+    }
+
+    #[test]
+    fn recv_masked_server_frame_fails() {
+        let buffer = vec![0x82, 0x82, 1, 2, 4, 8, 64, 64];
+        let mut stream = Cursor::new(buffer);
+        let mut operation = RecvOperation::new(1024, false);
+
+        assert!(operation.run(&mut stream).is_err());
+    }
+
+    #[test]
+    fn recv_too_long_message_fails() {
+        let buffer = vec![0x82, 126, 1, 44];
+        let mut stream = Cursor::new(buffer);
+        let mut operation = RecvOperation::new(256, false);
+
+        assert!(operation.run(&mut stream).is_err());
+    }
+
+    #[test]
+    fn recv_close_frame_fails_and_is_echoed() {
+        let buffer = vec![0x88, 4, 3, 232, 65, 66];
+        let mut stream = Cursor::new(buffer);
+        let mut operation = RecvOperation::new(1024, false);
+
+        assert!(operation.run(&mut stream).is_err());
+        assert_eq!(vec![(OPCODE_CLOSE, vec![3, 232])], operation.take_replies());
+    }
+}
@@ -0,0 +1,243 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::rc::Rc;
+use std::io;
+
+use byteorder::{ BigEndian, ByteOrder };
+
+use core::Message;
+use transport::async::stub::*;
+use io_error::*;
+use super::{FIN, OPCODE_BINARY, MASKED};
+
+/// Sends a message as a single binary frame.
+/// Frames sent by a client must be masked, which requires a copy of the message.
+pub struct SendOperation {
+    step: Option<SendOperationStep>
+}
+
+impl SendOperation {
+    pub fn new(msg: Rc<Message>, mask: Option<[u8; 4]>) -> SendOperation {
+        let frame_hdr = create_frame_header(OPCODE_BINARY, msg.len(), mask);
+
+        SendOperation { 
+            step: Some(SendOperationStep::FrameHdr(msg, frame_hdr, 0, mask))
+        }
+    }
+
+    pub fn run<T:io::Write>(&mut self, stream: &mut T) -> io::Result<bool> {
+        if let Some(step) = self.step.take() {
+            self.resume_at(stream, step)
+        } else {
+            Err(other_io_error("Cannot resume already finished send operation"))
+        }
+    }
+
+    fn resume_at<T:io::Write>(&mut self, stream: &mut T, step: SendOperationStep) -> io::Result<bool> {
+        let mut cur_step = step;
+
+        loop {
+            let (passed, next_step) = try!(cur_step.advance(stream));
+
+            if next_step.is_terminal() {
+                return Ok(true);
+            }
+            if !passed {
+                self.step = Some(next_step);
+                return Ok(false)
+            }
+
+            cur_step = next_step;
+        }
+    }
+}
+
+enum SendOperationStep {
+    FrameHdr(Rc<Message>, Vec<u8>, usize, Option<[u8; 4]>),
+    ProtocolHdr(Rc<Message>, usize),
+    UsrPayload(Rc<Message>, usize),
+    MaskedPayload(Vec<u8>, usize),
+    Terminal
+}
+
+impl SendOperationStep {
+    /// Writes one of the buffers composing the frame.
+    /// Returns whether the buffer was fully sent, and what is the next step.
+    fn advance<T:io::Write>(self, stream: &mut T) -> io::Result<(bool, SendOperationStep)> {
+        match self {
+            SendOperationStep::FrameHdr(msg, hdr, written, mask) => write_frame_hdr(stream, msg, hdr, written, mask),
+            SendOperationStep::ProtocolHdr(msg, written) => write_protocol_hdr(stream, msg, written),
+            SendOperationStep::UsrPayload(msg, written) => write_usr_payload(stream, msg, written),
+            SendOperationStep::MaskedPayload(payload, written) => write_masked_payload(stream, payload, written),
+            SendOperationStep::Terminal => Err(other_io_error("Cannot advance terminal step of send operation"))
+        }
+    }
+
+    fn is_terminal(&self) -> bool {
+        match *self {
+            SendOperationStep::Terminal => true,
+            _ => false,
+        }
+    }
+}
+
+/// Builds a whole control frame, such as a pong or a close, whose payload is short.
+pub fn create_control_frame(opcode: u8, payload: &[u8], mask: Option<[u8; 4]>) -> Vec<u8> {
+    let mut frame = create_frame_header(opcode, payload.len(), mask);
+
+    match mask {
+        Some(key) => frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ key[i % 4])),
+        None => frame.extend_from_slice(payload)
+    }
+
+    frame
+}
+
+fn create_frame_header(opcode: u8, payload_len: usize, mask: Option<[u8; 4]>) -> Vec<u8> {
+    let mut header = Vec::with_capacity(14);
+    let mask_bit = if mask.is_some() { MASKED } else { 0 };
+
+    header.push(FIN | opcode);
+
+    if payload_len < 126 {
+        header.push(mask_bit | payload_len as u8);
+    } else if payload_len <= 0xFFFF {
+        let mut buffer = [0u8; 2];
+
+        BigEndian::write_u16(&mut buffer, payload_len as u16);
+        header.push(mask_bit | 126);
+        header.extend_from_slice(&buffer);
+    } else {
+        let mut buffer = [0u8; 8];
+
+        BigEndian::write_u64(&mut buffer, payload_len as u64);
+        header.push(mask_bit | 127);
+        header.extend_from_slice(&buffer);
+    }
+
+    if let Some(key) = mask {
+        header.extend_from_slice(&key);
+    }
+
+    header
+}
+
+fn create_masked_payload(msg: &Message, key: [u8; 4]) -> Vec<u8> {
+    msg.get_header().iter().
+        chain(msg.get_body().iter()).
+        enumerate().
+        map(|(i, b)| b ^ key[i % 4]).
+        collect()
+}
+
+fn write_frame_hdr<T:io::Write>(stream: &mut T, msg: Rc<Message>, hdr: Vec<u8>, mut written: usize, mask: Option<[u8; 4]>) -> io::Result<(bool, SendOperationStep)> {
+    let sent = try!(stream.write_buffer(&hdr, &mut written));
+
+    if sent {
+        match mask {
+            Some(key) => Ok((true, SendOperationStep::MaskedPayload(create_masked_payload(&msg, key), 0))),
+            None => Ok((true, SendOperationStep::ProtocolHdr(msg, 0)))
+        }
+    } else {
+        Ok((false, SendOperationStep::FrameHdr(msg, hdr, written, mask)))
+    }
+}
+
+fn write_protocol_hdr<T:io::Write>(stream: &mut T, msg: Rc<Message>, mut written: usize) -> io::Result<(bool, SendOperationStep)> {
+    if msg.get_header().len() == 0 {
+        return Ok((true, SendOperationStep::UsrPayload(msg, 0)));
+    }
+
+    let sent = try!(stream.write_buffer(msg.get_header(), &mut written));
+    if sent {
+        Ok((true, SendOperationStep::UsrPayload(msg, 0)))
+    } else {
+        Ok((false, SendOperationStep::ProtocolHdr(msg, written)))
+    }
+}
+
+fn write_usr_payload<T:io::Write>(stream: &mut T, msg: Rc<Message>, mut written: usize) -> io::Result<(bool, SendOperationStep)> {
+    if msg.get_body().len() == 0 {
+        return Ok((true, SendOperationStep::Terminal));
+    }
+
+    let sent = try!(stream.write_buffer(msg.get_body(), &mut written));
+    if sent {
+        Ok((true, SendOperationStep::Terminal))
+    } else {
+        Ok((false, SendOperationStep::UsrPayload(msg, written)))
+    }
+}
+
+fn write_masked_payload<T:io::Write>(stream: &mut T, payload: Vec<u8>, mut written: usize) -> io::Result<(bool, SendOperationStep)> {
+    if payload.len() == 0 {
+        return Ok((true, SendOperationStep::Terminal));
+    }
+
+    let sent = try!(stream.write_buffer(&payload, &mut written));
+    if sent {
+        Ok((true, SendOperationStep::Terminal))
+    } else {
+        Ok((false, SendOperationStep::MaskedPayload(payload, written)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Deref;
+    use std::rc::Rc;
+
+    use core::Message;
+    use super::*;
+
+    #[test]
+    fn send_in_one_run() {
+        let header = vec!(1, 4, 3, 2);
+        let payload = vec!(65, 66, 67, 69);
+        let msg = Message::from_header_and_body(header, payload);
+        let mut operation = SendOperation::new(Rc::new(msg), None);
+        let mut stream = Vec::new();
+        let result = operation.run(&mut stream).expect("send should have succeeded");
+        let expected_bytes = [0x82u8, 8, 1, 4, 3, 2, 65, 66, 67, 69];
+
+        assert!(result);
+        assert_eq!(&expected_bytes, stream.deref());
+    }
+
+    #[test]
+    fn send_masked_in_one_run() {
+        let header = vec!(1, 4, 3, 2);
+        let payload = vec!(65, 66, 67, 69);
+        let msg = Message::from_header_and_body(header, payload);
+        let mut operation = SendOperation::new(Rc::new(msg), Some([1, 2, 4, 8]));
+        let mut stream = Vec::new();
+        let result = operation.run(&mut stream).expect("send should have succeeded");
+        let expected_bytes = [0x82u8, 0x88, 1, 2, 4, 8, 0, 6, 7, 10, 64, 64, 71, 77];
+
+        assert!(result);
+        assert_eq!(&expected_bytes, stream.deref());
+    }
+
+    #[test]
+    fn send_with_extended_length() {
+        let msg = Message::from_body(vec![0; 300]);
+        let mut operation = SendOperation::new(Rc::new(msg), None);
+        let mut stream = Vec::new();
+
+        operation.run(&mut stream).expect("send should have succeeded");
+
+        assert_eq!(&[0x82u8, 126, 1, 44], &stream[..4]);
+        assert_eq!(304, stream.len());
+    }
+
+    #[test]
+    fn control_frame_is_masked_like_messages() {
+        let frame = create_control_frame(0xA, &[65, 66, 67, 69], Some([1, 2, 4, 8]));
+
+        assert_eq!(&[0x8Au8, 0x84, 1, 2, 4, 8, 64, 64, 71, 77], &frame[..]);
+    }
+}
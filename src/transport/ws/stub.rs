@@ -0,0 +1,285 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::ops::Deref;
+use std::rc::Rc;
+use std::io;
+use std::io::Write;
+
+use mio;
+use mio::tcp::{TcpStream, Shutdown};
+
+use rand;
+
+use core::Message;
+use transport::ws::send::{SendOperation, create_control_frame};
+use transport::ws::recv::RecvOperation;
+use transport::ws::handshake::*;
+use transport::async::stub::*;
+use io_error::*;
+
+/*****************************************************************************/
+/*                                                                           */
+/* WsPipeStub                                                                */
+/*                                                                           */
+/*****************************************************************************/
+
+/// The client side sends the upgrade request and masks the frames it sends,
+/// the server side replies to the upgrade request.
+enum Role {
+    Client(String, String, String),
+    Server
+}
+
+pub struct WsPipeStub {
+    stream: TcpStream,
+    role: Role,
+    handshake_reader: HandshakeReader,
+    recv_max_size: u64,
+    send_operation: Option<SendOperation>,
+    recv_operation: Option<RecvOperation>,
+    output: Vec<u8>
+}
+
+impl Deref for WsPipeStub {
+    type Target = mio::Evented;
+    fn deref(&self) -> &Self::Target {
+        &self.stream
+    }
+}
+
+impl WsPipeStub {
+    pub fn client(stream: TcpStream, host: String, path: String, recv_max_size: u64) -> WsPipeStub {
+        let key = ::base64::encode(&rand::random::<[u8; 16]>());
+
+        WsPipeStub::new(stream, Role::Client(host, path, key), recv_max_size)
+    }
+
+    pub fn server(stream: TcpStream, recv_max_size: u64) -> WsPipeStub {
+        WsPipeStub::new(stream, Role::Server, recv_max_size)
+    }
+
+    fn new(stream: TcpStream, role: Role, recv_max_size: u64) -> WsPipeStub {
+        WsPipeStub {
+            stream: stream,
+            role: role,
+            handshake_reader: HandshakeReader::new(),
+            recv_max_size: recv_max_size,
+            send_operation: None,
+            recv_operation: None,
+            output: Vec::new()
+        }
+    }
+
+    fn mask(&self) -> Option<[u8; 4]> {
+        match self.role {
+            Role::Client(..) => Some(rand::random()),
+            Role::Server => None
+        }
+    }
+
+    fn is_server(&self) -> bool {
+        match self.role {
+            Role::Client(..) => false,
+            Role::Server => true
+        }
+    }
+
+    /// Writes as much of the pending output as possible,
+    /// what remains is written on the next writable event.
+    fn write_output(&mut self) -> io::Result<()> {
+        while self.output.is_empty() == false {
+            match self.stream.write(&self.output) {
+                Ok(0) => return Err(other_io_error("failed to write to the connection")),
+                Ok(written) => { self.output.drain(..written); },
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e)
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_handshake(&mut self) -> io::Result<()> {
+        try!(self.write_output());
+
+        if self.output.is_empty() {
+            Ok(())
+        } else {
+            Err(would_block_io_error("websocket handshake partially sent"))
+        }
+    }
+
+    /// Control frames and the rest of the handshake are written before the message frame.
+    fn run_send_operation(&mut self, mut send_operation: SendOperation) -> io::Result<bool> {
+        try!(self.write_output());
+
+        if self.output.is_empty() && try!(send_operation.run(&mut self.stream)) {
+            try!(self.write_output());
+            Ok(true)
+        } else {
+            self.send_operation = Some(send_operation);
+            Ok(false)
+        }
+    }
+
+    /// Replies to control frames are queued, and can not be written while a message frame is being sent.
+    fn run_recv_operation(&mut self, mut recv_operation: RecvOperation) -> io::Result<Option<Message>> {
+        let received = recv_operation.run(&mut self.stream);
+
+        for (opcode, payload) in recv_operation.take_replies() {
+            let frame = create_control_frame(opcode, &payload, self.mask());
+
+            self.output.extend_from_slice(&frame);
+        }
+
+        try!(self.flush_output());
+
+        match try!(received) {
+            Some(msg) => Ok(Some(msg)),
+            None => {
+                self.recv_operation = Some(recv_operation);
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Drop for WsPipeStub {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Sender for WsPipeStub                                                     */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Sender for WsPipeStub {
+    fn start_send(&mut self, msg: Rc<Message>) -> io::Result<bool> {
+        let send_operation = SendOperation::new(msg, self.mask());
+
+        self.run_send_operation(send_operation)
+    }
+
+    fn resume_send(&mut self) -> io::Result<bool> {
+        if let Some(send_operation) = self.send_operation.take() {
+            self.run_send_operation(send_operation)
+        } else {
+            Err(other_io_error("Cannot resume send: no pending operation"))
+        }
+    }
+
+    fn has_pending_send(&self) -> bool {
+        self.send_operation.is_some()
+    }
+
+    fn flush_output(&mut self) -> io::Result<()> {
+        if self.send_operation.is_some() {
+            Ok(())
+        } else {
+            self.write_output()
+        }
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Receiver for WsPipeStub                                                   */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Receiver for WsPipeStub {
+    fn start_recv(&mut self) -> io::Result<Option<Message>> {
+        let recv_operation = RecvOperation::new(self.recv_max_size, self.is_server());
+
+        self.run_recv_operation(recv_operation)
+    }
+
+    fn resume_recv(&mut self) -> io::Result<Option<Message>> {
+        if let Some(recv_operation) = self.recv_operation.take() {
+            self.run_recv_operation(recv_operation)
+        } else {
+            Err(other_io_error("Cannot resume recv: no pending operation"))
+        }
+    }
+
+    fn has_pending_recv(&self) -> bool {
+        self.recv_operation.is_some()
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Handshake for WsPipeStub                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+/// The http upgrade is driven by the client, so on the server side
+/// the response is sent only once the request has been received and checked.
+/// A request partially sent is resumed on the next call, while the rest of
+/// a response is written once the pipe is active.
+impl Handshake for WsPipeStub {
+    fn send_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
+        let (proto_id, _) = pids;
+
+        if self.output.is_empty() {
+            let request = match self.role {
+                Role::Client(ref host, ref path, ref key) => {
+                    let protocol = try!(sub_protocol(proto_id));
+
+                    create_request(host, path, key, &protocol)
+                },
+                Role::Server => return Ok(())
+            };
+
+            self.output.extend_from_slice(request.as_bytes());
+        }
+
+        self.write_handshake()
+    }
+    fn recv_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
+        let (proto_id, peer_proto_id) = pids;
+
+        match self.role {
+            Role::Client(_, _, ref key) => {
+                let protocol = try!(sub_protocol(proto_id));
+                let response = try!(self.handshake_reader.read(&mut self.stream));
+
+                check_response(response, key, &protocol)
+            },
+            Role::Server => {
+                let protocol = try!(sub_protocol(peer_proto_id));
+                let checked = {
+                    let request = try!(self.handshake_reader.read(&mut self.stream));
+
+                    check_request(request, &protocol)
+                };
+
+                match checked {
+                    Ok(key) => {
+                        self.output.extend_from_slice(create_response(&key, &protocol).as_bytes());
+                        self.write_output()
+                    },
+                    Err(e) => {
+                        self.output.extend_from_slice(create_failure_response().as_bytes());
+                        let _ = self.write_output();
+
+                        Err(e)
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl AsyncPipeStub for WsPipeStub {
+    fn peer_addr(&self) -> Option<String> {
+        self.stream.peer_addr().ok().map(|addr| addr.to_string())
+    }
+}
@@ -0,0 +1,66 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::time::Duration;
+pub use std::thread;
+pub use std::io;
+
+pub use futures::{Future, Stream};
+pub use futures::stream;
+
+pub use scaproust::*;
+
+pub use super::urls;
+pub use super::{sleep_some, make_timeout};
+
+describe! async_socket {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let url = urls::tcp::get();
+
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        let mut push = AsyncSocket::new(push).unwrap();
+        let mut pull = AsyncSocket::new(pull).unwrap();
+    }
+
+    it "send and recv a message" {
+        push.send(vec![65, 66, 67]).wait().unwrap();
+        let received = pull.recv().wait().unwrap();
+
+        assert_eq!(vec![65, 66, 67], received)
+    }
+
+    it "recv completes once a message is sent" {
+        let sender = thread::spawn(move || {
+            sleep_some();
+            push.send(vec![65, 66, 67]).wait().unwrap();
+            push
+        });
+
+        let received = pull.recv().wait().unwrap();
+
+        assert_eq!(vec![65, 66, 67], received);
+
+        let _ = sender.join();
+    }
+
+    it "be used as a sink and a stream" {
+        let msgs = vec![Ok(Message::from_body(vec![65])), Ok(Message::from_body(vec![66]))];
+        let msgs = stream::iter::<_, Message, io::Error>(msgs);
+
+        futures::Sink::send_all(push, msgs).wait().unwrap();
+
+        let received: Vec<Vec<u8>> = pull.take(2).map(|msg| msg.into()).collect().wait().unwrap();
+
+        assert_eq!(vec![vec![65], vec![66]], received)
+    }
+}
@@ -0,0 +1,77 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::time::Duration;
+pub use std::thread;
+pub use std::io;
+pub use std::os::unix::io::RawFd;
+
+pub use scaproust::*;
+
+pub use super::urls;
+pub use super::{sleep_some, make_timeout};
+
+pub fn is_readable(fd: RawFd) -> bool {
+    let mut pollfd = ::libc::pollfd { fd: fd, events: ::libc::POLLIN, revents: 0 };
+
+    unsafe { ::libc::poll(&mut pollfd, 1, 0) == 1 }
+}
+
+describe! fd {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let url = urls::tcp::get();
+        let timeout = make_timeout();
+
+        push.set_send_timeout(timeout).expect("Failed to set send timeout !");
+        pull.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+    }
+
+    it "is not readable while the socket is not ready" {
+        let send_fd = push.send_fd().unwrap();
+        let recv_fd = pull.recv_fd().unwrap();
+
+        assert!(!is_readable(send_fd));
+        assert!(!is_readable(recv_fd));
+    }
+
+    it "is the same for each call" {
+        let first = push.send_fd().unwrap();
+        let second = push.send_fd().unwrap();
+
+        assert_eq!(first, second);
+    }
+
+    it "becomes readable when the socket can send" {
+        let send_fd = push.send_fd().unwrap();
+
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+        sleep_some();
+
+        assert!(is_readable(send_fd));
+    }
+
+    it "stays readable until the last message is received" {
+        let recv_fd = pull.recv_fd().unwrap();
+
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+        push.send(vec![65, 66, 67]).unwrap();
+        sleep_some();
+
+        assert!(is_readable(recv_fd));
+
+        pull.recv().unwrap();
+        sleep_some();
+
+        assert!(!is_readable(recv_fd));
+    }
+}
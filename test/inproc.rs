@@ -0,0 +1,130 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::time::Duration;
+pub use std::thread;
+pub use std::io;
+
+pub use scaproust::*;
+
+pub use super::urls;
+pub use super::{sleep_some, make_timeout};
+
+describe! inproc {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let url = urls::inproc::get();
+        let timeout = make_timeout();
+
+        push.set_send_timeout(timeout).expect("Failed to set send timeout !");
+        pull.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+    }
+
+    it "can exchange a message" {
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        let received = pull.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], received);
+    }
+
+    it "can connect before bind" {
+        push.connect(&url).unwrap();
+        pull.bind(&url).unwrap();
+        sleep_some();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        let received = pull.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], received);
+    }
+
+    it "can not bind the same name twice" {
+        let mut other = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+        pull.bind(&url).unwrap();
+        let err = other.bind(&url).err().expect("bind should have failed");
+
+        assert_eq!(io::ErrorKind::AddrInUse, err.kind());
+    }
+
+    it "can exchange a request and a reply" {
+        let mut req = session.create_socket::<Req>().expect("Failed to create socket !");
+        let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+
+        req.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+        rep.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+
+        rep.bind(&url).unwrap();
+        req.connect(&url).unwrap();
+
+        req.send(vec![65, 66, 67]).unwrap();
+        let request = rep.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], request);
+
+        rep.send(vec![67, 66, 65]).unwrap();
+        let reply = req.recv().unwrap();
+        assert_eq!(vec![67, 66, 65], reply);
+    }
+
+    it "can exchange a survey and a response" {
+        let mut surv = session.create_socket::<Surveyor>().expect("Failed to create socket !");
+        let mut resp = session.create_socket::<Respondent>().expect("Failed to create socket !");
+
+        surv.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+        resp.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+
+        surv.bind(&url).unwrap();
+        resp.connect(&url).unwrap();
+        sleep_some();
+
+        surv.send(vec![65, 66, 67]).unwrap();
+        let survey = resp.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], survey);
+
+        resp.send(vec![67, 66, 65]).unwrap();
+        let response = surv.recv().unwrap();
+        assert_eq!(vec![67, 66, 65], response);
+    }
+
+    it "can forward a request and a reply through a device" {
+        let mut d_req = session.create_socket::<Req>().expect("Failed to create socket !");
+        let mut d_rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+        let mut req = session.create_socket::<Req>().expect("Failed to create socket !");
+        let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+        let d_req_url = urls::inproc::get();
+
+        d_req.bind(&d_req_url).unwrap();
+        d_rep.bind(&url).unwrap();
+
+        req.connect(&url).unwrap();
+        rep.connect(&d_req_url).unwrap();
+
+        req.set_recv_timeout(timeout).unwrap();
+        rep.set_recv_timeout(timeout).unwrap();
+
+        let device = session.create_bridge_device(d_rep, d_req).unwrap();
+        let device_thread = thread::spawn(move || device.run());
+
+        sleep_some();
+
+        req.send(vec![65, 66, 67]).unwrap();
+        let request = rep.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], request);
+
+        rep.send(vec![67, 66, 65]).unwrap();
+        let reply = req.recv().unwrap();
+        assert_eq!(vec![67, 66, 65], reply);
+
+        drop(session);
+        device_thread.join().unwrap().unwrap_err();
+    }
+
+}
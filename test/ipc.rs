@@ -0,0 +1,81 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::time::Duration;
+pub use std::thread;
+pub use std::io;
+
+pub use scaproust::*;
+
+pub use super::urls;
+pub use super::{sleep_some, make_timeout};
+
+describe! ipc {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let url = urls::ipc::get();
+        let timeout = make_timeout();
+
+        push.set_send_timeout(timeout).expect("Failed to set send timeout !");
+        pull.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+    }
+
+    it "can exchange a message" {
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        let received = pull.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], received);
+    }
+
+    it "can bind again over a stale socket file" {
+        {
+            let path = &url["ipc://".len()..];
+            let _ = ::std::os::unix::net::UnixListener::bind(path).unwrap();
+        }
+
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        let received = pull.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], received);
+    }
+
+    it "remove the socket file once the endpoint is closed" {
+        let path = ::std::path::Path::new(&url["ipc://".len()..]).to_path_buf();
+        let ep = pull.bind(&url).unwrap();
+
+        assert!(path.exists());
+
+        ep.close().unwrap();
+        sleep_some();
+
+        assert_eq!(false, path.exists());
+
+        let mut other = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+        other.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+        other.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        let received = other.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], received);
+    }
+
+    it "can not connect when nobody is listening" {
+        let err = push.connect(&url).err().expect("connect should have failed");
+
+        assert_eq!(io::ErrorKind::NotFound, err.kind());
+    }
+
+}
@@ -0,0 +1,85 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::time::Duration;
+pub use std::thread;
+pub use std::io;
+
+pub use scaproust::*;
+
+pub use super::urls;
+pub use super::{sleep_some, make_timeout};
+
+describe! monitor {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let url = urls::tcp::get();
+    }
+
+    it "reports bound, accepted and opened endpoints" {
+        let monitor = pull.monitor().unwrap();
+
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+        sleep_some();
+
+        match monitor.try_recv() {
+            Ok(MonitorEvent::Bound(_, bound_url)) => assert_eq!(url, bound_url),
+            _ => assert!(false, "Monitor should have received a bound event")
+        }
+        match monitor.try_recv() {
+            Ok(MonitorEvent::Accepted(..)) => {},
+            _ => assert!(false, "Monitor should have received an accepted event")
+        }
+        match monitor.try_recv() {
+            Ok(MonitorEvent::Opened(_)) => {},
+            _ => assert!(false, "Monitor should have received an opened event")
+        }
+    }
+
+    it "reports closed pipe and scheduled reconnect" {
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+        sleep_some();
+
+        let monitor = push.monitor().unwrap();
+
+        drop(pull);
+        sleep_some();
+
+        // the broken connection is only detected when writing to it
+        push.set_send_timeout(make_timeout()).unwrap();
+        let _ = push.send(vec![65, 66, 67]);
+        let _ = push.send(vec![65, 66, 67]);
+
+        match monitor.try_recv() {
+            Ok(MonitorEvent::Closed(..)) => {},
+            _ => assert!(false, "Monitor should have received a closed event")
+        }
+        match monitor.try_recv() {
+            Ok(MonitorEvent::ReconnectScheduled(..)) => {},
+            _ => assert!(false, "Monitor should have received a reconnect event")
+        }
+    }
+
+    it "reports rejected handshake" {
+        let mut push2 = session.create_socket::<Push>().expect("Failed to create socket !");
+        let monitor = push2.monitor().unwrap();
+
+        push.bind(&url).unwrap();
+        push2.connect(&url).unwrap();
+        sleep_some();
+
+        match monitor.try_recv() {
+            Ok(MonitorEvent::HandshakeRejected(..)) => {},
+            _ => assert!(false, "Monitor should have received a handshake event")
+        }
+    }
+}
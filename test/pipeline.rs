@@ -48,4 +48,24 @@ describe! can {
 
         assert_eq!(vec![65, 66, 67], received)
     }
+
+    it "fail to send without waiting when no peer is connected" {
+        let not_sent = push.try_send(vec![65, 66, 67]).unwrap_err();
+
+        assert_eq!(io::ErrorKind::WouldBlock, not_sent.kind());
+    }
+
+    it "receive without waiting once a message is available" {
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        let not_received = pull.try_recv().unwrap_err();
+        assert_eq!(io::ErrorKind::WouldBlock, not_received.kind());
+
+        push.try_send(vec![65, 66, 67]).unwrap();
+        sleep_some();
+
+        let received = pull.try_recv().unwrap();
+        assert_eq!(vec![65, 66, 67], received)
+    }
 }
\ No newline at end of file
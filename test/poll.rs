@@ -0,0 +1,67 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::time::Duration;
+pub use std::thread;
+pub use std::io;
+
+pub use scaproust::*;
+
+pub use super::urls;
+pub use super::{sleep_some, make_timeout};
+
+describe! poll {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let url = urls::tcp::get();
+        let timeout = Duration::from_millis(::SYS_TIMEOUT);
+    }
+
+    it "times out when no socket is ready" {
+        let mut requests = [PollRequest::new_for_send(&push), PollRequest::new_for_recv(&pull)];
+        let ready = session.poll(&mut requests, timeout).unwrap();
+
+        assert_eq!(0, ready);
+        assert!(!requests[0].can_send());
+        assert!(!requests[1].can_recv());
+    }
+
+    it "reports a connected socket can send" {
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        let mut requests = [PollRequest::new_for_send(&push), PollRequest::new_for_recv(&pull)];
+        let ready = session.poll(&mut requests, timeout).unwrap();
+
+        assert_eq!(1, ready);
+        assert!(requests[0].can_send());
+        assert!(!requests[1].can_recv());
+    }
+
+    it "reports a socket can recv once a message is available" {
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        let sender = thread::spawn(move || {
+            sleep_some();
+            push.send(vec![65, 66, 67]).unwrap();
+            push
+        });
+
+        let mut requests = [PollRequest::new_for_recv(&pull)];
+        let ready = session.poll(&mut requests, Duration::from_millis(::SYS_TIMEOUT * 4)).unwrap();
+
+        assert_eq!(1, ready);
+        assert!(requests[0].can_recv());
+        assert_eq!(vec![65, 66, 67], pull.try_recv().unwrap());
+
+        let _ = sender.join();
+    }
+}
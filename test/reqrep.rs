@@ -84,4 +84,335 @@ describe! can {
         let received_reply = req.recv().unwrap();
         assert_eq!(vec![66, 65, 67], received_reply);
     }
-}
\ No newline at end of file
+
+    it "poll for a reply without waiting" {
+        rep.bind(&url).unwrap();
+        req.connect(&url).unwrap();
+
+        req.send(vec![65, 66, 67]).unwrap();
+        let not_received = req.try_recv().unwrap_err();
+        assert_eq!(io::ErrorKind::WouldBlock, not_received.kind());
+
+        let received_request = rep.recv().unwrap();
+        rep.send(received_request).unwrap();
+        sleep_some();
+
+        let received_reply = req.try_recv().unwrap();
+        assert_eq!(vec![65, 66, 67], received_reply);
+    }
+
+    it "give up when the request has been resent too many times" {
+        rep.bind(&url).unwrap();
+        req.connect(&url).unwrap();
+        req.set_option(ConfigOption::ReqResendIvl(Duration::from_millis(50))).unwrap();
+        req.set_option(ConfigOption::ReqResendMax(Some(1))).unwrap();
+
+        req.send(vec![65, 66, 67]).unwrap();
+
+        let received_request1 = rep.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], received_request1);
+        let received_request2 = rep.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], received_request2);
+
+        let not_received = req.recv().unwrap_err();
+        assert_eq!(io::ErrorKind::ConnectionAborted, not_received.kind());
+    }
+
+    it "resend the request to another peer" {
+        let mut rep2 = session.create_socket::<Rep>().expect("Failed to create socket !");
+        let url2 = urls::tcp::get();
+
+        rep2.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+        rep.bind(&url).unwrap();
+        rep2.bind(&url2).unwrap();
+        req.connect(&url).unwrap();
+        req.connect(&url2).unwrap();
+        req.set_option(ConfigOption::ReqResendIvl(Duration::from_millis(100))).unwrap();
+        req.set_option(ConfigOption::ReqResendToOtherPeer(true)).unwrap();
+        sleep_some();
+
+        req.send(vec![65, 66, 67]).unwrap();
+
+        let received_request1 = rep.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], received_request1);
+        let received_request2 = rep2.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], received_request2);
+
+        rep2.send(vec![66, 65, 67]).unwrap();
+        let received_reply = req.recv().unwrap();
+        assert_eq!(vec![66, 65, 67], received_reply);
+    }
+}
+
+describe! raw {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+        let mut front = session.create_raw_socket::<Rep>().expect("Failed to create socket !");
+        let mut back = session.create_raw_socket::<Req>().expect("Failed to create socket !");
+        let mut req = session.create_socket::<Req>().expect("Failed to create socket !");
+        let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+        let front_url = urls::tcp::get();
+        let back_url = urls::tcp::get();
+        let timeout = make_timeout();
+
+        front.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+        back.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+        req.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+        rep.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+    }
+
+    it "receive a request with its header" {
+        front.bind(&front_url).unwrap();
+        req.connect(&front_url).unwrap();
+
+        req.send(vec![65, 66, 67]).unwrap();
+        let received = front.recv_msg().unwrap();
+
+        // pipe id followed by the request id
+        assert_eq!(8, received.get_header().len());
+        assert!(received.get_header()[4] & 0x80 != 0);
+        assert_eq!(&[65, 66, 67], received.get_body());
+    }
+
+    it "refuse to send a reply without pipe id" {
+        front.bind(&front_url).unwrap();
+        req.connect(&front_url).unwrap();
+
+        let not_sent = front.send_msg(Message::from_body(vec![66, 65, 67])).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, not_sent.kind());
+    }
+
+    it "forward requests and replies through a proxy" {
+        front.bind(&front_url).unwrap();
+        back.bind(&back_url).unwrap();
+        rep.connect(&back_url).unwrap();
+        req.connect(&front_url).unwrap();
+        sleep_some();
+
+        req.send(vec![65, 66, 67]).unwrap();
+        let request = front.recv_msg().unwrap();
+        back.send_msg(request).unwrap();
+
+        let received_request = rep.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], received_request);
+        rep.send(vec![66, 65, 67]).unwrap();
+
+        let reply = back.recv_msg().unwrap();
+        front.send_msg(reply).unwrap();
+
+        let received_reply = req.recv().unwrap();
+        assert_eq!(vec![66, 65, 67], received_reply);
+    }
+
+    it "receive the replies in turn from each peer" {
+        let mut reps = Vec::new();
+
+        back.set_send_timeout(timeout).expect("Failed to set send timeout !");
+
+        for _ in 0..4 {
+            let mut server = session.create_socket::<Rep>().expect("Failed to create socket !");
+            let url = urls::tcp::get();
+
+            server.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+            server.set_send_timeout(timeout).expect("Failed to set send timeout !");
+            server.bind(&url).unwrap();
+            back.connect(&url).unwrap();
+            reps.push(server);
+        }
+        sleep_some();
+
+        for i in 0..12u8 {
+            back.send_msg(Message::from_header_and_body(vec![0x80, 0, 0, i], vec![i])).unwrap();
+        }
+
+        for _ in 0..3 {
+            for (i, server) in reps.iter_mut().enumerate() {
+                let request = server.recv().unwrap();
+                server.send(vec![i as u8, request[0]]).unwrap();
+            }
+        }
+        sleep_some();
+
+        for _ in 0..3 {
+            let mut origins: Vec<u8> = (0..4).map(|_| back.recv_msg().unwrap().get_body()[0]).collect();
+
+            origins.sort();
+            assert_eq!(vec![0, 1, 2, 3], origins);
+        }
+    }
+}
+
+describe! contexts {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+        let mut req = session.create_socket::<Req>().expect("Failed to create socket !");
+        let mut server = session.create_raw_socket::<Rep>().expect("Failed to create socket !");
+        let url = urls::tcp::get();
+        let timeout = make_timeout();
+
+        req.set_send_timeout(timeout).expect("Failed to set send timeout !");
+        req.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+        server.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+    }
+
+    it "have their own outstanding request" {
+        server.bind(&url).unwrap();
+        req.connect(&url).unwrap();
+        sleep_some();
+
+        let mut ctx1 = req.create_context().unwrap();
+        let mut ctx2 = req.create_context().unwrap();
+
+        ctx1.send(vec![65]).unwrap();
+        ctx2.send(vec![66]).unwrap();
+
+        let request1 = server.recv_msg().unwrap();
+        let request2 = server.recv_msg().unwrap();
+
+        // reply in reverse order
+        server.send_msg(request2).unwrap();
+        server.send_msg(request1).unwrap();
+
+        assert_eq!(vec![65], ctx1.recv().unwrap());
+        assert_eq!(vec![66], ctx2.recv().unwrap());
+    }
+
+    it "do not interfere with the socket request" {
+        server.bind(&url).unwrap();
+        req.connect(&url).unwrap();
+        sleep_some();
+
+        let mut ctx = req.create_context().unwrap();
+
+        req.send(vec![65]).unwrap();
+        ctx.send(vec![66]).unwrap();
+
+        let request1 = server.recv_msg().unwrap();
+        let request2 = server.recv_msg().unwrap();
+
+        server.send_msg(request2).unwrap();
+        server.send_msg(request1).unwrap();
+
+        assert_eq!(vec![66], ctx.recv().unwrap());
+        assert_eq!(vec![65], req.recv().unwrap());
+    }
+
+    it "refuse to receive a reply before sending a request" {
+        let mut ctx = req.create_context().unwrap();
+
+        let not_received = ctx.recv().unwrap_err();
+        assert_eq!(io::ErrorKind::Other, not_received.kind());
+    }
+
+    it "be used from other threads" {
+        server.bind(&url).unwrap();
+        req.connect(&url).unwrap();
+        sleep_some();
+
+        let mut ctx = req.create_context().unwrap();
+        let client = thread::spawn(move || {
+            ctx.send(vec![65, 66, 67]).unwrap();
+            ctx.recv().unwrap()
+        });
+
+        let request = server.recv_msg().unwrap();
+        server.send_msg(request).unwrap();
+
+        assert_eq!(vec![65, 66, 67], client.join().unwrap());
+    }
+}
+
+describe! rep_contexts {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+        let mut req = session.create_socket::<Req>().expect("Failed to create socket !");
+        let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+        let url = urls::tcp::get();
+        let timeout = make_timeout();
+
+        req.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+        rep.set_send_timeout(timeout).expect("Failed to set send timeout !");
+        rep.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+    }
+
+    it "answer requests in any order" {
+        rep.bind(&url).unwrap();
+        req.connect(&url).unwrap();
+        sleep_some();
+
+        let mut req_ctx1 = req.create_context().unwrap();
+        let mut req_ctx2 = req.create_context().unwrap();
+        let mut rep_ctx1 = rep.create_context().unwrap();
+        let mut rep_ctx2 = rep.create_context().unwrap();
+
+        req_ctx1.send(vec![65]).unwrap();
+        req_ctx2.send(vec![66]).unwrap();
+
+        let request1 = rep_ctx1.recv().unwrap();
+        let request2 = rep_ctx2.recv().unwrap();
+
+        rep_ctx2.send(request2).unwrap();
+        rep_ctx1.send(request1).unwrap();
+
+        assert_eq!(vec![65], req_ctx1.recv().unwrap());
+        assert_eq!(vec![66], req_ctx2.recv().unwrap());
+    }
+
+    it "refuse to send a reply before receiving a request" {
+        let mut ctx = rep.create_context().unwrap();
+
+        let not_sent = ctx.send(vec![66, 65, 67]).unwrap_err();
+        assert_eq!(io::ErrorKind::Other, not_sent.kind());
+    }
+
+    it "handle requests from other threads" {
+        rep.bind(&url).unwrap();
+        req.connect(&url).unwrap();
+        sleep_some();
+
+        let mut ctx = rep.create_context().unwrap();
+        let worker = thread::spawn(move || {
+            let request = ctx.recv().unwrap();
+            ctx.send(request).unwrap();
+        });
+
+        req.send(vec![65, 66, 67]).unwrap();
+        assert_eq!(vec![65, 66, 67], req.recv().unwrap());
+
+        worker.join().unwrap();
+    }
+
+    it "fail the replies once the requester is gone" {
+        rep.bind(&url).unwrap();
+        req.connect(&url).unwrap();
+        sleep_some();
+
+        let mut req_ctx1 = req.create_context().unwrap();
+        let mut req_ctx2 = req.create_context().unwrap();
+        let mut rep_ctx1 = rep.create_context().unwrap();
+        let mut rep_ctx2 = rep.create_context().unwrap();
+
+        req_ctx1.send(vec![65]).unwrap();
+        req_ctx2.send(vec![66]).unwrap();
+
+        let request1 = rep_ctx1.recv().unwrap();
+        let request2 = rep_ctx2.recv().unwrap();
+
+        drop(req_ctx1);
+        drop(req_ctx2);
+        drop(req);
+        sleep_some();
+
+        let not_sent1 = rep_ctx1.send(request1).unwrap_err();
+        let not_sent2 = rep_ctx2.send(request2).unwrap_err();
+
+        assert_eq!(io::ErrorKind::ConnectionAborted, not_sent1.kind());
+        assert_eq!(io::ErrorKind::ConnectionAborted, not_sent2.kind());
+    }
+}
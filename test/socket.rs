@@ -103,3 +103,458 @@ describe! recv {
 
 }
 
+
+struct Unreachable;
+
+impl transport::Transport for Unreachable {
+    fn connect(&self, _: &str, _: (u16, u16), _: &core::config::Config) -> io::Result<Box<transport::pipe::Pipe>> {
+        Err(io::Error::new(io::ErrorKind::ConnectionRefused, "unreachable"))
+    }
+    fn bind(&self, _: &str, _: (u16, u16), _: &core::config::Config) -> io::Result<Box<transport::acceptor::Acceptor>> {
+        Err(io::Error::new(io::ErrorKind::AddrNotAvailable, "unreachable"))
+    }
+}
+
+describe! session {
+
+    before_each {
+        let _ = ::env_logger::init();
+    }
+
+    it "can use a custom transport" {
+        let mut session = SessionBuilder::new().
+            with_transport("custom", Box::new(Unreachable)).
+            start().
+            expect("Failed to create session !");
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+        let err = push.connect("custom://somewhere").err().unwrap();
+        assert_eq!(io::ErrorKind::ConnectionRefused, err.kind());
+
+        let err = pull.bind("custom://somewhere").err().unwrap();
+        assert_eq!(io::ErrorKind::AddrNotAvailable, err.kind());
+    }
+
+    it "should return an error for an unknown scheme" {
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+
+        let err = push.connect("unknown://127.0.0.1:5454").err().unwrap();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    }
+
+}
+
+describe! recv_max_size {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let url = urls::tcp::get();
+        let timeout = make_timeout();
+
+        push.set_send_timeout(timeout).unwrap();
+        pull.set_recv_timeout(timeout).unwrap();
+    }
+
+    it "should drop a message exceeding the limit" {
+        pull.set_option(ConfigOption::RecvMaxSize(2)).unwrap();
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        let err = pull.recv().unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+
+        push.send(vec![65, 66]).unwrap();
+        let received = pull.recv().unwrap();
+        assert_eq!(vec![65, 66], received);
+    }
+
+    it "should report the pipe closed because of the limit" {
+        let monitor = pull.monitor().unwrap();
+
+        pull.set_option(ConfigOption::RecvMaxSize(2)).unwrap();
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        let _ = pull.recv();
+
+        let too_long = monitor.try_iter().any(|event| match event {
+            MonitorEvent::MessageTooLong(_) => true,
+            _ => false
+        });
+        assert!(too_long, "Monitor should have received a message too long event");
+    }
+
+    it "can be unlimited" {
+        let payload = vec![65; 2 * 1024 * 1024];
+
+        pull.set_option(ConfigOption::RecvMaxSize(0)).unwrap();
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        push.send(payload.clone()).unwrap();
+        let received = pull.recv().unwrap();
+        assert_eq!(payload, received);
+    }
+
+}
+
+describe! linger {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let url = urls::tcp::get();
+        let payload = vec![65; 32 * 1024 * 1024];
+
+        pull.set_option(ConfigOption::RecvMaxSize(0)).unwrap();
+        push.set_send_timeout(Some(Duration::from_millis(250))).unwrap();
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+        sleep_some();
+    }
+
+    it "should flush a pending message after the socket is dropped" {
+        pull.set_recv_timeout(Some(Duration::from_secs(2))).unwrap();
+
+        let err = push.send(payload.clone()).unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+
+        drop(push);
+
+        let received = pull.recv().unwrap();
+        assert_eq!(payload, received);
+    }
+
+    it "should drop a pending message when zero" {
+        pull.set_recv_timeout(make_timeout()).unwrap();
+        push.set_option(ConfigOption::Linger(Duration::from_millis(0))).unwrap();
+
+        let err = push.send(payload.clone()).unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+
+        drop(push);
+
+        let err = pull.recv().unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+    }
+
+}
+
+describe! tcp_options {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let url = urls::tcp::get();
+        let timeout = make_timeout();
+
+        push.set_send_timeout(timeout).unwrap();
+        pull.set_recv_timeout(timeout).unwrap();
+    }
+
+    it "can be applied to connected and accepted streams" {
+        for socket in vec![&mut push, &mut pull] {
+            socket.set_tcp_nodelay(true).unwrap();
+            socket.set_option(ConfigOption::TcpKeepAlive(Some(Duration::from_secs(60)))).unwrap();
+            socket.set_option(ConfigOption::TcpKeepAliveInterval(Some(Duration::from_secs(10)))).unwrap();
+            socket.set_option(ConfigOption::TcpKeepAliveCount(Some(5))).unwrap();
+            socket.set_option(ConfigOption::TcpSendBufferSize(Some(128 * 1024))).unwrap();
+            socket.set_option(ConfigOption::TcpRecvBufferSize(Some(128 * 1024))).unwrap();
+        }
+
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        let received = pull.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], received);
+    }
+
+    it "can be applied to websocket streams" {
+        let url = urls::ws::get();
+
+        for socket in vec![&mut push, &mut pull] {
+            socket.set_tcp_nodelay(true).unwrap();
+            socket.set_option(ConfigOption::TcpKeepAlive(Some(Duration::from_secs(60)))).unwrap();
+            socket.set_option(ConfigOption::TcpSendBufferSize(Some(128 * 1024))).unwrap();
+        }
+
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        let received = pull.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], received);
+    }
+
+}
+
+describe! endpoint {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let url = urls::tcp::get();
+        let timeout = make_timeout();
+
+        push.set_send_timeout(timeout).unwrap();
+        pull.set_recv_timeout(timeout).unwrap();
+    }
+
+    it "can be closed after bind so the address can be bound again" {
+        let ep = pull.bind(&url).unwrap();
+
+        ep.close().unwrap();
+
+        let mut other = session.create_socket::<Pull>().expect("Failed to create socket !");
+        other.bind(&url).unwrap();
+    }
+
+    it "can be closed after connect so no message is sent to the peer" {
+        pull.bind(&url).unwrap();
+        let ep = push.connect(&url).unwrap();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        let received = pull.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], received);
+
+        ep.close().unwrap();
+
+        let err = push.send(vec![65, 66, 67]).unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+    }
+
+    it "can be closed while waiting for a reconnection" {
+        let ep = push.connect(&url).unwrap();
+
+        sleep_some();
+        ep.close().unwrap();
+    }
+
+}
+
+describe! get_option {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+    }
+
+    it "returns the default value of a generic option" {
+        let mut push = session.create_socket::<Push>().unwrap();
+        let linger = push.get_option(ConfigOptionKind::Linger).unwrap();
+
+        assert_eq!(ConfigOption::Linger(Duration::from_secs(1)), linger);
+    }
+
+    it "returns the value of a generic option that was set" {
+        let mut push = session.create_socket::<Push>().unwrap();
+
+        push.set_send_timeout(Some(Duration::from_millis(250))).unwrap();
+        push.set_send_priority(2).unwrap();
+
+        let timeout = push.get_option(ConfigOptionKind::SendTimeout).unwrap();
+        let priority = push.get_option(ConfigOptionKind::SendPriority).unwrap();
+
+        assert_eq!(ConfigOption::SendTimeout(Some(Duration::from_millis(250))), timeout);
+        assert_eq!(ConfigOption::SendPriority(2), priority);
+    }
+
+    it "returns the value of a protocol option" {
+        let mut req = session.create_socket::<Req>().unwrap();
+        let mut surv = session.create_socket::<Surveyor>().unwrap();
+        let mut sub = session.create_socket::<Sub>().unwrap();
+
+        req.set_option(ConfigOption::ReqResendIvl(Duration::from_millis(500))).unwrap();
+        sub.set_option(ConfigOption::Subscribe(String::from("B"))).unwrap();
+        sub.set_option(ConfigOption::Subscribe(String::from("A"))).unwrap();
+
+        let resend_ivl = req.get_option(ConfigOptionKind::ReqResendIvl).unwrap();
+        let deadline = surv.get_option(ConfigOptionKind::SurveyDeadline).unwrap();
+        let subscriptions = sub.get_option(ConfigOptionKind::Subscriptions).unwrap();
+
+        assert_eq!(ConfigOption::ReqResendIvl(Duration::from_millis(500)), resend_ivl);
+        assert_eq!(ConfigOption::SurveyDeadline(Duration::from_secs(1)), deadline);
+        assert_eq!(ConfigOption::Subscriptions(vec![String::from("A"), String::from("B")]), subscriptions);
+    }
+
+    it "returns the default resend policy of a req socket" {
+        let mut req = session.create_socket::<Req>().unwrap();
+
+        let resend_ivl_max = req.get_option(ConfigOptionKind::ReqResendIvlMax).unwrap();
+        let resend_max = req.get_option(ConfigOptionKind::ReqResendMax).unwrap();
+        let resend_to_other_peer = req.get_option(ConfigOptionKind::ReqResendToOtherPeer).unwrap();
+
+        assert_eq!(ConfigOption::ReqResendIvlMax(None), resend_ivl_max);
+        assert_eq!(ConfigOption::ReqResendMax(None), resend_max);
+        assert_eq!(ConfigOption::ReqResendToOtherPeer(false), resend_to_other_peer);
+    }
+
+    it "should return an error for an option of another protocol" {
+        let mut push = session.create_socket::<Push>().unwrap();
+        let err = push.get_option(ConfigOptionKind::ReqResendIvl).unwrap_err();
+
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    }
+
+    it "should return an error for a tls certificate" {
+        let mut push = session.create_socket::<Push>().unwrap();
+
+        push.set_option(ConfigOption::TlsCertificate(vec![65])).unwrap();
+
+        let err = push.get_option(ConfigOptionKind::TlsCertificate).unwrap_err();
+
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        assert_eq!(ConfigOptionKind::TlsCertificate, ConfigOption::TlsCertificate(vec![65]).kind());
+        assert_eq!(ConfigOptionKind::Subscriptions, ConfigOption::Subscribe(String::from("A")).kind());
+    }
+}
+
+describe! buffers {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+        let url = urls::tcp::get();
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+        push.set_send_timeout(make_timeout()).unwrap();
+        pull.set_recv_timeout(make_timeout()).unwrap();
+    }
+
+    it "let sends complete before the peer receives" {
+        push.set_option(ConfigOption::SendBufferSize(1024)).unwrap();
+        pull.set_option(ConfigOption::RecvBufferSize(1024)).unwrap();
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+        sleep_some();
+
+        for i in 0..20u8 {
+            push.send(vec![i; 100]).unwrap();
+        }
+
+        for i in 0..20u8 {
+            assert_eq!(vec![i; 100], pull.recv().unwrap());
+        }
+    }
+
+    it "accept messages larger than the buffer" {
+        push.set_option(ConfigOption::SendBufferSize(16)).unwrap();
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+        sleep_some();
+
+        push.send(vec![65; 4096]).unwrap();
+        assert_eq!(vec![65; 4096], pull.recv().unwrap());
+    }
+
+    it "are flushed when the socket is closed" {
+        push.set_option(ConfigOption::SendBufferSize(64 * 1024)).unwrap();
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+        sleep_some();
+
+        for i in 0..20u8 {
+            push.send(vec![i; 1000]).unwrap();
+        }
+        drop(push);
+
+        for i in 0..20u8 {
+            assert_eq!(vec![i; 1000], pull.recv().unwrap());
+        }
+    }
+}
+
+describe! endpoint_options {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull1 = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let mut pull2 = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let url1 = urls::tcp::get();
+        let url2 = urls::tcp::get();
+
+        pull1.set_recv_timeout(make_timeout()).unwrap();
+        pull2.set_recv_timeout(make_timeout()).unwrap();
+        pull1.bind(&url1).unwrap();
+        pull2.bind(&url2).unwrap();
+    }
+
+    it "can set the send priority of a single endpoint" {
+        let low = EndpointOptions::new().with(ConfigOption::SendPriority(15));
+        let high = EndpointOptions::new().with(ConfigOption::SendPriority(1));
+
+        push.connect_with(&url1, low).unwrap();
+        push.connect_with(&url2, high).unwrap();
+        sleep_some();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        assert_eq!(vec![65, 66, 67], pull2.recv().unwrap());
+
+        let priority = push.get_option(ConfigOptionKind::SendPriority).unwrap();
+        assert_eq!(ConfigOption::SendPriority(8), priority);
+    }
+
+    it "reject the socket wide options" {
+        let options = EndpointOptions::new().with(ConfigOption::Linger(Duration::from_millis(0)));
+        let err = push.connect_with(&url1, options).unwrap_err();
+
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    }
+}
+
+describe! endpoint_info {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let url = urls::tcp::get();
+    }
+
+    it "reports the url and state of each endpoint" {
+        let bound = pull.bind(&url).unwrap();
+        let connected = push.connect(&url).unwrap();
+        sleep_some();
+
+        let info = connected.info().unwrap();
+        assert_eq!(url, info.url);
+        assert_eq!(EndpointState::Active, info.state);
+
+        let info = bound.info().unwrap();
+        assert_eq!(url, info.url);
+        assert_eq!(EndpointState::Listening, info.state);
+        assert_eq!(1, info.accepted_pipes);
+        assert_eq!(1, info.peer_addrs.len());
+    }
+
+    it "reports the endpoints waiting for a retry" {
+        push.connect(&url).unwrap();
+        sleep_some();
+
+        let infos = push.endpoints().unwrap();
+        assert_eq!(1, infos.len());
+
+        match infos[0].state {
+            EndpointState::Retrying(_) => {},
+            ref state => panic!("unexpected state {:?}", state)
+        }
+    }
+}
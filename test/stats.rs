@@ -0,0 +1,112 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::time::Duration;
+pub use std::thread;
+pub use std::io;
+
+pub use scaproust::*;
+
+pub use super::urls;
+pub use super::{sleep_some, make_timeout};
+
+describe! stats {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let url = urls::tcp::get();
+        let timeout = make_timeout();
+
+        push.set_send_timeout(timeout).expect("Failed to set send timeout !");
+        pull.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+    }
+
+    it "are all zero for a new socket" {
+        assert_eq!(Statistics::default(), push.statistics().unwrap());
+    }
+
+    it "count connections and messages" {
+        let listener = pull.bind(&url).unwrap();
+        let connector = push.connect(&url).unwrap();
+        sleep_some();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        pull.recv().unwrap();
+
+        let push_stats = push.statistics().unwrap();
+        let pull_stats = pull.statistics().unwrap();
+
+        assert_eq!(1, push_stats.established_connections);
+        assert_eq!(1, push_stats.current_connections);
+        assert_eq!(1, push_stats.messages_sent);
+        assert_eq!(3, push_stats.bytes_sent);
+        assert_eq!(1, pull_stats.accepted_connections);
+        assert_eq!(1, pull_stats.messages_received);
+        assert_eq!(3, pull_stats.bytes_received);
+
+        assert_eq!(1, connector.statistics().unwrap().messages_sent);
+        assert_eq!(1, listener.statistics().unwrap().messages_received);
+    }
+
+    it "count connect errors" {
+        push.connect("tcp://127.0.0.1:1").unwrap();
+        sleep_some();
+
+        let stats = push.statistics().unwrap();
+
+        assert!(stats.connect_errors > 0);
+        assert_eq!(0, stats.current_connections);
+    }
+
+    it "count bind errors" {
+        pull.bind(&url).unwrap();
+
+        assert!(push.bind(&url).is_err());
+        assert_eq!(1, push.statistics().unwrap().bind_errors);
+    }
+
+    it "keep the traffic of closed endpoints" {
+        pull.bind(&url).unwrap();
+        let connector = push.connect(&url).unwrap();
+        sleep_some();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        pull.recv().unwrap();
+        connector.close().unwrap();
+
+        assert_eq!(1, push.statistics().unwrap().messages_sent);
+    }
+
+    it "keep the traffic of broken connections" {
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+        sleep_some();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        pull.recv().unwrap();
+        drop(pull);
+        sleep_some();
+
+        let mut other = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+        other.set_recv_timeout(timeout).unwrap();
+        other.bind(&url).unwrap();
+        sleep_some();
+        sleep_some();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        other.recv().unwrap();
+
+        let stats = push.statistics().unwrap();
+
+        assert_eq!(1, stats.broken_connections);
+        assert_eq!(2, stats.messages_sent);
+        assert_eq!(6, stats.bytes_sent);
+    }
+}
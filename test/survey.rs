@@ -73,4 +73,121 @@ describe! can {
         let not_sent = resp1.send(vec![66, 65, 67]).unwrap_err();
         assert_eq!(io::ErrorKind::Other, not_sent.kind());
     }
-}
\ No newline at end of file
+
+    it "collect the responses until the deadline" {
+        surv.bind(&url).unwrap();
+        resp1.connect(&url).unwrap();
+        resp2.connect(&url).unwrap();
+
+        sleep_some();
+
+        surv.send(vec![65, 66, 67]).unwrap();
+        resp1.recv().unwrap();
+        resp2.recv().unwrap();
+        resp1.send(vec![66, 67, 65]).unwrap();
+        resp2.send(vec![65, 67, 66]).unwrap();
+
+        let responses = surv.collect(Duration::from_millis(100)).unwrap();
+        assert_eq!(2, responses.len());
+
+        let not_received = surv.recv().unwrap_err();
+        assert_eq!(io::ErrorKind::Other, not_received.kind());
+    }
+
+    it "return a timeout error when the survey deadline is reached" {
+        surv.bind(&url).unwrap();
+        resp1.connect(&url).unwrap();
+        surv.set_recv_timeout(None).unwrap();
+        surv.set_option(ConfigOption::SurveyDeadline(Duration::from_millis(100))).unwrap();
+
+        sleep_some();
+
+        surv.send(vec![65, 66, 67]).unwrap();
+
+        let not_received = surv.recv().unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, not_received.kind());
+    }
+}
+
+describe! surveyor_contexts {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+        let mut surv = session.create_socket::<Surveyor>().expect("Failed to create socket !");
+        let mut resp = session.create_socket::<Respondent>().expect("Failed to create socket !");
+        let url = urls::tcp::get();
+        let timeout = make_timeout();
+
+        surv.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+        resp.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+    }
+
+    it "receive the responses to their own survey" {
+        surv.bind(&url).unwrap();
+        resp.connect(&url).unwrap();
+        sleep_some();
+
+        let mut ctx1 = surv.create_context().unwrap();
+        let mut ctx2 = surv.create_context().unwrap();
+        let mut resp_ctx1 = resp.create_context().unwrap();
+        let mut resp_ctx2 = resp.create_context().unwrap();
+
+        ctx1.send(vec![1]).unwrap();
+        ctx2.send(vec![2]).unwrap();
+
+        let survey1 = resp_ctx1.recv().unwrap();
+        let survey2 = resp_ctx2.recv().unwrap();
+        resp_ctx2.send(survey2).unwrap();
+        resp_ctx1.send(survey1).unwrap();
+
+        assert_eq!(vec![1], ctx1.recv().unwrap());
+        assert_eq!(vec![2], ctx2.recv().unwrap());
+    }
+
+    it "collect the responses to their own survey" {
+        surv.bind(&url).unwrap();
+        resp.connect(&url).unwrap();
+        sleep_some();
+
+        let mut ctx = surv.create_context().unwrap();
+
+        ctx.send(vec![65, 66, 67]).unwrap();
+        let survey = resp.recv().unwrap();
+        resp.send(survey).unwrap();
+
+        let responses = ctx.collect(Duration::from_millis(100)).unwrap();
+        assert_eq!(1, responses.len());
+        assert_eq!(&[65, 66, 67], responses[0].get_body());
+    }
+}
+
+describe! respondent_contexts {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+        let mut surv = session.create_socket::<Surveyor>().expect("Failed to create socket !");
+        let mut resp = session.create_socket::<Respondent>().expect("Failed to create socket !");
+        let url = urls::tcp::get();
+        let timeout = make_timeout();
+
+        surv.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+        resp.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+    }
+
+    it "answer a survey" {
+        surv.bind(&url).unwrap();
+        resp.connect(&url).unwrap();
+        sleep_some();
+
+        let mut ctx = resp.create_context().unwrap();
+
+        surv.send(vec![65, 66, 67]).unwrap();
+        let survey = ctx.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], survey);
+
+        ctx.send(vec![67, 66, 65]).unwrap();
+        assert_eq!(vec![67, 66, 65], surv.recv().unwrap());
+    }
+}
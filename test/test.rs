@@ -11,6 +11,10 @@
 extern crate log;
 extern crate env_logger;
 extern crate scaproust;
+#[cfg(not(windows))]
+extern crate libc;
+#[cfg(feature = "futures")]
+extern crate futures;
 
 mod socket;
 mod fair_queue;
@@ -21,6 +25,18 @@ mod pubsub;
 mod survey;
 mod bus;
 mod device;
+mod poll;
+mod monitor;
+mod stats;
+#[cfg(feature = "futures")]
+mod async_socket;
+#[cfg(not(windows))]
+mod fd;
+#[cfg(not(windows))]
+mod ipc;
+mod inproc;
+mod ws;
+mod tls;
 
 pub use std::time::Duration;
 pub use std::thread;
@@ -55,7 +71,7 @@ pub mod urls {
         static mut NEXT_PORT: AtomicUsize = ATOMIC_USIZE_INIT;
         const FIRST_PORT: usize = 18080;
 
-        fn next_port() -> usize {
+        pub fn next_port() -> usize {
             unsafe {
                 // If the atomic was never used, set it to the initial port
                 NEXT_PORT.compare_and_swap(0, FIRST_PORT, SeqCst);
@@ -70,7 +86,38 @@ pub mod urls {
         }
     }
 
-    mod ipc {
+    #[cfg(not(windows))]
+    pub mod ipc {
+        use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT};
+        use std::sync::atomic::Ordering::SeqCst;
+
+        static NEXT_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
+        pub fn get() -> String {
+            format!("ipc:///tmp/scaproust_test_{}.ipc", NEXT_ID.fetch_add(1, SeqCst))
+        }
+    }
+
+    pub mod ws {
+        pub fn get() -> String {
+            format!("ws://127.0.0.1:{}/", super::tcp::next_port())
+        }
+    }
+
+    pub mod tls {
+        pub fn get() -> String {
+            format!("tls+tcp://127.0.0.1:{}", super::tcp::next_port())
+        }
+    }
+
+    pub mod inproc {
+        use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT};
+        use std::sync::atomic::Ordering::SeqCst;
+
+        static NEXT_ID: AtomicUsize = ATOMIC_USIZE_INIT;
 
+        pub fn get() -> String {
+            format!("inproc://scaproust_test_{}", NEXT_ID.fetch_add(1, SeqCst))
+        }
     }
 }
@@ -0,0 +1,95 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::time::Duration;
+pub use std::thread;
+pub use std::io;
+
+pub use scaproust::*;
+
+pub use super::urls;
+pub use super::{sleep_some, make_timeout};
+
+pub static CERT: &'static [u8] = include_bytes!("tls/cert.pem");
+pub static KEY: &'static [u8] = include_bytes!("tls/key.pem");
+
+pub fn set_identity(socket: &mut Socket) {
+    socket.set_option(ConfigOption::TlsCertificate(CERT.to_vec())).expect("Failed to set certificate !");
+    socket.set_option(ConfigOption::TlsPrivateKey(KEY.to_vec())).expect("Failed to set private key !");
+    socket.set_option(ConfigOption::TlsCaCertificates(CERT.to_vec())).expect("Failed to set ca certificates !");
+}
+
+describe! tls {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+        let url = urls::tls::get();
+        let timeout = make_timeout();
+    }
+
+    it "can exchange a message" {
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+        set_identity(&mut push);
+        set_identity(&mut pull);
+        push.set_send_timeout(timeout).expect("Failed to set send timeout !");
+        pull.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        let received = pull.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], received);
+    }
+
+    it "can exchange a request and a reply with mutual authentication" {
+        let mut req = session.create_socket::<Req>().expect("Failed to create socket !");
+        let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+
+        set_identity(&mut req);
+        set_identity(&mut rep);
+        rep.set_option(ConfigOption::TlsVerifyClient(true)).expect("Failed to set verify client !");
+        req.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+        rep.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+
+        rep.bind(&url).unwrap();
+        req.connect(&url).unwrap();
+
+        req.send(vec![65, 66, 67]).unwrap();
+        let request = rep.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], request);
+
+        rep.send(vec![67, 66, 65]).unwrap();
+        let reply = req.recv().unwrap();
+        assert_eq!(vec![67, 66, 65], reply);
+    }
+
+    it "can not exchange when the client has no certificate" {
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+        set_identity(&mut pull);
+        pull.set_option(ConfigOption::TlsVerifyClient(true)).expect("Failed to set verify client !");
+        push.set_option(ConfigOption::TlsCaCertificates(CERT.to_vec())).expect("Failed to set ca certificates !");
+        push.set_send_timeout(timeout).expect("Failed to set send timeout !");
+
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        let err = push.send(vec![65, 66, 67]).unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+    }
+
+    it "can not bind without a certificate" {
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+        assert!(pull.bind(&url).is_err());
+    }
+
+}
@@ -0,0 +1,127 @@
+// Copyright 2016 Benoît Labaere (benoit.labaere@gmail.com)
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::time::Duration;
+pub use std::thread;
+pub use std::io;
+pub use std::io::{Read, Write};
+pub use std::net::TcpStream;
+
+pub use scaproust::*;
+
+pub use super::urls;
+pub use super::{sleep_some, make_timeout};
+
+describe! ws {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = SessionBuilder::build().expect("Failed to create session !");
+        let url = urls::ws::get();
+        let timeout = make_timeout();
+    }
+
+    it "can exchange a message" {
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+        push.set_send_timeout(timeout).expect("Failed to set send timeout !");
+        pull.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        let received = pull.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], received);
+    }
+
+    it "can exchange a large message" {
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let payload = vec![65; 100 * 1024];
+
+        push.set_send_timeout(timeout).expect("Failed to set send timeout !");
+        pull.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        push.send(payload.clone()).unwrap();
+        let received = pull.recv().unwrap();
+        assert_eq!(payload, received);
+    }
+
+    it "can exchange a request and a reply" {
+        let mut req = session.create_socket::<Req>().expect("Failed to create socket !");
+        let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+
+        req.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+        rep.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+
+        rep.bind(&url).unwrap();
+        req.connect(&url).unwrap();
+
+        req.send(vec![65, 66, 67]).unwrap();
+        let request = rep.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], request);
+
+        rep.send(vec![67, 66, 65]).unwrap();
+        let reply = req.recv().unwrap();
+        assert_eq!(vec![67, 66, 65], reply);
+    }
+
+    it "can not exchange with an incompatible protocol" {
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+
+        push.set_send_timeout(timeout).expect("Failed to set send timeout !");
+
+        rep.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        let err = push.send(vec![65, 66, 67]).unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+    }
+
+    it "answer pings and reject unmasked client frames" {
+        let mut pair = session.create_socket::<Pair>().expect("Failed to create socket !");
+        let addr = url.trim_left_matches("ws://").trim_right_matches('/').to_owned();
+        let upgrade = b"GET / HTTP/1.1\r\n\
+            Upgrade: websocket\r\n\
+            Connection: Upgrade\r\n\
+            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            Sec-WebSocket-Version: 13\r\n\
+            Sec-WebSocket-Protocol: x-pair.sp.nanomsg.org\r\n\r\n";
+
+        pair.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+        pair.bind(&url).unwrap();
+
+        let mut client = TcpStream::connect(&addr[..]).unwrap();
+        client.set_read_timeout(timeout).unwrap();
+        client.write_all(upgrade).unwrap();
+
+        let mut response = Vec::new();
+        let mut byte = [0u8; 1];
+        while response.ends_with(b"\r\n\r\n") == false {
+            client.read_exact(&mut byte).unwrap();
+            response.push(byte[0]);
+        }
+
+        // masked ping followed by a masked binary frame
+        client.write_all(&[0x89, 0x81, 0, 0, 0, 0, 7, 0x82, 0x81, 0, 0, 0, 0, 65]).unwrap();
+        assert_eq!(vec![65], pair.recv().unwrap());
+
+        let mut pong = [0u8; 3];
+        client.read_exact(&mut pong).unwrap();
+        assert_eq!([0x8A, 1, 7], pong);
+
+        client.write_all(&[0x82, 1, 66]).unwrap();
+        let err = pair.recv().unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+    }
+
+}